@@ -7,7 +7,7 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use mnem_core::config::ConfigManager;
+use mnem_core::config::{ConfigManager, RetentionPolicy};
 use ratatui::backend::CrosstermBackend;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
@@ -94,6 +94,17 @@ pub fn start_tui(repo: Arc<Repository>) -> Result<()> {
         state.available_branches = branches;
     }
 
+    if let Ok(pins) = repo.list_pins(None) {
+        state.pins = pins.into_iter().map(|p| (p.hash, p.label)).collect();
+    }
+
+    if let Ok(changesets) = repo.list_changesets(200) {
+        state.changesets = changesets
+            .into_iter()
+            .map(|c| (c.id, c.files.len()))
+            .collect();
+    }
+
     loop {
         // --- Logic: Data Fetching (ONLY when dirty flag is set) ---
         if state.dirty {
@@ -256,7 +267,7 @@ pub fn start_tui(repo: Arc<Repository>) -> Result<()> {
                         KeyCode::Enter => {
                             state.input_mode = false;
                             if state.view == ViewState::Search {
-                                if let Ok(results) = repo.grep_contents(&state.search_query, None) {
+                                if let Ok(results) = repo.grep_contents(&state.search_query, None, false) {
                                     state.search_results = results;
                                     state.search_state.select(Some(0));
                                 }
@@ -639,7 +650,9 @@ pub fn start_tui(repo: Arc<Repository>) -> Result<()> {
                             };
                             match state.settings_index {
                                 0 => {
-                                    let days = if config_manager.config.retention_days == 30 {
+                                    let days = if config_manager.config.retention
+                                        == RetentionPolicy::within_days(30)
+                                    {
                                         7
                                     } else {
                                         30
@@ -693,8 +706,10 @@ pub fn start_tui(repo: Arc<Repository>) -> Result<()> {
                                     // Run GC
                                     drop(config_manager);
                                     match repo.run_gc() {
-                                        Ok(n) => state
-                                            .set_notification(format!("GC Pruned {} snapshots", n)),
+                                        Ok(r) => state.set_notification(format!(
+                                            "GC Pruned {} snapshots",
+                                            r.snapshots_pruned
+                                        )),
                                         Err(e) => {
                                             state.set_notification(format!("GC Error: {}", e))
                                         }
@@ -890,7 +905,10 @@ fn group_snapshots(snapshots: Vec<Snapshot>) -> Vec<HistoryItem> {
         }
 
         let is_new_session = if let Some(last) = &last_processed {
-            if let (Ok(t1), Ok(t2)) = (
+            if let (Some(s1), Some(s2)) = (last.session_id, snap.session_id) {
+                // Recorded by the daemon
+                s1 != s2
+            } else if let (Ok(t1), Ok(t2)) = (
                 DateTime::parse_from_rfc3339(&last.timestamp),
                 DateTime::parse_from_rfc3339(&snap.timestamp),
             ) {
@@ -63,16 +63,10 @@ impl Command for InfoCommand {
                 checkpoints
                     .iter()
                     .filter(|(hash, _, _)| {
-                        if let Ok(Some((_, file_states_json))) =
-                            repo.db.get_checkpoint_by_hash(hash)
-                        {
-                            if let Ok(file_states) =
-                                serde_json::from_str::<Vec<(String, String)>>(&file_states_json)
-                            {
-                                return file_states.iter().any(|(path, _)| *path == file_path);
-                            }
-                        }
-                        false
+                        repo.db
+                            .checkpoint_files(hash)
+                            .map(|files| files.iter().any(|(path, _)| *path == file_path))
+                            .unwrap_or(false)
                     })
                     .count()
             } else {
@@ -3,7 +3,7 @@ use crate::ui::Layout;
 use anyhow::Result;
 use crossterm::style::Stylize;
 use mnem_core::{
-    config::{ConfigManager, Ide},
+    config::{ConfigManager, Ide, RetentionPolicy},
     env::get_base_dir,
 };
 
@@ -36,7 +36,7 @@ impl Command for ConfigCommand {
             layout.section_start("cf", "Global Configuration");
 
             let settings = [
-                ("Retention Days", cm.config.retention_days.to_string()),
+                ("Retention", cm.config.retention.summary()),
                 (
                     "Compression",
                     if cm.config.compression_enabled {
@@ -73,7 +73,9 @@ impl Command for ConfigCommand {
             let key = &args[3];
             let val = &args[4];
             match key.as_str() {
-                "retention_days" => cm.config.retention_days = val.parse()?,
+                "retention_days" => {
+                    cm.config.retention = RetentionPolicy::within_days(val.parse()?)
+                }
                 "compression" => cm.config.compression_enabled = val.parse()?,
                 "max_file_size_mb" => cm.config.max_file_size_mb = val.parse()?,
                 "use_mnemignore" => cm.config.use_mnemosyneignore = val.parse()?,
@@ -38,11 +38,11 @@ impl Command for GcCommand {
             ));
         } else {
             let repo = Repository::init()?;
-            let n = repo.run_gc()?;
+            let report = repo.run_gc()?;
             layout.item_simple(&format!(
                 "{} Local cleanup complete: {} chunks pruned.",
                 "√".green(),
-                n.to_string().bold()
+                report.chunks_deleted.to_string().bold()
             ));
         }
         layout.section_end();
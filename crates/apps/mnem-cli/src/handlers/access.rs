@@ -0,0 +1,177 @@
+//! How CLI commands reach a project's history.
+//!
+//! redb lets a single process open a database, and the daemon keeps every
+//! watched project open. Commands therefore go through the daemon whenever it
+//! is running, and only open the store themselves, exclusively, when it is not.
+
+use anyhow::{Context, Result};
+use mnem_core::AppError;
+use mnem_core::client::{DaemonClient, daemon_running};
+use mnem_core::crypto;
+use mnem_core::env::get_base_dir;
+use mnem_core::models::Pin;
+use mnem_core::protocol::{SnapshotContentResponse, SnapshotInfo, methods};
+use mnem_core::storage::Repository;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+pub enum Access {
+    Daemon(DaemonClient),
+    Local(Box<Repository>),
+}
+
+impl Access {
+    /// Connects to the daemon, or opens `project_path` exclusively when the
+    /// daemon is not running.
+    pub fn connect(project_path: &Path) -> Result<Self> {
+        if let Some(daemon) = Self::daemon()? {
+            return Ok(daemon);
+        }
+        let base_dir = get_base_dir()?;
+        unlock(&base_dir)?;
+        Self::open_local(base_dir, project_path)
+    }
+
+    /// Like [`Access::connect`], but never prompts: `None` when history is
+    /// encrypted with a passphrase nobody has supplied. For callers nobody is
+    /// watching, such as git hooks.
+    pub fn connect_unattended(project_path: &Path) -> Result<Option<Self>> {
+        if let Some(daemon) = Self::daemon()? {
+            return Ok(Some(daemon));
+        }
+        let base_dir = get_base_dir()?;
+        if crypto::needs_passphrase(&base_dir) {
+            return Ok(None);
+        }
+        Self::open_local(base_dir, project_path).map(Some)
+    }
+
+    fn daemon() -> Result<Option<Self>> {
+        if !daemon_running() {
+            return Ok(None);
+        }
+        let client = DaemonClient::connect()
+            .context("The daemon is running but not answering. Try 'mnem off' then 'mnem on'")?;
+        Ok(Some(Self::Daemon(client)))
+    }
+
+    fn open_local(base_dir: PathBuf, project_path: &Path) -> Result<Self> {
+        match Repository::open_exclusive(base_dir, project_path.to_path_buf()) {
+            Ok(repo) => Ok(Self::Local(Box::new(repo))),
+            Err(AppError::Locked(reason)) => anyhow::bail!(
+                "History is busy ({}). Wait for the other mnem command to finish.",
+                reason
+            ),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// History of one file, newest first.
+    pub fn history(&mut self, file_path: &str) -> Result<Vec<SnapshotInfo>> {
+        match self {
+            Self::Daemon(client) => {
+                let res = client.call(
+                    methods::SNAPSHOT_LIST,
+                    serde_json::json!({ "file_path": file_path }),
+                )?;
+                Ok(serde_json::from_value(res)?)
+            }
+            Self::Local(repo) => Ok(repo
+                .get_history(file_path)?
+                .into_iter()
+                .map(|sn| SnapshotInfo {
+                    id: sn.id,
+                    file_path: sn.file_path,
+                    timestamp: sn.timestamp,
+                    content_hash: sn.content_hash,
+                    git_branch: sn.git_branch,
+                    commit_hash: sn.commit_hash,
+                    commit_message: sn.commit_message,
+                    event: sn.event,
+                })
+                .collect()),
+        }
+    }
+
+    /// Pins of the project at `project_path`, newest first, limited to labels
+    /// containing `query` when given.
+    pub fn pins(&mut self, project_path: &Path, query: Option<&str>) -> Result<Vec<Pin>> {
+        match self {
+            Self::Daemon(client) => {
+                let res = client.call(
+                    methods::PIN_LIST,
+                    serde_json::json!({
+                        "project_path": project_path.to_string_lossy(),
+                        "query": query,
+                    }),
+                )?;
+                Ok(serde_json::from_value(res)?)
+            }
+            Self::Local(repo) => Ok(repo.list_pins(query)?),
+        }
+    }
+
+    /// Content of a stored version.
+    pub fn content(&mut self, content_hash: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Daemon(client) => {
+                let res = client.call(
+                    methods::SNAPSHOT_GET,
+                    serde_json::json!({ "content_hash": content_hash }),
+                )?;
+                let response: SnapshotContentResponse = serde_json::from_value(res)?;
+                Ok(response.into_bytes()?)
+            }
+            Self::Local(repo) => Ok(repo.get_content(content_hash)?),
+        }
+    }
+}
+
+/// Unlocks encrypted history for this process, prompting for the passphrase
+/// when one is needed and not set in the environment.
+pub fn unlock(base_dir: &Path) -> Result<Option<String>> {
+    if !crypto::needs_passphrase(base_dir) {
+        return Ok(None);
+    }
+    let passphrase = read_passphrase("Passphrase: ")?;
+    crypto::unlock(base_dir, Some(&passphrase))?;
+    Ok(Some(passphrase))
+}
+
+/// Reads a passphrase without echoing it, or a plain line when stdin is not
+/// a terminal.
+pub fn read_passphrase(prompt: &str) -> Result<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    if !std::io::stdin().is_terminal() {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    crossterm::terminal::enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let result = loop {
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e.into()),
+        };
+        match key.code {
+            KeyCode::Enter => break Ok(()),
+            KeyCode::Backspace => {
+                passphrase.pop();
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break Err(anyhow::anyhow!("Cancelled"));
+            }
+            KeyCode::Char(c) => passphrase.push(c),
+            _ => {}
+        }
+    };
+    crossterm::terminal::disable_raw_mode()?;
+    eprintln!();
+    result.map(|_| passphrase)
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
+use mnem_core::env::get_base_dir;
 
+use crate::handlers::access::unlock;
 use crate::ui::Layout;
 
 pub fn handle_on(_auto: bool) -> Result<()> {
@@ -7,7 +9,15 @@ pub fn handle_on(_auto: bool) -> Result<()> {
 
     let layout = Layout::new();
 
-    match client::ensure_daemon() {
+    // Checked here, so a wrong passphrase is reported instead of the daemon
+    // quietly exiting
+    let passphrase = if client::daemon_running() {
+        None
+    } else {
+        unlock(&get_base_dir()?)?
+    };
+
+    match client::ensure_daemon_with(passphrase.as_deref()) {
         Ok(true) => {
             layout.header_dashboard("DAEMON");
             layout.success_bright("✓ mnem daemon started");
@@ -1,6 +1,7 @@
 use anyhow::Result;
 
 use crate::ui::Layout;
+use mnem_core::storage::tiered::TierUsage;
 
 fn format_duration(secs: u64) -> String {
     if secs < 60 {
@@ -14,6 +15,14 @@ fn format_duration(secs: u64) -> String {
     }
 }
 
+fn format_usage(usage: TierUsage) -> String {
+    format!(
+        "{} objects, {:.2} MB",
+        usage.objects,
+        usage.bytes as f64 / 1024.0 / 1024.0
+    )
+}
+
 pub fn handle_status() -> Result<()> {
     use mnem_core::client::DaemonClient;
     use mnem_core::protocol::methods;
@@ -55,6 +64,28 @@ pub fn handle_status() -> Result<()> {
             layout.row_metric("", "Symbols", &status.total_symbols.to_string());
             layout.section_end();
 
+            layout.section_branch("ti", "Storage Tiers");
+            layout.row_metric("", "Hot", &format_usage(status.tiers.hot));
+            layout.row_metric("", "Warm (LZ4)", &format_usage(status.tiers.warm));
+            layout.row_metric("", "Cold (Zstd)", &format_usage(status.tiers.cold));
+            layout.row_metric("", "Packed", &format_usage(status.tiers.packed));
+            if status.tiers.legacy.objects > 0 {
+                layout.row_metric("", "Unmigrated", &format_usage(status.tiers.legacy));
+            }
+            let dictionary = status.tiers.dictionary;
+            if dictionary.versions > 0 {
+                layout.row_metric(
+                    "",
+                    "Dictionaries",
+                    &format!(
+                        "{} trained, {:.2} MB saved in packs",
+                        dictionary.versions,
+                        dictionary.pack_bytes_saved as f64 / 1024.0 / 1024.0
+                    ),
+                );
+            }
+            layout.section_end();
+
             layout.empty();
             layout.badge_success("READY", "Mnemosyne is running");
         }
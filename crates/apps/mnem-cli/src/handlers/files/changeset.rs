@@ -0,0 +1,70 @@
+use crate::handlers::access::Access;
+use crate::ui::Layout;
+use anyhow::Result;
+use mnem_core::models::Changeset;
+use mnem_core::protocol::methods;
+use std::path::Path;
+
+pub fn handle_changesets(limit: Option<usize>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let limit = limit.unwrap_or(20);
+    let changesets: Vec<Changeset> = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::CHANGESET_LIST,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "limit": limit,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.list_changesets(limit)?,
+    };
+
+    let layout = Layout::new();
+    layout.header_dashboard("CHANGESETS");
+    if changesets.is_empty() {
+        layout.warning("No changesets recorded yet.");
+        return Ok(());
+    }
+    for changeset in &changesets {
+        let when = if changeset.started_at == changeset.ended_at {
+            time_of(&changeset.started_at)
+        } else {
+            format!(
+                "{} – {}",
+                time_of(&changeset.started_at),
+                time_of(&changeset.ended_at)
+            )
+        };
+        let branch = changeset
+            .git_branch
+            .as_deref()
+            .map(|b| format!("  ({})", b))
+            .unwrap_or_default();
+        layout.section_branch(
+            &format!("#{}", changeset.id),
+            &format!("{}  {} saves{}", when, changeset.snapshot_count, branch),
+        );
+        for file in &changeset.files {
+            let path = Path::new(file);
+            layout.item_simple(
+                &path
+                    .strip_prefix(&cwd)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string(),
+            );
+        }
+        layout.section_end();
+    }
+    layout.footer("'mnem r --changeset <id>' reverts every file in a changeset.");
+    Ok(())
+}
+
+/// `2026-01-02T10:11:12.345Z` → `2026-01-02 10:11:12`
+fn time_of(timestamp: &str) -> String {
+    timestamp
+        .split('.')
+        .next()
+        .unwrap_or(timestamp)
+        .replacen('T', " ", 1)
+}
@@ -0,0 +1,87 @@
+use crate::handlers::access::Access;
+use crate::ui::Layout;
+use anyhow::Result;
+use mnem_core::models::{ChangeKind, CheckpointChange};
+use mnem_core::protocol::methods;
+use std::path::Path;
+
+pub fn handle_checkpoint_diff(from: String, to: String) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let changes: Vec<CheckpointChange> = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::CHECKPOINT_DIFF,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "from": from,
+                "to": to,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.checkpoint_diff(&from, &to)?,
+    };
+    render(
+        "CHECKPOINT DIFF",
+        &format!("{} → {}", short(&from), short(&to)),
+        &changes,
+        &cwd,
+    );
+    Ok(())
+}
+
+pub fn handle_checkpoint_status(checkpoint: String) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let changes: Vec<CheckpointChange> = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::CHECKPOINT_STATUS,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "checkpoint": checkpoint,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.checkpoint_status(&checkpoint)?,
+    };
+    render(
+        "CHECKPOINT STATUS",
+        &format!("{} → working tree", short(&checkpoint)),
+        &changes,
+        &cwd,
+    );
+    Ok(())
+}
+
+fn render(title: &str, section: &str, changes: &[CheckpointChange], cwd: &Path) {
+    let layout = Layout::new();
+    layout.header_dashboard(title);
+    layout.section_branch("cp", section);
+    if changes.is_empty() {
+        layout.item_simple("No changes");
+    }
+    let (mut added, mut removed, mut modified) = (0, 0, 0);
+    for change in changes {
+        let path = Path::new(&change.path);
+        let path = path.strip_prefix(cwd).unwrap_or(path).display();
+        let stats = format!("+{} -{}", change.lines_added, change.lines_removed);
+        match change.kind {
+            ChangeKind::Added => {
+                added += 1;
+                layout.item_green(&format!("A  {}  {}", path, stats));
+            }
+            ChangeKind::Removed => {
+                removed += 1;
+                layout.item_pink(&format!("D  {}  {}", path, stats));
+            }
+            ChangeKind::Modified => {
+                modified += 1;
+                layout.item_yellow(&format!("M  {}  {}", path, stats));
+            }
+        }
+    }
+    layout.section_end();
+    layout.footer(&format!(
+        "{} added, {} removed, {} modified. 'mnem r --checkpoint <hash> --path <glob>' reverts part of a checkpoint.",
+        added, removed, modified
+    ));
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..8.min(hash.len())]
+}
@@ -1,12 +1,16 @@
-use crate::ui::Layout;
+use crate::handlers::access::Access;
+use crate::handlers::workspace::bundle::parse_time;
+use crate::ui::{DiffStats, Layout};
 use anyhow::Result;
 use mnem_core::client::DaemonClient;
 use mnem_core::env::get_base_dir;
+use mnem_core::models::{Pin, SnapshotEvent};
 use mnem_core::protocol::SnapshotInfo;
 use mnem_core::protocol::methods;
 use mnem_core::storage::Repository;
+use mnem_core::utils::time::parse_ms;
 use similar::{ChangeTag, TextDiff};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 fn check_project_tracked(layout: &Layout) -> Result<(PathBuf, PathBuf)> {
@@ -32,7 +36,7 @@ pub fn handle_h(
     file: Option<String>,
     limit: Option<usize>,
     timeline: bool,
-    _since: Option<String>,
+    since: Option<String>,
     _branch: Option<String>,
 ) -> Result<()> {
     let layout = Layout::new();
@@ -57,40 +61,28 @@ pub fn handle_h(
     }
 
     let limit = limit.unwrap_or(20);
+    let since = since
+        .as_deref()
+        .map(parse_time)
+        .transpose()?
+        .map(|t| t.timestamp_millis());
 
     if timeline {
         return handle_timeline_view(file, &layout);
     }
 
-    // Try daemon first, fallback to direct access only on connection errors
-    // Don't retry on lock errors (daemon is running)
+    let mut access = Access::connect(&project_path)?;
+    // A daemon from before pins answers without them
+    let pins = access.pins(&project_path, None).unwrap_or_default();
     if let Some(ref f) = file {
-        match try_daemon_file_history(f, limit, &layout, &project_path) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                // Only fallback if it's a connection error, not a lock error
-                let err_str = format!("{}", e);
-                if err_str.contains("lock") || err_str.contains("Database already open") {
-                    // Daemon is running, the lock is expected - show error
-                    layout.error("Cannot access history while daemon is running. Run 'mnem off' first or wait for operations to complete.");
-                    return Ok(());
-                }
-                // For other errors, try direct access
-                handle_file_history_direct(f, limit, &layout, &project_path)
-            }
-        }
+        file_history(&mut access, f, limit, since, &layout, &project_path, &pins)
     } else {
-        match try_daemon_dashboard_view(limit, &layout, &project_path) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                let err_str = format!("{}", e);
-                if err_str.contains("lock") || err_str.contains("Database already open") {
-                    layout.error(
-                        "Cannot access history while daemon is running. Run 'mnem off' first.",
-                    );
-                    return Ok(());
-                }
-                handle_dashboard_view_direct(limit, &layout, &project_path)
+        match access {
+            Access::Daemon(client) => {
+                daemon_dashboard_view(client, limit, since, &layout, &project_path, &pins)
+            }
+            Access::Local(repo) => {
+                local_dashboard_view(&repo, limit, since, &layout, &project_path, &pins)
             }
         }
     }
@@ -108,72 +100,36 @@ fn handle_timeline_view(file: Option<String>, layout: &Layout) -> Result<()> {
     Ok(())
 }
 
-// Try to use daemon for file history
-fn try_daemon_file_history(
+fn file_history(
+    access: &mut Access,
     f: &str,
     limit: usize,
+    since: Option<i64>,
     layout: &Layout,
     project_path: &std::path::Path,
+    pins: &[Pin],
 ) -> Result<()> {
-    let mut client = DaemonClient::connect()?;
-    let full_path = if std::path::Path::new(f).is_absolute() {
-        f.to_string()
-    } else {
-        project_path.join(f).to_string_lossy().to_string()
-    };
+    let clean_path = f.trim_start_matches(".\\").trim_start_matches("./");
 
-    let res = client.call(
-        methods::SNAPSHOT_LIST,
-        serde_json::json!({ "file_path": full_path }),
-    )?;
-
-    let history: Vec<SnapshotInfo> = serde_json::from_value(res)?;
-    display_file_history(f, limit, layout, history)
-}
-
-// Fallback: direct database access for file history
-fn handle_file_history_direct(
-    f: &str,
-    limit: usize,
-    layout: &Layout,
-    project_path: &std::path::Path,
-) -> Result<()> {
-    let base_dir = get_base_dir()?;
-    let repo = Repository::open(base_dir, project_path.to_path_buf())?;
-
-    // Convert relative path to absolute by joining with project_path
-    let clean_path = if f.starts_with(".\\") {
-        &f[2..]
-    } else if f.starts_with("./") {
-        &f[2..]
-    } else {
-        f
-    };
-
-    // Use absolute path for database lookup (how files are stored internally)
+    // Files are stored under their absolute path
     let absolute_path = if std::path::Path::new(clean_path).is_absolute() {
         clean_path.to_string()
     } else {
         project_path.join(clean_path).to_string_lossy().to_string()
     };
 
-    let history_db = repo.get_history(&absolute_path)?;
+    let history = access.history(&absolute_path)?;
+    display_file_history(f, limit, layout, filter_since(history, since), pins)
+}
 
-    // Convert to SnapshotInfo format
-    let history: Vec<SnapshotInfo> = history_db
+fn filter_since(history: Vec<SnapshotInfo>, since: Option<i64>) -> Vec<SnapshotInfo> {
+    let Some(since) = since else {
+        return history;
+    };
+    history
         .into_iter()
-        .map(|sn| SnapshotInfo {
-            id: sn.id,
-            file_path: sn.file_path,
-            timestamp: sn.timestamp,
-            content_hash: sn.content_hash,
-            git_branch: sn.git_branch,
-            commit_hash: sn.commit_hash,
-            commit_message: None,
-        })
-        .collect();
-
-    display_file_history(f, limit, layout, history)
+        .filter(|snap| parse_ms(&snap.timestamp).is_some_and(|ts| ts >= since))
+        .collect()
 }
 
 fn display_file_history(
@@ -181,7 +137,9 @@ fn display_file_history(
     limit: usize,
     layout: &Layout,
     history: Vec<SnapshotInfo>,
+    pins: &[Pin],
 ) -> Result<()> {
+    let labels = pin_labels(pins);
     let clean_path = if f.starts_with(".\\") {
         &f[2..]
     } else if f.starts_with("./") {
@@ -202,11 +160,14 @@ fn display_file_history(
             ("A", "Added"),
             ("M", "Modified"),
             ("D", "Deleted"),
+            ("R", "Renamed"),
         ]);
         println!();
 
         for (i, snap) in history.iter().take(limit).enumerate() {
-            let hash_short = if snap.content_hash.len() >= 8 {
+            let hash_short = if snap.event.is_tombstone() {
+                "--------"
+            } else if snap.content_hash.len() >= 8 {
                 &snap.content_hash[..8]
             } else {
                 &snap.content_hash
@@ -221,7 +182,15 @@ fn display_file_history(
                 ""
             };
 
-            layout.row_history_compact(hash_short, "M", clean_path, time_only, i == 0, None);
+            let display_path = with_pin(clean_path.to_string(), &labels, &snap.content_hash);
+            layout.row_history_compact(
+                hash_short,
+                event_marker(&snap.event),
+                &display_path,
+                time_only,
+                i == 0,
+                None,
+            );
         }
         layout.footer_pagination(history.len().min(limit), history.len(), limit);
     }
@@ -230,19 +199,20 @@ fn display_file_history(
     Ok(())
 }
 
-// Try to use daemon for dashboard view
-fn try_daemon_dashboard_view(
+fn daemon_dashboard_view(
+    mut client: DaemonClient,
     limit: usize,
+    since: Option<i64>,
     layout: &Layout,
     project_path: &std::path::Path,
+    pins: &[Pin],
 ) -> Result<()> {
-    let mut client = DaemonClient::connect()?;
-
     let res = client.call(
         methods::PROJECT_GET_ACTIVITY,
         serde_json::json!({
             "limit": limit,
-            "project_path": project_path.to_string_lossy().to_string()
+            "project_path": project_path.to_string_lossy().to_string(),
+            "since": since
         }),
     )?;
 
@@ -260,19 +230,25 @@ fn try_daemon_dashboard_view(
         "Unknown".to_string()
     };
 
-    display_dashboard_view(limit, layout, project_path, project_name, history)
+    display_dashboard_view(limit, layout, project_path, project_name, history, pins)
 }
 
-// Fallback: direct database access for dashboard view
-fn handle_dashboard_view_direct(
+fn local_dashboard_view(
+    repo: &Repository,
     limit: usize,
+    since: Option<i64>,
     layout: &Layout,
     project_path: &std::path::Path,
+    pins: &[Pin],
 ) -> Result<()> {
-    let base_dir = get_base_dir()?;
-    let repo = Repository::open(base_dir, project_path.to_path_buf())?;
-
-    let history_db = repo.get_recent_activity(limit)?;
+    let history_db = match since {
+        Some(since) => {
+            let mut history = repo.history_between(since, i64::MAX)?;
+            history.truncate(limit);
+            history
+        }
+        None => repo.get_recent_activity(limit)?,
+    };
 
     // Convert to SnapshotInfo format
     let history: Vec<SnapshotInfo> = history_db
@@ -285,11 +261,12 @@ fn handle_dashboard_view_direct(
             git_branch: sn.git_branch,
             commit_hash: sn.commit_hash,
             commit_message: None,
+            event: sn.event,
         })
         .collect();
 
     let project_name = repo.project.name.clone();
-    display_dashboard_view(limit, layout, project_path, project_name, history)
+    display_dashboard_view(limit, layout, project_path, project_name, history, pins)
 }
 
 fn display_dashboard_view(
@@ -298,7 +275,9 @@ fn display_dashboard_view(
     project_path: &std::path::Path,
     project_name: String,
     history: Vec<SnapshotInfo>,
+    pins: &[Pin],
 ) -> Result<()> {
+    let labels = pin_labels(pins);
     let mut by_branch: BTreeMap<String, Vec<_>> = BTreeMap::new();
     for snap in &history {
         let branch = snap
@@ -315,7 +294,9 @@ fn display_dashboard_view(
         layout.item_simple("No recent activity");
     } else {
         for (i, snap) in history.iter().take(10).enumerate() {
-            let hash_short = if snap.content_hash.len() >= 8 {
+            let hash_short = if snap.event.is_tombstone() {
+                "--------"
+            } else if snap.content_hash.len() >= 8 {
                 &snap.content_hash[..8]
             } else {
                 &snap.content_hash
@@ -330,23 +311,47 @@ fn display_dashboard_view(
                 ""
             };
 
-            let p = snap.file_path.replace("\\\\?\\", "");
-            let p_path = std::path::Path::new(&p);
-            let display_path = if let Ok(rel) = p_path.strip_prefix(project_path) {
-                rel.to_string_lossy().to_string()
-            } else {
-                p
+            let display_path = match &snap.event {
+                SnapshotEvent::RenamedFrom { from } => format!(
+                    "{} → {}",
+                    relative_display(from, project_path),
+                    relative_display(&snap.file_path, project_path)
+                ),
+                _ => relative_display(&snap.file_path, project_path),
             };
-
-            layout.row_history_compact(hash_short, "M", &display_path, time_only, i == 0, None);
+            let display_path = with_pin(display_path, &labels, &snap.content_hash);
+
+            layout.row_history_compact(
+                hash_short,
+                event_marker(&snap.event),
+                &display_path,
+                time_only,
+                i == 0,
+                None,
+            );
         }
     }
     layout.section_end();
 
+    if !pins.is_empty() {
+        layout.section_branch("pn", "Pinned");
+        for pin in pins.iter().take(5) {
+            layout.row_history(
+                &pin.hash[..8.min(pin.hash.len())],
+                pin.created_at.get(..10).unwrap_or(&pin.created_at),
+                &pin.label,
+                false,
+            );
+        }
+        layout.section_end();
+    }
+
     layout.legend(&[
         ("● Latest", ""),
         ("· Past", ""),
         ("M", "Mod"),
+        ("D", "Del"),
+        ("R", "Moved"),
         ("C", "Checkpt"),
         ("G", "Commit"),
     ]);
@@ -357,11 +362,41 @@ fn display_dashboard_view(
     Ok(())
 }
 
+fn pin_labels(pins: &[Pin]) -> HashMap<&str, &str> {
+    pins.iter()
+        .map(|pin| (pin.hash.as_str(), pin.label.as_str()))
+        .collect()
+}
+
+/// `path` followed by the label of the pin on `content_hash`, if any.
+fn with_pin(path: String, labels: &HashMap<&str, &str>, content_hash: &str) -> String {
+    match labels.get(content_hash) {
+        Some(label) => format!("{} ⚑ {}", path, label),
+        None => path,
+    }
+}
+
+fn event_marker(event: &SnapshotEvent) -> &'static str {
+    match event {
+        SnapshotEvent::Modified => "M",
+        SnapshotEvent::Deleted => "D",
+        SnapshotEvent::RenamedFrom { .. } | SnapshotEvent::RenamedTo { .. } => "R",
+    }
+}
+
+fn relative_display(path: &str, project_path: &std::path::Path) -> String {
+    let p = path.replace("\\\\?\\", "");
+    match std::path::Path::new(&p).strip_prefix(project_path) {
+        Ok(rel) => rel.to_string_lossy().to_string(),
+        Err(_) => p,
+    }
+}
+
 pub fn compute_diff_stats(
     repo: &Repository,
     current_hash: &str,
     prev_hash: Option<&str>,
-) -> Option<(usize, usize)> {
+) -> Option<DiffStats> {
     let current_content = repo.get_content(current_hash).ok()?;
     let prev_content = if let Some(p) = prev_hash {
         repo.get_content(p).ok().unwrap_or_default()
@@ -369,6 +404,14 @@ pub fn compute_diff_stats(
         Vec::new()
     };
 
+    // Line diffs mean nothing for blobs; compare sizes and hashes instead
+    if repo.is_blob(&current_content) || repo.is_blob(&prev_content) {
+        return Some(DiffStats::Blob {
+            before: prev_hash.map(|h| (prev_content.len() as u64, h.to_string())),
+            after: current_content.len() as u64,
+        });
+    }
+
     let current_str = String::from_utf8_lossy(&current_content);
     let prev_str = String::from_utf8_lossy(&prev_content);
 
@@ -385,5 +428,5 @@ pub fn compute_diff_stats(
         }
     }
 
-    Some((added, removed))
+    Some(DiffStats::Lines(added, removed))
 }
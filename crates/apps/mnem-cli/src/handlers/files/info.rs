@@ -1,126 +1,97 @@
 use anyhow::Result;
 
+use crate::handlers::access::Access;
 use crate::ui::Layout;
-use mnem_core::client::DaemonClient;
 use mnem_core::protocol::methods;
 
 pub fn handle_info(_project: Option<String>) -> Result<()> {
-    use mnem_core::env::get_base_dir;
-    use mnem_core::storage::Repository;
     use std::collections::HashMap;
 
     let layout = Layout::new();
-    let base_dir = get_base_dir()?;
     let cwd = std::env::current_dir()?;
     let tracked_file = cwd.join(".mnemosyne").join("tracked");
 
-    // Try daemon first
-    let daemon = DaemonClient::connect().ok();
-
-    if let Some(mut client) = daemon {
-        // Daemon is running - use it to get project info
-        match client.call(
-            methods::PROJECT_GET_STATISTICS,
-            serde_json::json!({ "project_path": cwd.to_string_lossy().to_string() }),
-        ) {
-            Ok(res) => {
-                let stats: mnem_core::protocol::ProjectStatisticsResponse =
-                    serde_json::from_value(res)?;
-
-                // Get project info from daemon
-                let project_path = cwd.to_string_lossy().to_string();
-                let project_name = std::path::Path::new(&project_path)
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "Unknown".to_string());
-
-                layout.header_dashboard("PROJECT INFO");
-                layout.section_branch("pr", &project_name);
-                layout.row_labeled("◫", "Path", &project_path);
-                layout.row_labeled("◆", "ID", "tracked");
-                layout.row_metric(
-                    "",
-                    "Size",
-                    &format!("{:.2} MB", stats.size_bytes as f64 / 1024.0 / 1024.0),
-                );
-                layout.section_end();
-
-                layout.section_branch("st", "Activity Summary");
-                layout.row_metric("", "Total Snapshots", &stats.total_snapshots.to_string());
-                layout.row_metric("", "Unique Files", &stats.total_files.to_string());
-                layout.row_metric("", "Branches", &stats.total_branches.to_string());
-                layout.section_end();
+    if !tracked_file.exists() {
+        layout.header_dashboard("PROJECT NOT TRACKED");
+        layout.section_branch("pr", "Current Folder");
+        layout.row_labeled("◫", "Path", &cwd.to_string_lossy());
+        layout.section_end();
+        layout.empty();
+        layout.badge_error("ERROR", "This project is not tracked");
+        layout.info_bright("Run 'mnem track' to start tracking this project.");
+        return Ok(());
+    }
 
-                if !stats.extensions.is_empty() {
-                    layout.section_branch("fi", "File Types");
-                    for (ext, count) in stats.extensions.iter().take(6) {
-                        let icon = match ext.as_str() {
-                            "rs" => "🦀",
-                            "js" | "ts" | "jsx" | "tsx" => "📜",
-                            "py" => "🐍",
-                            "go" => "🐹",
-                            "java" => "☕",
-                            "c" | "cpp" | "h" | "hpp" => "⚙️",
-                            "html" | "css" | "scss" | "sass" => "🌐",
-                            "json" | "toml" | "yaml" | "yml" => "📝",
-                            "md" | "markdown" => "📖",
-                            "txt" => "📄",
-                            _ => "📄",
-                        };
-                        layout.row_key_value(
-                            &format!("{} .{}", icon, ext),
-                            &format!("{} files", count),
-                        );
-                    }
-                    layout.section_end();
+    let repo = match Access::connect(&cwd) {
+        Ok(Access::Daemon(mut client)) => {
+            let res = client.call(
+                methods::PROJECT_GET_STATISTICS,
+                serde_json::json!({ "project_path": cwd.to_string_lossy().to_string() }),
+            )?;
+            let stats: mnem_core::protocol::ProjectStatisticsResponse =
+                serde_json::from_value(res)?;
+
+            let project_path = cwd.to_string_lossy().to_string();
+            let project_name = std::path::Path::new(&project_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+
+            layout.header_dashboard("PROJECT INFO");
+            layout.section_branch("pr", &project_name);
+            layout.row_labeled("◫", "Path", &project_path);
+            layout.row_labeled("◆", "ID", "tracked");
+            layout.row_metric(
+                "",
+                "Size",
+                &format!("{:.2} MB", stats.size_bytes as f64 / 1024.0 / 1024.0),
+            );
+            layout.section_end();
+
+            layout.section_branch("st", "Activity Summary");
+            layout.row_metric("", "Total Snapshots", &stats.total_snapshots.to_string());
+            layout.row_metric("", "Unique Files", &stats.total_files.to_string());
+            layout.row_metric("", "Branches", &stats.total_branches.to_string());
+            layout.section_end();
+
+            if !stats.extensions.is_empty() {
+                layout.section_branch("fi", "File Types");
+                for (ext, count) in stats.extensions.iter().take(6) {
+                    let icon = match ext.as_str() {
+                        "rs" => "🦀",
+                        "js" | "ts" | "jsx" | "tsx" => "📜",
+                        "py" => "🐍",
+                        "go" => "🐹",
+                        "java" => "☕",
+                        "c" | "cpp" | "h" | "hpp" => "⚙️",
+                        "html" | "css" | "scss" | "sass" => "🌐",
+                        "json" | "toml" | "yaml" | "yml" => "📝",
+                        "md" | "markdown" => "📖",
+                        "txt" => "📄",
+                        _ => "📄",
+                    };
+                    layout
+                        .row_key_value(&format!("{} .{}", icon, ext), &format!("{} files", count));
                 }
-
-                layout.empty();
-                layout.badge_success("OK", "Project loaded from daemon");
-                return Ok(());
+                layout.section_end();
             }
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("lock") || msg.contains("Database already open") {
-                    // Fall through to try direct access
-                } else {
-                    // Some other daemon error, try direct access
-                }
+
+            if stats.total_commits > 0 {
+                layout.row_metric("", "Git Commits", &stats.total_commits.to_string());
             }
-        }
-    }
 
-    // Try direct access (daemon not running or error)
-    let repo = match Repository::open(base_dir.clone(), cwd.clone()) {
-        Ok(r) => r,
+            layout.empty();
+            layout.badge_success("OK", "Project loaded from daemon");
+            return Ok(());
+        }
+        Ok(Access::Local(repo)) => repo,
         Err(e) => {
-            let msg = e.to_string();
-            if msg.contains("lock") || msg.contains("Database already open") {
-                layout.header_dashboard("PROJECT LOCKED");
-                layout.section_branch("pr", "Current Folder");
-                layout.row_labeled("◫", "Path", &cwd.to_string_lossy());
-                layout.section_end();
-                layout.empty();
-                layout.badge_error("ERROR", "Daemon is running");
-                layout.info_bright(
-                    "Run 'mnem off' to access directly, or the daemon is actively tracking this project.",
-                );
-            } else if !tracked_file.exists() {
-                layout.header_dashboard("PROJECT NOT TRACKED");
-                layout.section_branch("pr", "Current Folder");
-                layout.row_labeled("◫", "Path", &cwd.to_string_lossy());
-                layout.section_end();
-                layout.empty();
-                layout.badge_error("ERROR", "This project is not tracked");
-                layout.info_bright("Run 'mnem track' to start tracking this project.");
-            } else {
-                layout.header_dashboard("PROJECT ERROR");
-                layout.section_branch("pr", "Current Folder");
-                layout.row_labeled("◫", "Path", &cwd.to_string_lossy());
-                layout.section_end();
-                layout.empty();
-                layout.badge_error("ERROR", &msg);
-            }
+            layout.header_dashboard("PROJECT ERROR");
+            layout.section_branch("pr", "Current Folder");
+            layout.row_labeled("◫", "Path", &cwd.to_string_lossy());
+            layout.section_end();
+            layout.empty();
+            layout.badge_error("ERROR", &e.to_string());
             return Ok(());
         }
     };
@@ -1,9 +1,15 @@
+pub mod changeset;
+pub mod checkpoint;
 pub mod history;
 pub mod info;
+pub mod pin;
 pub mod restore;
 pub mod search;
 
+pub use changeset::handle_changesets;
+pub use checkpoint::{handle_checkpoint_diff, handle_checkpoint_status};
 pub use history::handle_h;
 pub use info::handle_info;
+pub use pin::handle_pin;
 pub use restore::handle_r;
 pub use search::handle_s;
@@ -0,0 +1,89 @@
+use crate::handlers::access::Access;
+use crate::ui::Layout;
+use anyhow::Result;
+use mnem_core::models::{Pin, PinKind};
+use mnem_core::protocol::methods;
+
+pub fn handle_pin(
+    hash: Option<String>,
+    label: Option<String>,
+    list: bool,
+    remove: bool,
+    search: Option<String>,
+) -> Result<()> {
+    let layout = Layout::new();
+    let cwd = std::env::current_dir()?;
+    let mut access = Access::connect(&cwd)?;
+
+    if list || search.is_some() || (hash.is_none() && !remove) {
+        let pins = access.pins(&cwd, search.as_deref())?;
+        layout.header_dashboard("PINS");
+        layout.section_branch("pn", search.as_deref().unwrap_or("All"));
+        if pins.is_empty() {
+            layout.item_simple("No pins");
+        }
+        for pin in &pins {
+            layout.row_history(short_hash(pin), &short_time(pin), &describe(pin), false);
+        }
+        layout.section_end();
+        layout.footer("Pinned versions are never pruned. 'mnem pin --remove <hash>' unpins one.");
+        return Ok(());
+    }
+
+    let Some(hash) = hash else {
+        layout.usage("pin", "--remove <hash>");
+        return Ok(());
+    };
+
+    if remove {
+        let removed: Option<Pin> = match &mut access {
+            Access::Daemon(client) => serde_json::from_value(client.call(
+                methods::PIN_REMOVE,
+                serde_json::json!({ "project_path": cwd.to_string_lossy(), "hash": hash }),
+            )?)?,
+            Access::Local(repo) => repo.unpin(&hash)?,
+        };
+        match removed {
+            Some(pin) => layout.success(&format!("Unpinned {}", describe(&pin))),
+            None => layout.error(&format!("No single pin matches {}", hash)),
+        }
+        return Ok(());
+    }
+
+    let Some(label) = label else {
+        layout.usage("pin", "<hash> \"<label>\"");
+        return Ok(());
+    };
+    let pin: Pin = match &mut access {
+        Access::Daemon(client) => serde_json::from_value(client.call(
+            methods::PIN_ADD,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "hash": hash,
+                "label": label,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.pin(&hash, &label)?,
+    };
+    layout.success(&format!("Pinned {} {}", short_hash(&pin), describe(&pin)));
+    Ok(())
+}
+
+fn describe(pin: &Pin) -> String {
+    match pin.kind {
+        PinKind::Snapshot => format!("\"{}\"", pin.label),
+        PinKind::Checkpoint => format!("\"{}\" (checkpoint)", pin.label),
+    }
+}
+
+fn short_hash(pin: &Pin) -> &str {
+    &pin.hash[..8.min(pin.hash.len())]
+}
+
+/// `YYYY-MM-DD HH:MM` of the pin's creation.
+fn short_time(pin: &Pin) -> String {
+    pin.created_at
+        .get(..16)
+        .unwrap_or(&pin.created_at)
+        .replace('T', " ")
+}
@@ -1,11 +1,12 @@
 use anyhow::Result;
 
+use crate::handlers::access::Access;
 use crate::handlers::files::history::compute_diff_stats;
-use crate::ui::Layout;
-use mnem_core::client::DaemonClient;
-use mnem_core::protocol::SnapshotInfo;
+use crate::ui::{DiffStats, Layout};
+use mnem_core::config::Config;
+use mnem_core::protocol::ProjectRevertResponse;
 use mnem_core::protocol::methods;
-use mnem_core::storage::Repository;
+use mnem_core::storage::cdc;
 use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
@@ -61,6 +62,24 @@ fn cleanup_old_temp_files() {
     }
 }
 
+/// Size and hash change of a restored blob, which has no meaningful line
+/// diff. `None` when the restored file is ordinary text.
+fn blob_change(
+    config: &Config,
+    path: &str,
+    size_before: Option<u64>,
+    hash_before: Option<&str>,
+) -> Option<DiffStats> {
+    let content = fs::read(path).ok()?;
+    if !config.is_blob(content.len() as u64, cdc::is_binary(&content)) {
+        return None;
+    }
+    Some(DiffStats::Blob {
+        before: size_before.zip(hash_before.map(str::to_string)),
+        after: content.len() as u64,
+    })
+}
+
 pub fn handle_r(
     file: Option<String>,
     version: Option<usize>,
@@ -69,6 +88,8 @@ pub fn handle_r(
     to: Option<String>,
     symbol: Option<String>,
     checkpoint: Option<String>,
+    path: Option<String>,
+    changeset: Option<i64>,
     branch: Option<String>,
     limit: Option<usize>,
 ) -> Result<()> {
@@ -77,8 +98,6 @@ pub fn handle_r(
 
     let layout = Layout::new();
     let base_dir = get_base_dir()?;
-    let config = ConfigManager::new(&base_dir)?;
-    let ide = config.config.ide;
 
     cleanup_old_temp_files();
 
@@ -97,44 +116,63 @@ pub fn handle_r(
             return Ok(());
         }
     };
+    let config = ConfigManager::with_project(&base_dir, &project_path)?;
+    let ide = config.config.ide;
 
-    // Try daemon first; fall back to direct DB only when daemon is not available.
-    let daemon = DaemonClient::connect().ok();
-    // If daemon connection fails with IO error (stale socket), try direct DB
-    let repo_opt: Option<Repository> = if daemon.is_none() {
-        match Repository::open(base_dir.clone(), project_path.clone()) {
-            Ok(r) => Some(r),
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("lock") || msg.contains("Database already open") {
-                    layout.error("Cannot access history while daemon is running.");
-                    layout.info_bright("Run 'mnem off' to stop the daemon first.");
-                    return Ok(());
-                }
-                return Err(anyhow::anyhow!("{}", e));
-            }
-        }
-    } else {
-        None
-    };
+    let mut access = Access::connect(&project_path)?;
 
     // -----------------------------------------------------------------------
     // --checkpoint
     // -----------------------------------------------------------------------
     if let Some(ref cp) = checkpoint {
-        if let Some(mut client) = daemon {
-            let _ = client.call(
-                methods::PROJECT_REVERT_V1,
-                serde_json::json!({ "timestamp": cp }),
-            )?;
-            layout.success(&format!("Restored project from checkpoint {}", cp));
-        } else if let Some(repo) = repo_opt.as_ref() {
-            let count = repo.revert_to_checkpoint(cp)?;
-            layout.success(&format!("Restored {} files from checkpoint {}", count, cp));
+        let count = match &mut access {
+            Access::Daemon(client) => {
+                let res = client.call(
+                    methods::PROJECT_REVERT_V1,
+                    serde_json::json!({
+                        "project_path": project_path.to_string_lossy(),
+                        "checkpoint": cp,
+                        "path_glob": path,
+                    }),
+                )?;
+                serde_json::from_value::<ProjectRevertResponse>(res)?.files_restored
+            }
+            Access::Local(repo) => repo.revert_paths_to_checkpoint(cp, path.as_deref())?,
+        };
+        match &path {
+            Some(glob) => layout.success(&format!(
+                "Restored {} files matching {} from checkpoint {}",
+                count, glob, cp
+            )),
+            None => layout.success(&format!("Restored {} files from checkpoint {}", count, cp)),
         }
         return Ok(());
     }
 
+    // -----------------------------------------------------------------------
+    // --changeset
+    // -----------------------------------------------------------------------
+    if let Some(id) = changeset {
+        let count = match &mut access {
+            Access::Daemon(client) => {
+                let res = client.call(
+                    methods::CHANGESET_REVERT,
+                    serde_json::json!({
+                        "project_path": project_path.to_string_lossy(),
+                        "id": id,
+                    }),
+                )?;
+                serde_json::from_value::<ProjectRevertResponse>(res)?.files_restored
+            }
+            Access::Local(repo) => repo.revert_changeset(id)?,
+        };
+        layout.success(&format!(
+            "Reverted {} files changed in changeset #{}",
+            count, id
+        ));
+        return Ok(());
+    }
+
     // -----------------------------------------------------------------------
     // File operations
     // -----------------------------------------------------------------------
@@ -156,77 +194,41 @@ pub fn handle_r(
             project_path.join(f).to_string_lossy().to_string()
         };
 
-        // Try daemon (fast path — no temp files needed)
-        if let Some(mut client) = daemon {
-            match client.call(
-                methods::SNAPSHOT_LIST,
-                serde_json::json!({ "file_path": full_path }),
-            ) {
-                Ok(res) => {
-                    match serde_json::from_value::<Vec<SnapshotInfo>>(res.clone()) {
-                        Ok(mut history) => {
-                            if let Some(ref br) = branch {
-                                history.retain(|s| s.git_branch.as_deref().unwrap_or("main") == br);
-                            }
-                            let max = limit.unwrap_or(50);
-                            history.truncate(max);
-
-                            layout.header_dashboard("RESTORE VERSIONS");
-                            layout.section_branch("fi", f);
-                            layout.item_simple(&format!("Found {} versions", history.len()));
-
-                            for (i, snap) in history.iter().enumerate() {
-                                let hash_short =
-                                    &snap.content_hash[..8.min(snap.content_hash.len())];
-                                layout.row_version_with_link(
-                                    i + 1,
-                                    hash_short,
-                                    &snap.content_hash,
-                                    &snap.file_path,
-                                    &snap.timestamp,
-                                    i == 0,
-                                    None,
-                                    &ide,
-                                );
-                            }
-                            layout.section_end();
-                            layout.footer("Use 'mnem r <file> [version]' to restore");
-                            return Ok(());
-                        }
-                        Err(parse_err) => {
-                            // Debug: show what we got from daemon
-                            layout.warning(&format!("Daemon parse error: {}", parse_err));
-                            layout.info(&format!("Raw response: {}", res));
-                        }
-                    }
+        let repo = match access {
+            Access::Local(repo) => repo,
+            // Through the daemon (fast path — no temp files needed)
+            mut daemon => {
+                let mut history = daemon.history(&full_path)?;
+                if let Some(ref br) = branch {
+                    history.retain(|s| s.git_branch.as_deref().unwrap_or("main") == br);
                 }
-                Err(e) => {
-                    layout.warning(&format!("Daemon error: {e}"));
+                let max = limit.unwrap_or(50);
+                history.truncate(max);
+
+                layout.header_dashboard("RESTORE VERSIONS");
+                layout.section_branch("fi", f);
+                layout.item_simple(&format!("Found {} versions", history.len()));
+
+                for (i, snap) in history.iter().enumerate() {
+                    let hash_short = &snap.content_hash[..8.min(snap.content_hash.len())];
+                    layout.row_version_with_link(
+                        i + 1,
+                        hash_short,
+                        &snap.content_hash,
+                        &snap.file_path,
+                        &snap.timestamp,
+                        i == 0,
+                        None,
+                        &ide,
+                    );
                 }
-            }
-        }
-
-        // Fallback: direct DB (daemon not running)
-        let repo = match repo_opt {
-            Some(r) => r,
-            None => {
-                layout.error("Cannot connect to daemon and cannot open local DB.");
+                layout.section_end();
+                layout.footer("Use 'mnem r <file> [version]' to restore");
                 return Ok(());
             }
         };
 
-        let mut history = match repo.get_history(clean_path) {
-            Ok(h) => h,
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("lock") || msg.contains("Database already open") {
-                    layout.error("Cannot access history while daemon is running.");
-                    layout.info_bright("Run 'mnem off' to stop the daemon first.");
-                    return Ok(());
-                }
-                return Err(anyhow::anyhow!("{}", e));
-            }
-        };
+        let mut history = repo.get_history(&full_path)?;
 
         if let Some(ref br) = branch {
             history.retain(|s| s.git_branch.as_deref().unwrap_or("main") == br);
@@ -351,61 +353,61 @@ pub fn handle_r(
     // -----------------------------------------------------------------------
     // Restore helpers (daemon-first)
     // -----------------------------------------------------------------------
-    let do_restore = |daemon_opt: Option<DaemonClient>,
-                      repo_ref: Option<&Repository>,
-                      hash: &str,
-                      sym: Option<&String>|
-     -> Result<()> {
-        let target = project_path.join(clean_path).to_string_lossy().to_string();
-        if let Some(mut c) = daemon_opt {
-            if let Some(s) = sym {
-                let _ = c.call(
-                    methods::SNAPSHOT_RESTORE_SYMBOL_V1,
-                    serde_json::json!({ "content_hash": hash, "target_path": target, "symbol_name": s }),
-                )?;
-            } else {
-                let _ = c.call(
-                    methods::SNAPSHOT_RESTORE_V1,
-                    serde_json::json!({ "content_hash": hash, "target_path": target }),
-                )?;
+    let full_path = project_path.join(clean_path).to_string_lossy().to_string();
+    let do_restore = |access: &mut Access, hash: &str, sym: Option<&String>| -> Result<()> {
+        match access {
+            Access::Daemon(c) => {
+                if let Some(s) = sym {
+                    let _ = c.call(
+                        methods::SNAPSHOT_RESTORE_SYMBOL_V1,
+                        serde_json::json!({ "content_hash": hash, "target_path": full_path, "symbol_name": s }),
+                    )?;
+                } else {
+                    let _ = c.call(
+                        methods::SNAPSHOT_RESTORE_V1,
+                        serde_json::json!({ "content_hash": hash, "target_path": full_path }),
+                    )?;
+                }
             }
-        } else if let Some(repo) = repo_ref {
-            if let Some(s) = sym {
-                repo.restore_symbol(clean_path, hash, s)?;
-            } else {
-                repo.restore_file(hash, clean_path)?;
+            Access::Local(repo) => {
+                if let Some(s) = sym {
+                    repo.restore_symbol(clean_path, hash, s)?;
+                } else {
+                    repo.restore_file(hash, clean_path)?;
+                }
             }
-        } else {
-            anyhow::bail!("Neither daemon nor local DB is available");
         }
         Ok(())
     };
 
+    let size_before = fs::metadata(&full_path).ok().map(|m| m.len());
+    let report_blob = |hash_before: Option<&str>| {
+        if let Some(change) = blob_change(&config.config, &full_path, size_before, hash_before) {
+            layout.info(&change.describe());
+        }
+    };
+
     // --undo
     if undo {
-        let history = get_history_for_restore(
-            daemon.as_ref().map(|_| ()),
-            repo_opt.as_ref(),
-            &project_path,
-            clean_path,
-            &mut DaemonClient::connect().ok(),
-        )?;
+        let history = access.history(&full_path)?;
         if history.len() < 2 {
             anyhow::bail!("No previous version to restore");
         }
         let prev_hash = history[1].content_hash.clone();
         let prev_ts = history[1].timestamp.clone();
-        do_restore(daemon, repo_opt.as_ref(), &prev_hash, None)?;
+        do_restore(&mut access, &prev_hash, None)?;
         layout.success(&format!(
             "Restored {} to version from {}",
             clean_path, prev_ts
         ));
+        report_blob(Some(&history[0].content_hash));
         return Ok(());
     }
 
     // --to <hash>
     if let Some(ref hash) = to {
-        do_restore(daemon, repo_opt.as_ref(), hash, symbol.as_ref())?;
+        let history = access.history(&full_path).unwrap_or_default();
+        do_restore(&mut access, hash, symbol.as_ref())?;
         if let Some(ref sym) = symbol {
             layout.success(&format!(
                 "Restored symbol '{}' in {} to {}",
@@ -419,24 +421,19 @@ pub fn handle_r(
                 clean_path,
                 &hash[..8.min(hash.len())]
             ));
+            report_blob(history.first().map(|s| s.content_hash.as_str()));
         }
         return Ok(());
     }
 
     // <version>
     if let Some(v) = version {
-        let history = get_history_for_restore(
-            daemon.as_ref().map(|_| ()),
-            repo_opt.as_ref(),
-            &project_path,
-            clean_path,
-            &mut DaemonClient::connect().ok(),
-        )?;
+        let history = access.history(&full_path)?;
         if v == 0 || v > history.len() {
             anyhow::bail!("Invalid version number. Use --list to see available versions.");
         }
         let target_hash = history[v - 1].content_hash.clone();
-        do_restore(daemon, repo_opt.as_ref(), &target_hash, symbol.as_ref())?;
+        do_restore(&mut access, &target_hash, symbol.as_ref())?;
         if let Some(ref sym) = symbol {
             layout.success(&format!(
                 "Restored symbol '{}' in {} to version {}",
@@ -444,6 +441,7 @@ pub fn handle_r(
             ));
         } else {
             layout.success(&format!("Restored {} to version {}", clean_path, v));
+            report_blob(Some(&history[0].content_hash));
         }
         return Ok(());
     }
@@ -460,45 +458,3 @@ pub fn handle_r(
 
     Ok(())
 }
-
-/// Get snapshot history, preferring daemon then falling back to direct DB.
-fn get_history_for_restore(
-    daemon_present: Option<()>,
-    repo_opt: Option<&Repository>,
-    project_path: &PathBuf,
-    clean_path: &str,
-    client: &mut Option<DaemonClient>,
-) -> Result<Vec<SnapshotInfo>> {
-    if daemon_present.is_some() {
-        if let Some(c) = client.as_mut() {
-            let full_path = project_path.join(clean_path).to_string_lossy().to_string();
-            let res = c.call(
-                methods::SNAPSHOT_LIST,
-                serde_json::json!({ "file_path": full_path }),
-            )?;
-            match serde_json::from_value::<Vec<SnapshotInfo>>(res.clone()) {
-                Ok(history) => return Ok(history),
-                Err(e) => {
-                    eprintln!("Daemon parse error in get_history_for_restore: {}", e);
-                    eprintln!("Raw response: {:?}", res);
-                }
-            }
-        }
-    }
-    if let Some(repo) = repo_opt {
-        let snaps = repo.get_history(clean_path)?;
-        return Ok(snaps
-            .into_iter()
-            .map(|s| SnapshotInfo {
-                id: s.id,
-                file_path: s.file_path,
-                timestamp: s.timestamp,
-                content_hash: s.content_hash,
-                git_branch: s.git_branch,
-                commit_hash: s.commit_hash,
-                commit_message: s.commit_message,
-            })
-            .collect());
-    }
-    anyhow::bail!("Neither daemon nor local DB is available to fetch history")
-}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 
+use crate::handlers::access::Access;
 use crate::ui::Layout;
 use std::fs;
 use std::path::PathBuf;
@@ -53,9 +54,11 @@ pub fn handle_s(
     _file: Option<String>,
     limit: Option<usize>,
     semantic: bool,
+    regex: bool,
 ) -> Result<()> {
     use mnem_core::config::ConfigManager;
-    use mnem_core::storage::Repository;
+    use mnem_core::models::SearchResult;
+    use mnem_core::protocol::{SymbolLocation, methods};
     use std::collections::BTreeMap;
 
     let layout = Layout::new();
@@ -65,7 +68,8 @@ pub fn handle_s(
     };
     let config = ConfigManager::new(&base_dir)?;
     let ide = config.config.ide;
-    let repo = Repository::open(base_dir, cwd)?;
+    let mut access = Access::connect(&cwd)?;
+    let project_path = cwd.to_string_lossy().to_string();
 
     // Clean up old temp files
     cleanup_old_temp_files();
@@ -73,7 +77,13 @@ pub fn handle_s(
     let query = query.ok_or_else(|| anyhow::anyhow!("Specify a search query"))?;
 
     if semantic {
-        let results = repo.find_symbols(&query)?;
+        let results: Vec<SymbolLocation> = match &mut access {
+            Access::Daemon(client) => serde_json::from_value(client.call(
+                methods::SYMBOL_SEARCH,
+                serde_json::json!({ "query": query, "project_path": project_path }),
+            )?)?,
+            Access::Local(repo) => repo.find_symbols(&query)?,
+        };
         layout.header_dashboard(&format!("SEMANTIC: {}", query));
 
         if results.is_empty() {
@@ -84,7 +94,20 @@ pub fn handle_s(
             }
         }
     } else {
-        let results = repo.grep_contents(&query, None)?;
+        let results: Vec<SearchResult> = match &mut access {
+            Access::Daemon(client) => {
+                let res = client.call(
+                    methods::CONTENT_SEARCH_V1,
+                    serde_json::json!({
+                        "query": query,
+                        "regex": regex,
+                        "project_path": project_path,
+                    }),
+                )?;
+                serde_json::from_value(res["results"].clone())?
+            }
+            Access::Local(repo) => repo.grep_contents(&query, None, regex)?,
+        };
         layout.header_dashboard(&format!("SEARCH: {}", query));
 
         if results.is_empty() {
@@ -115,7 +138,7 @@ pub fn handle_s(
                 layout.row_file_path(&path);
 
                 // Get snapshot info and pre-create temp file
-                if let Ok(hist) = repo.get_history(&path) {
+                if let Ok(hist) = access.history(&path) {
                     if let Some(snap) = hist.first() {
                         let hash_short = &snap.content_hash[..8.min(snap.content_hash.len())];
                         let timestamp = snap.timestamp.to_string();
@@ -137,7 +160,7 @@ pub fn handle_s(
                         );
 
                         // Create temp file for snapshot version (reuse if exists)
-                        let temp_path = if let Ok(content) = repo.get_content(&snap.content_hash) {
+                        let temp_path = if let Ok(content) = access.content(&snap.content_hash) {
                             // Get original file extension
                             let extension = std::path::Path::new(&path)
                                 .extension()
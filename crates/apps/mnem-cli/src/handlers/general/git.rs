@@ -1,35 +1,73 @@
-
+use crate::handlers::access::Access;
+use crate::handlers::workspace::bundle::parse_time;
 use anyhow::Result;
+use mnem_core::env::get_base_dir;
+use mnem_core::protocol::{GitCommitInfo, methods};
+use mnem_core::storage::commit_queue::{self, GitCommitEvent};
+use mnem_core::storage::git_import::{GitImportOptions, GitImportReport};
+use std::path::Path;
+
+const HOOK_MARKER: &str = "# Mnemosyne post-commit hook";
+
+const HOOK_SCRIPT: &str = r#"#!/bin/sh
+# Mnemosyne post-commit hook
+# Links the latest snapshots to the official Git commit
 
-pub fn handle_git(commits: bool, log: bool, _hook: bool) -> Result<()> {
-    use mnem_core::env::get_base_dir;
-    use mnem_core::storage::Repository;
+if command -v mnem >/dev/null 2>&1; then
+    COMMIT_HASH=$(git rev-parse HEAD)
+    AUTHOR=$(git log -1 --pretty=%an)
+    MESSAGE=$(git log -1 --pretty=%s)
+    TIMESTAMP=$(git log -1 --pretty=%cI)
 
-    let base_dir = get_base_dir()?;
+    mnem git-event "$COMMIT_HASH" "$MESSAGE" "$AUTHOR" "$TIMESTAMP"
+else
+    echo "Warning: Mnemosyne (mnem) not found in PATH. Skipping integration."
+fi
+"#;
+
+pub fn handle_git(commits: bool, log: bool, hook: bool) -> Result<()> {
     let cwd = std::env::current_dir()?;
-    let repo = Repository::open(base_dir, cwd)?;
-
-    if commits {
-        let git_commits = repo.list_commits()?;
-        println!("Git Commits:");
-        println!("─");
-
-        for (hash, msg, author, ts, files) in git_commits {
-            println!("{}  {}  {}", &hash[..8], ts, msg);
-            println!("  Author: {}", author);
-            println!("  Files: {}", files);
-            println!();
-        }
-        return Ok(());
+
+    if hook {
+        return install_hook(&cwd);
     }
 
-    if log {
-        let git_commits = repo.list_commits()?;
-        println!("Git Log:");
-        println!("─");
+    if commits || log {
+        let git_commits: Vec<GitCommitInfo> = match Access::connect(&cwd)? {
+            Access::Daemon(mut client) => serde_json::from_value(client.call(
+                methods::GIT_LIST_COMMITS,
+                serde_json::json!({ "project_path": cwd.to_string_lossy() }),
+            )?)?,
+            Access::Local(repo) => repo
+                .list_commits()?
+                .into_iter()
+                .map(|(hash, message, author, timestamp, files)| GitCommitInfo {
+                    hash,
+                    message,
+                    author,
+                    timestamp,
+                    files,
+                })
+                .collect(),
+        };
+
+        if commits {
+            println!("Git Commits:");
+            println!("─");
 
-        for (hash, msg, _author, ts, _files) in git_commits {
-            println!("{}  {}  {}", &hash[..8], ts, msg);
+            for c in git_commits {
+                println!("{}  {}  {}", &c.hash[..8], c.timestamp, c.message);
+                println!("  Author: {}", c.author);
+                println!("  Files: {}", c.files);
+                println!();
+            }
+        } else {
+            println!("Git Log:");
+            println!("─");
+
+            for c in git_commits {
+                println!("{}  {}  {}", &c.hash[..8], c.timestamp, c.message);
+            }
         }
         return Ok(());
     }
@@ -37,7 +75,118 @@ pub fn handle_git(commits: bool, log: bool, _hook: bool) -> Result<()> {
     println!("Usage:");
     println!("  mnem git --commits   # list commits");
     println!("  mnem git --log      # git log");
-    println!("  mnem git --hook     # install hook");
+    println!("  mnem git --hook     # record commits instantly (optional)");
 
     Ok(())
 }
+
+fn install_hook(project_path: &Path) -> Result<()> {
+    let hooks_dir = project_path.join(".git").join("hooks");
+    if !hooks_dir.exists() {
+        anyhow::bail!("Not a git repository (no .git/hooks found)");
+    }
+
+    let hook_path = hooks_dir.join("post-commit");
+    let foreign =
+        std::fs::read_to_string(&hook_path).is_ok_and(|existing| !existing.contains(HOOK_MARKER));
+    if foreign {
+        anyhow::bail!(
+            "{} already exists. Add 'mnem git-event' to it by hand.",
+            hook_path.display()
+        );
+    }
+    std::fs::write(&hook_path, HOOK_SCRIPT)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(&hook_path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(&hook_path, perms)?;
+    }
+
+    println!("✓ Git hook installed");
+    println!("  Commits are recorded as they are made instead of on the next look at HEAD.");
+    Ok(())
+}
+
+/// Backfills the history from before tracking started with local git commits.
+pub fn handle_import_git(since: Option<String>, branch: Option<String>) -> Result<()> {
+    let options = GitImportOptions {
+        since: since.as_deref().map(parse_time).transpose()?,
+        branch,
+    };
+
+    let cwd = std::env::current_dir()?;
+    let report: GitImportReport = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::GIT_IMPORT,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "since": options.since.map(|t| t.to_rfc3339()),
+                "branch": options.branch,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.import_git(&options)?,
+    };
+    println!(
+        "✓ Imported {} commits from {}",
+        report.commits, report.branch
+    );
+    println!("  {} file versions written", report.snapshots);
+    if report.skipped > 0 {
+        println!(
+            "  {} newer commits skipped: history is already recorded from then on",
+            report.skipped
+        );
+    }
+    Ok(())
+}
+
+/// Called by the post-commit hook. Never waits for the history store or a
+/// passphrase: a commit that cannot be recorded right now is queued for
+/// whichever process owns the store next.
+pub fn handle_git_event(
+    hash: String,
+    message: String,
+    author: String,
+    timestamp: String,
+) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    if !cwd.join(".mnemosyne").join("tracked").exists() {
+        return Ok(());
+    }
+
+    let event = GitCommitEvent {
+        hash,
+        message,
+        author,
+        timestamp,
+    };
+    if record_commit(&cwd, &event).is_err() {
+        commit_queue::enqueue(&get_base_dir()?, &cwd, &event)?;
+    }
+    Ok(())
+}
+
+fn record_commit(project_path: &Path, event: &GitCommitEvent) -> Result<()> {
+    let Some(access) = Access::connect_unattended(project_path)? else {
+        anyhow::bail!("History is encrypted and locked");
+    };
+    match access {
+        Access::Daemon(mut client) => {
+            client.call(
+                methods::GIT_RECORD_COMMIT,
+                serde_json::json!({
+                    "project_path": project_path.to_string_lossy(),
+                    "hash": event.hash,
+                    "message": event.message,
+                    "author": event.author,
+                    "timestamp": event.timestamp,
+                }),
+            )?;
+        }
+        Access::Local(repo) => repo.record_git_commit(event)?,
+    }
+    Ok(())
+}
@@ -1,3 +1,5 @@
 pub mod git;
 
 pub use git::handle_git;
+pub use git::handle_git_event;
+pub use git::handle_import_git;
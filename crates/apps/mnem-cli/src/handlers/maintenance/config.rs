@@ -1,15 +1,32 @@
-
 use anyhow::Result;
 
 use crate::ui::Layout;
+use mnem_core::config::{ConfigManager, ConfigSource};
 
-pub fn handle_config(get: Option<String>, set: Option<String>, reset: bool) -> Result<()> {
-    use mnem_core::config::ConfigManager;
+pub fn handle_config(
+    get: Option<String>,
+    set: Option<String>,
+    reset: bool,
+    project: bool,
+) -> Result<()> {
     use mnem_core::env::get_base_dir;
 
     let layout = Layout::new();
     let base_dir = get_base_dir()?;
-    let config = ConfigManager::new(&base_dir)?;
+    let cwd = std::env::current_dir()?;
+    let tracked = cwd.join(".mnemosyne").join("tracked").exists();
+
+    if project && !tracked {
+        layout.badge_error("ERROR", "This project is not tracked");
+        layout.info_bright("Run 'mnem track' to start tracking this project.");
+        anyhow::bail!("Project not tracked");
+    }
+
+    let mut config = if tracked {
+        ConfigManager::with_project(&base_dir, &cwd)?
+    } else {
+        ConfigManager::new(&base_dir)?
+    };
 
     if reset {
         layout.header_dashboard("CONFIG");
@@ -19,21 +36,21 @@ pub fn handle_config(get: Option<String>, set: Option<String>, reset: bool) -> R
     }
 
     if let Some(key) = get {
+        let key = match key.as_str() {
+            "max-file-size" => "max_file_size_mb".to_string(),
+            _ => key,
+        };
         layout.header_dashboard("CONFIG");
         layout.section_timeline("cf", "Setting");
-        match key.as_str() {
-            "ide" => layout.row_labeled("◆", "IDE", &config.config.ide.as_str()),
-            "max-file-size" => layout.row_labeled(
-                "◫",
-                "Max File Size",
-                &format!("{} MB", config.config.max_file_size_mb),
-            ),
-            "retention-days" => layout.row_labeled(
-                "◷",
-                "Retention Days",
-                &config.config.retention_days.to_string(),
-            ),
-            _ => layout.error(&format!("Unknown config key: {}", key)),
+        match config.get(&key) {
+            Ok((value, source)) => {
+                let text = value
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| value.to_string());
+                layout.row_labeled("◆", &key, &format!("{} ({})", text, source.as_str()))
+            }
+            Err(_) => layout.error(&format!("Unknown config key: {}", key)),
         }
         layout.section_end();
         return Ok(());
@@ -42,34 +59,77 @@ pub fn handle_config(get: Option<String>, set: Option<String>, reset: bool) -> R
     if let Some(key_value) = set {
         let parts: Vec<&str> = key_value.splitn(2, '=').collect();
         if parts.len() != 2 {
-            layout.error("Usage: mnem config --set key=value");
+            layout.error("Usage: mnem config --set key=value [--project]");
             return Ok(());
         }
 
+        let layer = if project {
+            ConfigSource::Project
+        } else {
+            ConfigSource::Global
+        };
         layout.header_dashboard("CONFIG");
-        layout.success_bright(&format!("✓ Set {} = {}", parts[0], parts[1]));
+        match config.set(parts[0], parts[1], layer) {
+            Ok(()) => layout.success_bright(&format!(
+                "✓ Set {} = {} ({})",
+                parts[0],
+                parts[1],
+                layer.as_str()
+            )),
+            Err(e) => layout.error(&e.to_string()),
+        }
         return Ok(());
     }
 
     layout.header_dashboard("CONFIGURATION");
     layout.section_timeline("cf", "Current Settings");
-    layout.row_labeled("◆", "IDE", &config.config.ide.as_str());
+    layout.row_labeled(
+        "◆",
+        "IDE",
+        &with_source(&config, "ide", config.config.ide.as_str()),
+    );
     layout.row_labeled(
         "◫",
         "Max File Size",
-        &format!("{} MB", config.config.max_file_size_mb),
+        &with_source(
+            &config,
+            "max_file_size_mb",
+            &format!("{} MB", config.config.max_file_size_mb),
+        ),
     );
     layout.row_labeled(
         "◷",
-        "Retention Days",
-        &config.config.retention_days.to_string(),
+        "Retention",
+        &with_source(&config, "retention", &config.config.retention.summary()),
+    );
+    layout.row_labeled(
+        "◧",
+        "Binary Files",
+        &with_source(&config, "binary", &describe_binary(&config.config.binary)),
     );
     layout.section_end();
     layout.empty();
     layout.badge_info(
         "TIP",
-        "Use 'mnem config --set key=value' to change settings",
+        "Use 'mnem config --set key=value' to change settings, add --project for this project only",
     );
 
     Ok(())
 }
+
+fn with_source(config: &ConfigManager, key: &str, text: &str) -> String {
+    match config.get(key) {
+        Ok((_, ConfigSource::Default)) | Err(_) => text.to_string(),
+        Ok((_, source)) => format!("{} ({})", text, source.as_str()),
+    }
+}
+
+fn describe_binary(policy: &mnem_core::config::BinaryPolicy) -> String {
+    if !policy.enabled {
+        "not tracked".to_string()
+    } else if policy.max_size_mb == 0 {
+        "tracked · no size limit".to_string()
+    } else {
+        format!("tracked · up to {} MB", policy.max_size_mb)
+    }
+}
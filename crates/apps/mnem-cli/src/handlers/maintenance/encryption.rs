@@ -0,0 +1,57 @@
+use crate::handlers::access::read_passphrase;
+use anyhow::Result;
+use mnem_core::client::daemon_running;
+use mnem_core::crypto::{self, KeySource};
+use mnem_core::env::get_base_dir;
+use std::path::PathBuf;
+
+/// Enables encryption at rest. `key_file` of `Some` uses a key file, created
+/// when missing; an empty path means `master.key` in the data directory.
+pub fn handle_encryption_init(key_file: Option<PathBuf>) -> Result<()> {
+    let base_dir = get_base_dir()?;
+    if let Some(source) = crypto::status(&base_dir)? {
+        anyhow::bail!("Encryption is already enabled ({})", source.as_str());
+    }
+    if daemon_running() {
+        anyhow::bail!("Stop the daemon first with 'mnem off'");
+    }
+
+    match key_file {
+        Some(path) => {
+            let path = (!path.as_os_str().is_empty()).then_some(path);
+            let used = crypto::enable_with_key_file(&base_dir, path.as_deref())?;
+            println!("✓ Encryption enabled with key file {}", used.display());
+            println!("  Back it up: history cannot be read without it.");
+        }
+        None => {
+            let passphrase = read_passphrase("New passphrase: ")?;
+            if read_passphrase("Repeat passphrase: ")? != passphrase {
+                anyhow::bail!("Passphrases do not match");
+            }
+            crypto::enable_with_passphrase(&base_dir, &passphrase)?;
+            println!("✓ Encryption enabled with a passphrase");
+            println!("  History cannot be recovered without it.");
+        }
+    }
+    println!("  Each project is encrypted the next time it is opened.");
+    println!("  Run 'mnem on' to start the daemon.");
+    Ok(())
+}
+
+pub fn handle_encryption_status() -> Result<()> {
+    let base_dir = get_base_dir()?;
+    match crypto::status(&base_dir)? {
+        None => println!("Encryption: off"),
+        Some(source) => {
+            println!("Encryption: on");
+            println!("  Key:     {}", source.as_str());
+            if source == KeySource::Passphrase {
+                println!(
+                    "  Unlock:  'mnem on' prompts for it, or set {}",
+                    crypto::PASSPHRASE_ENV
+                );
+            }
+        }
+    }
+    Ok(())
+}
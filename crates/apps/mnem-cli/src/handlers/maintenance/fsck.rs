@@ -0,0 +1,57 @@
+use crate::handlers::access::Access;
+use anyhow::Result;
+use mnem_core::protocol::methods;
+use mnem_core::storage::fsck::FsckReport;
+
+pub fn handle_fsck(repair: bool) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let access = Access::connect(&cwd)?;
+
+    println!("Checking history integrity...");
+    let report: FsckReport = match access {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::MAINTENANCE_FSCK,
+            serde_json::json!({ "project_path": cwd.to_string_lossy(), "repair": repair }),
+        )?)?,
+        Access::Local(repo) => repo.fsck(repair)?,
+    };
+    println!(
+        "  Checked {} snapshots, {} chunks, {} objects",
+        report.snapshots_checked, report.chunks_checked, report.objects_checked
+    );
+
+    for issue in &report.issues {
+        let mut subject = String::new();
+        if let Some(id) = issue.snapshot_id {
+            subject.push_str(&format!(" snapshot #{}", id));
+        }
+        if let Some(hash) = &issue.object {
+            subject.push_str(&format!(" {}", &hash[..hash.len().min(12)]));
+        }
+        println!("  ✗ {}{}: {}", issue.kind.as_str(), subject, issue.detail);
+    }
+
+    if let Some(repairs) = &report.repairs {
+        println!("✓ Repair complete");
+        println!("  Snapshots quarantined: {}", repairs.snapshots_quarantined);
+        println!("  Chunk rows restored:   {}", repairs.chunk_rows_restored);
+        println!("  Rows deleted:          {}", repairs.rows_deleted);
+        println!("  Objects deleted:       {}", repairs.objects_deleted);
+        println!("  Chunks reindexed:      {}", repairs.trigrams_reindexed);
+        println!(
+            "  Symbols reindexed:     {} snapshots",
+            repairs.symbols_reindexed
+        );
+        return Ok(());
+    }
+
+    if report.is_clean() {
+        println!("✓ No problems found");
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "{} problem(s) found, run 'mnem fsck --repair' to fix them",
+            report.issues.len()
+        )
+    }
+}
@@ -1,12 +1,12 @@
+use crate::handlers::access::Access;
 use anyhow::Result;
+use mnem_core::config::RetentionPolicy;
+use mnem_core::models::GcReport;
+use mnem_core::protocol::methods;
 
-pub fn handle_gc(keep: Option<usize>, dry_run: bool, aggressive: bool) -> Result<()> {
-    use mnem_core::env::get_base_dir;
-    use mnem_core::storage::Repository;
-
-    let base_dir = get_base_dir()?;
+pub fn handle_gc(keep: Option<u64>, dry_run: bool, aggressive: bool) -> Result<()> {
     let cwd = std::env::current_dir()?;
-    let repo = Repository::open(base_dir, cwd)?;
+    let access = Access::connect(&cwd)?;
 
     if dry_run {
         println!("Dry run - would clean old snapshots");
@@ -16,17 +16,34 @@ pub fn handle_gc(keep: Option<usize>, dry_run: bool, aggressive: bool) -> Result
 
     println!("Running garbage collection...");
 
-    if let Some(days) = keep {
-        println!("  Keeping last {} days", days);
+    match keep {
+        Some(0) => println!("  Keeping all history"),
+        Some(days) => println!("  Keeping last {} days", days),
+        None => {}
     }
 
     if aggressive {
         println!("  Aggressive mode: enabled");
     }
 
-    // This would actually run the GC
-    // let removed = repo.run_gc()?;
+    let report: GcReport = match access {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::MAINTENANCE_GC,
+            serde_json::json!({ "project_path": cwd.to_string_lossy(), "keep_days": keep }),
+        )?)?,
+        Access::Local(repo) => match keep {
+            Some(days) => repo.run_gc_with_policy(&RetentionPolicy::within_days(days))?,
+            None => repo.run_gc()?,
+        },
+    };
     println!("✓ Garbage collection complete");
+    println!("  Snapshots pruned: {}", report.snapshots_pruned);
+    println!("  Chunks deleted:   {}", report.chunks_deleted);
+    println!(
+        "  Symbols deleted:  {} ({} references, {} deltas)",
+        report.symbols_deleted, report.references_deleted, report.deltas_deleted
+    );
+    println!("  Reclaimed:        {} bytes", report.bytes_reclaimed);
 
     Ok(())
 }
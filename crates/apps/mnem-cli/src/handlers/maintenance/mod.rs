@@ -1,9 +1,13 @@
 pub mod config;
+pub mod encryption;
+pub mod fsck;
 pub mod gc;
 pub mod uninstall;
 pub mod update;
 
 pub use config::handle_config;
+pub use encryption::{handle_encryption_init, handle_encryption_status};
+pub use fsck::handle_fsck;
 pub use gc::handle_gc;
 pub use uninstall::handle_uninstall;
 pub use update::handle_update;
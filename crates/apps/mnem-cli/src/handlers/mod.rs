@@ -1,3 +1,4 @@
+pub mod access;
 pub mod daemon;
 pub mod files;
 pub mod general;
@@ -8,13 +9,27 @@ pub use daemon::handle_mcp;
 pub use daemon::handle_off;
 pub use daemon::handle_on;
 pub use daemon::handle_status;
+pub use files::handle_changesets;
+pub use files::handle_checkpoint_diff;
+pub use files::handle_checkpoint_status;
 pub use files::handle_h;
 pub use files::handle_info;
+pub use files::handle_pin;
 pub use files::handle_r;
 pub use files::handle_s;
 pub use general::handle_git;
+pub use general::handle_git_event;
+pub use general::handle_import_git;
 pub use maintenance::handle_config;
+pub use maintenance::handle_encryption_init;
+pub use maintenance::handle_encryption_status;
+pub use maintenance::handle_fsck;
 pub use maintenance::handle_gc;
 pub use maintenance::handle_uninstall;
 pub use maintenance::handle_update;
+pub use workspace::handle_bundle_export;
+pub use workspace::handle_bundle_import;
+pub use workspace::handle_export_tree;
+pub use workspace::handle_sessions;
+pub use workspace::handle_timesheet;
 pub use workspace::handle_track;
@@ -0,0 +1,111 @@
+use crate::handlers::access::Access;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use mnem_core::protocol::{BundleImportResponse, methods};
+use mnem_core::storage::bundle::{BundleFilter, BundleManifest};
+use std::path::{Path, PathBuf};
+
+/// Accepts an RFC 3339 timestamp or a `YYYY-MM-DD` date (local midnight).
+pub(crate) fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid time '{}', expected YYYY-MM-DD or RFC 3339", value))?;
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .with_context(|| format!("Invalid local time '{}'", value))
+}
+
+pub fn handle_bundle_export(
+    output: PathBuf,
+    since: Option<String>,
+    until: Option<String>,
+    path: Option<String>,
+) -> Result<()> {
+    let filter = BundleFilter {
+        since: since.as_deref().map(parse_time).transpose()?,
+        until: until.as_deref().map(parse_time).transpose()?,
+        path_glob: path,
+    };
+
+    let cwd = std::env::current_dir()?;
+    let output = cwd.join(output);
+    let manifest: BundleManifest = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::BUNDLE_EXPORT,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "output": output.to_string_lossy(),
+                "since": filter.since.map(|t| t.to_rfc3339()),
+                "until": filter.until.map(|t| t.to_rfc3339()),
+                "path_glob": filter.path_glob,
+            }),
+        )?)?,
+        Access::Local(repo) => repo.export_bundle(&output, &filter)?,
+    };
+    println!("✓ Bundle written to {}", output.display());
+    println!(
+        "  {} snapshots, {} sessions, {} commits, {} checkpoints",
+        manifest.snapshots, manifest.sessions, manifest.commits, manifest.checkpoints
+    );
+    println!(
+        "  {} symbols, {} objects",
+        manifest.symbols, manifest.objects
+    );
+
+    Ok(())
+}
+
+pub fn handle_bundle_import(input: PathBuf) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let input = cwd.join(input);
+    let (project_id, manifest, report) = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => {
+            let response: BundleImportResponse = serde_json::from_value(client.call(
+                methods::BUNDLE_IMPORT,
+                serde_json::json!({
+                    "project_path": cwd.to_string_lossy(),
+                    "input": input.to_string_lossy(),
+                }),
+            )?)?;
+            (tracked_project_id(&cwd), response.manifest, response.report)
+        }
+        Access::Local(repo) => {
+            let (manifest, report) = repo.import_bundle(&input)?;
+            (Some(repo.project.id.clone()), manifest, report)
+        }
+    };
+    if project_id.is_some_and(|id| id != manifest.project_id) {
+        println!(
+            "  Note: bundle comes from project '{}' ({})",
+            manifest.project_name, manifest.project_id
+        );
+    }
+    println!("✓ Bundle imported from {}", input.display());
+    println!(
+        "  Snapshots: {} imported, {} already present",
+        report.snapshots_imported, report.snapshots_skipped
+    );
+    println!(
+        "  {} sessions, {} commits, {} checkpoints, {} symbols",
+        report.sessions_imported,
+        report.commits_imported,
+        report.checkpoints_imported,
+        report.symbols_imported
+    );
+    println!("  Objects written: {}", report.objects_written);
+
+    Ok(())
+}
+
+fn tracked_project_id(project_path: &Path) -> Option<String> {
+    let tracked = std::fs::read_to_string(project_path.join(".mnemosyne").join("tracked")).ok()?;
+    tracked
+        .lines()
+        .find_map(|l| l.strip_prefix("project_id:"))
+        .map(|id| id.trim().to_string())
+}
@@ -0,0 +1,84 @@
+use crate::handlers::access::Access;
+use crate::handlers::workspace::bundle::parse_time;
+use anyhow::Result;
+use mnem_core::protocol::methods;
+use mnem_core::storage::tree::{MANIFEST_FILE, TreeManifest};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub fn handle_export_tree(at: String, dest: PathBuf, tar: bool) -> Result<()> {
+    // Dates are local; checkpoints and commits go through as typed
+    let at = parse_time(&at).map(|t| t.to_rfc3339()).unwrap_or(at);
+    let cwd = std::env::current_dir()?;
+    let to_stdout = tar && dest == Path::new("-");
+
+    let manifest: TreeManifest = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => {
+            // The daemon writes files, so stdout goes through a temporary archive
+            let output = if to_stdout {
+                std::env::temp_dir().join(format!("mnem-export-{}.tar", std::process::id()))
+            } else {
+                cwd.join(&dest)
+            };
+            let response = client.call(
+                methods::PROJECT_EXPORT_TREE,
+                serde_json::json!({
+                    "project_path": cwd.to_string_lossy(),
+                    "at": at,
+                    "output": output.to_string_lossy(),
+                    "tar": tar,
+                }),
+            );
+            if to_stdout {
+                let copied = match &response {
+                    Ok(_) => copy_to_stdout(&output),
+                    Err(_) => Ok(()),
+                };
+                let _ = std::fs::remove_file(&output);
+                copied?;
+            }
+            serde_json::from_value(response?)?
+        }
+        Access::Local(repo) => {
+            if to_stdout {
+                repo.export_tree_tar(&at, std::io::stdout().lock())?
+            } else if tar {
+                let file = std::fs::File::create(cwd.join(&dest))?;
+                repo.export_tree_tar(&at, std::io::BufWriter::new(file))?
+            } else {
+                repo.export_tree(&at, &cwd.join(&dest))?
+            }
+        }
+    };
+
+    // Keep stdout clean for the archive
+    let mut out: Box<dyn Write> = if to_stdout {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    writeln!(
+        out,
+        "✓ {} files as of {} ({})",
+        manifest.files.len(),
+        manifest.at,
+        manifest.source
+    )?;
+    if !to_stdout {
+        let written = cwd.join(&dest);
+        let manifest_at = if tar {
+            format!("{} inside the archive", MANIFEST_FILE)
+        } else {
+            written.join(MANIFEST_FILE).display().to_string()
+        };
+        writeln!(out, "  Written to {}", written.display())?;
+        writeln!(out, "  Manifest: {}", manifest_at)?;
+    }
+    Ok(())
+}
+
+fn copy_to_stdout(path: &Path) -> Result<()> {
+    let mut archive = std::fs::File::open(path)?;
+    std::io::copy(&mut archive, &mut std::io::stdout().lock())?;
+    Ok(())
+}
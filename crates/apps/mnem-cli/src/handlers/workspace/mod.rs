@@ -1,3 +1,11 @@
+pub mod bundle;
+pub mod export_tree;
+pub mod session;
+pub mod timesheet;
 pub mod track;
 
+pub use bundle::{handle_bundle_export, handle_bundle_import};
+pub use export_tree::handle_export_tree;
+pub use session::handle_sessions;
+pub use timesheet::{TimesheetArgs, handle_timesheet};
 pub use track::handle_track;
@@ -0,0 +1,74 @@
+use crate::handlers::access::Access;
+use crate::ui::Layout;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use mnem_core::protocol::{SessionResponse, methods};
+
+pub fn handle_sessions(limit: Option<usize>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let limit = limit.unwrap_or(20);
+    let sessions: Vec<SessionResponse> = match Access::connect(&cwd)? {
+        Access::Daemon(mut client) => serde_json::from_value(client.call(
+            methods::SESSION_GET_LIST,
+            serde_json::json!({
+                "project_path": cwd.to_string_lossy(),
+                "limit": limit,
+            }),
+        )?)?,
+        Access::Local(repo) => repo
+            .list_sessions(limit)?
+            .into_iter()
+            .map(SessionResponse::from)
+            .collect(),
+    };
+
+    let layout = Layout::new();
+    layout.header_dashboard("SESSIONS");
+    if sessions.is_empty() {
+        layout.warning("No sessions recorded yet.");
+        return Ok(());
+    }
+    for session in &sessions {
+        let start = parse(&session.start_time);
+        let (until, end) = match session.end_time.as_deref().and_then(parse) {
+            Some(end) => (end.format("%H:%M").to_string(), end),
+            None => ("now".to_string(), Local::now()),
+        };
+        let when = match start {
+            Some(start) => format!(
+                "{} – {}  ({})",
+                start.format("%Y-%m-%d %H:%M"),
+                until,
+                format_minutes((end - start).num_minutes().max(0))
+            ),
+            None => session.start_time.clone(),
+        };
+        let code = format!("#{}", session.id);
+        layout.section_branch(&code, &when);
+        if session.end_time.is_none() {
+            layout.item_green("Active");
+        }
+        if let Some(branch) = &session.branch {
+            layout.row_key_value("Branch", branch);
+        }
+        layout.row_key_value("Files", &session.file_count.to_string());
+        layout.row_key_value("Saves", &session.snapshot_count.to_string());
+        layout.section_end();
+    }
+    layout.footer("A session ends after 'session_idle_minutes' without a save.");
+    Ok(())
+}
+
+fn parse(timestamp: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Local))
+}
+
+fn format_minutes(minutes: i64) -> String {
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::handlers::access::Access;
+use crate::ui::Layout;
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use mnem_core::env::get_base_dir;
+use mnem_core::protocol::{TimesheetEntry, methods};
+use mnem_core::storage::registry::ProjectRegistry;
+use mnem_core::storage::timesheet::{self, TimesheetOptions};
+use std::path::PathBuf;
+
+pub struct TimesheetArgs {
+    pub period: String,
+    pub by: Option<String>,
+    pub round: u64,
+    pub rounding: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub all: bool,
+    pub format: String,
+}
+
+pub fn handle_timesheet(args: TimesheetArgs) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let mut options: TimesheetOptions = serde_json::from_value(serde_json::json!({
+        "period": args.period,
+        "group_by": args.by,
+        "round_minutes": args.round,
+        "rounding": args.rounding,
+    }))?;
+    options.from = args.since.as_deref().map(day_start).transpose()?;
+    // --until names the last day included
+    options.to = args
+        .until
+        .as_deref()
+        .map(|day| day_start(day).map(|start| start + Duration::days(1).num_milliseconds()))
+        .transpose()?;
+
+    let projects: Vec<PathBuf> = if args.all {
+        ProjectRegistry::new(&get_base_dir()?)?
+            .list_projects()
+            .into_iter()
+            .map(|p| PathBuf::from(p.path))
+            .filter(|p| p.exists())
+            .collect()
+    } else {
+        vec![cwd.clone()]
+    };
+    let mut spans = Vec::new();
+    let mut from_daemon = None;
+    for project in &projects {
+        match Access::connect(project)? {
+            // The daemon bills every project in one go, opening those it
+            // does not watch
+            Access::Daemon(mut client) => {
+                let mut params = serde_json::to_value(&options)?;
+                if args.all {
+                    params["project_paths"] = serde_json::json!(
+                        projects
+                            .iter()
+                            .map(|p| p.to_string_lossy())
+                            .collect::<Vec<_>>()
+                    );
+                } else {
+                    params["project_path"] = serde_json::json!(cwd.to_string_lossy());
+                }
+                let res = client.call(methods::SESSION_GET_TIMESHEET, params)?;
+                from_daemon = Some(serde_json::from_value(res)?);
+                break;
+            }
+            Access::Local(repo) => spans.extend(repo.session_spans()?),
+        }
+    }
+    let entries: Vec<TimesheetEntry> = match from_daemon {
+        Some(entries) => entries,
+        None => timesheet::build(&spans, &options, &Local)
+            .into_iter()
+            .map(TimesheetEntry::from)
+            .collect(),
+    };
+
+    match args.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&entries)?),
+        "csv" => print_csv(&entries),
+        _ => render(&entries),
+    }
+    Ok(())
+}
+
+fn day_start(day: &str) -> Result<i64> {
+    let date = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .with_context(|| format!("Expected a date like 2026-01-31, got '{}'", day))?;
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Ok(Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| midnight.and_utc().timestamp_millis()))
+}
+
+fn render(entries: &[TimesheetEntry]) {
+    let layout = Layout::new();
+    layout.header_dashboard("TIMESHEET");
+    if entries.is_empty() {
+        layout.warning("No sessions in this range.");
+        return;
+    }
+    let mut total = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if i == 0 || entries[i - 1].date != entry.date {
+            if i > 0 {
+                layout.section_end();
+            }
+            let minutes: u64 = entries
+                .iter()
+                .filter(|e| e.date == entry.date)
+                .map(|e| e.duration_minutes)
+                .sum();
+            layout.section_branch(&entry.date, &format_minutes(minutes));
+        }
+        total += entry.duration_minutes;
+        let name = entry
+            .project
+            .as_deref()
+            .or(entry.branch.as_deref())
+            .unwrap_or("all");
+        layout.row_key_value(
+            name,
+            &format!(
+                "{}  ({} files, {} saves)",
+                format_minutes(entry.duration_minutes),
+                entry.file_count,
+                entry.snapshot_count
+            ),
+        );
+    }
+    layout.section_end();
+    layout.footer(&format!("Total {}", format_minutes(total)));
+}
+
+fn print_csv(entries: &[TimesheetEntry]) {
+    println!("period,project,branch,minutes,hours,files,saves");
+    for entry in entries {
+        println!(
+            "{},{},{},{},{:.2},{},{}",
+            csv_field(&entry.date),
+            csv_field(entry.project.as_deref().unwrap_or("")),
+            csv_field(entry.branch.as_deref().unwrap_or("")),
+            entry.duration_minutes,
+            entry.duration_minutes as f64 / 60.0,
+            entry.file_count,
+            entry.snapshot_count
+        );
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_minutes(minutes: u64) -> String {
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
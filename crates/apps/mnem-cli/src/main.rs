@@ -76,6 +76,12 @@ enum Commands {
         symbol: Option<String>,
         #[arg(long)]
         checkpoint: Option<String>,
+        /// With --checkpoint, only revert files matching this glob
+        #[arg(long)]
+        path: Option<String>,
+        /// Undo every file a changeset touched
+        #[arg(long)]
+        changeset: Option<i64>,
         #[arg(long)]
         branch: Option<String>,
         #[arg(long)]
@@ -90,18 +96,122 @@ enum Commands {
         limit: Option<usize>,
         #[arg(long)]
         semantic: bool,
+        #[arg(long)]
+        regex: bool,
+    },
+    #[command(about = "List groups of files saved together")]
+    Changesets {
+        #[arg(long, short)]
+        limit: Option<usize>,
+    },
+    #[command(about = "List coding sessions")]
+    Sessions {
+        #[arg(long, short)]
+        limit: Option<usize>,
+    },
+    #[command(about = "Report time spent per day, week or month")]
+    Timesheet {
+        #[arg(long, default_value = "day", value_parser = ["day", "week", "month"])]
+        period: String,
+        /// Break each period down per branch or per project
+        #[arg(long, value_parser = ["branch", "project"])]
+        by: Option<String>,
+        /// Round every entry to a multiple of this many minutes
+        #[arg(long, default_value_t = 0)]
+        round: u64,
+        #[arg(long, default_value = "nearest", value_parser = ["nearest", "up", "down"])]
+        rounding: String,
+        /// First day to include, as YYYY-MM-DD
+        #[arg(long)]
+        since: Option<String>,
+        /// Last day to include, as YYYY-MM-DD
+        #[arg(long)]
+        until: Option<String>,
+        /// Every tracked project, not just this one
+        #[arg(long)]
+        all: bool,
+        #[arg(long, default_value = "table", value_parser = ["table", "csv", "json"])]
+        format: String,
+    },
+    #[command(about = "Compare checkpoints")]
+    Checkpoint {
+        #[command(subcommand)]
+        action: CheckpointAction,
+    },
+    #[command(about = "Pin a snapshot or checkpoint so it is never pruned")]
+    Pin {
+        hash: Option<String>,
+        label: Option<String>,
+        #[arg(long, short)]
+        list: bool,
+        #[arg(long, short)]
+        remove: bool,
+        #[arg(long, short)]
+        search: Option<String>,
     },
     #[command(about = "Show project info")]
     Info { project: Option<String> },
     #[command(about = "Garbage collection")]
     Gc {
+        /// Keep this many days of history instead of the retention policy (0 keeps all)
         #[arg(long)]
-        keep: Option<usize>,
+        keep: Option<u64>,
         #[arg(long, short)]
         dry_run: bool,
         #[arg(long)]
         aggressive: bool,
     },
+    #[command(about = "Export or import portable history bundles")]
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    #[command(about = "Write the project as it was at some point into a directory")]
+    ExportTree {
+        /// A time (YYYY-MM-DD or RFC 3339), a checkpoint or a recorded commit
+        #[arg(long)]
+        at: String,
+        /// Directory to create, or the archive with --tar ('-' for stdout)
+        dest: PathBuf,
+        /// Write a tar archive instead of a directory
+        #[arg(long)]
+        tar: bool,
+    },
+    #[command(about = "Verify history integrity")]
+    Fsck {
+        #[arg(long)]
+        repair: bool,
+    },
+    #[command(about = "Encrypt history at rest")]
+    Encryption {
+        #[command(subcommand)]
+        action: EncryptionAction,
+    },
+    #[command(about = "Git integration")]
+    Git {
+        #[arg(long)]
+        commits: bool,
+        #[arg(long)]
+        log: bool,
+        #[arg(long)]
+        hook: bool,
+    },
+    #[command(about = "Import history from git commits made before tracking")]
+    ImportGit {
+        /// Only commits since this date (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+        /// Branch to import instead of the checked out one
+        #[arg(long)]
+        branch: Option<String>,
+    },
+    #[command(hide = true)]
+    GitEvent {
+        hash: String,
+        message: String,
+        author: String,
+        timestamp: String,
+    },
     #[command(about = "Manage config")]
     Config {
         #[arg(long, short)]
@@ -110,6 +220,8 @@ enum Commands {
         set: Option<String>,
         #[arg(long)]
         reset: bool,
+        #[arg(long)]
+        project: bool,
     },
     #[command(about = "Uninstall mnem")]
     Uninstall {},
@@ -126,6 +238,43 @@ enum Commands {
     McpStatus {},
 }
 
+#[derive(Subcommand)]
+enum BundleAction {
+    #[command(about = "Write the project's history to a bundle file")]
+    Export {
+        output: PathBuf,
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long)]
+        until: Option<String>,
+        #[arg(long)]
+        path: Option<String>,
+    },
+    #[command(about = "Merge a bundle file into the project's history")]
+    Import { input: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum CheckpointAction {
+    #[command(about = "List files added, removed and modified between two checkpoints")]
+    Diff { from: String, to: String },
+    #[command(about = "Compare the working tree against a checkpoint")]
+    Status { checkpoint: String },
+}
+
+#[derive(Subcommand)]
+enum EncryptionAction {
+    #[command(about = "Enable encryption with a passphrase or a key file")]
+    Init {
+        /// Use a key file instead of a passphrase, created when missing
+        /// (default: master.key in the data directory)
+        #[arg(long, num_args = 0..=1, default_missing_value = "")]
+        key_file: Option<PathBuf>,
+    },
+    #[command(about = "Show whether history is encrypted")]
+    Status {},
+}
+
 fn main() -> Result<()> {
     std::fs::write("/tmp/mnem_debug.log", "DEBUG: main() called\n").ok();
     let cli = Cli::parse();
@@ -155,24 +304,91 @@ fn main() -> Result<()> {
             to,
             symbol,
             checkpoint,
+            path,
+            changeset,
             branch,
             limit,
         }) => handlers::handle_r(
-            file, version, list, undo, to, symbol, checkpoint, branch, limit,
+            file, version, list, undo, to, symbol, checkpoint, path, changeset, branch, limit,
         ),
         Some(Commands::S {
             query,
             file,
             limit,
             semantic,
-        }) => handlers::handle_s(query, file, limit, semantic),
+            regex,
+        }) => handlers::handle_s(query, file, limit, semantic, regex),
+        Some(Commands::Changesets { limit }) => handlers::handle_changesets(limit),
+        Some(Commands::Sessions { limit }) => handlers::handle_sessions(limit),
+        Some(Commands::Timesheet {
+            period,
+            by,
+            round,
+            rounding,
+            since,
+            until,
+            all,
+            format,
+        }) => handlers::handle_timesheet(handlers::workspace::TimesheetArgs {
+            period,
+            by,
+            round,
+            rounding,
+            since,
+            until,
+            all,
+            format,
+        }),
+        Some(Commands::Checkpoint { action }) => match action {
+            CheckpointAction::Diff { from, to } => handlers::handle_checkpoint_diff(from, to),
+            CheckpointAction::Status { checkpoint } => {
+                handlers::handle_checkpoint_status(checkpoint)
+            }
+        },
+        Some(Commands::Pin {
+            hash,
+            label,
+            list,
+            remove,
+            search,
+        }) => handlers::handle_pin(hash, label, list, remove, search),
         Some(Commands::Info { project }) => handlers::handle_info(project),
         Some(Commands::Gc {
             keep,
             dry_run,
             aggressive,
         }) => handlers::handle_gc(keep, dry_run, aggressive),
-        Some(Commands::Config { get, set, reset }) => handlers::handle_config(get, set, reset),
+        Some(Commands::Bundle { action }) => match action {
+            BundleAction::Export {
+                output,
+                since,
+                until,
+                path,
+            } => handlers::handle_bundle_export(output, since, until, path),
+            BundleAction::Import { input } => handlers::handle_bundle_import(input),
+        },
+        Some(Commands::ExportTree { at, dest, tar }) => {
+            handlers::handle_export_tree(at, dest, tar)
+        }
+        Some(Commands::Fsck { repair }) => handlers::handle_fsck(repair),
+        Some(Commands::Encryption { action }) => match action {
+            EncryptionAction::Init { key_file } => handlers::handle_encryption_init(key_file),
+            EncryptionAction::Status {} => handlers::handle_encryption_status(),
+        },
+        Some(Commands::Git { commits, log, hook }) => handlers::handle_git(commits, log, hook),
+        Some(Commands::ImportGit { since, branch }) => handlers::handle_import_git(since, branch),
+        Some(Commands::GitEvent {
+            hash,
+            message,
+            author,
+            timestamp,
+        }) => handlers::handle_git_event(hash, message, author, timestamp),
+        Some(Commands::Config {
+            get,
+            set,
+            reset,
+            project,
+        }) => handlers::handle_config(get, set, reset, project),
         Some(Commands::Uninstall {}) => handlers::handle_uninstall(),
         Some(Commands::Update { check_only }) => handlers::handle_update(check_only),
         Some(Commands::McpStart {}) => handlers::handle_mcp("start"),
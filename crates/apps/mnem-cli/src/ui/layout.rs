@@ -3,6 +3,48 @@ use crate::ui_components::elements::Hyperlink;
 use crossterm::style::Stylize;
 use mnem_macros::UiDebug;
 
+/// What changed between a version of a file and the one before it.
+pub enum DiffStats {
+    /// Lines added and removed.
+    Lines(usize, usize),
+    /// Binary or oversized content: sizes in bytes and the previous hash.
+    Blob {
+        before: Option<(u64, String)>,
+        after: u64,
+    },
+}
+
+impl DiffStats {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Lines(added, removed) => format!("+{} -{}", added, removed),
+            Self::Blob {
+                before: Some((size, hash)),
+                after,
+            } => format!(
+                "{} → {} (was {})",
+                format_size(*size),
+                format_size(*after),
+                &hash[..8.min(hash.len())]
+            ),
+            Self::Blob {
+                before: None,
+                after,
+            } => format!("{} (new)", format_size(*after)),
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+    }
+}
+
 pub struct LayoutBuilder {
     theme: Option<Theme>,
     padding: usize,
@@ -694,7 +736,7 @@ impl Layout {
         file_path: &str,
         time: &str,
         is_latest: bool,
-        diff_stats: Option<(usize, usize)>,
+        diff_stats: Option<DiffStats>,
         ide: &mnem_core::config::Ide,
     ) {
         let icon = if is_latest {
@@ -705,14 +747,16 @@ impl Layout {
 
         let link = Hyperlink::ide_link(hash, file_path, ide);
 
-        let diff_text = if let Some((added, removed)) = diff_stats {
-            format!(
+        let diff_text = match diff_stats {
+            Some(DiffStats::Lines(added, removed)) => format!(
                 "{} {}",
                 format!("+{}", added).with(self.theme.success),
                 format!("-{}", removed).with(self.theme.error)
-            )
-        } else {
-            String::new()
+            ),
+            Some(blob @ DiffStats::Blob { .. }) => {
+                blob.describe().with(self.theme.accent).to_string()
+            }
+            None => String::new(),
         };
 
         println!(
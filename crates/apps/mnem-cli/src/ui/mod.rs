@@ -30,7 +30,7 @@ pub use highlight::TsHighlighter;
 pub use colors::*;
 
 // Re-export layout system
-pub use layout::{Layout, LayoutBuilder};
+pub use layout::{DiffStats, Layout, LayoutBuilder};
 
 // Re-export UI components and elements for convenience
 pub use crate::ui_components::{Elements, Hyperlink, List, Messages, Status};
@@ -4,6 +4,7 @@ pub mod os;
 pub mod state;
 pub mod rpc_handler;
 pub mod maintenance;
+pub mod writer;
 
 pub use monitor::Monitor;
 pub use power::PowerProfile;
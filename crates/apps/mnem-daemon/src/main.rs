@@ -1,17 +1,36 @@
 use anyhow::Result;
 use log::{error, info, warn};
+use mnem_core::crypto;
 use mnem_core::env::get_base_dir;
 use mnem_core::protocol::{self, JsonRpcRequest, JsonRpcResponse, PID_FILE};
 
 use mnem_core::storage::registry::ProjectRegistry;
-use mnem_core::Repository;
+use mnem_core::{AppError, AppResult, Repository};
 use mnem_daemon::{DaemonState, Monitor};
 use mnem_daemon::rpc_handler::handle_request;
 use mnem_daemon::maintenance::run_background_maintenance;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
+/// Opens a project, waiting for a CLI command that took it exclusively just
+/// before the daemon started to let go of it.
+async fn open_when_released(base_dir: &Path, project_path: &Path) -> AppResult<Repository> {
+    let mut attempts = 0;
+    loop {
+        match Repository::open(base_dir.to_path_buf(), project_path.to_path_buf()) {
+            Err(AppError::Locked(reason)) if attempts < 60 => {
+                if attempts == 0 {
+                    info!("Waiting for {:?}: {}", project_path, reason);
+                }
+                attempts += 1;
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
 async fn restore_watched_projects(base_dir: &PathBuf, state: &Arc<DaemonState>) {
 
     let registry = match ProjectRegistry::new(base_dir) {
@@ -39,7 +58,7 @@ async fn restore_watched_projects(base_dir: &PathBuf, state: &Arc<DaemonState>)
 
         let path_key = project.path.clone();
 
-        match Repository::open(base_dir.clone(), project_path.clone()) {
+        match open_when_released(base_dir, &project_path).await {
             Ok(repo) => {
                 let repo = Arc::new(repo);
                 let monitor = Arc::new(Monitor::with_state(project_path, repo.clone(), state.clone()));
@@ -72,6 +91,14 @@ async fn restore_watched_projects(base_dir: &PathBuf, state: &Arc<DaemonState>)
     }
 }
 
+/// The passphrase `mnem on` writes to our stdin.
+fn read_passphrase() -> Option<String> {
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).ok()?;
+    let passphrase = line.trim_end_matches(['\r', '\n']);
+    (!passphrase.is_empty()).then(|| passphrase.to_string())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let base_dir = get_base_dir()?;
@@ -108,6 +135,21 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Encrypted history is unlocked once, before any project is opened
+    let passphrase = if crypto::needs_passphrase(&base_dir) {
+        read_passphrase()
+    } else {
+        None
+    };
+    match crypto::unlock(&base_dir, passphrase.as_deref()) {
+        Ok(true) => info!("Encrypted history unlocked."),
+        Ok(false) => {}
+        Err(e) => {
+            error!("Cannot unlock encrypted history: {}", e);
+            std::process::exit(1);
+        }
+    }
+
     std::fs::write(&pid_path, std::process::id().to_string())?;
 
     // Generate Auth Token
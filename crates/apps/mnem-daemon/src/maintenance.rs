@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use log::{info, error};
 use mnem_core::Repository;
+use crate::rpc_handler::run_blocking;
 use crate::state::DaemonState;
 
 pub async fn run_background_maintenance(state: Arc<DaemonState>) {
@@ -12,16 +13,31 @@ pub async fn run_background_maintenance(state: Arc<DaemonState>) {
         let repos: Vec<Arc<Repository>> = state.repos.iter().map(|r| r.value().clone()).collect();
 
         for repo in repos {
-            match repo.run_gc() {
-                Ok(pruned) => {
-                    if pruned > 0 {
-                        info!("GC pruned {} snapshots in {}", pruned, repo.project.path);
+            match step(&repo, |repo| repo.replay_queued_commits()).await {
+                Ok(replayed) => {
+                    if replayed > 0 {
+                        info!("Recorded {} queued commits in {}", replayed, repo.project.path);
+                    }
+                }
+                Err(e) => error!("Replaying queued commits failed for {}: {}", repo.project.path, e),
+            }
+
+            match step(&repo, |repo| repo.run_gc()).await {
+                Ok(report) => {
+                    if report.snapshots_pruned > 0 || report.chunks_deleted > 0 {
+                        info!(
+                            "GC pruned {} snapshots and {} chunks ({} bytes) in {}",
+                            report.snapshots_pruned,
+                            report.chunks_deleted,
+                            report.bytes_reclaimed,
+                            repo.project.path
+                        );
                     }
                 }
                 Err(e) => error!("GC failed for {}: {}", repo.project.path, e),
             }
 
-            match repo.run_migration() {
+            match step(&repo, |repo| repo.run_migration()).await {
                 Ok(moved) => {
                     if moved > 0 {
                         info!("Migrated {} objects in {}", moved, repo.project.path);
@@ -29,7 +45,43 @@ pub async fn run_background_maintenance(state: Arc<DaemonState>) {
                 }
                 Err(e) => error!("Migration failed for {}: {}", repo.project.path, e),
             }
+
+            match step(&repo, |repo| repo.train_dictionary()).await {
+                Ok(Some(version)) => {
+                    info!("Trained compression dictionary v{} for {}", version, repo.project.path);
+                }
+                Ok(None) => {}
+                Err(e) => error!("Dictionary training failed for {}: {}", repo.project.path, e),
+            }
+
+            match step(&repo, |repo| repo.repack()).await {
+                Ok(packed) => {
+                    if packed > 0 {
+                        info!("Packed {} objects in {}", packed, repo.project.path);
+                    }
+                }
+                Err(e) => error!("Repacking failed for {}: {}", repo.project.path, e),
+            }
+
+            match step(&repo, |repo| repo.index_pending_trigrams()).await {
+                Ok(indexed) => {
+                    if indexed > 0 {
+                        info!("Indexed trigrams of {} chunks in {}", indexed, repo.project.path);
+                    }
+                }
+                Err(e) => error!("Trigram indexing failed for {}: {}", repo.project.path, e),
+            }
         }
     }
 }
 
+/// Runs one maintenance step on `repo` through [`run_blocking`].
+async fn step<T, F>(repo: &Arc<Repository>, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&Repository) -> mnem_core::AppResult<T> + Send + 'static,
+{
+    let repo = repo.clone();
+    run_blocking(move || f(&repo)).await
+}
+
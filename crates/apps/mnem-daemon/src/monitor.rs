@@ -1,7 +1,10 @@
-use ignore::gitignore::GitignoreBuilder;
+use crate::writer::GroupCommitWriter;
+use mnem_core::storage::{cdc, filter};
 use mnem_core::{AppError, AppResult, Repository};
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
@@ -10,17 +13,21 @@ use tokio::sync::mpsc;
 pub struct Monitor {
     root_path: PathBuf,
     repo: Arc<Repository>,
+    writer: Arc<GroupCommitWriter>,
     state: Option<Arc<crate::state::DaemonState>>,
 }
 
 struct FileEvent {
     path: PathBuf,
+    /// Set when the watcher reported a rename with both ends.
+    renamed_from: Option<PathBuf>,
 }
 
 impl Monitor {
     pub fn new(root_path: PathBuf, repo: Arc<Repository>) -> Self {
         Self {
             root_path,
+            writer: Arc::new(GroupCommitWriter::new(repo.clone())),
             repo,
             state: None,
         }
@@ -32,6 +39,7 @@ impl Monitor {
         state: Arc<crate::state::DaemonState>,
     ) -> Self {
         Self {
+            writer: state.writer_for(&root_path.to_string_lossy(), &repo),
             root_path,
             repo,
             state: Some(state),
@@ -58,8 +66,20 @@ impl Monitor {
         tokio::task::spawn_blocking(move || {
             while let Ok(res) = n_rx.recv() {
                 if let Ok(event) = res {
+                    if let EventKind::Modify(ModifyKind::Name(RenameMode::Both)) = event.kind
+                        && let [from, to] = &event.paths[..]
+                    {
+                        let _ = tx.blocking_send(FileEvent {
+                            path: to.clone(),
+                            renamed_from: Some(from.clone()),
+                        });
+                        continue;
+                    }
                     for path in event.paths {
-                        let _ = tx.blocking_send(FileEvent { path });
+                        let _ = tx.blocking_send(FileEvent {
+                            path,
+                            renamed_from: None,
+                        });
                     }
                 }
             }
@@ -68,27 +88,40 @@ impl Monitor {
         // Event Loop: Adaptive Debouncing + Periodic Polling
         let mut debounced_paths: std::collections::HashMap<PathBuf, (Instant, u32)> =
             std::collections::HashMap::new();
+        // Destination -> source of renames waiting for their destination to settle
+        let mut renames: HashMap<PathBuf, PathBuf> = HashMap::new();
         let mut interval = tokio::time::interval(Duration::from_millis(500));
         let mut polling_interval = tokio::time::interval(Duration::from_secs(30));
         let mut new_files_interval = tokio::time::interval(Duration::from_secs(60));
 
         let mnemignore = self.get_mnemignore()?;
-        let config_max_size = self
+        let config = self
             .repo
             .config
             .lock()
             .unwrap_or_else(|p| p.into_inner())
             .config
-            .max_file_size_mb;
-        let max_file_size = config_max_size * 1024 * 1024;
+            .clone();
 
         log::info!("Monitor loop started for {:?}", self.root_path);
         loop {
             tokio::select! {
                 Some(event) = rx.recv() => {
-                    if !self.is_ignored(&event.path, Some(&mnemignore)) {
+                    let path = match event.renamed_from {
+                        Some(from) if !self.is_ignored(&from, Some(&mnemignore)) => {
+                            if self.is_ignored(&event.path, Some(&mnemignore)) {
+                                // Moved out of sight, which is a deletion as far as we can tell
+                                from
+                            } else {
+                                renames.insert(event.path.clone(), from);
+                                event.path
+                            }
+                        }
+                        _ => event.path,
+                    };
+                    if !self.is_ignored(&path, Some(&mnemignore)) {
                         let now = Instant::now();
-                        let entry = debounced_paths.entry(event.path).or_insert((now, 0));
+                        let entry = debounced_paths.entry(path).or_insert((now, 0));
 
                         // Adaptive delay: increase delay if file changes too frequently
                         entry.1 += 1;
@@ -118,8 +151,9 @@ impl Monitor {
                     }
 
                     if !to_save.is_empty() {
+                        let to_save = self.settle_moves(to_save, &mut renames);
                         to_save.par_iter().for_each(|path| {
-                            self.process_file(path, max_file_size);
+                            self.process_file(path, &config);
                         });
                     }
                 }
@@ -136,17 +170,32 @@ impl Monitor {
                         let path = entry.path();
                         if path.is_file() && !self.is_ignored(path, Some(&mnemignore)) {
                             // Check if this file has any snapshots at all
-                            if let Ok(history) = self.repo.get_history(&path.to_string_lossy()) {
-                                if history.is_empty() {
-                                    // This is a new file, save it
-                                    log::info!("Polling: found new file {:?}", path);
-                                    self.process_file(path, max_file_size);
-                                }
+                            let path_str = path.to_string_lossy();
+                            if let Ok(None) = self.repo.db.get_last_hash(&path_str) {
+                                // This is a new file, save it
+                                log::info!("Polling: found new file {:?}", path);
+                                self.process_file(path, &config);
                             }
                         }
                     }
                 }
                 _ = polling_interval.tick() => {
+                    match self.repo.close_idle_session() {
+                        Ok(Some(id)) => log::info!("Closed idle session {}", id),
+                        Ok(None) => {}
+                        Err(e) => log::warn!("Failed to close idle session: {}", e),
+                    }
+                    match self.repo.sync_git_head() {
+                        Ok(Some(sync)) if sync.commits > 0 => log::info!(
+                            "Recorded {} commits up to {}, {} snapshots linked",
+                            sync.commits,
+                            &sync.head[..8],
+                            sync.linked
+                        ),
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Failed to read git HEAD: {}", e),
+                    }
+
                     // Periodic polling to catch missed notify events (backup mechanism)
                     if let Ok(recent_snapshots) = self.repo.get_recent_activity(100) {
                         let mut missed_changes = Vec::new();
@@ -174,7 +223,7 @@ impl Monitor {
                         if !missed_changes.is_empty() {
                             log::info!("Polling detected {} missed changes", missed_changes.len());
                             for path in missed_changes {
-                                self.process_file(&path, max_file_size);
+                                self.process_file(&path, &config);
                             }
                         }
                     }
@@ -183,7 +232,56 @@ impl Monitor {
         }
     }
 
-    fn process_file(&self, path: &Path, max_file_size: u64) {
+    /// Records renames and deletions among `paths` and returns the paths that
+    /// still exist, for a regular save.
+    fn settle_moves(
+        &self,
+        paths: Vec<PathBuf>,
+        renames: &mut HashMap<PathBuf, PathBuf>,
+    ) -> Vec<PathBuf> {
+        let mut moves = Vec::new();
+        let mut removed = Vec::new();
+        let mut present = Vec::new();
+        for path in paths {
+            if let Some(from) = renames.remove(&path) {
+                moves.push((from, path.clone()));
+            }
+            if path.exists() {
+                present.push(path);
+            } else {
+                removed.push(path);
+            }
+        }
+
+        // Editors and some platforms report a move as a delete plus a create
+        match self.repo.match_renames(&removed, &present) {
+            Ok(pairs) => moves.extend(pairs),
+            Err(e) => log::error!("Failed to match renames: {:?}", e),
+        }
+        for (from, to) in &moves {
+            match self.repo.record_rename(from, to) {
+                Ok(0) => {}
+                Ok(n) => log::info!("Recorded move of {} file(s) from {:?} to {:?}", n, from, to),
+                Err(e) => log::error!("Failed to record move of {:?}: {:?}", from, e),
+            }
+        }
+        let moved: HashSet<&PathBuf> = moves.iter().map(|(from, _)| from).collect();
+        for path in removed.iter().filter(|p| !moved.contains(p)) {
+            match self.repo.record_deletion(path) {
+                Ok(0) => {}
+                Ok(n) => log::info!("Recorded deletion of {} file(s) under {:?}", n, path),
+                Err(e) => log::error!("Failed to record deletion of {:?}: {:?}", path, e),
+            }
+        }
+        if let Some(ref state) = self.state
+            && (!moves.is_empty() || !removed.is_empty())
+        {
+            state.invalidate_history_cache(None);
+        }
+        present
+    }
+
+    fn process_file(&self, path: &Path, config: &mnem_core::config::Config) {
         if !path.is_file() {
             return;
         }
@@ -197,18 +295,11 @@ impl Monitor {
             }
         }
 
-        // File size limit (audit 4.6)
-        if let Ok(metadata) = path.metadata() {
-            if metadata.len() > max_file_size {
-                return; // Skip files exceeding size limit
-            }
-        }
-
         // Check binary content
         let is_binary = if let Ok(mut file) = std::fs::File::open(path) {
             let mut buffer = [0; 1024];
             if let Ok(n) = std::io::Read::read(&mut file, &mut buffer) {
-                content_inspector::inspect(&buffer[..n]).is_binary()
+                cdc::is_binary(&buffer[..n])
             } else {
                 false
             }
@@ -216,25 +307,27 @@ impl Monitor {
             false
         };
 
-        log::info!("File {:?} is_binary: {}", path, is_binary);
-
-        if !is_binary {
-            log::info!("Processing file: {:?}", path);
-            let start = Instant::now();
-            match self.repo.save_snapshot_from_file(path) {
-                Ok(hash) => {
-                    let duration = start.elapsed().as_micros() as u64;
-                    if let Some(ref state) = self.state {
-                        state.record_save(duration);
-                        // Invalidate history cache for this file
-                        state.invalidate_history_cache(Some(&path.to_string_lossy()));
-                    }
-                    log::info!("Saved file {:?} with hash {}", path, &hash[..8]);
+        // File size limits (audit 4.6): binaries and text over the limit are
+        // kept as blobs, up to their own cap
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        if !config.accepts(size, is_binary) {
+            log::info!("Skipping {:?}: {} bytes, binary: {}", path, size, is_binary);
+            return;
+        }
+
+        log::info!("Processing file: {:?}", path);
+        let start = Instant::now();
+        match self.writer.save_file(path) {
+            Ok(hash) => {
+                let duration = start.elapsed().as_micros() as u64;
+                if let Some(ref state) = self.state {
+                    state.record_save(duration);
+                    // Invalidate history cache for this file
+                    state.invalidate_history_cache(Some(&path.to_string_lossy()));
                 }
-                Err(e) => log::error!("Failed to save {:?}: {:?}", path, e),
+                log::info!("Saved file {:?} with hash {}", path, &hash[..8]);
             }
-        } else {
-            log::info!("Skipping binary file: {:?}", path);
+            Err(e) => log::error!("Failed to save {:?}: {:?}", path, e),
         }
     }
 
@@ -248,25 +341,40 @@ impl Monitor {
             .git_ignore(false)
             .build();
 
-        let max_file_size = self
+        let config = self
             .repo
             .config
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner())
             .config
-            .max_file_size_mb
-            * 1024
-            * 1024;
+            .clone();
 
         let entries: Vec<_> = walker
             .filter_map(|r| r.ok())
             .filter(|e| e.path().is_file() && !self.is_ignored(e.path(), Some(&mnemignore)))
             .collect();
 
+        // Catch up on files moved or deleted while the daemon was not running
+        let missing: Vec<PathBuf> = self
+            .repo
+            .db
+            .get_latest_state()?
+            .into_iter()
+            .map(|(path, _)| PathBuf::from(path))
+            .filter(|path| !path.exists())
+            .collect();
+        if !missing.is_empty() {
+            let paths = missing
+                .into_iter()
+                .chain(entries.iter().map(|e| e.path().to_path_buf()))
+                .collect();
+            self.settle_moves(paths, &mut HashMap::new());
+        }
+
         // Process in chunks of 100 files with rayon parallelism (audit 4.5)
         for chunk in entries.chunks(100) {
             chunk.par_iter().for_each(|entry| {
-                self.process_file(entry.path(), max_file_size);
+                self.process_file(entry.path(), &config);
             });
         }
 
@@ -275,28 +383,7 @@ impl Monitor {
 
     /// Path-based ignore check using path components instead of string contains (audit 5.5).
     fn is_ignored(&self, path: &Path, mnemignore: Option<&ignore::gitignore::Gitignore>) -> bool {
-        let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
-
-        // Use path components for exact matching (audit 5.5)
-        let has_ignored_component = relative.components().any(|c| {
-            let s = c.as_os_str().to_string_lossy();
-            s == "target"
-                || s == ".git"
-                || s == "node_modules"
-                || s == ".DS_Store"
-                || s == ".mnemosyne"
-        });
-
-        if has_ignored_component {
-            return true;
-        }
-
-        if let Some(mi) = mnemignore {
-            if mi.matched(relative, false).is_ignore() {
-                return true;
-            }
-        }
-        false
+        filter::is_ignored(&self.root_path, path, mnemignore)
     }
 
     fn get_mnemignore(&self) -> AppResult<ignore::gitignore::Gitignore> {
@@ -307,28 +394,7 @@ impl Monitor {
             .unwrap_or_else(|poisoned| poisoned.into_inner())
             .config
             .clone();
-        let mut builder = GitignoreBuilder::new(&self.root_path);
-
-        // 1. Global Ignore (audit 1.2)
-        if let Some(home) = dirs::home_dir() {
-            let global_ignore = home.join(".mnemosyne").join(".mnemignore");
-            if global_ignore.exists() {
-                builder.add(global_ignore);
-            }
-        }
-
-        // 2. Project-level .mnemignore
-        if config.use_mnemosyneignore {
-            if let Some(ignore_path) =
-                Some(self.root_path.join(".mnemosyneignore")).filter(|p| p.exists())
-            {
-                builder.add(ignore_path);
-            }
-        }
-
-        Ok(builder
-            .build()
-            .map_err(|e| AppError::Config(format!("Mnemignore build failed: {}", e)))?)
+        filter::mnemignore(&self.root_path, &config)
     }
 }
 
@@ -385,10 +451,29 @@ mod tests {
         std::os::unix::fs::symlink(&external_file, &symlink_path).unwrap();
 
         // process_file should skip it
-        monitor.process_file(&symlink_path, 10 * 1024 * 1024);
+        monitor.process_file(&symlink_path, &mnem_core::config::Config::default());
 
         // Verify no snapshot was created for the external file
-        let history = repo.get_history(&symlink_path.to_string_lossy()).unwrap();
+        let history = repo
+            .get_file_history(&symlink_path.to_string_lossy())
+            .unwrap();
         assert_eq!(history.len(), 0);
     }
+
+    #[test]
+    fn test_monitors_of_a_project_share_one_writer() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("project");
+        fs::create_dir_all(project_dir.join(".mnemosyne")).unwrap();
+        fs::write(project_dir.join(".mnemosyne/tracked"), "project_id: p").unwrap();
+        fs::create_dir_all(dir.path().join("home")).unwrap();
+        let repo =
+            Arc::new(Repository::open(dir.path().join("home"), project_dir.clone()).unwrap());
+        let state = Arc::new(crate::state::DaemonState::new(String::new()));
+
+        let first = Monitor::with_state(project_dir.clone(), repo.clone(), state.clone());
+        let second = Monitor::with_state(project_dir, repo, state.clone());
+        assert!(Arc::ptr_eq(&first.writer, &second.writer));
+        assert_eq!(state.writers.len(), 1);
+    }
 }
@@ -7,12 +7,16 @@ use std::sync::atomic::Ordering;
 
 use crate::Monitor;
 use crate::state::{DaemonState, InitializationState};
-use mnem_core::Repository;
+use mnem_core::{ConfigManager, Repository};
+use mnem_core::config::{ConfigSource, RetentionPolicy};
 use mnem_core::env::get_base_dir;
 use mnem_core::protocol::jsonrpc_errors::*;
 use mnem_core::protocol::mnem_errors::*;
 use mnem_core::protocol::{self, JsonRpcRequest, JsonRpcResponse, PROTOCOL_VERSION};
 use mnem_core::protocol::{InitializeParams, InitializeResult, ServerCapabilities, ServerInfo};
+use mnem_core::storage::tiered::TierOccupancy;
+use mnem_core::storage::timesheet;
+use mnem_core::utils::time::{now_ms, parse_ms};
 
 /// List of methods that can be called before initialization
 const UNRESTRICTED_METHODS: &[&str] = &[
@@ -120,6 +124,28 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                 protocol::methods::MCP_START.to_string(),
                 protocol::methods::MCP_STOP.to_string(),
                 protocol::methods::MCP_STATUS.to_string(),
+                protocol::methods::CONFIG_GET_V1.to_string(),
+                protocol::methods::PROJECT_REVERT_V1.to_string(),
+                protocol::methods::PROJECT_EXPORT_TREE.to_string(),
+                protocol::methods::CHECKPOINT_DIFF.to_string(),
+                protocol::methods::CHECKPOINT_STATUS.to_string(),
+                protocol::methods::CHANGESET_LIST.to_string(),
+                protocol::methods::CHANGESET_REVERT.to_string(),
+                protocol::methods::SESSION_GET_LIST.to_string(),
+                protocol::methods::SESSION_GET_ACTIVE.to_string(),
+                protocol::methods::SESSION_GET_TIMESHEET.to_string(),
+                protocol::methods::MAINTENANCE_GC.to_string(),
+                protocol::methods::MAINTENANCE_FSCK.to_string(),
+                protocol::methods::BUNDLE_EXPORT.to_string(),
+                protocol::methods::BUNDLE_IMPORT.to_string(),
+                protocol::methods::GIT_RECORD_COMMIT.to_string(),
+                protocol::methods::GIT_LIST_COMMITS.to_string(),
+                protocol::methods::GIT_IMPORT.to_string(),
+                protocol::methods::PIN_LIST.to_string(),
+                protocol::methods::PIN_ADD.to_string(),
+                protocol::methods::PIN_REMOVE.to_string(),
+                protocol::methods::TIER_CONFIG_GET_V1.to_string(),
+                protocol::methods::TIER_CONFIG_SET_V1.to_string(),
             ];
             *state.server_capabilities.write() = Some(capabilities.clone());
 
@@ -170,10 +196,18 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
 
             let mut total_snapshots = 0;
             let mut total_symbols = 0;
+            let mut active_sessions = 0;
+            let mut tiers = TierOccupancy::default();
             for repo_entry in state.repos.iter() {
                 let repo = repo_entry.value();
                 total_snapshots += repo.db.get_snapshot_count().unwrap_or(0);
+                if let Ok(Some(_)) = repo.get_active_session() {
+                    active_sessions += 1;
+                }
                 total_symbols += repo.db.get_symbol_count().unwrap_or(0);
+                if let Ok(occupancy) = repo.fs.tier_occupancy() {
+                    tiers.add(&occupancy);
+                }
             }
 
             let total_size = state.calculate_total_size();
@@ -182,7 +216,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                 version: env!("CARGO_PKG_VERSION").into(),
                 uptime_secs: state.start_time.elapsed().as_secs(),
                 watched_projects: state.monitors.iter().map(|m| m.key().clone()).collect(),
-                active_sessions: 0,
+                active_sessions,
                 history_size_bytes: total_size,
                 total_size_bytes: total_size,
                 avg_response_time_ms: avg_time,
@@ -190,6 +224,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                 total_saves,
                 total_snapshots: total_snapshots as u64,
                 total_symbols: total_symbols as u64,
+                tiers,
             };
             JsonRpcResponse::success(req.id, serde_json::to_value(status).unwrap_or(json!({})))
         }
@@ -362,6 +397,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
 
             state.monitors.remove(&params.project_path);
             state.repos.remove(&params.project_path);
+            state.writers.remove(&params.project_path);
             info!("Unwatched project: {}", params.project_path);
             JsonRpcResponse::success(req.id, json!({"status": "unwatched"}))
         }
@@ -456,6 +492,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                                     git_branch: sn.git_branch,
                                     commit_hash: sn.commit_hash,
                                     commit_message,
+                                    event: sn.event,
                                 }
                             })
                             .collect();
@@ -501,6 +538,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                                         git_branch: sn.git_branch,
                                         commit_hash: sn.commit_hash,
                                         commit_message,
+                                        event: sn.event,
                                     }
                                 })
                                 .collect();
@@ -540,8 +578,10 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                 let repo = repo_entry.value();
                 match repo.get_content(&params.content_hash) {
                     Ok(content) => {
-                        let text = String::from_utf8_lossy(&content).to_string();
-                        return JsonRpcResponse::success(req.id, json!({"content": text}));
+                        return JsonRpcResponse::success(
+                            req.id,
+                            json!(protocol::SnapshotContentResponse::new(&content)),
+                        );
                     }
                     Err(_) => continue,
                 }
@@ -648,7 +688,15 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
 
             for repo_entry in state.repos.iter() {
                 let repo = repo_entry.value();
-                match repo.grep_contents(&params.query, params.path_filter.as_deref()) {
+                if params
+                    .project_path
+                    .as_ref()
+                    .is_some_and(|filter_path| repo.project.path != *filter_path)
+                {
+                    continue;
+                }
+
+                match repo.grep_contents(&params.query, params.path_filter.as_deref(), params.regex) {
                     Ok(results) => all_results.extend(results),
                     Err(e) => error!("Search failed for {}: {}", repo.project.path, e),
                 }
@@ -720,7 +768,11 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                     }
                 }
 
-                if let Ok(history) = repo.db.get_global_history(params.limit) {
+                let history = match params.since {
+                    Some(since) => repo.db.history_between(since, i64::MAX),
+                    None => repo.db.get_global_history(params.limit),
+                };
+                if let Ok(history) = history {
                     for sn in history {
                         let commit_message = sn.commit_hash.as_ref().and_then(|h| {
                             repo.db
@@ -737,12 +789,15 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                             git_branch: sn.git_branch,
                             commit_hash: sn.commit_hash,
                             commit_message,
+                            event: sn.event,
                         });
                     }
                 }
             }
 
-            all_activity.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            all_activity.sort_by_key(|sn| {
+                std::cmp::Reverse(parse_ms(&sn.timestamp))
+            });
             all_activity.truncate(params.limit);
 
             JsonRpcResponse::success(
@@ -775,7 +830,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                 let mut symbols_map = json!({});
                 for f in files {
                     files_list.push(json!(f.path));
-                    if let Ok(snaps) = repo.db.get_history(&f.path) {
+                    if let Ok(snaps) = repo.db.get_file_history(&f.path) {
                         if let Some(latest) = snaps.first() {
                             if let Ok(symbols) = repo.db.get_symbols_for_snapshot(latest.id) {
                                 symbols_map[&f.path] = json!(
@@ -836,14 +891,20 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                     .and_then(|h| h.first().map(|sn| sn.timestamp.clone()))
                     .unwrap_or_default();
 
+                let now = now_ms();
+                let activity_by_day = repo
+                    .db
+                    .activity_between(now - 29 * 24 * 60 * 60 * 1000, now)
+                    .unwrap_or_default();
+
                 let resp = protocol::ProjectStatisticsResponse {
                     total_snapshots,
                     total_files,
                     total_branches: repo.list_branches().unwrap_or_default().len(),
-                    total_commits: 0,
-                    size_bytes: 0,
+                    total_commits: repo.list_commits().map(|c| c.len()).unwrap_or(0),
+                    size_bytes: repo.get_project_size().unwrap_or(0),
                     last_activity,
-                    activity_by_day: Vec::new(),
+                    activity_by_day,
                     activity_by_hour: Vec::new(),
                     top_files: Vec::new(),
                     top_branches: Vec::new(),
@@ -864,7 +925,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
             for repo_entry in state.repos.iter() {
                 let repo = repo_entry.value();
                 if file_path.starts_with(&repo.project.path) {
-                    if let Ok(history) = repo.db.get_history(file_path) {
+                    if let Ok(history) = repo.db.get_file_history(file_path) {
                         if let Some(latest) = history.first() {
                             let info = json!({
                                 "path": file_path,
@@ -947,6 +1008,7 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
                                     git_branch: sn.git_branch,
                                     commit_hash: sn.commit_hash,
                                     commit_message,
+                                    event: sn.event,
                                 },
                                 "symbol_name": sym.name,
                                 "symbol_kind": sym.kind,
@@ -986,6 +1048,41 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
             JsonRpcResponse::success(req.id, json!({ "deltas": all_deltas }))
         }
 
+        protocol::methods::CONFIG_GET_V1 => {
+            let params: protocol::ConfigGetParams = match serde_json::from_value(req.params.clone()) {
+                Ok(p) => p,
+                Err(e) => {
+                    return JsonRpcResponse::error(req.id, -32602, format!("Invalid params: {}", e));
+                }
+            };
+
+            let base_dir = match get_base_dir() {
+                Ok(d) => d,
+                Err(e) => return JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            };
+            // Read from disk so edits made by `mnem config` are visible without a reload
+            let manager = match &params.project_path {
+                Some(path) => ConfigManager::with_project(&base_dir, std::path::Path::new(path)),
+                None => ConfigManager::new(&base_dir),
+            };
+            let manager = match manager {
+                Ok(m) => m,
+                Err(e) => return JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            };
+
+            match manager.get(&params.key) {
+                Ok((value, source)) => {
+                    let response = protocol::ConfigGetResponse {
+                        key: params.key,
+                        value: serde_json::to_value(value).unwrap_or(json!(null)),
+                        source,
+                    };
+                    JsonRpcResponse::success(req.id, serde_json::to_value(response).unwrap_or(json!({})))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32602, e.to_string()),
+            }
+        }
+
         protocol::methods::GET_WATCHED_PROJECTS | protocol::methods::PROJECT_LIST => {
             let projects: Vec<protocol::WatchedProject> = state
                 .monitors
@@ -1124,6 +1221,661 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
             )
         }
 
+        protocol::methods::PROJECT_REVERT | protocol::methods::PROJECT_REVERT_V1 => {
+            let params: protocol::ProjectRevertParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let repo = match params.project_path.as_deref() {
+                Some(path) => watched_repo(state, path),
+                None if state.repos.len() == 1 => {
+                    state.repos.iter().next().map(|r| r.value().clone())
+                }
+                None => None,
+            };
+            let Some(repo) = repo else {
+                return JsonRpcResponse::error(req.id, -32000, "No project selected or found".into());
+            };
+
+            let result = match (params.checkpoint, params.timestamp) {
+                (Some(checkpoint), _) => {
+                    let path_glob = params.path_glob;
+                    run_blocking(move || {
+                        repo.revert_paths_to_checkpoint(&checkpoint, path_glob.as_deref())
+                    })
+                    .await
+                }
+                (None, Some(timestamp)) => {
+                    run_blocking(move || repo.revert_to_timestamp(&timestamp)).await
+                }
+                (None, None) => Err("Specify a checkpoint or a timestamp".to_string()),
+            };
+            match result {
+                Ok(files_restored) => JsonRpcResponse::success(
+                    req.id,
+                    json!(protocol::ProjectRevertResponse { files_restored }),
+                ),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::CHECKPOINT_DIFF => {
+            let params: protocol::CheckpointDiffParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match run_blocking(move || repo.checkpoint_diff(&params.from, &params.to)).await {
+                Ok(changes) => JsonRpcResponse::success(req.id, json!(changes)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::CHECKPOINT_STATUS => {
+            let params: protocol::CheckpointStatusParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match run_blocking(move || repo.checkpoint_status(&params.checkpoint)).await {
+                Ok(changes) => JsonRpcResponse::success(req.id, json!(changes)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::CHANGESET_LIST => {
+            let params: protocol::ChangesetListParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let limit = params.limit.unwrap_or(20);
+            match run_blocking(move || repo.list_changesets(limit)).await {
+                Ok(changesets) => JsonRpcResponse::success(req.id, json!(changesets)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::SESSION_GET_LIST => {
+            let params: protocol::SessionListParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let limit = params.limit.unwrap_or(20);
+            match run_blocking(move || repo.list_sessions(limit)).await {
+                Ok(sessions) => {
+                    let sessions: Vec<protocol::SessionResponse> =
+                        sessions.into_iter().map(Into::into).collect();
+                    JsonRpcResponse::success(req.id, json!(sessions))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::SESSION_GET_ACTIVE => {
+            let params: protocol::SessionActiveParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match run_blocking(move || repo.get_active_session()).await {
+                Ok(session) => JsonRpcResponse::success(
+                    req.id,
+                    json!(session.map(protocol::SessionResponse::from)),
+                ),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::SESSION_GET_TIMESHEET => {
+            let params: protocol::TimesheetParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            // Every project at once, so overlapping sessions are only billed once
+            let mut repos: Vec<Arc<Repository>> = match &params.project_path {
+                Some(path) => match repo_containing(state, path) {
+                    Some(repo) => vec![repo],
+                    None => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            -32000,
+                            "Project not watched".into(),
+                        );
+                    }
+                },
+                None if params.project_paths.is_empty() => {
+                    state.repos.iter().map(|r| r.value().clone()).collect()
+                }
+                None => Vec::new(),
+            };
+            let mut unwatched = Vec::new();
+            for path in &params.project_paths {
+                match watched_repo(state, path) {
+                    Some(repo) if repos.iter().any(|r| Arc::ptr_eq(r, &repo)) => {}
+                    Some(repo) => repos.push(repo),
+                    None if std::path::Path::new(path).exists() => {
+                        unwatched.push(PathBuf::from(path))
+                    }
+                    None => {}
+                }
+            }
+            let options = params.options;
+            match run_blocking(move || {
+                let mut spans = Vec::new();
+                for repo in &repos {
+                    spans.extend(repo.session_spans()?);
+                }
+                // Projects that are not watched are opened just for this
+                if !unwatched.is_empty() {
+                    let base_dir = get_base_dir()?;
+                    for path in unwatched {
+                        spans.extend(Repository::open(base_dir.clone(), path)?.session_spans()?);
+                    }
+                }
+                Ok(timesheet::build(&spans, &options, &chrono::Local))
+            })
+            .await
+            {
+                Ok(entries) => {
+                    let entries: Vec<protocol::TimesheetEntry> =
+                        entries.into_iter().map(Into::into).collect();
+                    JsonRpcResponse::success(req.id, json!(entries))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::CHANGESET_REVERT => {
+            let params: protocol::ChangesetRevertParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match run_blocking(move || repo.revert_changeset(params.id)).await {
+                Ok(files_restored) => JsonRpcResponse::success(
+                    req.id,
+                    json!(protocol::ProjectRevertResponse { files_restored }),
+                ),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::GC_RUN | protocol::methods::MAINTENANCE_GC => {
+            let params: protocol::MaintenanceGcParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let policy = params.keep_days.map(RetentionPolicy::within_days);
+            match run_blocking(move || match policy {
+                Some(policy) => repo.run_gc_with_policy(&policy),
+                None => repo.run_gc(),
+            })
+            .await
+            {
+                Ok(report) => {
+                    state.invalidate_history_cache(None);
+                    JsonRpcResponse::success(req.id, json!(report))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::MAINTENANCE_FSCK => {
+            let params: protocol::MaintenanceFsckParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match run_blocking(move || repo.fsck(params.repair)).await {
+                Ok(report) => {
+                    state.invalidate_history_cache(None);
+                    JsonRpcResponse::success(req.id, json!(report))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::BUNDLE_EXPORT => {
+            let params: protocol::BundleExportParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let parse = |value: Option<String>| {
+                value
+                    .map(|v| {
+                        chrono::DateTime::parse_from_rfc3339(&v)
+                            .map(|ts| ts.with_timezone(&chrono::Utc))
+                            .map_err(|e| format!("Invalid time '{}': {}", v, e))
+                    })
+                    .transpose()
+            };
+            let filter = match (parse(params.since), parse(params.until)) {
+                (Ok(since), Ok(until)) => mnem_core::storage::bundle::BundleFilter {
+                    since,
+                    until,
+                    path_glob: params.path_glob,
+                },
+                (Err(e), _) | (_, Err(e)) => {
+                    return JsonRpcResponse::error(req.id, INVALID_PARAMS, e);
+                }
+            };
+            let output = PathBuf::from(params.output);
+            match run_blocking(move || repo.export_bundle(&output, &filter)).await {
+                Ok(manifest) => JsonRpcResponse::success(req.id, json!(manifest)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::PROJECT_EXPORT_TREE => {
+            let params: protocol::ProjectExportTreeParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let output = PathBuf::from(params.output);
+            let at = params.at;
+            let result = if params.tar {
+                run_blocking(move || {
+                    let file = std::fs::File::create(&output)?;
+                    repo.export_tree_tar(&at, std::io::BufWriter::new(file))
+                })
+                .await
+            } else {
+                run_blocking(move || repo.export_tree(&at, &output)).await
+            };
+            match result {
+                Ok(manifest) => JsonRpcResponse::success(req.id, json!(manifest)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::BUNDLE_IMPORT => {
+            let params: protocol::BundleImportParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let input = PathBuf::from(params.input);
+            match run_blocking(move || repo.import_bundle(&input)).await {
+                Ok((manifest, report)) => {
+                    state.invalidate_history_cache(None);
+                    JsonRpcResponse::success(
+                        req.id,
+                        json!(protocol::BundleImportResponse { manifest, report }),
+                    )
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+
+        protocol::methods::GIT_RECORD_COMMIT => {
+            let params: protocol::GitRecordCommitParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match repo.record_git_commit(&params.commit) {
+                Ok(()) => JsonRpcResponse::success(req.id, json!({ "status": "recorded" })),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::GIT_LIST_COMMITS => {
+            let params: protocol::GitListCommitsParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match repo.list_commits() {
+                Ok(commits) => {
+                    let commits: Vec<protocol::GitCommitInfo> = commits
+                        .into_iter()
+                        .map(|(hash, message, author, timestamp, files)| protocol::GitCommitInfo {
+                            hash,
+                            message,
+                            author,
+                            timestamp,
+                            files,
+                        })
+                        .collect();
+                    JsonRpcResponse::success(req.id, json!(commits))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::GIT_IMPORT => {
+            let params: protocol::GitImportParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = watched_repo(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            let since = match params
+                .since
+                .as_deref()
+                .map(chrono::DateTime::parse_from_rfc3339)
+                .transpose()
+            {
+                Ok(since) => since.map(|ts| ts.with_timezone(&chrono::Utc)),
+                Err(e) => {
+                    return JsonRpcResponse::error(
+                        req.id,
+                        INVALID_PARAMS,
+                        format!("Invalid time: {}", e),
+                    );
+                }
+            };
+            let options = mnem_core::storage::git_import::GitImportOptions {
+                since,
+                branch: params.branch,
+            };
+            match run_blocking(move || repo.import_git(&options)).await {
+                Ok(report) => {
+                    state.invalidate_history_cache(None);
+                    JsonRpcResponse::success(req.id, json!(report))
+                }
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e),
+            }
+        }
+        protocol::methods::PIN_LIST => {
+            let params: protocol::PinListParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match repo.list_pins(params.query.as_deref()) {
+                Ok(pins) => JsonRpcResponse::success(req.id, json!(pins)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::PIN_ADD => {
+            let params: protocol::PinAddParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match repo.pin(&params.hash, &params.label) {
+                Ok(pin) => JsonRpcResponse::success(req.id, json!(pin)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::PIN_REMOVE => {
+            let params: protocol::PinRemoveParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let Some(repo) = repo_containing(state, &params.project_path) else {
+                return JsonRpcResponse::error(req.id, -32000, "Project not watched".into());
+            };
+            match repo.unpin(&params.hash) {
+                Ok(pin) => JsonRpcResponse::success(req.id, json!(pin)),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::TIER_CONFIG_GET_V1 => {
+            let params: protocol::TierConfigGetParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            match load_config(params.project_path.as_deref()) {
+                Ok(manager) => JsonRpcResponse::success(
+                    req.id,
+                    json!(protocol::TierConfigGetResponse {
+                        config: manager.config.tiers,
+                    }),
+                ),
+                Err(e) => JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            }
+        }
+
+        protocol::methods::TIER_CONFIG_SET_V1 => {
+            let params: protocol::TierConfigSetParams =
+                match serde_json::from_value(req.params.clone()) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            req.id,
+                            INVALID_PARAMS,
+                            format!("Invalid params: {}", e),
+                        );
+                    }
+                };
+
+            let (mut manager, layer) = match load_config(params.project_path.as_deref()) {
+                Ok(m) if params.project_path.is_some() => (m, ConfigSource::Project),
+                Ok(m) => (m, ConfigSource::Global),
+                Err(e) => return JsonRpcResponse::error(req.id, -32000, e.to_string()),
+            };
+            let tiers = &params.config;
+            let values = [
+                ("tiers.hot_window_hours", tiers.hot_window_hours.to_string()),
+                ("tiers.warm_window_days", tiers.warm_window_days.to_string()),
+                ("tiers.cold_compression_level", tiers.cold_compression_level.to_string()),
+            ];
+            for (key, value) in values {
+                if let Err(e) = manager.set(key, &value, layer) {
+                    return JsonRpcResponse::error(req.id, INVALID_PARAMS, e.to_string());
+                }
+            }
+
+            // Watched projects pick the new windows up at their next migration
+            for repo_entry in state.repos.iter() {
+                let repo = repo_entry.value();
+                if params.project_path.as_ref().is_some_and(|p| p != &repo.project.path) {
+                    continue;
+                }
+                if let Ok(reloaded) = load_config(Some(&repo.project.path)) {
+                    *repo.config.lock().unwrap_or_else(|p| p.into_inner()) = reloaded;
+                }
+            }
+
+            JsonRpcResponse::success(
+                req.id,
+                json!(protocol::TierConfigGetResponse {
+                    config: manager.config.tiers,
+                }),
+            )
+        }
+
         _ => JsonRpcResponse::error(
             req.id,
             METHOD_NOT_FOUND,
@@ -1135,6 +1887,46 @@ pub async fn handle_request(req: &JsonRpcRequest, state: &Arc<DaemonState>) -> J
     response
 }
 
+/// Config as on disk, with `project_path`'s own file layered on top.
+fn load_config(project_path: Option<&str>) -> mnem_core::AppResult<ConfigManager> {
+    let base_dir = get_base_dir()?;
+    match project_path {
+        Some(path) => ConfigManager::with_project(&base_dir, std::path::Path::new(path)),
+        None => ConfigManager::new(&base_dir),
+    }
+}
+
+/// Looks up the repository of a watched project.
+fn watched_repo(state: &DaemonState, project_path: &str) -> Option<Arc<Repository>> {
+    state.repos.get(project_path).map(|r| r.value().clone())
+}
+
+/// The watched project `path` belongs to; `path` may be the project root or
+/// anything under it.
+fn repo_containing(state: &DaemonState, path: &str) -> Option<Arc<Repository>> {
+    watched_repo(state, path).or_else(|| {
+        let path = std::path::Path::new(path);
+        state
+            .repos
+            .iter()
+            .find(|r| path.starts_with(r.key()))
+            .map(|r| r.value().clone())
+    })
+}
+
+/// Runs a long repository operation (GC, fsck, bundles, reverts) off the
+/// async workers so other clients stay responsive.
+pub(crate) async fn run_blocking<T, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> mnem_core::AppResult<T> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(e) => Err(format!("Task failed: {}", e)),
+    }
+}
+
 /// Find the mnem-mcp binary. Searches:
 /// 1. Same directory as the current executable
 /// 2. PATH
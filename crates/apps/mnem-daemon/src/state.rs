@@ -1,4 +1,5 @@
 use crate::Monitor;
+use crate::writer::GroupCommitWriter;
 use dashmap::DashMap;
 use lru::LruCache;
 use mnem_core::Repository;
@@ -33,6 +34,9 @@ pub struct DaemonState {
     /// Active repositories keyed by project path (Concurrent Map)
     pub repos: DashMap<String, Arc<Repository>>,
 
+    /// Group-commit writers keyed by project path, one per repository
+    pub writers: DashMap<String, Arc<GroupCommitWriter>>,
+
     /// LRU cache for history queries (file_path -> history results)
     pub history_cache: RwLock<LruCache<String, Vec<Snapshot>>>,
 
@@ -67,6 +71,7 @@ impl DaemonState {
             auth_token,
             monitors: DashMap::new(),
             repos: DashMap::new(),
+            writers: DashMap::new(),
             history_cache: RwLock::new(LruCache::new(HISTORY_CACHE_SIZE)),
             total_requests: AtomicU64::new(0),
             total_processing_time_us: AtomicU64::new(0),
@@ -92,6 +97,15 @@ impl DaemonState {
         *self.init_state.read() == InitializationState::Shutdown
     }
 
+    /// The writer saves into `project_path`'s repository go through, so
+    /// every monitor of the project shares its group commits.
+    pub fn writer_for(&self, project_path: &str, repo: &Arc<Repository>) -> Arc<GroupCommitWriter> {
+        self.writers
+            .entry(project_path.to_string())
+            .or_insert_with(|| Arc::new(GroupCommitWriter::new(repo.clone())))
+            .clone()
+    }
+
     /// Record a request execution time
     pub fn record_request(&self, duration_us: u64) {
         self.total_requests.fetch_add(1, Ordering::Relaxed);
@@ -0,0 +1,89 @@
+use mnem_core::storage::repository::PreparedSnapshot;
+use mnem_core::{AppError, AppResult, Repository};
+use parking_lot::Mutex;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc;
+
+struct PendingSave {
+    prepared: PreparedSnapshot,
+    reply: mpsc::Sender<(PreparedSnapshot, AppResult<i64>)>,
+}
+
+/// Group-commit writer for one repository.
+///
+/// Hashing, chunking and CAS writes happen on the calling thread. The database
+/// rows are queued, and whichever caller gets the commit lock first writes
+/// everything queued so far in a single transaction. Saves from parallel scan
+/// workers therefore share one fsync instead of paying one each.
+pub struct GroupCommitWriter {
+    repo: Arc<Repository>,
+    queue: Mutex<Vec<PendingSave>>,
+    commit_lock: Mutex<()>,
+}
+
+impl GroupCommitWriter {
+    pub fn new(repo: Arc<Repository>) -> Self {
+        Self {
+            repo,
+            queue: Mutex::new(Vec::new()),
+            commit_lock: Mutex::new(()),
+        }
+    }
+
+    /// Saves `path` and returns its content hash once the snapshot is durable.
+    pub fn save_file(&self, path: &Path) -> AppResult<String> {
+        let content = Repository::read_file_content(path)?;
+        let Some(prepared) = self.repo.prepare_snapshot(path, content)? else {
+            return Ok(self
+                .repo
+                .db
+                .get_last_hash(&path.to_string_lossy())?
+                .unwrap_or_default());
+        };
+
+        let (tx, rx) = mpsc::channel();
+        self.queue.lock().push(PendingSave {
+            prepared,
+            reply: tx,
+        });
+
+        {
+            let _guard = self.commit_lock.lock();
+            // Empty when a previous leader already committed our entry
+            let pending = std::mem::take(&mut *self.queue.lock());
+            if !pending.is_empty() {
+                self.commit(pending);
+            }
+        }
+
+        let (prepared, result) = rx
+            .recv()
+            .map_err(|e| AppError::Internal(format!("Group commit writer dropped: {}", e)))?;
+        let snapshot_id = result?;
+        let hash = prepared.content_hash.clone();
+        self.repo.index_snapshot(prepared, snapshot_id);
+        Ok(hash)
+    }
+
+    fn commit(&self, pending: Vec<PendingSave>) {
+        let refs: Vec<&PreparedSnapshot> = pending.iter().map(|p| &p.prepared).collect();
+        let result = self.repo.commit_snapshots(&refs);
+        log::debug!("Group commit of {} snapshot(s)", pending.len());
+        match result {
+            Ok(ids) => {
+                for (save, id) in pending.into_iter().zip(ids) {
+                    let _ = save.reply.send((save.prepared, Ok(id)));
+                }
+            }
+            Err(e) => {
+                let msg = e.to_string();
+                for save in pending {
+                    let _ = save
+                        .reply
+                        .send((save.prepared, Err(AppError::Database(msg.clone()))));
+                }
+            }
+        }
+    }
+}
@@ -81,6 +81,12 @@ pub struct AppState {
     pub search_results: Vec<SearchResult>,
     pub search_query: String,
 
+    // Pin labels by content or checkpoint hash
+    pub pins: std::collections::HashMap<String, String>,
+
+    // Number of files in each changeset
+    pub changesets: std::collections::HashMap<i64, usize>,
+
     // Selection
     pub selected_file: Option<String>,
     pub diff_base_hash: Option<String>,
@@ -148,6 +154,8 @@ impl Default for AppState {
             search_state: ListState::default(),
             search_results: Vec::new(),
             search_query: String::new(),
+            pins: std::collections::HashMap::new(),
+            changesets: std::collections::HashMap::new(),
             selected_file: None,
             diff_base_hash: None,
             cached_diff: Vec::new(),
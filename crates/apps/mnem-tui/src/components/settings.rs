@@ -26,7 +26,7 @@ pub fn render(f: &mut Frame, area: Rect, state: &AppState) {
         .split(inner_area);
 
     let options = vec![
-        ("Retention", format!("{} days", state.config.retention_days)),
+        ("Retention", state.config.retention.summary()),
         (
             "Compression",
             if state.config.compression_enabled {
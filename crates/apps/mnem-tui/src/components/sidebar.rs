@@ -171,6 +171,26 @@ pub fn render_timeline(f: &mut Frame, area: Rect, state: &mut AppState) {
                         ));
                     }
 
+                    if let Some(label) = state.pins.get(&s.content_hash) {
+                        spans.push(Span::styled(
+                            format!("⚑ {} ", label),
+                            Style::default()
+                                .fg(theme.success)
+                                .add_modifier(Modifier::BOLD),
+                        ));
+                    }
+
+                    // Only worth marking when the save came with others
+                    if let Some(id) = s.changeset_id
+                        && let Some(&files) = state.changesets.get(&id)
+                        && files > 1
+                    {
+                        spans.push(Span::styled(
+                            format!("⧉ #{}·{} ", id, files),
+                            Style::default().fg(theme.text_dim),
+                        ));
+                    }
+
                     spans.push(Span::styled(
                         s.git_branch.clone().unwrap_or_else(|| "".into()),
                         Style::default()
@@ -488,7 +488,7 @@ fn render_settings_view(f: &mut Frame, area: Rect, state: &AppState) {
     let theme = &state.theme;
 
     let items = vec![
-        ("Retention", format!("{} days", state.config.retention_days)),
+        ("Retention", state.config.retention.summary()),
         (
             "Compression",
             if state.config.compression_enabled {
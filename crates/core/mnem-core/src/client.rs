@@ -3,7 +3,7 @@ use crate::error::{AppError, AppResult};
 use crate::process::is_process_running;
 use crate::protocol::{JsonRpcRequest, JsonRpcResponse, PID_FILE};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(unix)]
@@ -171,11 +171,14 @@ impl DaemonClient {
 
 /// Check if the mnem-daemon daemon is currently running.
 pub fn daemon_running() -> bool {
-    let base_dir = match get_base_dir() {
-        Ok(dir) => dir,
-        Err(_) => return false,
-    };
+    match get_base_dir() {
+        Ok(dir) => daemon_running_in(&dir),
+        Err(_) => false,
+    }
+}
 
+/// Check if a daemon owning `base_dir` is currently running.
+pub fn daemon_running_in(base_dir: &Path) -> bool {
     let pid_path = base_dir.join(PID_FILE);
     if !pid_path.exists() {
         return false;
@@ -194,38 +197,51 @@ pub fn daemon_running() -> bool {
 /// Ensure the mnem-daemon daemon is running. If not, attempt to start it.
 /// Returns Ok(true) if daemon was started, Ok(false) if already running.
 pub fn ensure_daemon() -> AppResult<bool> {
+    ensure_daemon_with(None)
+}
+
+/// Like [`ensure_daemon`], handing `passphrase` to a daemon that has to
+/// unlock encrypted history.
+pub fn ensure_daemon_with(passphrase: Option<&str>) -> AppResult<bool> {
     if daemon_running() {
         return Ok(false);
     }
 
+    let base_dir = get_base_dir()?;
+    if passphrase.is_none() && crate::crypto::needs_passphrase(&base_dir) {
+        return Err(AppError::Encryption(
+            "History is encrypted with a passphrase. Run 'mnem on' to enter it".into(),
+        ));
+    }
+
     let daemon_bin = find_daemon_binary()?;
+    // The passphrase goes over a pipe, never the command line
+    let stdin = || match passphrase {
+        Some(_) => std::process::Stdio::piped(),
+        None => std::process::Stdio::null(),
+    };
 
     // Spawn daemon as a detached background process
     #[cfg(unix)]
-    {
-        let child = std::process::Command::new(&daemon_bin)
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .spawn()
-            .map_err(|e| {
-                AppError::Internal(format!(
-                    "Failed to start mnem-daemon at {:?}: {}",
-                    daemon_bin, e
-                ))
-            })?;
-
-        // Don't wait on child - let it run detached
-        std::mem::forget(child);
-    }
+    let mut child = std::process::Command::new(&daemon_bin)
+        .stdin(stdin())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            AppError::Internal(format!(
+                "Failed to start mnem-daemon at {:?}: {}",
+                daemon_bin, e
+            ))
+        })?;
     #[cfg(windows)]
-    {
+    let mut child = {
         use std::os::windows::process::CommandExt;
         const DETACHED_PROCESS: u32 = 0x00000008;
         const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-        let _child = std::process::Command::new(&daemon_bin)
-            .stdin(std::process::Stdio::null())
+        std::process::Command::new(&daemon_bin)
+            .stdin(stdin())
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .creation_flags(DETACHED_PROCESS | CREATE_NO_WINDOW)
@@ -235,12 +251,18 @@ pub fn ensure_daemon() -> AppResult<bool> {
                     "Failed to start mnem-daemon at {:?}: {}",
                     daemon_bin, e
                 ))
-            })?;
+            })?
+    };
+
+    if let (Some(passphrase), Some(mut pipe)) = (passphrase, child.stdin.take()) {
+        writeln!(pipe, "{}", passphrase)
+            .map_err(|e| AppError::Internal(format!("Failed to pass the passphrase: {}", e)))?;
     }
+    // Don't wait on child - let it run detached
+    std::mem::forget(child);
 
     // Wait for daemon to be ready (up to 10 seconds)
     // We check both PID and socket readiness to avoid race conditions
-    let base_dir = get_base_dir()?;
     let socket_path = crate::protocol::get_socket_path(&base_dir);
 
     for _i in 0..100 {
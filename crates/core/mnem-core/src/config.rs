@@ -1,4 +1,5 @@
 use crate::error::{AppError, AppResult};
+use crate::storage::tiered::TierConfig;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -33,9 +34,84 @@ impl Ide {
     }
 }
 
+/// Generational retention: recent history is kept in full and older history
+/// is thinned to one snapshot per file per hour, then per day, then per week.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep every snapshot younger than this many hours.
+    pub keep_all_hours: u64,
+    /// Then keep one snapshot per file per hour, up to this many days old.
+    pub hourly_days: u64,
+    /// Then one per file per day, up to this many days old.
+    pub daily_days: u64,
+    /// Then one per file per week, up to this many weeks old. 0 keeps them forever.
+    pub weekly_weeks: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_all_hours: 24,
+            hourly_days: 7,
+            daily_days: 30,
+            weekly_weeks: 0,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// The default tiers, cut off once history is `days` days old (rounded
+    /// up to whole weeks). 0 keeps history forever.
+    pub fn within_days(days: u64) -> Self {
+        let default = Self::default();
+        if days == 0 {
+            return default;
+        }
+        Self {
+            keep_all_hours: default.keep_all_hours.min(days * 24),
+            hourly_days: default.hourly_days.min(days),
+            daily_days: default.daily_days.min(days),
+            weekly_weeks: days.div_ceil(7),
+        }
+    }
+
+    /// One-line description, e.g. `all 24h · hourly 7d · daily 30d · weekly forever`.
+    pub fn summary(&self) -> String {
+        let weekly = if self.weekly_weeks == 0 {
+            "forever".to_string()
+        } else {
+            format!("{}w", self.weekly_weeks)
+        };
+        format!(
+            "all {}h · hourly {}d · daily {}d · weekly {}",
+            self.keep_all_hours, self.hourly_days, self.daily_days, weekly
+        )
+    }
+}
+
+/// Binary files, and text files over `max_file_size_mb`, are kept as opaque
+/// blobs cut by content-defined chunking.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BinaryPolicy {
+    /// Track blobs at all. When off, binaries and oversized text are skipped.
+    pub enabled: bool,
+    /// Skip blobs larger than this many megabytes. 0 means no limit.
+    pub max_size_mb: u64,
+}
+
+impl Default for BinaryPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: 100,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
-    pub retention_days: u64,
     pub compression_enabled: bool,
     pub use_mnemosyneignore: bool,
     pub theme_index: usize,
@@ -43,46 +119,108 @@ pub struct Config {
     pub max_file_size_mb: u64,
     #[serde(default)]
     pub ide: Ide,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    #[serde(default)]
+    pub tiers: TierConfig,
+    #[serde(default)]
+    pub binary: BinaryPolicy,
+    /// Saves less than this many seconds apart join the same changeset.
+    /// 0 gives every save its own.
+    #[serde(default = "default_changeset_window_secs")]
+    pub changeset_window_secs: u64,
+    /// A coding session ends after this many minutes without a save.
+    #[serde(default = "default_session_idle_minutes")]
+    pub session_idle_minutes: u64,
 }
 
 fn default_max_file_size_mb() -> u64 {
     10
 }
 
+fn default_changeset_window_secs() -> u64 {
+    5
+}
+
+fn default_session_idle_minutes() -> u64 {
+    30
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
-            retention_days: 30,
             compression_enabled: true,
             use_mnemosyneignore: true,
             theme_index: 0,
             max_file_size_mb: default_max_file_size_mb(),
             ide: Ide::default(),
+            retention: RetentionPolicy::default(),
+            tiers: TierConfig::default(),
+            binary: BinaryPolicy::default(),
+            changeset_window_secs: default_changeset_window_secs(),
+            session_idle_minutes: default_session_idle_minutes(),
         }
     }
 }
 
+impl Config {
+    /// Whether a file of `size` bytes is stored as a blob rather than as text.
+    pub fn is_blob(&self, size: u64, binary: bool) -> bool {
+        binary || size > self.max_file_size_mb * 1024 * 1024
+    }
+
+    /// Whether a file of `size` bytes should be tracked at all.
+    pub fn accepts(&self, size: u64, binary: bool) -> bool {
+        if !self.is_blob(size, binary) {
+            return true;
+        }
+        self.binary.enabled
+            && (self.binary.max_size_mb == 0 || size <= self.binary.max_size_mb * 1024 * 1024)
+    }
+}
+
+/// Layer a configuration value comes from, in increasing order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    /// `~/.mnemosyne/config.toml`
+    Global,
+    /// `<project>/.mnemosyne/config.toml`
+    Project,
+}
+
+impl ConfigSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Global => "global",
+            Self::Project => "project",
+        }
+    }
+}
+
+/// Effective configuration: built-in defaults, overridden by the global file,
+/// overridden in turn by the project file when one is loaded.
 pub struct ConfigManager {
     config_path: PathBuf,
+    project_config_path: Option<PathBuf>,
+    global: toml::Table,
+    project: toml::Table,
     pub config: Config,
 }
 
 impl ConfigManager {
     pub fn new(base_dir: &std::path::Path) -> AppResult<Self> {
         let config_path = base_dir.join("config.toml");
-        let config = if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path).map_err(AppError::IoGeneric)?;
-            toml::from_str(&content).unwrap_or_default()
-        } else {
-            Config::default()
-        };
 
         // Auto-save default if missing
         if !config_path.exists() {
-            if let Err(e) = Self::save_to_path(&config, &config_path) {
+            if let Err(e) = Self::save_to_path(&Config::default(), &config_path) {
                 eprintln!("Warning: failed to save default config: {}", e);
             }
         }
+        let global = Self::load_table(&config_path);
 
         // Create default global .mnemignore if missing
         let ignore_path = base_dir.join(".mnemignore");
@@ -134,31 +272,146 @@ Gemfile.lock
             let _ = std::fs::write(&ignore_path, default_ignore);
         }
 
-        Ok(Self {
+        let mut manager = Self {
             config_path,
-            config,
-        })
+            project_config_path: None,
+            global,
+            project: toml::Table::new(),
+            config: Config::default(),
+        };
+        manager.config = manager.merged().unwrap_or_default();
+        Ok(manager)
     }
 
-    pub fn save(&self) -> AppResult<()> {
-        Self::save_to_path(&self.config, &self.config_path)
+    /// Loads the global config with `<project_root>/.mnemosyne/config.toml` layered on top.
+    pub fn with_project(
+        base_dir: &std::path::Path,
+        project_root: &std::path::Path,
+    ) -> AppResult<Self> {
+        let mut manager = Self::new(base_dir)?;
+        let project_config_path = project_root.join(".mnemosyne").join("config.toml");
+        manager.project = Self::load_table(&project_config_path);
+        manager.project_config_path = Some(project_config_path);
+        manager.config = manager.merged().unwrap_or_default();
+        Ok(manager)
+    }
+
+    /// A missing or unreadable file contributes nothing, as before layering existed.
+    fn load_table(path: &std::path::Path) -> toml::Table {
+        let mut table = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+            .unwrap_or_default();
+        migrate_retention_days(&mut table);
+        table
+    }
+
+    fn defaults() -> toml::Table {
+        toml::Table::try_from(Config::default()).unwrap_or_default()
+    }
+
+    fn merged_table(&self) -> toml::Table {
+        let mut table = Self::defaults();
+        merge_into(&mut table, &self.global);
+        merge_into(&mut table, &self.project);
+        table
+    }
+
+    fn merged(&self) -> AppResult<Config> {
+        self.merged_table()
+            .try_into()
+            .map_err(|e: toml::de::Error| AppError::Config(e.to_string()))
+    }
+
+    /// Effective value of a dotted key such as `retention.daily_days`, and the
+    /// layer it was taken from.
+    pub fn get(&self, key: &str) -> AppResult<(toml::Value, ConfigSource)> {
+        let path = key_path(key);
+        let value = lookup(&self.merged_table(), &path)
+            .cloned()
+            .ok_or_else(|| AppError::Config(format!("Unknown config key: {}", key)))?;
+        let source = if lookup(&self.project, &path).is_some() {
+            ConfigSource::Project
+        } else if lookup(&self.global, &path).is_some() {
+            ConfigSource::Global
+        } else {
+            ConfigSource::Default
+        };
+        Ok((value, source))
+    }
+
+    /// Sets `key` in the global or project file. `raw` is parsed as the type
+    /// the key already has, and the result must still form a valid config.
+    pub fn set(&mut self, key: &str, raw: &str, layer: ConfigSource) -> AppResult<()> {
+        let (current, _) = self.get(key)?;
+        let value = parse_as(&current, raw)
+            .ok_or_else(|| AppError::Config(format!("Invalid value for {}: {}", key, raw)))?;
+        let path = key_path(key);
+
+        let (table, file) = match layer {
+            ConfigSource::Default => {
+                return Err(AppError::Config("Defaults cannot be changed".into()));
+            }
+            ConfigSource::Global => (&mut self.global, self.config_path.clone()),
+            ConfigSource::Project => match &self.project_config_path {
+                Some(p) => (&mut self.project, p.clone()),
+                None => return Err(AppError::Config("No project config loaded".into())),
+            },
+        };
+        let previous = table.clone();
+        insert(table, &path, value);
+        let config = match self.merged() {
+            Ok(c) => c,
+            Err(e) => {
+                match layer {
+                    ConfigSource::Project => self.project = previous,
+                    _ => self.global = previous,
+                }
+                return Err(e);
+            }
+        };
+        let table = match layer {
+            ConfigSource::Project => &self.project,
+            _ => &self.global,
+        };
+        let content = toml::to_string_pretty(table).map_err(|e| AppError::Config(e.to_string()))?;
+        Self::write_atomic(&content, &file)?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Writes the effective config to the global file. Values that only come
+    /// from the project file are left out so they do not leak into other projects.
+    pub fn save(&mut self) -> AppResult<()> {
+        let mut table =
+            toml::Table::try_from(&self.config).map_err(|e| AppError::Config(e.to_string()))?;
+        strip_project_values(&mut table, &self.project, &self.global);
+        let content =
+            toml::to_string_pretty(&table).map_err(|e| AppError::Config(e.to_string()))?;
+        Self::write_atomic(&content, &self.config_path)?;
+        self.global = table;
+        Ok(())
     }
 
     fn save_to_path(config: &Config, path: &PathBuf) -> AppResult<()> {
         let content =
             toml::to_string_pretty(config).map_err(|e| AppError::Config(e.to_string()))?;
+        Self::write_atomic(&content, path)
+    }
 
+    fn write_atomic(content: &str, path: &PathBuf) -> AppResult<()> {
         // Atomic write: write to tempfile then rename to prevent corruption on crash
         let parent = path.parent().unwrap_or(std::path::Path::new("."));
         let temp = tempfile::NamedTempFile::new_in(parent).map_err(AppError::IoGeneric)?;
-        std::fs::write(temp.path(), &content).map_err(AppError::IoGeneric)?;
+        std::fs::write(temp.path(), content).map_err(AppError::IoGeneric)?;
         temp.persist(path)
             .map_err(|e| AppError::IoGeneric(e.error))?;
         Ok(())
     }
 
+    /// Keeps history for `days` days; see [`RetentionPolicy::within_days`].
     pub fn update_retention(&mut self, days: u64) -> AppResult<()> {
-        self.config.retention_days = days;
+        self.config.retention = RetentionPolicy::within_days(days);
         self.save()
     }
 
@@ -168,6 +421,102 @@ Gemfile.lock
     }
 }
 
+/// Files from before the generational policy cap history with
+/// `retention_days`. Unless the file also has a `[retention]` table, that
+/// becomes the policy; the old key is dropped either way.
+fn migrate_retention_days(table: &mut toml::Table) {
+    let Some(days) = table.remove("retention_days") else {
+        return;
+    };
+    if table.contains_key("retention") {
+        return;
+    }
+    let days = days
+        .as_integer()
+        .and_then(|d| u64::try_from(d).ok())
+        .unwrap_or(0);
+    if let Ok(policy) = toml::Table::try_from(RetentionPolicy::within_days(days)) {
+        table.insert("retention".into(), toml::Value::Table(policy));
+    }
+}
+
+/// Accepts `max-file-size-mb` as well as `max_file_size_mb`.
+fn key_path(key: &str) -> Vec<String> {
+    key.split('.').map(|part| part.replace('-', "_")).collect()
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (first, rest) = path.split_first()?;
+    let value = table.get(first)?;
+    if rest.is_empty() {
+        Some(value)
+    } else {
+        lookup(value.as_table()?, rest)
+    }
+}
+
+fn insert(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        table.insert(first.clone(), value);
+        return;
+    }
+    let entry = table
+        .entry(first.clone())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(inner) = entry {
+        insert(inner, rest, value);
+    }
+}
+
+fn merge_into(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge_into(b, o),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Replaces values that equal the project override with the global file's
+/// own value, or drops them when the global file has none.
+fn strip_project_values(table: &mut toml::Table, project: &toml::Table, global: &toml::Table) {
+    for (key, project_value) in project {
+        let global_value = global.get(key);
+        match (table.get_mut(key), project_value) {
+            (Some(toml::Value::Table(t)), toml::Value::Table(p)) => {
+                let empty = toml::Table::new();
+                let g = global_value.and_then(|v| v.as_table()).unwrap_or(&empty);
+                strip_project_values(t, p, g);
+            }
+            (Some(current), _) if current == project_value => match global_value {
+                Some(g) => *current = g.clone(),
+                None => {
+                    table.remove(key);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn parse_as(current: &toml::Value, raw: &str) -> Option<toml::Value> {
+    match current {
+        toml::Value::Integer(_) => raw.parse().ok().map(toml::Value::Integer),
+        toml::Value::Boolean(_) => raw.parse().ok().map(toml::Value::Boolean),
+        toml::Value::Float(_) => raw.parse().ok().map(toml::Value::Float),
+        toml::Value::String(_) => Some(toml::Value::String(raw.to_string())),
+        _ => format!("v = {}", raw)
+            .parse::<toml::Table>()
+            .ok()?
+            .remove("v"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,7 +526,7 @@ mod tests {
     fn default_config_created_when_missing() {
         let dir = TempDir::new().unwrap();
         let config_manager = ConfigManager::new(dir.path()).unwrap();
-        assert_eq!(config_manager.config.retention_days, 30);
+        assert_eq!(config_manager.config.retention, RetentionPolicy::default());
         assert!(dir.path().join("config.toml").exists());
     }
 
@@ -189,7 +538,145 @@ mod tests {
             config_manager.update_retention(7).unwrap();
         }
         let config_manager = ConfigManager::new(dir.path()).unwrap();
-        assert_eq!(config_manager.config.retention_days, 7);
+        assert_eq!(
+            config_manager.config.retention,
+            RetentionPolicy::within_days(7)
+        );
+    }
+
+    #[test]
+    fn retention_days_becomes_the_policy() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "retention_days = 14\ncompression_enabled = false\n").unwrap();
+        let mut config_manager = ConfigManager::new(dir.path()).unwrap();
+        let policy = &config_manager.config.retention;
+        assert_eq!((policy.hourly_days, policy.daily_days), (7, 14));
+        assert_eq!(policy.weekly_weeks, 2);
+        assert!(!config_manager.config.compression_enabled);
+        assert!(config_manager.get("retention_days").is_err());
+
+        config_manager.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("retention_days"));
+        assert!(saved.contains("weekly_weeks = 2"));
+
+        // 0 kept history forever
+        std::fs::write(&path, "retention_days = 0\n").unwrap();
+        let config_manager = ConfigManager::new(dir.path()).unwrap();
+        assert_eq!(config_manager.config.retention, RetentionPolicy::default());
+    }
+
+    #[test]
+    fn retention_policy_defaults_when_missing() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            "retention_days = 30\ncompression_enabled = true\nuse_mnemosyneignore = true\ntheme_index = 0\n\n[retention]\nhourly_days = 3\n",
+        )
+        .unwrap();
+        let config_manager = ConfigManager::new(dir.path()).unwrap();
+        let policy = &config_manager.config.retention;
+        assert_eq!(policy.hourly_days, 3);
+        assert_eq!(policy.keep_all_hours, 24);
+        assert_eq!(policy.daily_days, 30);
+    }
+
+    #[test]
+    fn binary_policy_caps_blobs_separately() {
+        let mut config = Config::default();
+        let mb = 1024 * 1024;
+        assert!(config.accepts(mb, false));
+        assert!(!config.is_blob(mb, false));
+        assert!(config.is_blob(mb, true));
+        assert!(config.accepts(50 * mb, false));
+        assert!(config.is_blob(50 * mb, false));
+        assert!(!config.accepts(101 * mb, true));
+
+        config.binary.max_size_mb = 0;
+        assert!(config.accepts(101 * mb, true));
+        config.binary.enabled = false;
+        assert!(!config.accepts(mb, true));
+        assert!(!config.accepts(50 * mb, false));
+        assert!(config.accepts(mb, false));
+    }
+
+    #[test]
+    fn project_config_overrides_global() {
+        let home = TempDir::new().unwrap();
+        let project = TempDir::new().unwrap();
+        std::fs::create_dir_all(project.path().join(".mnemosyne")).unwrap();
+
+        let mut global = ConfigManager::new(home.path()).unwrap();
+        global
+            .set("max_file_size_mb", "20", ConfigSource::Global)
+            .unwrap();
+
+        let mut layered = ConfigManager::with_project(home.path(), project.path()).unwrap();
+        assert_eq!(layered.config.max_file_size_mb, 20);
+        layered
+            .set("max-file-size-mb", "100", ConfigSource::Project)
+            .unwrap();
+        layered
+            .set("retention.daily_days", "90", ConfigSource::Project)
+            .unwrap();
+
+        let layered = ConfigManager::with_project(home.path(), project.path()).unwrap();
+        assert_eq!(layered.config.max_file_size_mb, 100);
+        assert_eq!(layered.config.retention.daily_days, 90);
+        assert_eq!(layered.config.retention.hourly_days, 7);
+        let (value, source) = layered.get("max_file_size_mb").unwrap();
+        assert_eq!(value.as_integer(), Some(100));
+        assert_eq!(source, ConfigSource::Project);
+        assert_eq!(
+            layered.get("compression_enabled").unwrap().1,
+            ConfigSource::Global
+        );
+
+        // The global view is unaffected
+        let global = ConfigManager::new(home.path()).unwrap();
+        assert_eq!(global.config.max_file_size_mb, 20);
+        assert_eq!(global.config.retention.daily_days, 30);
+    }
+
+    #[test]
+    fn save_does_not_leak_project_values() {
+        let home = TempDir::new().unwrap();
+        let project = TempDir::new().unwrap();
+        std::fs::create_dir_all(project.path().join(".mnemosyne")).unwrap();
+        let mut layered = ConfigManager::with_project(home.path(), project.path()).unwrap();
+        layered
+            .set("max_file_size_mb", "100", ConfigSource::Project)
+            .unwrap();
+        layered.toggle_compression().unwrap();
+
+        let global = ConfigManager::new(home.path()).unwrap();
+        assert_eq!(global.config.max_file_size_mb, 10);
+        assert!(!global.config.compression_enabled);
+    }
+
+    #[test]
+    fn set_rejects_invalid_values() {
+        let home = TempDir::new().unwrap();
+        let mut config = ConfigManager::new(home.path()).unwrap();
+        assert!(
+            config
+                .set("max_file_size_mb", "lots", ConfigSource::Global)
+                .is_err()
+        );
+        assert!(config.set("ide", "Emacs", ConfigSource::Global).is_err());
+        assert!(
+            config
+                .set("no_such_key", "1", ConfigSource::Global)
+                .is_err()
+        );
+        assert!(
+            config
+                .set("max_file_size_mb", "5", ConfigSource::Project)
+                .is_err()
+        );
+        assert_eq!(config.config.max_file_size_mb, 10);
+        assert_eq!(config.config.ide, Ide::Zed);
     }
 
     #[test]
@@ -0,0 +1,584 @@
+//! Encryption at rest.
+//!
+//! A keyring in `~/.mnemosyne` switches encryption on for every project. The
+//! master key is read from a key file or derived from a passphrase
+//! (PBKDF2-HMAC-SHA256), and is unlocked once per process: by the daemon when
+//! it starts, or by a CLI command that opens history without it.
+//!
+//! While unlocked:
+//! - CAS objects and packs are sealed with ChaCha20-Poly1305,
+//! - database records are sealed, and interned strings (paths, branches,
+//!   symbol names) are sealed and looked up by a keyed token,
+//! - object ids are BLAKE3 hashes keyed with a secret derived from the master
+//!   key, so identical content still deduplicates but an id no longer confirms
+//!   a guess about the content.
+//!
+//! Every seal is bound to where it is stored (an object's id, a record's table
+//! and key, a string's id), so a sealed value copied over another fails to
+//! open instead of being read as that one.
+//!
+//! History written before encryption was enabled, or sealed before seals were
+//! bound, stays readable and is sealed again the next time its project is
+//! opened.
+
+use crate::error::{AppError, AppResult};
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+pub const KEYRING_FILE: &str = "keyring.json";
+pub const KEY_FILE: &str = "master.key";
+/// Passphrase for processes that cannot prompt for it.
+pub const PASSPHRASE_ENV: &str = "MNEM_PASSPHRASE";
+
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const MIN_PASSPHRASE_LEN: usize = 8;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+/// Prefix of data sealed before seals were bound to their place.
+const SEALED_MAGIC: [u8; 4] = *b"mENC";
+/// Prefix of data sealed and bound to its place.
+const BOUND_MAGIC: [u8; 4] = *b"mENB";
+/// Prefix of interned strings that are tokens or sealed values. Paths,
+/// branches and symbol names never start with it.
+const STRING_MARKER: char = '\u{1}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Passphrase,
+    KeyFile,
+}
+
+impl KeySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeySource::Passphrase => "passphrase",
+            KeySource::KeyFile => "key file",
+        }
+    }
+}
+
+/// Contents of `keyring.json`. Holds nothing secret.
+#[derive(Debug, Serialize, Deserialize)]
+struct Keyring {
+    source: KeySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_file: Option<PathBuf>,
+    /// Hex PBKDF2 salt, for a passphrase.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    salt: String,
+    #[serde(default)]
+    iterations: u32,
+    /// Derived from the master key, to reject a wrong passphrase or key file.
+    check: String,
+}
+
+impl Keyring {
+    fn path(base_dir: &Path) -> PathBuf {
+        base_dir.join(KEYRING_FILE)
+    }
+
+    fn load(base_dir: &Path) -> AppResult<Option<Self>> {
+        let path = Self::path(base_dir);
+        let raw = match fs::read(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::Io { path, source: e }),
+        };
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .map_err(|e| AppError::Encryption(format!("Unreadable keyring {:?}: {}", path, e)))
+    }
+
+    fn save(&self, base_dir: &Path) -> AppResult<()> {
+        let path = Self::path(base_dir);
+        if path.exists() {
+            return Err(AppError::Encryption(
+                "Encryption is already enabled".to_string(),
+            ));
+        }
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::create_dir_all(base_dir).map_err(AppError::IoGeneric)?;
+        fs::write(&path, json).map_err(|e| AppError::Io { path, source: e })
+    }
+
+    fn master_key(&self, passphrase: Option<&str>) -> AppResult<[u8; KEY_LEN]> {
+        match self.source {
+            KeySource::KeyFile => {
+                let path = self
+                    .key_file
+                    .as_deref()
+                    .ok_or_else(|| AppError::Encryption("Keyring names no key file".to_string()))?;
+                key_from_file(path)
+            }
+            KeySource::Passphrase => {
+                let passphrase = passphrase.ok_or_else(|| {
+                    AppError::Encryption(format!(
+                        "History is encrypted with a passphrase. Set {} or run 'mnem on' to enter it",
+                        PASSPHRASE_ENV
+                    ))
+                })?;
+                let salt = from_hex(&self.salt)
+                    .ok_or_else(|| AppError::Encryption("Keyring salt is malformed".to_string()))?;
+                Ok(stretch(passphrase, &salt, self.iterations))
+            }
+        }
+    }
+}
+
+/// Keys derived from the master key.
+pub(crate) struct Keys {
+    object_id: [u8; KEY_LEN],
+    index: [u8; KEY_LEN],
+    hash_prefix: [u8; KEY_LEN],
+    cipher: LessSafeKey,
+}
+
+impl Keys {
+    fn derive(master: &[u8; KEY_LEN]) -> AppResult<Self> {
+        let cipher_key = blake3::derive_key("mnemosyne 2024 cipher", master);
+        let unbound = UnboundKey::new(&CHACHA20_POLY1305, &cipher_key)
+            .map_err(|_| AppError::Encryption("Invalid cipher key".to_string()))?;
+        Ok(Self {
+            object_id: blake3::derive_key("mnemosyne 2024 object id", master),
+            index: blake3::derive_key("mnemosyne 2024 string index", master),
+            hash_prefix: blake3::derive_key("mnemosyne 2024 hash prefix", master),
+            cipher: LessSafeKey::new(unbound),
+        })
+    }
+
+    fn check(master: &[u8; KEY_LEN]) -> String {
+        to_hex(&blake3::derive_key("mnemosyne 2024 key check", master)[..16])
+    }
+
+    fn seal(&self, data: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| AppError::Encryption("No randomness for a nonce".to_string()))?;
+
+        let mut out = Vec::with_capacity(BOUND_MAGIC.len() + NONCE_LEN + data.len() + 16);
+        out.extend_from_slice(&BOUND_MAGIC);
+        out.extend_from_slice(&nonce);
+        let mut body = data.to_vec();
+        self.cipher
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut body,
+            )
+            .map_err(|_| AppError::Encryption("Sealing failed".to_string()))?;
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Opens data sealed with `aad`, or sealed unbound before seals were
+    /// bound to their place.
+    fn open(&self, sealed: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+        let header = SEALED_MAGIC.len() + NONCE_LEN;
+        if !is_sealed(sealed) || sealed.len() < header {
+            return Err(AppError::Encryption("Not sealed data".to_string()));
+        }
+        let aad = if is_bound(sealed) { aad } else { &[] };
+        let nonce = Nonce::try_assume_unique_for_key(&sealed[SEALED_MAGIC.len()..header])
+            .map_err(|_| AppError::Encryption("Malformed nonce".to_string()))?;
+        let mut body = sealed[header..].to_vec();
+        let len = self
+            .cipher
+            .open_in_place(nonce, Aad::from(aad), &mut body)
+            .map_err(|_| {
+                AppError::Encryption(
+                    "Sealed data failed authentication (wrong key or tampered)".to_string(),
+                )
+            })?
+            .len();
+        body.truncate(len);
+        Ok(body)
+    }
+}
+
+static KEYS: RwLock<Option<Arc<Keys>>> = RwLock::new(None);
+
+fn keys() -> Option<Arc<Keys>> {
+    KEYS.read().unwrap_or_else(|p| p.into_inner()).clone()
+}
+
+fn install(keys: Keys) {
+    *KEYS.write().unwrap_or_else(|p| p.into_inner()) = Some(Arc::new(keys));
+}
+
+/// Whether this process holds the master key.
+pub fn is_unlocked() -> bool {
+    keys().is_some()
+}
+
+/// Forgets the master key.
+pub fn lock() {
+    *KEYS.write().unwrap_or_else(|p| p.into_inner()) = None;
+}
+
+/// How history under `base_dir` is encrypted, if at all.
+pub fn status(base_dir: &Path) -> AppResult<Option<KeySource>> {
+    Ok(Keyring::load(base_dir)?.map(|k| k.source))
+}
+
+/// Whether unlocking needs a passphrase nobody has supplied yet.
+pub fn needs_passphrase(base_dir: &Path) -> bool {
+    !is_unlocked()
+        && std::env::var(PASSPHRASE_ENV).is_err()
+        && matches!(status(base_dir), Ok(Some(KeySource::Passphrase)))
+}
+
+/// Enables encryption with a key derived from `passphrase`, and unlocks it.
+pub fn enable_with_passphrase(base_dir: &Path, passphrase: &str) -> AppResult<()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(AppError::Encryption(format!(
+            "The passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| AppError::Encryption("No randomness for a salt".to_string()))?;
+    let master = stretch(passphrase, &salt, PBKDF2_ITERATIONS);
+    Keyring {
+        source: KeySource::Passphrase,
+        key_file: None,
+        salt: to_hex(&salt),
+        iterations: PBKDF2_ITERATIONS,
+        check: Keys::check(&master),
+    }
+    .save(base_dir)?;
+    install(Keys::derive(&master)?);
+    Ok(())
+}
+
+/// Enables encryption with a key file, and unlocks it. A missing file is
+/// created with a fresh random key; without a path it is `master.key` in
+/// `base_dir`. Returns the key file used.
+pub fn enable_with_key_file(base_dir: &Path, key_file: Option<&Path>) -> AppResult<PathBuf> {
+    let path = key_file
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| base_dir.join(KEY_FILE));
+    if Keyring::path(base_dir).exists() {
+        return Err(AppError::Encryption(
+            "Encryption is already enabled".to_string(),
+        ));
+    }
+    if !path.exists() {
+        let mut key = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut key)
+            .map_err(|_| AppError::Encryption("No randomness for a key".to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(AppError::IoGeneric)?;
+        }
+        fs::write(&path, key).map_err(|e| AppError::Io {
+            path: path.clone(),
+            source: e,
+        })?;
+        crate::utils::auth::AuthManager::restrict_permissions(&path)?;
+    }
+    let path = fs::canonicalize(&path).map_err(|e| AppError::Io {
+        path: path.clone(),
+        source: e,
+    })?;
+
+    let master = key_from_file(&path)?;
+    Keyring {
+        source: KeySource::KeyFile,
+        key_file: Some(path.clone()),
+        salt: String::new(),
+        iterations: 0,
+        check: Keys::check(&master),
+    }
+    .save(base_dir)?;
+    install(Keys::derive(&master)?);
+    Ok(path)
+}
+
+/// Unlocks history under `base_dir`. A passphrase falls back to
+/// `MNEM_PASSPHRASE`. Returns false when encryption is not enabled.
+pub fn unlock(base_dir: &Path, passphrase: Option<&str>) -> AppResult<bool> {
+    let Some(keyring) = Keyring::load(base_dir)? else {
+        return Ok(false);
+    };
+    let from_env = std::env::var(PASSPHRASE_ENV).ok();
+    let master = keyring.master_key(passphrase.or(from_env.as_deref()))?;
+    if Keys::check(&master) != keyring.check {
+        return Err(AppError::Encryption(match keyring.source {
+            KeySource::Passphrase => "Wrong passphrase".to_string(),
+            KeySource::KeyFile => "The key file does not match this keyring".to_string(),
+        }));
+    }
+    install(Keys::derive(&master)?);
+    Ok(true)
+}
+
+/// Unlocks history under `base_dir` unless this process already did, or
+/// encryption is not enabled.
+pub fn ensure_unlocked(base_dir: &Path) -> AppResult<()> {
+    if !is_unlocked() {
+        unlock(base_dir, None)?;
+    }
+    Ok(())
+}
+
+fn stretch(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    let rounds = NonZeroU32::new(iterations.max(1)).unwrap_or(NonZeroU32::MIN);
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        rounds,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+fn key_from_file(path: &Path) -> AppResult<[u8; KEY_LEN]> {
+    let raw = fs::read(path).map_err(|e| AppError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    if raw.len() < KEY_LEN {
+        return Err(AppError::Encryption(format!(
+            "Key file {:?} holds fewer than {} bytes",
+            path, KEY_LEN
+        )));
+    }
+    Ok(blake3::derive_key("mnemosyne 2024 key file", &raw))
+}
+
+/// Starts hashing an object: keyed while unlocked, plain BLAKE3 otherwise.
+pub(crate) fn object_hasher() -> blake3::Hasher {
+    match keys() {
+        Some(keys) => blake3::Hasher::new_keyed(&keys.object_id),
+        None => blake3::Hasher::new(),
+    }
+}
+
+/// The id `content` is stored under.
+pub(crate) fn object_id(content: &[u8]) -> String {
+    object_hasher()
+        .update(content)
+        .finalize()
+        .to_hex()
+        .to_string()
+}
+
+/// Whether `hash` names `content`, under either the keyed or the plain id.
+/// Objects stored before encryption was enabled keep their plain ids.
+pub(crate) fn is_object_id(content: &[u8], hash: &str) -> bool {
+    object_id(content) == hash || blake3::hash(content).to_hex().as_str() == hash
+}
+
+/// Whether `hash` is an id of the empty object.
+pub(crate) fn is_empty_object(hash: &str) -> bool {
+    is_object_id(b"", hash)
+}
+
+pub(crate) fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(&SEALED_MAGIC) || is_bound(data)
+}
+
+/// Whether `data` is sealed and bound to its place.
+pub(crate) fn is_bound(data: &[u8]) -> bool {
+    data.starts_with(&BOUND_MAGIC)
+}
+
+/// Seals `data` bound to `aad` while unlocked. `None` when encryption is off.
+pub(crate) fn seal(data: &[u8], aad: &[u8]) -> AppResult<Option<Vec<u8>>> {
+    keys().map(|keys| keys.seal(data, aad)).transpose()
+}
+
+/// Seals `data` bound to `aad` while unlocked, unless it already is sealed.
+pub(crate) fn seal_if_unlocked<'a>(data: &'a [u8], aad: &[u8]) -> AppResult<Cow<'a, [u8]>> {
+    if is_sealed(data) {
+        return Ok(Cow::Borrowed(data));
+    }
+    Ok(match seal(data, aad)? {
+        Some(sealed) => Cow::Owned(sealed),
+        None => Cow::Borrowed(data),
+    })
+}
+
+/// Seals `data` bound to `aad` if it is in the clear or sealed unbound.
+/// `None` when it is already bound, or while locked.
+pub(crate) fn reseal(data: &[u8], aad: &[u8]) -> AppResult<Option<Vec<u8>>> {
+    if is_bound(data) {
+        return Ok(None);
+    }
+    let Some(keys) = keys() else {
+        return Ok(None);
+    };
+    if is_sealed(data) {
+        keys.seal(&keys.open(data, aad)?, aad).map(Some)
+    } else {
+        keys.seal(data, aad).map(Some)
+    }
+}
+
+/// Opens data written by [`seal`] with the same `aad`.
+pub(crate) fn open(sealed: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+    keys()
+        .ok_or_else(|| AppError::Encryption("History is encrypted and locked".to_string()))?
+        .open(sealed, aad)
+}
+
+/// Key an interned string is indexed under: a keyed token while unlocked.
+pub(crate) fn string_key(s: &str) -> Cow<'_, str> {
+    match keys() {
+        Some(keys) => {
+            let token = blake3::keyed_hash(&keys.index, s.as_bytes());
+            Cow::Owned(format!("{}{}", STRING_MARKER, token.to_hex()))
+        }
+        None => Cow::Borrowed(s),
+    }
+}
+
+/// Key a content hash is indexed under for lookups by a short hash: its
+/// first `len` hex digits, as a keyed token while unlocked. `None` when the
+/// hash is shorter.
+pub(crate) fn hash_prefix_key(hash: &str, len: usize) -> Option<String> {
+    let prefix = hash.get(..len)?.to_lowercase();
+    Some(match keys() {
+        Some(keys) => {
+            let token = blake3::keyed_hash(&keys.hash_prefix, prefix.as_bytes());
+            format!("{}{}", STRING_MARKER, &token.to_hex()[..32])
+        }
+        None => prefix,
+    })
+}
+
+/// Whether a stored value is sealed and bound to its place.
+pub(crate) fn is_bound_string(stored: &str) -> bool {
+    stored
+        .strip_prefix(STRING_MARKER)
+        .and_then(|hex| from_hex(hex.get(..2 * BOUND_MAGIC.len())?))
+        .is_some_and(|magic| magic == BOUND_MAGIC)
+}
+
+/// Value an interned string is stored as: sealed and bound to `aad` while
+/// unlocked.
+pub(crate) fn seal_string<'a>(s: &'a str, aad: &[u8]) -> AppResult<Cow<'a, str>> {
+    Ok(match seal(s.as_bytes(), aad)? {
+        Some(sealed) => Cow::Owned(format!("{}{}", STRING_MARKER, to_hex(&sealed))),
+        None => Cow::Borrowed(s),
+    })
+}
+
+/// Reads a value written by [`seal_string`] with the same `aad`.
+pub(crate) fn open_string<'a>(stored: &'a str, aad: &[u8]) -> AppResult<Cow<'a, str>> {
+    let Some(hex) = stored.strip_prefix(STRING_MARKER) else {
+        return Ok(Cow::Borrowed(stored));
+    };
+    let sealed =
+        from_hex(hex).ok_or_else(|| AppError::Encryption("Malformed sealed string".to_string()))?;
+    String::from_utf8(open(&sealed, aad)?)
+        .map(Cow::Owned)
+        .map_err(|_| AppError::Encryption("Sealed string is not UTF-8".to_string()))
+}
+
+/// Replaces trigram ids by keyed ones while unlocked, so the search index
+/// does not spell out the content it indexes.
+pub(crate) fn trigram_blinder() -> impl Fn(u32) -> u32 {
+    let keys = keys();
+    move |trigram| match &keys {
+        Some(keys) => {
+            let hash = blake3::keyed_hash(&keys.index, &trigram.to_le_bytes());
+            let bytes = hash.as_bytes();
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+        None => trigram,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_keys() -> Keys {
+        Keys::derive(&[7u8; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn test_seal_roundtrip_and_tamper_detection() {
+        let keys = test_keys();
+        let sealed = keys.seal(b"API_KEY=secret", b"object a").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(keys.open(&sealed, b"object a").unwrap(), b"API_KEY=secret");
+
+        // Same plaintext, fresh nonce
+        assert_ne!(keys.seal(b"API_KEY=secret", b"object a").unwrap(), sealed);
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(keys.open(&tampered, b"object a").is_err());
+        let other = Keys::derive(&[8u8; KEY_LEN]).unwrap();
+        assert!(other.open(&sealed, b"object a").is_err());
+    }
+
+    #[test]
+    fn test_sealed_value_swapped_to_another_place_fails_to_open() {
+        let keys = test_keys();
+        let a = keys.seal(b"content of a", b"object a").unwrap();
+        let b = keys.seal(b"content of b", b"object b").unwrap();
+        assert!(keys.open(&a, b"object b").is_err());
+        assert!(keys.open(&b, b"object a").is_err());
+        assert!(keys.open(&a, b"").is_err());
+    }
+
+    #[test]
+    fn test_unbound_seal_still_opens() {
+        let keys = test_keys();
+        let mut legacy = keys.seal(b"old record", b"").unwrap();
+        legacy[..SEALED_MAGIC.len()].copy_from_slice(&SEALED_MAGIC);
+        assert!(is_sealed(&legacy) && !is_bound(&legacy));
+        assert_eq!(keys.open(&legacy, b"anywhere").unwrap(), b"old record");
+    }
+
+    #[test]
+    fn test_passphrase_check_rejects_a_wrong_passphrase() {
+        let salt = [1u8; SALT_LEN];
+        let right = stretch("correct horse", &salt, 10);
+        assert_eq!(right, stretch("correct horse", &salt, 10));
+        assert_ne!(
+            Keys::check(&right),
+            Keys::check(&stretch("wrong horse", &salt, 10))
+        );
+        assert_ne!(right, stretch("correct horse", &[2u8; SALT_LEN], 10));
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0u8, 1, 0xab, 0xff];
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert!(from_hex("abc").is_none());
+        assert!(from_hex("zz").is_none());
+    }
+}
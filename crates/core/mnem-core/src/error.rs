@@ -36,6 +36,15 @@ pub enum AppError {
     #[error("Internal error: {0}")]
     Internal(String),
 
+    #[error("History is locked: {0}")]
+    Locked(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Git error: {0}")]
+    Git(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
@@ -0,0 +1,388 @@
+//! Just enough of git to see what was committed, read straight from `.git`.
+//!
+//! Covers refs (loose and packed), detached HEADs, linked worktrees, loose
+//! objects and v2 packfiles with both delta kinds. There is no writing and no
+//! SHA-256 object format; a repository using it reads as having no HEAD.
+
+mod objects;
+
+pub use objects::{Kind, ObjectStore};
+
+use crate::error::{AppError, AppResult};
+use chrono::{FixedOffset, TimeZone};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Symbolic refs pointing at symbolic refs, at most this deep.
+const MAX_REF_DEPTH: usize = 5;
+
+/// A SHA-1 object id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Oid([u8; 20]);
+
+impl Oid {
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 40 {
+            return None;
+        }
+        let mut bytes = [0u8; 20];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self(bytes.try_into().ok()?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Oid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Oid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Oid({})", self)
+    }
+}
+
+/// Where HEAD points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    /// `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// `None` on a branch without commits yet.
+    pub oid: Option<Oid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub oid: Oid,
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    /// Author name, without the email.
+    pub author: String,
+    /// Committer time, seconds since the epoch.
+    pub time: i64,
+    /// Committer UTC offset in minutes.
+    pub offset_minutes: i32,
+    pub message: String,
+}
+
+impl Commit {
+    /// First line of the message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("").trim()
+    }
+
+    /// Committer time in RFC3339, in the committer's own offset like
+    /// `git log --format=%cI`.
+    pub fn timestamp(&self) -> String {
+        FixedOffset::east_opt(self.offset_minutes * 60)
+            .and_then(|tz| tz.timestamp_opt(self.time, 0).single())
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| crate::utils::time::format_ms(self.time * 1000))
+    }
+
+    fn parse(oid: Oid, data: &[u8]) -> AppResult<Self> {
+        let text = String::from_utf8_lossy(data);
+        let (headers, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = String::new();
+        let mut committed = None;
+        for line in headers.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            match key {
+                "tree" => tree = Oid::from_hex(value),
+                "parent" => parents.extend(Oid::from_hex(value)),
+                "author" => author = identity_name(value).to_string(),
+                "committer" => committed = identity_time(value),
+                _ => {}
+            }
+        }
+        let tree = tree.ok_or_else(|| AppError::Git(format!("Commit {} has no tree", oid)))?;
+        let (time, offset_minutes) = committed.unwrap_or((0, 0));
+        Ok(Self {
+            oid,
+            tree,
+            parents,
+            author,
+            time,
+            offset_minutes,
+            message: message.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    /// e.g. `0o100644` for a file, `0o40000` for a directory.
+    pub mode: u32,
+    pub name: String,
+    pub oid: Oid,
+}
+
+impl TreeEntry {
+    pub fn is_tree(&self) -> bool {
+        self.mode == 0o40000
+    }
+
+    /// Regular files, executable or not; not symlinks or submodules.
+    pub fn is_file(&self) -> bool {
+        self.mode & 0o170000 == 0o100000
+    }
+}
+
+/// A repository found on disk: where its metadata and work tree are.
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    /// `.git`, or `.git/worktrees/<name>` for a linked worktree.
+    git_dir: PathBuf,
+    /// Where objects, branches and packed refs live, shared by every worktree.
+    common_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+impl GitRepo {
+    /// The repository whose work tree contains `path`, looking upwards like
+    /// git does.
+    pub fn discover(path: &Path) -> Option<Self> {
+        path.ancestors().find_map(Self::open)
+    }
+
+    /// The repository whose work tree is exactly `work_tree`.
+    pub fn open(work_tree: &Path) -> Option<Self> {
+        let dot_git = work_tree.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else {
+            // Linked worktrees and submodules have a `gitdir: <path>` file
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            let target = content.trim().strip_prefix("gitdir:")?.trim();
+            work_tree.join(target)
+        };
+        if !git_dir.join("HEAD").is_file() {
+            return None;
+        }
+        let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => git_dir.join(common.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        Some(Self {
+            git_dir,
+            common_dir,
+            work_tree: work_tree.to_path_buf(),
+        })
+    }
+
+    pub fn work_tree(&self) -> &Path {
+        &self.work_tree
+    }
+
+    pub fn head(&self) -> AppResult<Head> {
+        let path = self.git_dir.join("HEAD");
+        let content = std::fs::read_to_string(&path).map_err(|e| AppError::Io {
+            path: path.clone(),
+            source: e,
+        })?;
+        let content = content.trim();
+        if let Some(name) = content.strip_prefix("ref:") {
+            let name = name.trim();
+            return Ok(Head {
+                branch: Some(name.strip_prefix("refs/heads/").unwrap_or(name).to_string()),
+                oid: self.resolve_ref(name)?,
+            });
+        }
+        Ok(Head {
+            branch: None,
+            oid: Oid::from_hex(content),
+        })
+    }
+
+    /// The commit a ref such as `refs/heads/main` points to, following
+    /// symbolic refs. `None` if it does not exist.
+    pub fn resolve_ref(&self, name: &str) -> AppResult<Option<Oid>> {
+        let mut name = name.to_string();
+        'follow: for _ in 0..MAX_REF_DEPTH {
+            // Per-worktree refs first, then the shared ones
+            for dir in [&self.git_dir, &self.common_dir] {
+                let Ok(content) = std::fs::read_to_string(dir.join(&name)) else {
+                    continue;
+                };
+                let content = content.trim();
+                if let Some(target) = content.strip_prefix("ref:") {
+                    name = target.trim().to_string();
+                    continue 'follow;
+                }
+                return Ok(Oid::from_hex(content));
+            }
+            return self.packed_ref(&name);
+        }
+        Err(AppError::Git(format!("Ref {} is nested too deeply", name)))
+    }
+
+    fn packed_ref(&self, name: &str) -> AppResult<Option<Oid>> {
+        let Ok(content) = std::fs::read_to_string(self.common_dir.join("packed-refs")) else {
+            return Ok(None);
+        };
+        Ok(content
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, ref_name)| *ref_name == name)
+            .and_then(|(hex, _)| Oid::from_hex(hex)))
+    }
+
+    /// Opens the object database, listing the packs present right now.
+    pub fn objects(&self) -> AppResult<ObjectStore> {
+        ObjectStore::open(self.common_dir.join("objects"))
+    }
+}
+
+impl ObjectStore {
+    pub fn commit(&self, oid: &Oid) -> AppResult<Commit> {
+        match self.read(oid)? {
+            (Kind::Commit, data) => Commit::parse(*oid, &data),
+            (kind, _) => Err(AppError::Git(format!(
+                "{} is a {:?}, not a commit",
+                oid, kind
+            ))),
+        }
+    }
+
+    pub fn tree(&self, oid: &Oid) -> AppResult<Arc<Vec<TreeEntry>>> {
+        if let Some(entries) = self.trees.lock().ok().and_then(|t| t.get(oid).cloned()) {
+            return Ok(entries);
+        }
+        let data = match self.read(oid)? {
+            (Kind::Tree, data) => data,
+            (kind, _) => {
+                return Err(AppError::Git(format!(
+                    "{} is a {:?}, not a tree",
+                    oid, kind
+                )));
+            }
+        };
+        let entries = Arc::new(
+            parse_tree(&data).ok_or_else(|| AppError::Git(format!("Tree {} is corrupt", oid)))?,
+        );
+        if let Ok(mut trees) = self.trees.lock() {
+            trees.insert(*oid, entries.clone());
+        }
+        Ok(entries)
+    }
+
+    pub fn blob(&self, oid: &Oid) -> AppResult<Vec<u8>> {
+        match self.read(oid)? {
+            (Kind::Blob, data) => Ok(data),
+            (kind, _) => Err(AppError::Git(format!(
+                "{} is a {:?}, not a blob",
+                oid, kind
+            ))),
+        }
+    }
+
+    /// The file at `path` (`/`-separated, relative to the root of `tree`), if
+    /// it is a regular file there.
+    pub fn file_at(&self, tree: &Oid, path: &str) -> AppResult<Option<Oid>> {
+        let mut current = *tree;
+        let mut parts = path.split('/').filter(|p| !p.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            let entries = self.tree(&current)?;
+            let Some(entry) = entries.iter().find(|e| e.name == part) else {
+                return Ok(None);
+            };
+            match parts.peek() {
+                None if entry.is_file() => return Ok(Some(entry.oid)),
+                Some(_) if entry.is_tree() => current = entry.oid,
+                _ => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Entries are `<octal mode> <name>\0<20-byte id>`, back to back.
+fn parse_tree(mut data: &[u8]) -> Option<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    while !data.is_empty() {
+        let space = data.iter().position(|&b| b == b' ')?;
+        let nul = space + data[space..].iter().position(|&b| b == 0)?;
+        let mode = u32::from_str_radix(std::str::from_utf8(&data[..space]).ok()?, 8).ok()?;
+        let name = String::from_utf8_lossy(&data[space + 1..nul]).into_owned();
+        let oid = Oid::from_bytes(data.get(nul + 1..nul + 21)?)?;
+        entries.push(TreeEntry { mode, name, oid });
+        data = &data[nul + 21..];
+    }
+    Some(entries)
+}
+
+/// `Name <email> 1700000000 +0200` -> `Name`
+fn identity_name(value: &str) -> &str {
+    value.split(" <").next().unwrap_or(value).trim()
+}
+
+/// `Name <email> 1700000000 +0200` -> seconds and offset in minutes
+fn identity_time(value: &str) -> Option<(i64, i32)> {
+    let mut fields = value.rsplit(' ');
+    let offset = fields.next()?;
+    let time = fields.next()?.parse().ok()?;
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset.trim_start_matches(['+', '-']);
+    let hours: i32 = digits.get(..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    Some((time, sign * (hours * 60 + minutes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_headers_and_message() {
+        let oid = Oid::from_hex("0123456789abcdef0123456789abcdef01234567").unwrap();
+        let data = b"tree 89abcdef0123456789abcdef0123456789abcdef\n\
+parent 0123456789abcdef0123456789abcdef01234567\n\
+author Ada Lovelace <ada@example.com> 1700000000 +0200\n\
+committer Ada Lovelace <ada@example.com> 1700000100 -0130\n\
+gpgsig -----BEGIN PGP SIGNATURE-----\n \n -----END PGP SIGNATURE-----\n\
+\n\
+Fix the engine\n\nLonger text.\n";
+        let commit = Commit::parse(oid, data).unwrap();
+        assert_eq!(commit.oid, oid);
+        assert_eq!(commit.parents.len(), 1);
+        assert_eq!(commit.author, "Ada Lovelace");
+        assert_eq!(commit.summary(), "Fix the engine");
+        assert_eq!(commit.time, 1700000100);
+        assert_eq!(commit.offset_minutes, -90);
+        assert_eq!(commit.timestamp(), "2023-11-14T20:45:00-01:30");
+    }
+
+    #[test]
+    fn test_tree_entries() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"100644 lib.rs\0");
+        data.extend_from_slice(&[7; 20]);
+        data.extend_from_slice(b"40000 src\0");
+        data.extend_from_slice(&[9; 20]);
+        let entries = parse_tree(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_file() && !entries[0].is_tree());
+        assert_eq!(entries[1].name, "src");
+        assert!(entries[1].is_tree());
+        assert!(parse_tree(&data[..data.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,404 @@
+//! Objects under `.git/objects`, loose or in v2 packfiles.
+
+use super::Oid;
+use crate::error::{AppError, AppResult};
+use flate2::read::ZlibDecoder;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Deeper chains than git itself ever writes are treated as corruption.
+const MAX_DELTA_DEPTH: usize = 1000;
+
+const IDX_MAGIC: &[u8] = b"\xfftOc";
+const FANOUT_LEN: usize = 256 * 4;
+const IDX_HEADER_LEN: usize = 8 + FANOUT_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_pack(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::Commit),
+            2 => Some(Self::Tree),
+            3 => Some(Self::Blob),
+            4 => Some(Self::Tag),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"commit" => Some(Self::Commit),
+            b"tree" => Some(Self::Tree),
+            b"blob" => Some(Self::Blob),
+            b"tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+}
+
+/// Read access to one repository's objects. Packs are listed once when the
+/// store is opened, so open a fresh store after the repository changes.
+pub struct ObjectStore {
+    dir: PathBuf,
+    packs: Vec<Pack>,
+    /// Parsed trees, which are read over and over when looking up paths.
+    pub(super) trees: Mutex<HashMap<Oid, Arc<Vec<super::TreeEntry>>>>,
+}
+
+impl ObjectStore {
+    pub(super) fn open(dir: PathBuf) -> AppResult<Self> {
+        let mut packs = Vec::new();
+        if let Ok(entries) = std::fs::read_dir(dir.join("pack")) {
+            let mut indexes: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "idx"))
+                .collect();
+            indexes.sort();
+            for idx in indexes {
+                match Pack::open(&idx) {
+                    Ok(pack) => packs.push(pack),
+                    // A pack still being written by git has no usable index yet
+                    Err(e) => log::warn!("Skipping pack {}: {}", idx.display(), e),
+                }
+            }
+        }
+        Ok(Self {
+            dir,
+            packs,
+            trees: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The kind and content of `oid`.
+    pub fn read(&self, oid: &Oid) -> AppResult<(Kind, Vec<u8>)> {
+        self.read_at_depth(oid, 0)
+    }
+
+    fn read_at_depth(&self, oid: &Oid, depth: usize) -> AppResult<(Kind, Vec<u8>)> {
+        if let Some(object) = self.read_loose(oid)? {
+            return Ok(object);
+        }
+        for pack in &self.packs {
+            if let Some(offset) = pack.find(oid)? {
+                return pack.read_at(offset, self, depth);
+            }
+        }
+        Err(AppError::NotFound(format!("Git object {}", oid)))
+    }
+
+    fn read_loose(&self, oid: &Oid) -> AppResult<Option<(Kind, Vec<u8>)>> {
+        let hex = oid.to_string();
+        let path = self.dir.join(&hex[..2]).join(&hex[2..]);
+        let compressed = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::Io { path, source: e }),
+        };
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&compressed[..])
+            .read_to_end(&mut raw)
+            .map_err(|e| corrupt(&path, e))?;
+        let nul = raw
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| corrupt(&path, "missing header"))?;
+        let header = &raw[..nul];
+        let (name, size) = header
+            .iter()
+            .position(|&b| b == b' ')
+            .map(|space| (&header[..space], &header[space + 1..]))
+            .ok_or_else(|| corrupt(&path, "bad header"))?;
+        let kind = Kind::from_name(name).ok_or_else(|| corrupt(&path, "unknown type"))?;
+        let size: usize = std::str::from_utf8(size)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| corrupt(&path, "bad size"))?;
+        let content = raw.split_off(nul + 1);
+        if content.len() != size {
+            return Err(corrupt(&path, "size mismatch"));
+        }
+        Ok(Some((kind, content)))
+    }
+}
+
+/// A `.pack` and its v2 `.idx`, both mapped.
+struct Pack {
+    path: PathBuf,
+    idx: Mmap,
+    data: Mmap,
+    count: usize,
+}
+
+impl Pack {
+    fn open(idx_path: &Path) -> AppResult<Self> {
+        let path = idx_path.with_extension("pack");
+        let idx = map(idx_path)?;
+        let data = map(&path)?;
+        if idx.len() < IDX_HEADER_LEN || &idx[..4] != IDX_MAGIC || be_u32(&idx, 4)? != 2 {
+            return Err(corrupt(idx_path, "not a version 2 index"));
+        }
+        let count = be_u32(&idx, IDX_HEADER_LEN - 4)? as usize;
+        if idx.len() < IDX_HEADER_LEN + count * (20 + 4 + 4) || data.len() < 12 {
+            return Err(corrupt(idx_path, "truncated"));
+        }
+        Ok(Self {
+            path,
+            idx,
+            data,
+            count,
+        })
+    }
+
+    /// Offset of `oid` in the pack, found by binary search within its fanout
+    /// bucket.
+    fn find(&self, oid: &Oid) -> AppResult<Option<u64>> {
+        let first_byte = oid.as_bytes()[0] as usize;
+        let mut low = match first_byte {
+            0 => 0,
+            b => be_u32(&self.idx, 8 + (b - 1) * 4)? as usize,
+        };
+        let mut high = be_u32(&self.idx, 8 + first_byte * 4)? as usize;
+        let names = IDX_HEADER_LEN;
+        while low < high {
+            let mid = (low + high) / 2;
+            let name = &self.idx[names + mid * 20..names + mid * 20 + 20];
+            match name.cmp(oid.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return self.offset(mid).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    fn offset(&self, index: usize) -> AppResult<u64> {
+        let offsets = IDX_HEADER_LEN + self.count * (20 + 4);
+        let offset = be_u32(&self.idx, offsets + index * 4)?;
+        if offset & 0x8000_0000 == 0 {
+            return Ok(offset as u64);
+        }
+        // Packs over 2 GiB keep the real offset in a table of 64-bit entries
+        let large = offsets + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+        let bytes = self
+            .idx
+            .get(large..large + 8)
+            .ok_or_else(|| corrupt(&self.path, "bad large offset"))?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap_or_default()))
+    }
+
+    fn read_at(
+        &self,
+        offset: u64,
+        store: &ObjectStore,
+        depth: usize,
+    ) -> AppResult<(Kind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            return Err(corrupt(&self.path, "delta chain too deep"));
+        }
+        let start = offset as usize;
+        let mut pos = start;
+        let mut byte = self.byte(pos)?;
+        let code = (byte >> 4) & 7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = self.byte(pos)?;
+            size |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or_else(|| corrupt(&self.path, "bad entry size"))?;
+            shift += 7;
+        }
+        pos += 1;
+
+        match code {
+            // OFS_DELTA: the base is earlier in this pack
+            6 => {
+                let mut byte = self.byte(pos)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = self.byte(pos)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                pos += 1;
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| corrupt(&self.path, "bad delta base"))?;
+                let (kind, base) = self.read_at(base_offset, store, depth + 1)?;
+                let delta = self.inflate(pos, size)?;
+                Ok((
+                    kind,
+                    apply_delta(&base, &delta).map_err(|e| corrupt(&self.path, e))?,
+                ))
+            }
+            // REF_DELTA: the base is named, and may live anywhere
+            7 => {
+                let base_oid = self
+                    .data
+                    .get(pos..pos + 20)
+                    .and_then(Oid::from_bytes)
+                    .ok_or_else(|| corrupt(&self.path, "truncated entry"))?;
+                let (kind, base) = store.read_at_depth(&base_oid, depth + 1)?;
+                let delta = self.inflate(pos + 20, size)?;
+                Ok((
+                    kind,
+                    apply_delta(&base, &delta).map_err(|e| corrupt(&self.path, e))?,
+                ))
+            }
+            code => {
+                let kind =
+                    Kind::from_pack(code).ok_or_else(|| corrupt(&self.path, "unknown type"))?;
+                Ok((kind, self.inflate(pos, size)?))
+            }
+        }
+    }
+
+    fn byte(&self, pos: usize) -> AppResult<u8> {
+        self.data
+            .get(pos)
+            .copied()
+            .ok_or_else(|| corrupt(&self.path, "truncated entry"))
+    }
+
+    fn inflate(&self, pos: usize, size: usize) -> AppResult<Vec<u8>> {
+        let compressed = self
+            .data
+            .get(pos..)
+            .ok_or_else(|| corrupt(&self.path, "truncated entry"))?;
+        let mut out = Vec::with_capacity(size);
+        ZlibDecoder::new(compressed)
+            .take(size as u64)
+            .read_to_end(&mut out)
+            .map_err(|e| corrupt(&self.path, e))?;
+        if out.len() != size {
+            return Err(corrupt(&self.path, "size mismatch"));
+        }
+        Ok(out)
+    }
+}
+
+/// Rebuilds an object from its `base` and a git delta: two varint sizes, then
+/// copy-from-base and insert instructions.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut pos = 0;
+    let base_size = varint(delta, &mut pos).ok_or("truncated delta")?;
+    let size = varint(delta, &mut pos).ok_or("truncated delta")?;
+    if base_size != base.len() {
+        return Err("delta base size mismatch");
+    }
+    let mut out = Vec::with_capacity(size);
+    while let Some(&op) = delta.get(pos) {
+        pos += 1;
+        if op & 0x80 != 0 {
+            let mut from = 0usize;
+            let mut len = 0usize;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    from |= (*delta.get(pos).ok_or("truncated delta")? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    len |= (*delta.get(pos).ok_or("truncated delta")? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            let copied = from
+                .checked_add(len)
+                .and_then(|end| base.get(from..end))
+                .ok_or("delta copies past its base")?;
+            out.extend_from_slice(copied);
+        } else if op != 0 {
+            let inserted = delta.get(pos..pos + op as usize).ok_or("truncated delta")?;
+            out.extend_from_slice(inserted);
+            pos += op as usize;
+        } else {
+            return Err("reserved delta instruction");
+        }
+    }
+    if out.len() != size {
+        return Err("delta result size mismatch");
+    }
+    Ok(out)
+}
+
+/// Little-endian base-128, as used for delta sizes.
+fn varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn map(path: &Path) -> AppResult<Mmap> {
+    let file = File::open(path).map_err(|e| AppError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    // SAFETY: git never rewrites a pack or index in place; new ones get new names.
+    unsafe { Mmap::map(&file) }.map_err(|e| AppError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn be_u32(data: &[u8], at: usize) -> AppResult<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| AppError::Git("Truncated pack index".into()))
+}
+
+fn corrupt(path: &Path, reason: impl std::fmt::Display) -> AppError {
+    AppError::Git(format!("{} is corrupt: {}", path.display(), reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_copies_and_inserts() {
+        let base = b"hello world";
+        // base size 11, result size 11: copy "hello ", insert "there"
+        let delta = [
+            11,
+            11,
+            0x80 | 0x01 | 0x10,
+            0,
+            6,
+            5,
+            b't',
+            b'h',
+            b'e',
+            b'r',
+            b'e',
+        ];
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"hello there");
+        assert!(apply_delta(b"short", &delta).is_err());
+        assert!(apply_delta(base, &[11, 11, 0]).is_err());
+    }
+}
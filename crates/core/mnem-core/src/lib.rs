@@ -1,7 +1,9 @@
 pub mod client;
 pub mod config;
+pub mod crypto;
 pub mod env;
 pub mod error;
+pub mod git;
 pub mod ipc;
 pub mod models;
 pub mod os;
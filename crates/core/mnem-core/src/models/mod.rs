@@ -11,8 +11,83 @@ pub struct Snapshot {
     pub content_hash: String, // This could now be the "root" hash or just a reference
     pub git_branch: Option<String>,
     pub session_id: Option<i64>,
+    pub changeset_id: Option<i64>,
     pub commit_hash: Option<String>,
     pub commit_message: Option<String>,
+    pub event: SnapshotEvent,
+}
+
+/// What happened to the file when a snapshot was taken.
+///
+/// `Deleted` and `RenamedTo` are tombstones: they carry no content and mark
+/// the path as absent from that point on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotEvent {
+    #[default]
+    Modified,
+    Deleted,
+    /// The file was moved here from `from`.
+    RenamedFrom {
+        from: String,
+    },
+    /// The file was moved away to `to`.
+    RenamedTo {
+        to: String,
+    },
+}
+
+impl SnapshotEvent {
+    pub fn is_tombstone(&self) -> bool {
+        matches!(self, Self::Deleted | Self::RenamedTo { .. })
+    }
+}
+
+/// A label protecting a snapshot or checkpoint from retention and GC.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Pin {
+    /// Content hash of the pinned snapshot, or the checkpoint hash.
+    pub hash: String,
+    pub label: String,
+    pub kind: PinKind,
+    pub created_at: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinKind {
+    Snapshot,
+    Checkpoint,
+}
+
+/// How one file differs between a checkpoint and another checkpoint or the
+/// working tree. Line counts are zero for blobs.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CheckpointChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Snapshots saved together, each within the changeset window of the last.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Changeset {
+    pub id: i64,
+    pub started_at: String,
+    pub ended_at: String,
+    pub git_branch: Option<String>,
+    /// Paths touched, sorted.
+    pub files: Vec<String>,
+    pub snapshot_count: usize,
 }
 
 pub struct FileEntry {
@@ -30,6 +105,17 @@ pub struct SearchResult {
     pub content: String,
 }
 
+/// Outcome of a garbage collection run.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct GcReport {
+    pub snapshots_pruned: usize,
+    pub chunks_deleted: usize,
+    pub symbols_deleted: usize,
+    pub references_deleted: usize,
+    pub deltas_deleted: usize,
+    pub bytes_reclaimed: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct Session {
     pub id: i64,
@@ -40,9 +126,11 @@ pub struct Session {
     pub snapshot_count: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct TimesheetEntry {
+    /// Day, ISO week or month label.
     pub date: String,
+    pub project: Option<String>,
     pub branch: Option<String>,
     pub duration_minutes: u64,
     pub file_count: usize,
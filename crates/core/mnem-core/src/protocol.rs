@@ -294,8 +294,22 @@ pub mod methods {
     pub const SESSION_GET_TIMESHEET: &str = "mnem/session/timesheet";
     pub const PROJECT_CREATE_CHECKPOINT: &str = "mnem/project/checkpoint";
     pub const PROJECT_REVERT_V1: &str = "mnem/project/revert";
+    pub const PROJECT_EXPORT_TREE: &str = "mnem/project/exportTree";
+    pub const CHECKPOINT_DIFF: &str = "mnem/checkpoint/diff";
+    pub const CHECKPOINT_STATUS: &str = "mnem/checkpoint/status";
+    pub const CHANGESET_LIST: &str = "mnem/changeset/list";
+    pub const CHANGESET_REVERT: &str = "mnem/changeset/revert";
     pub const PROJECT_RELOAD: &str = "mnem/project/reload";
     pub const MAINTENANCE_GC: &str = "mnem/maintenance/gc";
+    pub const MAINTENANCE_FSCK: &str = "mnem/maintenance/fsck";
+    pub const BUNDLE_EXPORT: &str = "mnem/bundle/export";
+    pub const BUNDLE_IMPORT: &str = "mnem/bundle/import";
+    pub const GIT_RECORD_COMMIT: &str = "mnem/git/commit";
+    pub const GIT_LIST_COMMITS: &str = "mnem/git/commits";
+    pub const GIT_IMPORT: &str = "mnem/git/import";
+    pub const PIN_LIST: &str = "mnem/pin/list";
+    pub const PIN_ADD: &str = "mnem/pin/add";
+    pub const PIN_REMOVE: &str = "mnem/pin/remove";
     pub const CONFIG_GET_V1: &str = "mnem/config/get";
     pub const CONFIG_SET_V1: &str = "mnem/config/set";
     pub const TIER_CONFIG_GET_V1: &str = "mnem/tier/config/get";
@@ -380,11 +394,44 @@ pub struct SnapshotContentParams {
     pub content_hash: String,
 }
 
+/// A stored version's content. `content` is lossy text for editors that only
+/// show it; the exact bytes are in `content_base64`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotContentResponse {
+    pub content: String,
+    #[serde(default)]
+    pub content_base64: Option<String>,
+}
+
+impl SnapshotContentResponse {
+    pub fn new(content: &[u8]) -> Self {
+        use base64::Engine;
+        Self {
+            content: String::from_utf8_lossy(content).to_string(),
+            content_base64: Some(base64::engine::general_purpose::STANDARD.encode(content)),
+        }
+    }
+
+    /// The exact content, or the text from a daemon that only sends that.
+    pub fn into_bytes(self) -> crate::AppResult<Vec<u8>> {
+        use base64::Engine;
+        match self.content_base64 {
+            Some(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| crate::AppError::Protocol(e.to_string())),
+            None => Ok(self.content.into_bytes()),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SnapshotActivityParams {
     pub limit: usize,
     pub project_path: Option<String>,
     pub branch: Option<String>,
+    /// Only snapshots at or after this instant (UTC epoch milliseconds).
+    #[serde(default)]
+    pub since: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -518,11 +565,207 @@ pub struct ContentSearchParams {
     pub query: String,
     pub path_filter: Option<String>,
     pub limit: Option<usize>,
+    #[serde(default)]
+    pub regex: bool,
+    /// Only search this watched project.
+    #[serde(default)]
+    pub project_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigGetParams {
+    pub key: String,
+    /// Layer this project's `.mnemosyne/config.toml` over the global config.
+    pub project_path: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigGetResponse {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub source: crate::config::ConfigSource,
+}
+
+/// Reverts to a checkpoint hash when one is given, otherwise to `timestamp`.
+/// `path_glob`, relative to the project root, limits a checkpoint revert to
+/// the files it matches.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectRevertParams {
+    pub project_path: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    #[serde(default)]
+    pub path_glob: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectRevertResponse {
+    pub files_restored: usize,
+}
+
+/// `from` and `to` may be checkpoint hash prefixes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckpointDiffParams {
+    pub project_path: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Compares the working tree against `checkpoint`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckpointStatusParams {
+    pub project_path: String,
+    pub checkpoint: String,
+}
+
+/// `project_path` may also be a file inside the project.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangesetListParams {
+    pub project_path: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Answered with a [`ProjectRevertResponse`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangesetRevertParams {
+    pub project_path: String,
+    pub id: i64,
+}
+
+/// `at` is an RFC3339 time, a checkpoint or a recorded commit. `output`
+/// must be absolute since the daemon does not share the caller's working
+/// directory. Answered with a [`crate::storage::tree::TreeManifest`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectExportTreeParams {
+    pub project_path: String,
+    pub at: String,
+    pub output: String,
+    /// Write a tar archive to `output` instead of a directory.
+    #[serde(default)]
+    pub tar: bool,
+}
+
+/// Answered with [`SessionResponse`]s, newest first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionListParams {
+    pub project_path: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Answered with a [`SessionResponse`], or null when no session is open.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionActiveParams {
+    pub project_path: String,
+}
+
+/// Answered with [`TimesheetEntry`]s. Without `project_path`, sessions of
+/// every watched project are merged.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimesheetParams {
+    #[serde(default)]
+    pub project_path: Option<String>,
+    /// Projects to bill together, watched or not; every watched project when
+    /// this and `project_path` are both empty.
+    #[serde(default)]
+    pub project_paths: Vec<String>,
+    #[serde(flatten)]
+    pub options: crate::storage::timesheet::TimesheetOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceGcParams {
+    pub project_path: String,
+    /// Keep this many days of history instead of the configured retention
+    /// policy; see [`crate::config::RetentionPolicy::within_days`].
+    #[serde(default)]
+    pub keep_days: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceFsckParams {
+    pub project_path: String,
+    #[serde(default)]
+    pub repair: bool,
+}
+
+/// `since`/`until` are RFC3339; `output` must be absolute since the daemon
+/// does not share the caller's working directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleExportParams {
+    pub project_path: String,
+    pub output: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub path_glob: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleImportParams {
+    pub project_path: String,
+    pub input: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleImportResponse {
+    pub manifest: crate::storage::bundle::BundleManifest,
+    pub report: crate::storage::bundle::ImportReport,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitRecordCommitParams {
+    pub project_path: String,
+    #[serde(flatten)]
+    pub commit: crate::storage::commit_queue::GitCommitEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitListCommitsParams {
+    pub project_path: String,
+}
+
+/// `since` is RFC3339. Answered with a
+/// [`crate::storage::git_import::GitImportReport`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitImportParams {
+    pub project_path: String,
+    pub since: Option<String>,
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitCommitInfo {
+    pub hash: String,
+    pub message: String,
+    pub author: String,
     pub timestamp: String,
+    pub files: usize,
+}
+
+/// `project_path` may also be a file inside the project. `query` keeps the
+/// pins whose label contains it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinListParams {
+    pub project_path: String,
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+/// `hash` may be a prefix of a snapshot content hash or a checkpoint hash.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinAddParams {
+    pub project_path: String,
+    pub hash: String,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinRemoveParams {
+    pub project_path: String,
+    pub hash: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -546,21 +789,23 @@ pub struct GetWatchedProjectsResponse {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct TierConfig {
-    pub hot_max_age_days: u64,
-    pub warm_max_age_days: u64,
-    pub hot_max_memory_mb: u64,
-    pub compression_level: u32, // 0-21 for zstd
+pub struct TierConfigGetParams {
+    /// Effective settings for this project; the global ones when absent.
+    #[serde(default)]
+    pub project_path: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TierConfigSetParams {
-    pub config: TierConfig,
+    /// Written to this project's config; to the global one when absent.
+    #[serde(default)]
+    pub project_path: Option<String>,
+    pub config: crate::storage::tiered::TierConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TierConfigGetResponse {
-    pub config: TierConfig,
+    pub config: crate::storage::tiered::TierConfig,
 }
 
 // Responses
@@ -584,6 +829,8 @@ pub struct StatusResponse {
     pub total_snapshots: u64,
     #[serde(default)]
     pub total_symbols: u64,
+    #[serde(default)]
+    pub tiers: crate::storage::tiered::TierOccupancy,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -595,6 +842,8 @@ pub struct SnapshotInfo {
     pub git_branch: Option<String>,
     pub commit_hash: Option<String>,
     pub commit_message: Option<String>,
+    #[serde(default)]
+    pub event: crate::models::SnapshotEvent,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -607,15 +856,43 @@ pub struct SessionResponse {
     pub snapshot_count: usize,
 }
 
+impl From<crate::models::Session> for SessionResponse {
+    fn from(session: crate::models::Session) -> Self {
+        Self {
+            id: session.id,
+            start_time: session.start_time,
+            end_time: session.end_time,
+            branch: session.git_branch,
+            file_count: session.file_count,
+            snapshot_count: session.snapshot_count,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TimesheetEntry {
     pub date: String,
+    #[serde(default)]
+    pub project: Option<String>,
     pub branch: Option<String>,
     pub duration_minutes: u64,
     pub file_count: usize,
     pub snapshot_count: usize,
 }
 
+impl From<crate::models::TimesheetEntry> for TimesheetEntry {
+    fn from(entry: crate::models::TimesheetEntry) -> Self {
+        Self {
+            date: entry.date,
+            project: entry.project,
+            branch: entry.branch,
+            duration_minutes: entry.duration_minutes,
+            file_count: entry.file_count,
+            snapshot_count: entry.snapshot_count,
+        }
+    }
+}
+
 /// Parameters for starting the MCP server
 #[derive(Debug, Serialize, Deserialize)]
 pub struct McpStartParams {
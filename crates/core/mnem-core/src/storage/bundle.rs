@@ -0,0 +1,1016 @@
+//! Portable history bundles.
+//!
+//! A bundle is a single file holding a project's snapshots, sessions,
+//! commits, checkpoints and semantic rows together with exactly the objects
+//! they reference. Paths are stored relative to the project root and strings
+//! are stored by value, so a bundle can be imported into any checkout of the
+//! project: ids and interned strings are remapped on import and snapshots
+//! already present are skipped.
+//!
+//! Layout: the [`MAGIC`] bytes followed by a zstd stream of sections, each a
+//! tag byte, a little-endian `u64` length and the payload. The JSON manifest
+//! comes first so a bundle can be described without reading it all, then the
+//! JSON history, one section per object (hash followed by the raw bytes) and
+//! an end marker.
+
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use crate::models::SnapshotEvent;
+use crate::storage::Repository;
+use crate::storage::database::{
+    BACKFILLED_ID, CHECKPOINT_FILES, CHECKPOINTS, CHUNK_REFS, CHUNKS, CheckpointData, ChunkData,
+    DeltaData, EventData, FILE_SNAPSHOTS, GIT_COMMITS, GitCommitData, HASH_PREFIX_LEN,
+    HASH_PREFIXES, METADATA, ReferenceData, SESSIONS, SNAPSHOT_CHUNKS, SNAPSHOT_TIMES, SNAPSHOTS,
+    STRING_INDEX, STRINGS, SYMBOL_DELTAS, SYMBOL_REFERENCES, SYMBOLS, SessionData, SnapshotData,
+    SymbolData, intern_string_in, next_id_in, retain_chunk_ref_in,
+};
+use crate::storage::schema::{decode_record, encode_record, row_binding};
+use crate::utils::time::{format_ms, parse_ms};
+use chrono::{DateTime, Utc};
+use redb::ReadableTable;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub const MAGIC: &[u8; 8] = b"MNEMBNDL";
+pub const BUNDLE_VERSION: u32 = 1;
+
+const TAG_MANIFEST: u8 = b'M';
+const TAG_HISTORY: u8 = b'H';
+const TAG_OBJECT: u8 = b'O';
+const TAG_END: u8 = b'E';
+
+/// Same limit CAS applies when decompressing, plus room for the hash.
+const MAX_SECTION_SIZE: u64 = 256 * 1024 * 1024 + 64;
+
+/// Limits what [`export`] writes. Empty filters export everything.
+#[derive(Debug, Clone, Default)]
+pub struct BundleFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Glob matched against paths relative to the project root.
+    pub path_glob: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub project_id: String,
+    pub project_name: String,
+    pub created_at: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub path_glob: Option<String>,
+    pub snapshots: usize,
+    pub sessions: usize,
+    pub commits: usize,
+    pub checkpoints: usize,
+    pub symbols: usize,
+    pub objects: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub snapshots_imported: usize,
+    /// Snapshots the project already had.
+    pub snapshots_skipped: usize,
+    pub sessions_imported: usize,
+    pub commits_imported: usize,
+    pub checkpoints_imported: usize,
+    pub symbols_imported: usize,
+    pub objects_written: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BundleHistory {
+    snapshots: Vec<BundleSnapshot>,
+    sessions: Vec<BundleSession>,
+    commits: Vec<BundleCommit>,
+    checkpoints: Vec<BundleCheckpoint>,
+    symbols: Vec<BundleSymbol>,
+    references: Vec<BundleReference>,
+    deltas: Vec<BundleDelta>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleSnapshot {
+    id: i64,
+    path: String,
+    timestamp: String,
+    content_hash: String,
+    git_branch: Option<String>,
+    session_id: Option<i64>,
+    /// Remapped to fresh changeset ids on import.
+    #[serde(default)]
+    changeset_id: Option<i64>,
+    commit_hash: Option<String>,
+    commit_message: Option<String>,
+    /// Other paths in the event are relative like `path`.
+    #[serde(default)]
+    event: SnapshotEvent,
+    /// Chunk hashes in file order; empty for whole-file objects.
+    chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleSession {
+    id: i64,
+    start_time: String,
+    end_time: Option<String>,
+    git_branch: Option<String>,
+    file_count: usize,
+    snapshot_count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleCommit {
+    hash: String,
+    message: String,
+    author: String,
+    timestamp: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleCheckpoint {
+    hash: String,
+    timestamp: String,
+    description: Option<String>,
+    /// (relative path, content hash)
+    file_states: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleSymbol {
+    id: i64,
+    name: String,
+    kind: String,
+    scope: Option<String>,
+    snapshot_id: i64,
+    chunk_hash: String,
+    structural_hash: String,
+    start_line: usize,
+    end_line: usize,
+    start_byte: usize,
+    end_byte: usize,
+    parent_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleReference {
+    snapshot_id: i64,
+    symbol_name: String,
+    start_line: usize,
+    start_byte: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleDelta {
+    from_snapshot_id: Option<i64>,
+    to_snapshot_id: i64,
+    symbol_name: String,
+    new_name: Option<String>,
+    delta_kind: String,
+    structural_hash: String,
+}
+
+pub(crate) fn relative_path(root: &str, path: &str) -> String {
+    match path.strip_prefix(root) {
+        Some(rest) => rest.trim_start_matches(['/', '\\']).replace('\\', "/"),
+        None => path.to_string(),
+    }
+}
+
+fn absolute_path(root: &str, path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        Path::new(root).join(path).to_string_lossy().to_string()
+    }
+}
+
+fn in_range(timestamp: i64, filter: &BundleFilter) -> bool {
+    filter
+        .since
+        .is_none_or(|since| timestamp >= since.timestamp_millis())
+        && filter
+            .until
+            .is_none_or(|until| timestamp <= until.timestamp_millis())
+}
+
+fn bundle_ms(value: &str) -> AppResult<i64> {
+    parse_ms(value)
+        .ok_or_else(|| AppError::Internal(format!("Invalid timestamp in bundle: {}", value)))
+}
+
+fn write_section(w: &mut impl Write, tag: u8, payload: &[&[u8]]) -> AppResult<()> {
+    let len: usize = payload.iter().map(|p| p.len()).sum();
+    w.write_all(&[tag])?;
+    w.write_all(&(len as u64).to_le_bytes())?;
+    for part in payload {
+        w.write_all(part)?;
+    }
+    Ok(())
+}
+
+fn read_section(r: &mut impl Read) -> AppResult<(u8, Vec<u8>)> {
+    let mut header = [0u8; 9];
+    r.read_exact(&mut header)
+        .map_err(|e| AppError::Internal(format!("Truncated bundle: {}", e)))?;
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&header[1..]);
+    let len = u64::from_le_bytes(len);
+    if len > MAX_SECTION_SIZE {
+        return Err(AppError::Internal(format!(
+            "Bundle section of {} bytes exceeds the size limit",
+            len
+        )));
+    }
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload)
+        .map_err(|e| AppError::Internal(format!("Truncated bundle: {}", e)))?;
+    Ok((header[0], payload))
+}
+
+fn lookup(strings: &HashMap<u32, String>, id: u32) -> AppResult<String> {
+    strings
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| AppError::Database(format!("String id {} does not resolve", id)))
+}
+
+/// Collects the history selected by `filter` with strings and paths made portable.
+fn collect_history(repo: &Repository, filter: &BundleFilter) -> AppResult<BundleHistory> {
+    let glob = filter
+        .path_glob
+        .as_deref()
+        .map(|g| {
+            globset::Glob::new(g)
+                .map(|g| g.compile_matcher())
+                .map_err(|e| AppError::Config(format!("Invalid path glob: {}", e)))
+        })
+        .transpose()?;
+    let root = repo.project.path.as_str();
+
+    let txn = repo
+        .db
+        .redb()
+        .begin_read()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut strings: HashMap<u32, String> = HashMap::new();
+    for res in txn
+        .open_table(STRINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let s = crypto::open_string(v.value(), &row_binding(STRINGS, k.value()))?;
+        strings.insert(k.value(), s.into_owned());
+    }
+
+    let mut history = BundleHistory::default();
+    let links = txn
+        .open_table(SNAPSHOT_CHUNKS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for res in txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SnapshotData>(SNAPSHOTS, k.value(), v.value()) else {
+            continue;
+        };
+        if data.file_path_id == 0 || !in_range(data.timestamp, filter) {
+            continue;
+        }
+        let path = relative_path(root, &lookup(&strings, data.file_path_id)?);
+        if glob.as_ref().is_some_and(|g| !g.is_match(&path)) {
+            continue;
+        }
+        let id = k.value();
+        let mut chunks = Vec::new();
+        for res in links
+            .range((id, 0)..=(id, u32::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            chunks.push(v.value().to_string());
+        }
+        history.snapshots.push(BundleSnapshot {
+            id: data.id,
+            path,
+            timestamp: format_ms(data.timestamp),
+            content_hash: data.content_hash,
+            git_branch: data
+                .git_branch_id
+                .map(|b| lookup(&strings, b))
+                .transpose()?,
+            session_id: data.session_id,
+            changeset_id: data.changeset_id,
+            commit_hash: data.commit_hash,
+            commit_message: data.commit_message,
+            event: data
+                .event
+                .resolve(|id| Ok(relative_path(root, &lookup(&strings, id)?)))?,
+            chunks,
+        });
+    }
+
+    let snapshot_ids: HashSet<i64> = history.snapshots.iter().map(|s| s.id).collect();
+    let session_ids: HashSet<i64> = history
+        .snapshots
+        .iter()
+        .filter_map(|s| s.session_id)
+        .collect();
+    let commit_hashes: HashSet<&str> = history
+        .snapshots
+        .iter()
+        .filter_map(|s| s.commit_hash.as_deref())
+        .collect();
+    let content_hashes: HashSet<&str> = history
+        .snapshots
+        .iter()
+        .map(|s| s.content_hash.as_str())
+        .collect();
+
+    for res in txn
+        .open_table(SESSIONS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SessionData>(SESSIONS, k.value(), v.value()) else {
+            continue;
+        };
+        if !session_ids.contains(&data.id) {
+            continue;
+        }
+        history.sessions.push(BundleSession {
+            id: data.id,
+            start_time: format_ms(data.start_time),
+            end_time: data.end_time.map(format_ms),
+            git_branch: data
+                .git_branch_id
+                .map(|b| lookup(&strings, b))
+                .transpose()?,
+            file_count: data.file_count,
+            snapshot_count: data.snapshot_count,
+        });
+    }
+
+    for res in txn
+        .open_table(GIT_COMMITS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        if !commit_hashes.contains(k.value()) {
+            continue;
+        }
+        let Ok(data) = decode_record::<GitCommitData>(GIT_COMMITS, k.value(), v.value()) else {
+            continue;
+        };
+        history.commits.push(BundleCommit {
+            hash: data.hash,
+            message: data.message,
+            author: data.author,
+            timestamp: data.timestamp,
+        });
+    }
+
+    // A checkpoint only keeps the files whose content travels with the bundle
+    let checkpoint_files = txn
+        .open_table(CHECKPOINT_FILES)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for res in txn
+        .open_table(CHECKPOINTS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<CheckpointData>(CHECKPOINTS, k.value(), v.value()) else {
+            continue;
+        };
+        if !in_range(data.timestamp, filter) {
+            continue;
+        }
+        let mut file_states = Vec::new();
+        for row in checkpoint_files
+            .range((data.hash.as_str(), 0)..=(data.hash.as_str(), u32::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = row.map_err(|e| AppError::Database(e.to_string()))?;
+            if content_hashes.contains(v.value()) {
+                let path = lookup(&strings, k.value().1)?;
+                file_states.push((relative_path(root, &path), v.value().to_string()));
+            }
+        }
+        if file_states.is_empty() {
+            continue;
+        }
+        history.checkpoints.push(BundleCheckpoint {
+            hash: data.hash,
+            timestamp: format_ms(data.timestamp),
+            description: data.description,
+            file_states,
+        });
+    }
+
+    for res in txn
+        .open_table(SYMBOLS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SymbolData>(SYMBOLS, k.value(), v.value()) else {
+            continue;
+        };
+        if !snapshot_ids.contains(&data.snapshot_id) {
+            continue;
+        }
+        history.symbols.push(BundleSymbol {
+            id: data.id,
+            name: lookup(&strings, data.name_id)?,
+            kind: lookup(&strings, data.kind_id)?,
+            scope: data.scope_id.map(|s| lookup(&strings, s)).transpose()?,
+            snapshot_id: data.snapshot_id,
+            chunk_hash: data.chunk_hash,
+            structural_hash: data.structural_hash,
+            start_line: data.start_line,
+            end_line: data.end_line,
+            start_byte: data.start_byte,
+            end_byte: data.end_byte,
+            parent_id: data.parent_id,
+        });
+    }
+
+    for res in txn
+        .open_table(SYMBOL_REFERENCES)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<ReferenceData>(SYMBOL_REFERENCES, k.value(), v.value())
+        else {
+            continue;
+        };
+        if !snapshot_ids.contains(&data.snapshot_id) {
+            continue;
+        }
+        history.references.push(BundleReference {
+            snapshot_id: data.snapshot_id,
+            symbol_name: lookup(&strings, data.symbol_name_id)?,
+            start_line: data.start_line,
+            start_byte: data.start_byte,
+        });
+    }
+
+    for res in txn
+        .open_table(SYMBOL_DELTAS)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<DeltaData>(SYMBOL_DELTAS, k.value(), v.value()) else {
+            continue;
+        };
+        if !snapshot_ids.contains(&data.to_snapshot_id) {
+            continue;
+        }
+        history.deltas.push(BundleDelta {
+            from_snapshot_id: data.from_snapshot_id,
+            to_snapshot_id: data.to_snapshot_id,
+            symbol_name: lookup(&strings, data.symbol_name_id)?,
+            new_name: data.new_name_id.map(|n| lookup(&strings, n)).transpose()?,
+            delta_kind: data.delta_kind,
+            structural_hash: data.structural_hash,
+        });
+    }
+
+    Ok(history)
+}
+
+/// Objects a history needs: every chunk, plus the whole-file object of
+/// snapshots written before chunking.
+fn referenced_objects(history: &BundleHistory) -> BTreeSet<String> {
+    let mut objects = BTreeSet::new();
+    for s in &history.snapshots {
+        if s.event.is_tombstone() {
+            continue;
+        }
+        if s.chunks.is_empty() {
+            objects.insert(s.content_hash.clone());
+        } else {
+            objects.extend(s.chunks.iter().cloned());
+        }
+    }
+    objects
+}
+
+/// Writes the history selected by `filter` to a bundle at `path`.
+pub fn export(repo: &Repository, path: &Path, filter: &BundleFilter) -> AppResult<BundleManifest> {
+    let history = collect_history(repo, filter)?;
+    let mut objects = referenced_objects(&history);
+    // Empty files have no chunks and no object
+    objects.retain(|hash| !crypto::is_empty_object(hash) || repo.fs.exists(hash));
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_VERSION,
+        project_id: repo.project.id.clone(),
+        project_name: repo.project.name.clone(),
+        created_at: chrono::Local::now().to_rfc3339(),
+        since: filter.since.map(|t| t.to_rfc3339()),
+        until: filter.until.map(|t| t.to_rfc3339()),
+        path_glob: filter.path_glob.clone(),
+        snapshots: history.snapshots.len(),
+        sessions: history.sessions.len(),
+        commits: history.commits.len(),
+        checkpoints: history.checkpoints.len(),
+        symbols: history.symbols.len(),
+        objects: objects.len(),
+    };
+
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(MAGIC)?;
+    let mut encoder = zstd::stream::Encoder::new(temp.as_file_mut(), 3)?;
+    let manifest_json =
+        serde_json::to_vec(&manifest).map_err(|e| AppError::Internal(e.to_string()))?;
+    write_section(&mut encoder, TAG_MANIFEST, &[&manifest_json])?;
+    let history_json =
+        serde_json::to_vec(&history).map_err(|e| AppError::Internal(e.to_string()))?;
+    write_section(&mut encoder, TAG_HISTORY, &[&history_json])?;
+    for hash in &objects {
+        let content = repo.fs.peek(hash).map_err(|e| {
+            AppError::NotFound(format!(
+                "Object {} is missing ({}); run 'mnem fsck --repair' first",
+                hash, e
+            ))
+        })?;
+        write_section(&mut encoder, TAG_OBJECT, &[hash.as_bytes(), &content])?;
+    }
+    write_section(&mut encoder, TAG_END, &[])?;
+    encoder.finish()?;
+    temp.as_file().sync_all()?;
+    temp.persist(path)
+        .map_err(|e| AppError::IoGeneric(e.error))?;
+
+    Ok(manifest)
+}
+
+/// Opens a bundle and returns its manifest with a reader positioned after it.
+fn open(path: &Path) -> AppResult<(BundleManifest, impl Read)> {
+    let mut file =
+        std::io::BufReader::new(std::fs::File::open(path).map_err(|e| AppError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        })?);
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(AppError::Internal(format!(
+            "{} is not a mnemosyne bundle",
+            path.display()
+        )));
+    }
+    let mut decoder = zstd::stream::Decoder::new(file)?;
+    let (tag, payload) = read_section(&mut decoder)?;
+    if tag != TAG_MANIFEST {
+        return Err(AppError::Internal("Bundle has no manifest".into()));
+    }
+    let manifest: BundleManifest =
+        serde_json::from_slice(&payload).map_err(|e| AppError::Internal(e.to_string()))?;
+    if manifest.format_version > BUNDLE_VERSION {
+        return Err(AppError::Internal(format!(
+            "Bundle format v{} is newer than this build supports (v{})",
+            manifest.format_version, BUNDLE_VERSION
+        )));
+    }
+    Ok((manifest, decoder))
+}
+
+/// Reads the manifest of the bundle at `path`.
+pub fn read_manifest(path: &Path) -> AppResult<BundleManifest> {
+    Ok(open(path)?.0)
+}
+
+/// Merges the bundle at `path` into `repo`.
+///
+/// Callers must make sure GC does not run concurrently: objects are written
+/// before the rows that reference them.
+pub fn import(repo: &Repository, path: &Path) -> AppResult<(BundleManifest, ImportReport)> {
+    let (manifest, mut reader) = open(path)?;
+    let mut report = ImportReport::default();
+
+    let (tag, payload) = read_section(&mut reader)?;
+    if tag != TAG_HISTORY {
+        return Err(AppError::Internal("Bundle has no history section".into()));
+    }
+    let history: BundleHistory =
+        serde_json::from_slice(&payload).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let compression = repo.is_compression_enabled();
+    loop {
+        let (tag, payload) = read_section(&mut reader)?;
+        match tag {
+            TAG_OBJECT if payload.len() >= 64 => {
+                let (hash, content) = payload.split_at(64);
+                let hash = std::str::from_utf8(hash)
+                    .map_err(|_| AppError::Internal("Malformed object hash in bundle".into()))?;
+                if !crypto::is_object_id(content, hash) {
+                    return Err(AppError::Internal(format!(
+                        "Bundle object {} is corrupt, or was exported from history encrypted with another key",
+                        hash
+                    )));
+                }
+                if !repo.fs.exists(hash) {
+                    repo.fs.write_as(hash, content, compression)?;
+                    report.objects_written += 1;
+                }
+            }
+            TAG_END => break,
+            _ => return Err(AppError::Internal("Malformed bundle section".into())),
+        }
+    }
+
+    for hash in referenced_objects(&history) {
+        if !crypto::is_empty_object(&hash) && !repo.fs.exists(&hash) {
+            return Err(AppError::NotFound(format!(
+                "Bundle does not contain object {}",
+                hash
+            )));
+        }
+    }
+
+    merge_history(repo, &history, &mut report)?;
+    repo.index_pending_trigrams()?;
+    Ok((manifest, report))
+}
+
+/// Id allocation and string interning inside one write transaction.
+struct Ids<'txn> {
+    meta: redb::Table<'txn, &'static str, u64>,
+    strings: redb::Table<'txn, u32, &'static str>,
+    index: redb::Table<'txn, &'static str, u32>,
+}
+
+impl Ids<'_> {
+    fn next(&mut self, key: &str) -> AppResult<u64> {
+        next_id_in(&mut self.meta, key)
+    }
+
+    fn intern(&mut self, s: &str) -> AppResult<u32> {
+        intern_string_in(&mut self.meta, &mut self.strings, &mut self.index, s)
+    }
+}
+
+fn merge_history(
+    repo: &Repository,
+    history: &BundleHistory,
+    report: &mut ImportReport,
+) -> AppResult<()> {
+    let root = repo.project.path.as_str();
+    let txn = repo
+        .db
+        .redb()
+        .begin_write()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    {
+        let mut ids = Ids {
+            meta: txn
+                .open_table(METADATA)
+                .map_err(|e| AppError::Database(e.to_string()))?,
+            strings: txn
+                .open_table(STRINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?,
+            index: txn
+                .open_table(STRING_INDEX)
+                .map_err(|e| AppError::Database(e.to_string()))?,
+        };
+
+        // Sessions are matched by start time
+        let mut sessions = txn
+            .open_table(SESSIONS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut existing_sessions: HashMap<i64, i64> = HashMap::new();
+        for res in sessions
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if let Ok(data) = decode_record::<SessionData>(SESSIONS, k.value(), v.value()) {
+                existing_sessions.insert(data.start_time, data.id);
+            }
+        }
+        let mut session_map: HashMap<i64, i64> = HashMap::new();
+        for s in &history.sessions {
+            let start_time = bundle_ms(&s.start_time)?;
+            if let Some(id) = existing_sessions.get(&start_time) {
+                session_map.insert(s.id, *id);
+                continue;
+            }
+            let id = ids.next("session_id")?;
+            let data = SessionData {
+                id: id as i64,
+                start_time,
+                end_time: s.end_time.as_deref().map(bundle_ms).transpose()?,
+                git_branch_id: s.git_branch.as_deref().map(|b| ids.intern(b)).transpose()?,
+                file_count: s.file_count,
+                snapshot_count: s.snapshot_count,
+            };
+            sessions
+                .insert(id, &*encode_record(SESSIONS, id, &data)?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            session_map.insert(s.id, id as i64);
+            report.sessions_imported += 1;
+        }
+
+        let mut commits = txn
+            .open_table(GIT_COMMITS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for c in &history.commits {
+            let known = commits
+                .get(c.hash.as_str())
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_some();
+            if known {
+                continue;
+            }
+            let data = GitCommitData {
+                hash: c.hash.clone(),
+                message: c.message.clone(),
+                author: c.author.clone(),
+                timestamp: c.timestamp.clone(),
+            };
+            commits
+                .insert(
+                    c.hash.as_str(),
+                    &*encode_record(GIT_COMMITS, c.hash.as_str(), &data)?,
+                )
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            report.commits_imported += 1;
+        }
+
+        // A snapshot of the same file, time and content is already there
+        let mut snapshots = txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut existing: HashMap<(u32, i64, String), i64> = HashMap::new();
+        for res in snapshots
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if let Ok(data) = decode_record::<SnapshotData>(SNAPSHOTS, k.value(), v.value()) {
+                existing.insert(
+                    (data.file_path_id, data.timestamp, data.content_hash),
+                    data.id,
+                );
+            }
+        }
+
+        let mut file_snapshots = txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut snapshot_times = txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut hash_prefixes = txn
+            .open_table(HASH_PREFIXES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut links = txn
+            .open_table(SNAPSHOT_CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut chunks = txn
+            .open_table(CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut refs = txn
+            .open_table(CHUNK_REFS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut snapshot_map: HashMap<i64, i64> = HashMap::new();
+        let mut changeset_map: HashMap<i64, i64> = HashMap::new();
+        let mut imported: HashSet<i64> = HashSet::new();
+        let mut last_id = None;
+        let mut ordered: Vec<&BundleSnapshot> = history.snapshots.iter().collect();
+        ordered.sort_by_key(|s| s.id);
+        for s in ordered {
+            let file_path_id = ids.intern(&absolute_path(root, &s.path))?;
+            let timestamp = bundle_ms(&s.timestamp)?;
+            let key = (file_path_id, timestamp, s.content_hash.clone());
+            if let Some(id) = existing.get(&key) {
+                snapshot_map.insert(s.id, *id);
+                report.snapshots_skipped += 1;
+                continue;
+            }
+
+            let id = ids.next("snapshot_id")?;
+            let changeset_id = match s.changeset_id {
+                Some(cid) => match changeset_map.get(&cid) {
+                    Some(mapped) => Some(*mapped),
+                    None => {
+                        let mapped = ids.next("changeset_id")? as i64;
+                        changeset_map.insert(cid, mapped);
+                        Some(mapped)
+                    }
+                },
+                None => None,
+            };
+            let data = SnapshotData {
+                id: id as i64,
+                file_path_id,
+                timestamp,
+                content_hash: s.content_hash.clone(),
+                git_branch_id: s.git_branch.as_deref().map(|b| ids.intern(b)).transpose()?,
+                session_id: s.session_id.and_then(|sid| session_map.get(&sid).copied()),
+                commit_hash: s.commit_hash.clone(),
+                commit_message: s.commit_message.clone(),
+                event: match &s.event {
+                    SnapshotEvent::Modified => EventData::Modified,
+                    SnapshotEvent::Deleted => EventData::Deleted,
+                    SnapshotEvent::RenamedFrom { from } => {
+                        EventData::RenamedFrom(ids.intern(&absolute_path(root, from))?)
+                    }
+                    SnapshotEvent::RenamedTo { to } => {
+                        EventData::RenamedTo(ids.intern(&absolute_path(root, to))?)
+                    }
+                },
+                changeset_id,
+            };
+            snapshots
+                .insert(id, &*encode_record(SNAPSHOTS, id, &data)?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            file_snapshots
+                .insert((file_path_id, id), ())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            snapshot_times
+                .insert((timestamp, id), ())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            if let Some(key) = crypto::hash_prefix_key(&data.content_hash, HASH_PREFIX_LEN) {
+                hash_prefixes
+                    .insert((key.as_str(), id), ())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+            for (pos, hash) in s.chunks.iter().enumerate() {
+                let known = chunks
+                    .get(hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                    .is_some();
+                if !known {
+                    let chunk = ChunkData {
+                        hash: hash.clone(),
+                        kind_id: ids.intern("raw")?,
+                    };
+                    chunks
+                        .insert(
+                            hash.as_str(),
+                            &*encode_record(CHUNKS, hash.as_str(), &chunk)?,
+                        )
+                        .map_err(|e| AppError::Database(e.to_string()))?;
+                }
+                links
+                    .insert((id, pos as u32), hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                retain_chunk_ref_in(&mut refs, hash)?;
+            }
+            snapshot_map.insert(s.id, id as i64);
+            imported.insert(s.id);
+            last_id = Some(id);
+            report.snapshots_imported += 1;
+        }
+        // Bundled history interleaves in time with what is already here
+        if let Some(last_id) = last_id {
+            ids.meta
+                .insert(BACKFILLED_ID, last_id)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+
+        let mut checkpoints = txn
+            .open_table(CHECKPOINTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut checkpoint_files = txn
+            .open_table(CHECKPOINT_FILES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for c in &history.checkpoints {
+            let known = checkpoints
+                .get(c.hash.as_str())
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_some();
+            if known {
+                continue;
+            }
+            for (path, hash) in &c.file_states {
+                let path_id = ids.intern(&absolute_path(root, path))?;
+                checkpoint_files
+                    .insert((c.hash.as_str(), path_id), hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+            let data = CheckpointData {
+                hash: c.hash.clone(),
+                timestamp: bundle_ms(&c.timestamp)?,
+                description: c.description.clone(),
+            };
+            checkpoints
+                .insert(
+                    c.hash.as_str(),
+                    &*encode_record(CHECKPOINTS, c.hash.as_str(), &data)?,
+                )
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            report.checkpoints_imported += 1;
+        }
+
+        // Skipped snapshots keep the semantic rows they already have
+        let mut symbols = txn
+            .open_table(SYMBOLS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut symbol_map: HashMap<i64, i64> = HashMap::new();
+        let mut ordered: Vec<&BundleSymbol> = history
+            .symbols
+            .iter()
+            .filter(|s| imported.contains(&s.snapshot_id))
+            .collect();
+        ordered.sort_by_key(|s| s.id);
+        for sym in ordered {
+            let id = ids.next("symbol_id")?;
+            let data = SymbolData {
+                id: id as i64,
+                name_id: ids.intern(&sym.name)?,
+                kind_id: ids.intern(&sym.kind)?,
+                scope_id: sym.scope.as_deref().map(|s| ids.intern(s)).transpose()?,
+                snapshot_id: snapshot_map[&sym.snapshot_id],
+                chunk_hash: sym.chunk_hash.clone(),
+                structural_hash: sym.structural_hash.clone(),
+                start_line: sym.start_line,
+                end_line: sym.end_line,
+                start_byte: sym.start_byte,
+                end_byte: sym.end_byte,
+                parent_id: sym.parent_id.and_then(|p| symbol_map.get(&p).copied()),
+            };
+            symbols
+                .insert(id, &*encode_record(SYMBOLS, id, &data)?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            symbol_map.insert(sym.id, id as i64);
+            report.symbols_imported += 1;
+        }
+
+        let mut references = txn
+            .open_table(SYMBOL_REFERENCES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for r in history
+            .references
+            .iter()
+            .filter(|r| imported.contains(&r.snapshot_id))
+        {
+            let id = ids.next("reference_id")?;
+            let data = ReferenceData {
+                id: id as i64,
+                symbol_name_id: ids.intern(&r.symbol_name)?,
+                snapshot_id: snapshot_map[&r.snapshot_id],
+                start_line: r.start_line,
+                start_byte: r.start_byte,
+            };
+            references
+                .insert(id, &*encode_record(SYMBOL_REFERENCES, id, &data)?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+
+        let mut deltas = txn
+            .open_table(SYMBOL_DELTAS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for d in history
+            .deltas
+            .iter()
+            .filter(|d| imported.contains(&d.to_snapshot_id))
+        {
+            let id = ids.next("delta_id")?;
+            let data = DeltaData {
+                id: id as i64,
+                from_snapshot_id: d
+                    .from_snapshot_id
+                    .and_then(|f| snapshot_map.get(&f).copied()),
+                to_snapshot_id: snapshot_map[&d.to_snapshot_id],
+                symbol_name_id: ids.intern(&d.symbol_name)?,
+                new_name_id: d.new_name.as_deref().map(|n| ids.intern(n)).transpose()?,
+                delta_kind: d.delta_kind.clone(),
+                structural_hash: d.structural_hash.clone(),
+            };
+            deltas
+                .insert(id, &*encode_record(SYMBOL_DELTAS, id, &data)?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+    }
+    txn.commit()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(())
+}
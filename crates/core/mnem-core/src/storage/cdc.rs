@@ -0,0 +1,95 @@
+//! Content-defined chunking for binary and oversized files.
+//!
+//! Semantic chunking only makes sense for source text. Blobs are cut with
+//! FastCDC instead: boundaries depend on the bytes around them rather than on
+//! fixed offsets, so an edit only changes the chunks it touches and the rest
+//! of the file deduplicates against earlier versions in CAS.
+
+use bytes::Bytes;
+use fastcdc::v2020::FastCDC;
+
+const MIN_CHUNK: u32 = 16 * 1024;
+const AVG_CHUNK: u32 = 64 * 1024;
+const MAX_CHUNK: u32 = 256 * 1024;
+
+/// Bytes inspected to tell binary content from text.
+const SNIFF_LEN: usize = 1024;
+
+/// Whether `content` looks binary, judged from its first kilobyte.
+pub fn is_binary(content: &[u8]) -> bool {
+    content_inspector::inspect(&content[..content.len().min(SNIFF_LEN)]).is_binary()
+}
+
+/// Splits `content` into `(offset, bytes)` chunks in file order. The chunks
+/// share `content`'s buffer.
+pub fn chunk(content: &Bytes) -> Vec<(usize, Bytes)> {
+    if content.is_empty() {
+        return Vec::new();
+    }
+    FastCDC::new(content, MIN_CHUNK, AVG_CHUNK, MAX_CHUNK)
+        .map(|c| (c.offset, content.slice(c.offset..c.offset + c.length)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Deterministic pseudo-random bytes, so chunk boundaries are not trivial.
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_cover_the_content_in_order() {
+        let content = Bytes::from(noise(1024 * 1024, 7));
+        let chunks = chunk(&content);
+        assert!(chunks.len() > 1);
+        let mut offset = 0;
+        for (start, data) in &chunks {
+            assert_eq!(*start, offset);
+            assert!(data.len() <= MAX_CHUNK as usize);
+            offset += data.len();
+        }
+        assert_eq!(offset, content.len());
+        assert!(chunk(&Bytes::new()).is_empty());
+    }
+
+    #[test]
+    fn an_insertion_only_changes_nearby_chunks() {
+        let original = noise(1024 * 1024, 11);
+        let mut edited = original.clone();
+        edited.splice(300_000..300_000, b"inserted".iter().copied());
+
+        let before: HashSet<Bytes> = chunk(&Bytes::from(original))
+            .into_iter()
+            .map(|(_, data)| data)
+            .collect();
+        let after = chunk(&Bytes::from(edited));
+        let changed = after
+            .iter()
+            .filter(|(_, data)| !before.contains(data))
+            .count();
+        assert!(
+            changed <= 2,
+            "{} of {} chunks changed",
+            changed,
+            after.len()
+        );
+    }
+
+    #[test]
+    fn detects_binary_content() {
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_binary(b"fn main() {}\n"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Snapshots linked to the git commits that contain them.
+//!
+//! Every time HEAD moves, the commits reachable from it that are not recorded
+//! yet are read from `.git` ([`crate::git`]), oldest first. Each is recorded,
+//! and every unlinked snapshot of a path the commit changed whose content is
+//! the blob committed there is linked to it, so a snapshot ends up on the
+//! first commit that contains it. The post-commit hook is not needed; it only
+//! makes a commit show up before the next look at HEAD.
+
+use crate::crypto;
+use crate::error::AppResult;
+use crate::git::{Commit, GitRepo, ObjectStore, Oid};
+use crate::storage::Repository;
+use crate::storage::git_import::diff_trees;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// A branch switch onto a long unrecorded history only looks this far back.
+const MAX_NEW_COMMITS: usize = 64;
+
+/// Git stamps commits to the second, so a save in the same second as the
+/// commit still counts as before it.
+const CLOCK_SLACK_MS: i64 = 1000;
+
+/// What one look at HEAD did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadSync {
+    pub head: String,
+    /// Commits recorded.
+    pub commits: usize,
+    /// Snapshots linked to them.
+    pub linked: usize,
+}
+
+/// Records and links the commits HEAD gained since `last_head`, which is
+/// updated even when reading them fails so a broken repository is not read
+/// again on every tick. `None` when HEAD has not moved or there is no commit
+/// to look at.
+pub(crate) fn sync_head(
+    repo: &Repository,
+    last_head: &mut Option<Oid>,
+) -> AppResult<Option<HeadSync>> {
+    let Some(git) = GitRepo::discover(Path::new(&repo.project.path)) else {
+        return Ok(None);
+    };
+    let Some(head) = git.head()?.oid else {
+        return Ok(None);
+    };
+    if *last_head == Some(head) {
+        return Ok(None);
+    }
+    *last_head = Some(head);
+
+    let objects = git.objects()?;
+    let mut new = Vec::new();
+    let mut next = Some(head);
+    while let Some(oid) = next {
+        if new.len() == MAX_NEW_COMMITS || repo.db.get_git_commit(&oid.to_string())?.is_some() {
+            break;
+        }
+        let commit = objects.commit(&oid)?;
+        // First parents only: the line of work this branch was built on
+        next = commit.parents.first().copied();
+        new.push(commit);
+    }
+
+    let mut linker = Linker::new(repo, &git, &objects);
+    let mut linked = 0;
+    for commit in new.iter().rev() {
+        repo.db.insert_git_commit(
+            &commit.oid.to_string(),
+            commit.summary(),
+            &commit.author,
+            &commit.timestamp(),
+        )?;
+        linked += linker.link(commit)?;
+    }
+    Ok(Some(HeadSync {
+        head: head.to_string(),
+        commits: new.len(),
+        linked,
+    }))
+}
+
+/// Links unlinked snapshots to the commit `hash` if it can be read from the
+/// project's repository. Returns how many were linked.
+pub(crate) fn link_commit(repo: &Repository, hash: &str) -> AppResult<usize> {
+    let Some(git) = GitRepo::discover(Path::new(&repo.project.path)) else {
+        return Ok(0);
+    };
+    let Some(oid) = Oid::from_hex(hash) else {
+        return Ok(0);
+    };
+    let objects = git.objects()?;
+    let commit = objects.commit(&oid)?;
+    Linker::new(repo, &git, &objects).link(&commit)
+}
+
+struct Linker<'a> {
+    repo: &'a Repository,
+    objects: &'a ObjectStore,
+    work_tree: PathBuf,
+    /// Content hash of each blob read so far.
+    blobs: HashMap<Oid, String>,
+}
+
+impl<'a> Linker<'a> {
+    fn new(repo: &'a Repository, git: &GitRepo, objects: &'a ObjectStore) -> Self {
+        Self {
+            repo,
+            objects,
+            work_tree: git.work_tree().to_path_buf(),
+            blobs: HashMap::new(),
+        }
+    }
+
+    fn link(&mut self, commit: &Commit) -> AppResult<usize> {
+        // A snapshot first committed here is of a path the commit changed
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(self.objects.commit(parent)?.tree),
+            None => None,
+        };
+        let mut changes = Vec::new();
+        diff_trees(
+            self.objects,
+            parent_tree,
+            Some(commit.tree),
+            "",
+            &mut changes,
+        )?;
+        let committed: HashMap<String, Oid> = changes
+            .into_iter()
+            .filter_map(|(path, blob)| {
+                let path = self.work_tree.join(path).to_string_lossy().to_string();
+                Some((path, blob?))
+            })
+            .collect();
+        let paths: Vec<String> = committed.keys().cloned().collect();
+
+        let until = commit.time * 1000 + CLOCK_SLACK_MS;
+        let mut ids = Vec::new();
+        for (id, path, hash, timestamp) in self.repo.db.unlinked_snapshots(&paths)? {
+            if timestamp > until {
+                continue;
+            }
+            let blob = committed[&path];
+            let committed_hash = match self.blobs.get(&blob) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let hash = crypto::object_id(&self.objects.blob(&blob)?);
+                    self.blobs.insert(blob, hash.clone());
+                    hash
+                }
+            };
+            if hash == committed_hash {
+                ids.push(id);
+            }
+        }
+        self.repo
+            .db
+            .link_snapshots_to_commit(&ids, &commit.oid.to_string())?;
+        Ok(ids.len())
+    }
+}
+
+/// `path` relative to `work_tree`, `/`-separated as in git trees.
+pub(crate) fn tree_path(work_tree: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(work_tree).ok()?;
+    let parts: Option<Vec<&str>> = relative
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect();
+    let parts = parts?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
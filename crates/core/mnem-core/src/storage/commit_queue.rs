@@ -0,0 +1,120 @@
+//! Git commits reported while the history store was busy.
+//!
+//! The post-commit hook must never wait on, or race, the daemon for the redb
+//! lock. When neither the daemon nor an exclusive local open is available the
+//! hook appends the commit to `.mnemosyne/pending_commits.jsonl`, and whichever
+//! process owns the store next replays it.
+//!
+//! The hook may hold no key to seal the queue with, so under encryption only a
+//! commit's hash and time are queued; its message and author are read back
+//! from git when it is replayed.
+
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use crate::git::{GitRepo, Oid};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+const QUEUE_FILE: &str = "pending_commits.jsonl";
+/// The queue is renamed here while it is replayed, so appends made meanwhile
+/// start a fresh queue instead of being lost.
+const CLAIMED_FILE: &str = "pending_commits.replaying";
+
+/// A commit as reported by the post-commit hook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitCommitEvent {
+    pub hash: String,
+    pub message: String,
+    pub author: String,
+    pub timestamp: String,
+}
+
+/// Appends `event` to the queue of `project_path`, without its message and
+/// author when history under `base_dir` is encrypted.
+pub fn enqueue(base_dir: &Path, project_path: &Path, event: &GitCommitEvent) -> AppResult<()> {
+    let path = project_path.join(".mnemosyne").join(QUEUE_FILE);
+    let event = if crypto::status(base_dir)?.is_some() {
+        GitCommitEvent {
+            message: String::new(),
+            author: String::new(),
+            ..event.clone()
+        }
+    } else {
+        event.clone()
+    };
+    let mut line = serde_json::to_string(&event)
+        .map_err(|e| AppError::Internal(format!("Failed to encode commit: {}", e)))?;
+    line.push('\n');
+
+    // A single append keeps concurrent hooks from interleaving lines.
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| AppError::Io {
+            path: path.clone(),
+            source: e,
+        })?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| AppError::Io { path, source: e })
+}
+
+/// Hands every queued commit to `apply`, oldest first, and clears the queue.
+///
+/// A failed `apply` leaves the claimed queue in place to be replayed in full
+/// next time; recording a commit twice is harmless.
+pub(crate) fn replay(
+    mnem_dir: &Path,
+    mut apply: impl FnMut(&GitCommitEvent) -> AppResult<()>,
+) -> AppResult<usize> {
+    let queue = mnem_dir.join(QUEUE_FILE);
+    let claimed = mnem_dir.join(CLAIMED_FILE);
+    if !claimed.exists() {
+        if !queue.exists() {
+            return Ok(0);
+        }
+        std::fs::rename(&queue, &claimed).map_err(|e| AppError::Io {
+            path: queue.clone(),
+            source: e,
+        })?;
+    }
+
+    let content = std::fs::read_to_string(&claimed).map_err(|e| AppError::Io {
+        path: claimed.clone(),
+        source: e,
+    })?;
+    let mut applied = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<GitCommitEvent>(line) {
+            Ok(mut event) => {
+                if event.message.is_empty() && event.author.is_empty() {
+                    read_back(mnem_dir, &mut event);
+                }
+                apply(&event)?;
+                applied += 1;
+            }
+            Err(e) => log::warn!("Dropping unreadable queued commit: {}", e),
+        }
+    }
+
+    std::fs::remove_file(&claimed).map_err(|e| AppError::Io {
+        path: claimed,
+        source: e,
+    })?;
+    Ok(applied)
+}
+
+/// Fills in the message and author [`enqueue`] left out, from the project's
+/// git repository. Left blank when the commit is gone.
+fn read_back(mnem_dir: &Path, event: &mut GitCommitEvent) {
+    let project = mnem_dir.parent().unwrap_or(mnem_dir);
+    let commit = GitRepo::discover(project)
+        .zip(Oid::from_hex(&event.hash))
+        .and_then(|(git, oid)| git.objects().and_then(|objects| objects.commit(&oid)).ok());
+    if let Some(commit) = commit {
+        event.message = commit.summary().to_string();
+        event.author = commit.author;
+    }
+}
@@ -1,43 +1,133 @@
+use crate::crypto;
 use crate::error::{AppError, AppResult};
-use crate::models::{FileEntry, SemanticSymbol, Session, Snapshot, SymbolReference};
-use redb::{Database as Redb, ReadableTable, ReadableTableMetadata, TableDefinition};
+use crate::models::{
+    Changeset, FileEntry, GcReport, Pin, PinKind, SemanticSymbol, Session, Snapshot, SnapshotEvent,
+    SymbolReference,
+};
+use crate::storage::retention::RetentionCandidate;
+use crate::storage::schema::{
+    self, AppliedMigration, MIGRATIONS, MigrationReport, SCHEMA_VERSION, decode_record,
+    encode_record, row_binding,
+};
+use crate::storage::timesheet::SessionSpan;
+use crate::storage::trigram;
+use crate::utils::time::format_ms;
+use redb::{
+    Database as Redb, MultimapTableDefinition, MultimapTableHandle, ReadableMultimapTable,
+    ReadableTable, ReadableTableMetadata, TableDefinition, TableHandle,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 // Table Definitions
-const SNAPSHOTS: TableDefinition<u64, &[u8]> = TableDefinition::new("snapshots");
-const GIT_COMMITS: TableDefinition<&str, &[u8]> = TableDefinition::new("git_commits");
-const SESSIONS: TableDefinition<u64, &[u8]> = TableDefinition::new("sessions");
-const CHECKPOINTS: TableDefinition<&str, &[u8]> = TableDefinition::new("project_checkpoints");
-const CHUNKS: TableDefinition<&str, &[u8]> = TableDefinition::new("chunks");
-const SNAPSHOT_CHUNKS: TableDefinition<(u64, u32), &str> = TableDefinition::new("snapshot_chunks");
-const SYMBOLS: TableDefinition<u64, &[u8]> = TableDefinition::new("symbols");
-const SYMBOL_REFERENCES: TableDefinition<u64, &[u8]> = TableDefinition::new("symbol_references");
-const SYMBOL_DELTAS: TableDefinition<u64, &[u8]> = TableDefinition::new("symbol_deltas");
-const METADATA: TableDefinition<&str, u64> = TableDefinition::new("metadata");
+pub(crate) const SNAPSHOTS: TableDefinition<u64, &[u8]> = TableDefinition::new("snapshots");
+pub(crate) const GIT_COMMITS: TableDefinition<&str, &[u8]> = TableDefinition::new("git_commits");
+pub(crate) const SESSIONS: TableDefinition<u64, &[u8]> = TableDefinition::new("sessions");
+pub(crate) const CHECKPOINTS: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("project_checkpoints");
+// (checkpoint hash, file path id) -> content hash of the file in that checkpoint
+pub(crate) const CHECKPOINT_FILES: TableDefinition<(&str, u32), &str> =
+    TableDefinition::new("checkpoint_files");
+pub(crate) const CHUNKS: TableDefinition<&str, &[u8]> = TableDefinition::new("chunks");
+pub(crate) const SNAPSHOT_CHUNKS: TableDefinition<(u64, u32), &str> =
+    TableDefinition::new("snapshot_chunks");
+pub(crate) const SYMBOLS: TableDefinition<u64, &[u8]> = TableDefinition::new("symbols");
+pub(crate) const SYMBOL_REFERENCES: TableDefinition<u64, &[u8]> =
+    TableDefinition::new("symbol_references");
+pub(crate) const SYMBOL_DELTAS: TableDefinition<u64, &[u8]> = TableDefinition::new("symbol_deltas");
+pub(crate) const METADATA: TableDefinition<&str, u64> = TableDefinition::new("metadata");
 
 // Improvements: String Interning & Trigram Index
-const STRINGS: TableDefinition<u32, &str> = TableDefinition::new("strings");
-const STRING_INDEX: TableDefinition<&str, u32> = TableDefinition::new("string_index");
-const CHUNK_TRIGRAMS: TableDefinition<&str, u64> = TableDefinition::new("chunk_trigrams");
+pub(crate) const STRINGS: TableDefinition<u32, &str> = TableDefinition::new("strings");
+pub(crate) const STRING_INDEX: TableDefinition<&str, u32> = TableDefinition::new("string_index");
+// chunk hash -> number of distinct trigrams; presence marks the chunk as indexed
+pub(crate) const CHUNK_TRIGRAMS: TableDefinition<&str, u64> =
+    TableDefinition::new("chunk_trigrams");
+// Inverted index: trigram -> chunks containing it
+pub(crate) const TRIGRAM_POSTINGS: MultimapTableDefinition<u32, &str> =
+    MultimapTableDefinition::new("trigram_postings");
+
+// Snapshots fsck found unrecoverable, moved out of SNAPSHOTS
+pub(crate) const QUARANTINE: TableDefinition<u64, &[u8]> = TableDefinition::new("quarantine");
+
+// chunk hash -> number of SNAPSHOT_CHUNKS links; absent once unreferenced
+pub(crate) const CHUNK_REFS: TableDefinition<&str, u64> = TableDefinition::new("chunk_refs");
+
+// Secondary index: (file_path_id, snapshot_id) so per-file lookups are a range scan
+pub(crate) const FILE_SNAPSHOTS: TableDefinition<(u32, u64), ()> =
+    TableDefinition::new("file_snapshots");
+
+// Secondary index: (epoch millis, snapshot_id) so time-window queries are a range scan
+pub(crate) const SNAPSHOT_TIMES: TableDefinition<(i64, u64), ()> =
+    TableDefinition::new("snapshot_times");
+
+// Secondary index: (hash prefix key, snapshot_id) so short hash lookups only
+// read the snapshots that can match
+pub(crate) const HASH_PREFIXES: TableDefinition<(&str, u64), ()> =
+    TableDefinition::new("hash_prefixes");
+
+/// Hex digits of a content hash that HASH_PREFIXES is keyed by.
+pub(crate) const HASH_PREFIX_LEN: usize = 4;
+
+/// Metadata key of the highest snapshot id written out of time order, by a
+/// git import or a bundle merge. Past it, a file's highest snapshot id is its
+/// newest snapshot.
+pub(crate) const BACKFILLED_ID: &str = "backfilled_id";
+/// Metadata `sealed` once records are sealed bound to their rows.
+const SEALED_BOUND: u64 = 2;
+
+// Snapshot content hash or checkpoint hash -> label kept safe from retention
+pub(crate) const PINS: TableDefinition<&str, &[u8]> = TableDefinition::new("pins");
 
 #[derive(Serialize, Deserialize, Clone)]
-struct SnapshotData {
-    id: i64,
-    file_path_id: u32,
-    timestamp: String,
-    content_hash: String,
-    git_branch_id: Option<u32>,
-    session_id: Option<i64>,
-    commit_hash: Option<String>,
+pub(crate) struct SnapshotData {
+    pub(crate) id: i64,
+    pub(crate) file_path_id: u32,
+    /// UTC epoch milliseconds.
+    pub(crate) timestamp: i64,
+    pub(crate) content_hash: String,
+    pub(crate) git_branch_id: Option<u32>,
+    pub(crate) session_id: Option<i64>,
+    pub(crate) commit_hash: Option<String>,
     #[serde(default)]
-    commit_message: Option<String>,
+    pub(crate) commit_message: Option<String>,
+    pub(crate) event: EventData,
+    /// Saves that landed within the changeset window of each other share an id.
+    pub(crate) changeset_id: Option<i64>,
+}
+
+/// [`SnapshotEvent`] with the other path interned.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub(crate) enum EventData {
+    #[default]
+    Modified,
+    Deleted,
+    RenamedFrom(u32),
+    RenamedTo(u32),
+}
+
+impl EventData {
+    pub(crate) fn is_tombstone(&self) -> bool {
+        matches!(self, Self::Deleted | Self::RenamedTo(_))
+    }
+
+    pub(crate) fn resolve(
+        &self,
+        mut lookup: impl FnMut(u32) -> AppResult<String>,
+    ) -> AppResult<SnapshotEvent> {
+        Ok(match self {
+            Self::Modified => SnapshotEvent::Modified,
+            Self::Deleted => SnapshotEvent::Deleted,
+            Self::RenamedFrom(id) => SnapshotEvent::RenamedFrom { from: lookup(*id)? },
+            Self::RenamedTo(id) => SnapshotEvent::RenamedTo { to: lookup(*id)? },
+        })
+    }
 }
 
 /// Helper function to safely deserialize SnapshotData, skipping old format or corrupted records
-fn deserialize_snapshot_data(value: &[u8]) -> Option<SnapshotData> {
-    let data: SnapshotData = bincode::deserialize(value).ok()?;
+fn deserialize_snapshot_data(id: u64, value: &[u8]) -> Option<SnapshotData> {
+    let data: SnapshotData = decode_record(SNAPSHOTS, id, value).ok()?;
     if data.file_path_id == 0 {
         return None;
     }
@@ -45,71 +135,434 @@ fn deserialize_snapshot_data(value: &[u8]) -> Option<SnapshotData> {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct GitCommitData {
-    hash: String,
-    message: String,
-    author: String,
-    timestamp: String,
+pub(crate) struct GitCommitData {
+    pub(crate) hash: String,
+    pub(crate) message: String,
+    pub(crate) author: String,
+    pub(crate) timestamp: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct SessionData {
-    id: i64,
-    start_time: String,
-    end_time: Option<String>,
-    git_branch_id: Option<u32>,
-    file_count: usize,
-    snapshot_count: usize,
+pub(crate) struct SessionData {
+    pub(crate) id: i64,
+    /// UTC epoch milliseconds.
+    pub(crate) start_time: i64,
+    pub(crate) end_time: Option<i64>,
+    pub(crate) git_branch_id: Option<u32>,
+    pub(crate) file_count: usize,
+    pub(crate) snapshot_count: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct CheckpointData {
-    hash: String,
-    timestamp: String,
-    description: Option<String>,
-    file_states: String,
+pub(crate) struct PinData {
+    pub(crate) label: String,
+    pub(crate) checkpoint: bool,
+    /// UTC epoch milliseconds.
+    pub(crate) created: i64,
 }
 
+/// A checkpoint's files are rows in `CHECKPOINT_FILES`.
 #[derive(Serialize, Deserialize, Clone)]
-struct ChunkData {
-    hash: String,
-    kind_id: u32,
+pub(crate) struct CheckpointData {
+    pub(crate) hash: String,
+    /// UTC epoch milliseconds.
+    pub(crate) timestamp: i64,
+    pub(crate) description: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct SymbolData {
-    id: i64,
-    name_id: u32,
-    kind_id: u32,
-    scope_id: Option<u32>,
-    snapshot_id: i64,
-    chunk_hash: String,
-    structural_hash: String,
-    start_line: usize,
-    end_line: usize,
-    start_byte: usize,
-    end_byte: usize,
-    parent_id: Option<i64>,
+pub(crate) struct ChunkData {
+    pub(crate) hash: String,
+    pub(crate) kind_id: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct ReferenceData {
-    id: i64,
-    symbol_name_id: u32,
-    snapshot_id: i64,
-    start_line: usize,
-    start_byte: usize,
+pub(crate) struct SymbolData {
+    pub(crate) id: i64,
+    pub(crate) name_id: u32,
+    pub(crate) kind_id: u32,
+    pub(crate) scope_id: Option<u32>,
+    pub(crate) snapshot_id: i64,
+    pub(crate) chunk_hash: String,
+    pub(crate) structural_hash: String,
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+    pub(crate) start_byte: usize,
+    pub(crate) end_byte: usize,
+    pub(crate) parent_id: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-struct DeltaData {
-    id: i64,
-    from_snapshot_id: Option<i64>,
-    to_snapshot_id: i64,
-    symbol_name_id: u32,
-    new_name_id: Option<u32>,
-    delta_kind: String,
-    structural_hash: String,
+pub(crate) struct ReferenceData {
+    pub(crate) id: i64,
+    pub(crate) symbol_name_id: u32,
+    pub(crate) snapshot_id: i64,
+    pub(crate) start_line: usize,
+    pub(crate) start_byte: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct DeltaData {
+    pub(crate) id: i64,
+    pub(crate) from_snapshot_id: Option<i64>,
+    pub(crate) to_snapshot_id: i64,
+    pub(crate) symbol_name_id: u32,
+    pub(crate) new_name_id: Option<u32>,
+    pub(crate) delta_kind: String,
+    pub(crate) structural_hash: String,
+}
+
+/// A snapshot waiting to be written by [`Database::commit_batch`].
+pub struct NewSnapshot {
+    pub file_path: String,
+    /// UTC epoch milliseconds.
+    pub timestamp: i64,
+    /// Empty for tombstones.
+    pub content_hash: String,
+    pub git_branch: Option<String>,
+    pub session_id: Option<i64>,
+    /// The commit this version is known to belong to when it is recorded,
+    /// e.g. for history imported from git.
+    pub commit_hash: Option<String>,
+    pub event: SnapshotEvent,
+    /// Chunk hashes in file order, with the chunk bytes for trigram indexing.
+    pub chunks: Vec<(String, bytes::Bytes)>,
+}
+
+/// Unit of work grouping one or more snapshot saves into a single transaction.
+#[derive(Default)]
+pub struct WriteBatch {
+    snapshots: Vec<NewSnapshot>,
+    /// Quiet window for changesets in milliseconds, when grouping.
+    changeset_window_ms: Option<i64>,
+    /// Idle timeout for sessions in milliseconds, when tracking them.
+    session_idle_ms: Option<i64>,
+    /// Whether the batch holds versions older than snapshots already stored.
+    backfill: bool,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts the batch in one changeset, joining the latest snapshot's when
+    /// that was taken less than `window_ms` before the batch. A window of 0
+    /// always starts a new changeset.
+    pub fn group_changesets(&mut self, window_ms: i64) {
+        self.changeset_window_ms = Some(window_ms);
+    }
+
+    /// Puts the batch in the open session, closing it first and opening a
+    /// new one when nothing was saved in it for `idle_ms` or the batch is on
+    /// another branch.
+    pub fn track_sessions(&mut self, idle_ms: i64) {
+        self.session_idle_ms = Some(idle_ms);
+    }
+
+    /// Marks the batch as history older than what is already stored, so
+    /// lookups of a file's latest snapshot go by time over it.
+    pub fn backfill(&mut self) {
+        self.backfill = true;
+    }
+
+    pub fn add_snapshot(&mut self, snapshot: NewSnapshot) {
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+pub(crate) fn next_id_in(meta: &mut redb::Table<&str, u64>, key: &str) -> AppResult<u64> {
+    let current = meta
+        .get(key)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|v| v.value())
+        .unwrap_or(0);
+    let next = current + 1;
+    meta.insert(key, next)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(next)
+}
+
+pub(crate) fn intern_string_in(
+    meta: &mut redb::Table<&str, u64>,
+    strings: &mut redb::Table<u32, &str>,
+    index: &mut redb::Table<&str, u32>,
+    s: &str,
+) -> AppResult<u32> {
+    let key = crypto::string_key(s);
+    if let Some(id) = index
+        .get(&*key)
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        return Ok(id.value());
+    }
+    let id = next_id_in(meta, "string_id")? as u32;
+    strings
+        .insert(id, &*crypto::seal_string(s, &row_binding(STRINGS, id))?)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    index
+        .insert(&*key, id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(id)
+}
+
+/// A file's newest snapshot by (timestamp, id). Ids follow time except in
+/// backfilled history, so only a file last written by a backfill is read
+/// whole.
+fn newest_snapshot_in(
+    meta: &impl ReadableTable<&'static str, u64>,
+    file_snapshots: &impl ReadableTable<(u32, u64), ()>,
+    snapshots: &impl ReadableTable<u64, &'static [u8]>,
+    file_path_id: u32,
+) -> AppResult<Option<SnapshotData>> {
+    let backfilled = meta
+        .get(BACKFILLED_ID)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|v| v.value())
+        .unwrap_or(0);
+    let mut newest: Option<SnapshotData> = None;
+    for res in file_snapshots
+        .range((file_path_id, 0)..=(file_path_id, u64::MAX))
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .rev()
+    {
+        let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let id = key.value().1;
+        let Some(data) = snapshots
+            .get(id)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .and_then(|v| deserialize_snapshot_data(id, v.value()))
+        else {
+            continue;
+        };
+        if newest.is_none() && id > backfilled {
+            return Ok(Some(data));
+        }
+        if newest
+            .as_ref()
+            .is_none_or(|n| (data.timestamp, data.id) > (n.timestamp, n.id))
+        {
+            newest = Some(data);
+        }
+    }
+    Ok(newest)
+}
+
+/// The changeset a save taken at `timestamp` belongs to: the latest
+/// snapshot's when it is less than `window_ms` older, otherwise a new one.
+fn changeset_for_in(
+    meta: &mut redb::Table<&str, u64>,
+    snapshots: &redb::Table<u64, &[u8]>,
+    snapshot_times: &redb::Table<(i64, u64), ()>,
+    timestamp: i64,
+    window_ms: i64,
+) -> AppResult<i64> {
+    let latest = snapshot_times
+        .last()
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|(k, _)| k.value());
+    // Either way round: history backfilled before the latest save is not
+    // part of its changeset
+    if let Some((_, id)) = latest.filter(|(at, _)| (timestamp - at).abs() < window_ms) {
+        let joined = snapshots
+            .get(id)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .and_then(|v| decode_record::<SnapshotData>(SNAPSHOTS, id, v.value()).ok())
+            .and_then(|data| data.changeset_id);
+        if let Some(changeset_id) = joined {
+            return Ok(changeset_id);
+        }
+    }
+    Ok(next_id_in(meta, "changeset_id")? as i64)
+}
+
+/// The session that has not been closed yet, if any. Sessions are opened in
+/// id order, so only the newest one can be open.
+fn open_session_in(
+    sessions: &impl ReadableTable<u64, &'static [u8]>,
+) -> AppResult<Option<SessionData>> {
+    let Some((k, v)) = sessions
+        .last()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+    let data: SessionData = decode_record(SESSIONS, k.value(), v.value())?;
+    Ok(data.end_time.is_none().then_some(data))
+}
+
+/// When the last save of `session` happened, or when it started if nothing
+/// was saved in it yet.
+fn session_last_active_in(
+    snapshots: &impl ReadableTable<u64, &'static [u8]>,
+    snapshot_times: &impl ReadableTable<(i64, u64), ()>,
+    session: &SessionData,
+) -> AppResult<i64> {
+    let latest = snapshot_times
+        .last()
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|(k, _)| k.value());
+    let Some((at, id)) = latest else {
+        return Ok(session.start_time);
+    };
+    let in_session = snapshots
+        .get(id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .and_then(|v| decode_record::<SnapshotData>(SNAPSHOTS, id, v.value()).ok())
+        .is_some_and(|data| data.session_id == Some(session.id));
+    Ok(if in_session {
+        at.max(session.start_time)
+    } else {
+        session.start_time
+    })
+}
+
+/// The session a save at `timestamp` on `git_branch_id` belongs to. Closes
+/// the open session when it went idle or was on another branch; the caller
+/// writes the returned session back once its counts are updated.
+fn session_for_in(
+    meta: &mut redb::Table<&str, u64>,
+    sessions: &mut redb::Table<u64, &[u8]>,
+    snapshots: &redb::Table<u64, &[u8]>,
+    snapshot_times: &redb::Table<(i64, u64), ()>,
+    timestamp: i64,
+    git_branch_id: Option<u32>,
+    idle_ms: i64,
+) -> AppResult<SessionData> {
+    if let Some(mut open) = open_session_in(sessions)? {
+        let last_active = session_last_active_in(snapshots, snapshot_times, &open)?;
+        if timestamp - last_active < idle_ms && open.git_branch_id == git_branch_id {
+            return Ok(open);
+        }
+        open.end_time = Some(last_active);
+        let bytes = encode_record(SESSIONS, open.id as u64, &open)?;
+        sessions
+            .insert(open.id as u64, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(SessionData {
+        id: next_id_in(meta, "session_id")? as i64,
+        start_time: timestamp,
+        end_time: None,
+        git_branch_id,
+        file_count: 0,
+        snapshot_count: 0,
+    })
+}
+
+/// Id of an interned string, if it was ever interned.
+pub(crate) fn find_string_in(
+    index: &impl ReadableTable<&'static str, u32>,
+    s: &str,
+) -> AppResult<Option<u32>> {
+    Ok(index
+        .get(&*crypto::string_key(s))
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|id| id.value()))
+}
+
+fn index_chunk_trigrams_in(
+    postings: &mut redb::MultimapTable<u32, &str>,
+    markers: &mut redb::Table<&str, u64>,
+    chunk_hash: &str,
+    content: &[u8],
+) -> AppResult<()> {
+    let trigrams = trigram::trigrams(content);
+    let blind = crypto::trigram_blinder();
+    for t in &trigrams {
+        postings
+            .insert(blind(*t), chunk_hash)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    markers
+        .insert(chunk_hash, trigrams.len() as u64)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(())
+}
+
+pub(crate) fn retain_chunk_ref_in(
+    refs: &mut redb::Table<&str, u64>,
+    chunk_hash: &str,
+) -> AppResult<()> {
+    let count = refs
+        .get(chunk_hash)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|v| v.value())
+        .unwrap_or(0);
+    refs.insert(chunk_hash, count + 1)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Drops one reference; the row disappears when the count reaches zero.
+fn release_chunk_ref_in(refs: &mut redb::Table<&str, u64>, chunk_hash: &str) -> AppResult<()> {
+    let count = refs
+        .get(chunk_hash)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .map(|v| v.value())
+        .unwrap_or(0);
+    if count > 1 {
+        refs.insert(chunk_hash, count - 1)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    } else {
+        refs.remove(chunk_hash)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Keeps the records of a u64-keyed table for which `keep` holds and returns
+/// how many were removed. Rows that fail to decode are kept.
+pub(crate) fn retain_records<T: serde::de::DeserializeOwned>(
+    txn: &redb::WriteTransaction,
+    def: TableDefinition<u64, &[u8]>,
+    keep: impl Fn(&T) -> bool,
+) -> AppResult<usize> {
+    let mut table = txn
+        .open_table(def)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut removed = 0;
+    table
+        .retain(|k, v| match decode_record::<T>(def, k, v) {
+            Ok(record) if !keep(&record) => {
+                removed += 1;
+                false
+            }
+            _ => true,
+        })
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(removed)
+}
+
+fn copy_table<K: redb::Key + 'static, V: redb::Value + 'static>(
+    from: &redb::ReadTransaction,
+    to: &redb::WriteTransaction,
+    def: TableDefinition<K, V>,
+) -> AppResult<()> {
+    let source = from
+        .open_table(def)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut target = to
+        .open_table(def)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for entry in source
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (key, value) = entry.map_err(|e| AppError::Database(e.to_string()))?;
+        target
+            .insert(key.value(), value.value())
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(())
 }
 
 pub struct Database {
@@ -118,10 +571,223 @@ pub struct Database {
 }
 
 impl Database {
+    /// Opens the store and applies any pending schema migrations.
     pub fn new(path: PathBuf) -> AppResult<Self> {
-        let db = Redb::builder()
-            .create(&path)
-            .map_err(|e| AppError::Internal(format!("Failed to open redb: {}", e)))?;
+        let mut db = Self::open_unmigrated(path)?;
+        let report = db.migrate(false)?;
+        if !report.is_noop() {
+            log::info!(
+                "Migrated {:?} from schema v{} to v{}",
+                db.path,
+                report.from_version,
+                report.to_version
+            );
+        }
+        if db.check_encryption()? {
+            // Sealing frees pages that still hold the plaintext and redb does
+            // not scrub them, so live records move to a fresh file
+            let path = db.path.clone();
+            let fresh = db.copy_to_fresh_file()?;
+            drop(db);
+            std::fs::rename(&fresh, &path)?;
+            db = Self::open_unmigrated(path)?;
+            db.remove_backups();
+        }
+        Ok(db)
+    }
+
+    /// Refuses an encrypted store while locked, and seals a plaintext one,
+    /// or one sealed before records were bound to their rows, while
+    /// unlocked. Returns whether anything was sealed.
+    fn check_encryption(&mut self) -> AppResult<bool> {
+        let sealed = self.sealed_version()?;
+        let unlocked = crypto::is_unlocked();
+        if sealed.is_some() && !unlocked {
+            return Err(AppError::Encryption(format!(
+                "{} is encrypted and locked",
+                self.path.display()
+            )));
+        }
+        if !unlocked || sealed == Some(SEALED_BOUND) {
+            return Ok(false);
+        }
+
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let changed = schema::seal_history(&write_txn, sealed.is_none())?;
+        {
+            let mut meta = write_txn
+                .open_table(METADATA)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            meta.insert("sealed", SEALED_BOUND)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        log::info!("Sealed {} records in {:?}", changed, self.path);
+        Ok(true)
+    }
+
+    /// Copies every table into a new file next to the store and returns its
+    /// path. Fails rather than drop a table it does not know about.
+    fn copy_to_fresh_file(&self) -> AppResult<PathBuf> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let known = [
+            SNAPSHOTS.name(),
+            GIT_COMMITS.name(),
+            SESSIONS.name(),
+            CHECKPOINTS.name(),
+            CHECKPOINT_FILES.name(),
+            CHUNKS.name(),
+            SNAPSHOT_CHUNKS.name(),
+            SYMBOLS.name(),
+            SYMBOL_REFERENCES.name(),
+            SYMBOL_DELTAS.name(),
+            METADATA.name(),
+            STRINGS.name(),
+            STRING_INDEX.name(),
+            CHUNK_TRIGRAMS.name(),
+            QUARANTINE.name(),
+            CHUNK_REFS.name(),
+            FILE_SNAPSHOTS.name(),
+            SNAPSHOT_TIMES.name(),
+            HASH_PREFIXES.name(),
+            PINS.name(),
+        ];
+        let tables = read_txn
+            .list_tables()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let multimaps = read_txn
+            .list_multimap_tables()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for table in tables {
+            if !known.contains(&table.name()) {
+                return Err(AppError::Database(format!(
+                    "Unknown table {:?} in {}",
+                    table.name(),
+                    self.path.display()
+                )));
+            }
+        }
+        for table in multimaps {
+            if table.name() != TRIGRAM_POSTINGS.name() {
+                return Err(AppError::Database(format!(
+                    "Unknown table {:?} in {}",
+                    table.name(),
+                    self.path.display()
+                )));
+            }
+        }
+
+        let mut fresh_path = self.path.clone().into_os_string();
+        fresh_path.push(".sealing");
+        let fresh_path = PathBuf::from(fresh_path);
+        let _ = std::fs::remove_file(&fresh_path);
+        let fresh = Redb::create(&fresh_path).map_err(|e| AppError::Database(e.to_string()))?;
+        let write_txn = fresh
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        copy_table(&read_txn, &write_txn, SNAPSHOTS)?;
+        copy_table(&read_txn, &write_txn, GIT_COMMITS)?;
+        copy_table(&read_txn, &write_txn, SESSIONS)?;
+        copy_table(&read_txn, &write_txn, CHECKPOINTS)?;
+        copy_table(&read_txn, &write_txn, CHECKPOINT_FILES)?;
+        copy_table(&read_txn, &write_txn, CHUNKS)?;
+        copy_table(&read_txn, &write_txn, SNAPSHOT_CHUNKS)?;
+        copy_table(&read_txn, &write_txn, SYMBOLS)?;
+        copy_table(&read_txn, &write_txn, SYMBOL_REFERENCES)?;
+        copy_table(&read_txn, &write_txn, SYMBOL_DELTAS)?;
+        copy_table(&read_txn, &write_txn, METADATA)?;
+        copy_table(&read_txn, &write_txn, STRINGS)?;
+        copy_table(&read_txn, &write_txn, STRING_INDEX)?;
+        copy_table(&read_txn, &write_txn, CHUNK_TRIGRAMS)?;
+        copy_table(&read_txn, &write_txn, QUARANTINE)?;
+        copy_table(&read_txn, &write_txn, CHUNK_REFS)?;
+        copy_table(&read_txn, &write_txn, FILE_SNAPSHOTS)?;
+        copy_table(&read_txn, &write_txn, SNAPSHOT_TIMES)?;
+        copy_table(&read_txn, &write_txn, HASH_PREFIXES)?;
+        copy_table(&read_txn, &write_txn, PINS)?;
+        {
+            let from = read_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut to = write_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for entry in from.iter().map_err(|e| AppError::Database(e.to_string()))? {
+                let (trigram, chunks) = entry.map_err(|e| AppError::Database(e.to_string()))?;
+                for chunk in chunks {
+                    let chunk = chunk.map_err(|e| AppError::Database(e.to_string()))?;
+                    to.insert(trigram.value(), chunk.value())
+                        .map_err(|e| AppError::Database(e.to_string()))?;
+                }
+            }
+        }
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(fresh_path)
+    }
+
+    /// Whether records are sealed.
+    pub fn is_sealed(&self) -> AppResult<bool> {
+        Ok(self.sealed_version()?.is_some())
+    }
+
+    /// How records are sealed: [`SEALED_BOUND`], or 1 when sealed before
+    /// records were bound to their rows.
+    fn sealed_version(&self) -> AppResult<Option<u64>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let meta = read_txn
+            .open_table(METADATA)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(meta
+            .get("sealed")
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .map(|v| v.value()))
+    }
+
+    fn remove_backups(&self) {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return;
+        };
+        let prefix = format!("{}.v", name.to_string_lossy());
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with(&prefix)
+                && file_name.ends_with(".bak")
+                && let Err(e) = std::fs::remove_file(entry.path())
+            {
+                log::warn!(
+                    "Failed to remove plaintext backup {:?}: {}",
+                    entry.path(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Opens the store without running migrations, e.g. to inspect them with
+    /// `migrate(true)` first.
+    pub fn open_unmigrated(path: PathBuf) -> AppResult<Self> {
+        let db = Redb::builder().create(&path).map_err(|e| match e {
+            redb::DatabaseError::DatabaseAlreadyOpen => {
+                AppError::Locked(format!("{} is open in another process", path.display()))
+            }
+            e => AppError::Internal(format!("Failed to open redb: {}", e)),
+        })?;
 
         let write_txn = db
             .begin_write()
@@ -139,6 +805,9 @@ impl Database {
             let _ = write_txn
                 .open_table(CHECKPOINTS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(CHECKPOINT_FILES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
             let _ = write_txn
                 .open_table(CHUNKS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -163,11 +832,48 @@ impl Database {
             let _ = write_txn
                 .open_table(CHUNK_TRIGRAMS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(FILE_SNAPSHOTS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(SNAPSHOT_TIMES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(HASH_PREFIXES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(CHUNK_REFS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(QUARANTINE)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let _ = write_txn
+                .open_table(PINS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
 
             let mut meta = write_txn
                 .open_table(METADATA)
                 .map_err(|e| AppError::Database(e.to_string()))?;
 
+            // A store whose counters were never initialized is brand new and
+            // starts at the current schema; anything older without a version is v0
+            let is_new = meta
+                .get("snapshot_id")
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_none();
+            if meta
+                .get("schema_version")
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_none()
+            {
+                let version = if is_new { SCHEMA_VERSION } else { 0 };
+                meta.insert("schema_version", version)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+
             if meta
                 .get("snapshot_id")
                 .map_err(|e| AppError::Database(e.to_string()))?
@@ -223,6 +929,109 @@ impl Database {
         Ok(Self { db, path })
     }
 
+    pub(crate) fn redb(&self) -> &Redb {
+        &self.db
+    }
+
+    pub fn schema_version(&self) -> AppResult<u64> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let meta = read_txn
+            .open_table(METADATA)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(meta
+            .get("schema_version")
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .map(|v| v.value())
+            .unwrap_or(0))
+    }
+
+    /// Runs every registered migration newer than the stored schema version in
+    /// one write transaction.
+    ///
+    /// With `dry_run` the transaction is aborted after counting the rows each
+    /// step would touch. Otherwise the database file is copied next to itself
+    /// before anything is rewritten.
+    pub fn migrate(&self, dry_run: bool) -> AppResult<MigrationReport> {
+        let from_version = self.schema_version()?;
+        if from_version > SCHEMA_VERSION {
+            return Err(AppError::Database(format!(
+                "Database schema v{} is newer than this build supports (v{})",
+                from_version, SCHEMA_VERSION
+            )));
+        }
+        let mut report = MigrationReport {
+            from_version,
+            to_version: from_version,
+            dry_run,
+            applied: Vec::new(),
+            backup_path: None,
+        };
+        let pending: Vec<&schema::Migration> = MIGRATIONS
+            .iter()
+            .filter(|m| m.version > from_version)
+            .collect();
+        if pending.is_empty() {
+            return Ok(report);
+        }
+
+        if !dry_run {
+            report.backup_path = Some(self.backup(from_version)?);
+        }
+
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for migration in pending {
+            let rows_changed = migration.apply(&write_txn)?;
+            report.applied.push(AppliedMigration {
+                version: migration.version,
+                description: migration.description.to_string(),
+                rows_changed,
+            });
+            report.to_version = migration.version;
+        }
+        {
+            let mut meta = write_txn
+                .open_table(METADATA)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            meta.insert("schema_version", report.to_version)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+        if dry_run {
+            write_txn
+                .abort()
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        } else {
+            write_txn
+                .commit()
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+        Ok(report)
+    }
+
+    fn backup(&self, version: u64) -> AppResult<PathBuf> {
+        let file_name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "mnemosyne.db".to_string());
+        let backup_path = self.path.with_file_name(format!(
+            "{}.v{}-{}.bak",
+            file_name,
+            version,
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        ));
+        std::fs::copy(&self.path, &backup_path).map_err(|e| AppError::Io {
+            path: backup_path.clone(),
+            source: e,
+        })?;
+        Ok(backup_path)
+    }
+
     fn next_id(&self, key: &str) -> AppResult<u64> {
         let write_txn = self
             .db
@@ -232,15 +1041,7 @@ impl Database {
             let mut meta = write_txn
                 .open_table(METADATA)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let current = meta
-                .get(key)
-                .map_err(|e| AppError::Database(e.to_string()))?
-                .map(|v| v.value())
-                .unwrap_or(0);
-            let next = current + 1;
-            meta.insert(key, next)
-                .map_err(|e| AppError::Database(e.to_string()))?;
-            next
+            next_id_in(&mut meta, key)?
         };
         write_txn
             .commit()
@@ -256,11 +1057,8 @@ impl Database {
         let index = read_txn
             .open_table(STRING_INDEX)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        if let Some(id) = index
-            .get(s)
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            return Ok(id.value());
+        if let Some(id) = find_string_in(&index, s)? {
+            return Ok(id);
         }
         drop(read_txn);
         let write_txn = self
@@ -271,27 +1069,13 @@ impl Database {
             let mut meta = write_txn
                 .open_table(METADATA)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let current = meta
-                .get("string_id")
-                .map_err(|e| AppError::Database(e.to_string()))?
-                .map(|v| v.value())
-                .unwrap_or(0) as u32;
-            let next = current + 1;
-            meta.insert("string_id", next as u64)
-                .map_err(|e| AppError::Database(e.to_string()))?;
             let mut strings = write_txn
                 .open_table(STRINGS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
             let mut index = write_txn
                 .open_table(STRING_INDEX)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            strings
-                .insert(next, s)
-                .map_err(|e| AppError::Database(e.to_string()))?;
-            index
-                .insert(s, next)
-                .map_err(|e| AppError::Database(e.to_string()))?;
-            next
+            intern_string_in(&mut meta, &mut strings, &mut index, s)?
         };
         write_txn
             .commit()
@@ -311,7 +1095,7 @@ impl Database {
             .get(id)
             .map_err(|e| AppError::Database(e.to_string()))?
             .ok_or_else(|| AppError::Internal(format!("String ID {} not found", id)))?;
-        Ok(s.value().to_string())
+        Ok(crypto::open_string(s.value(), &row_binding(STRINGS, id))?.into_owned())
     }
 
     pub fn get_git_commit(&self, hash: &str) -> AppResult<Option<(String, String, String)>> {
@@ -326,8 +1110,7 @@ impl Database {
             .get(hash)
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let data: GitCommitData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let data: GitCommitData = decode_record(GIT_COMMITS, hash, v.value())?;
             Ok(Some((data.message, data.author, data.timestamp)))
         } else {
             Ok(None)
@@ -355,7 +1138,7 @@ impl Database {
                 author: author.to_string(),
                 timestamp: timestamp.to_string(),
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(GIT_COMMITS, hash, &data)?;
             table
                 .insert(hash, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -367,6 +1150,19 @@ impl Database {
     }
 
     pub fn link_snapshot_to_commit(&self, snapshot_id: i64, commit_hash: &str) -> AppResult<()> {
+        self.link_snapshots_to_commit(&[snapshot_id], commit_hash)
+    }
+
+    /// Links every snapshot in `snapshot_ids` to `commit_hash` in one write
+    /// transaction. Missing ids are skipped.
+    pub fn link_snapshots_to_commit(
+        &self,
+        snapshot_ids: &[i64],
+        commit_hash: &str,
+    ) -> AppResult<()> {
+        if snapshot_ids.is_empty() {
+            return Ok(());
+        }
         let write_txn = self
             .db
             .begin_write()
@@ -375,23 +1171,24 @@ impl Database {
             let mut table = write_txn
                 .open_table(SNAPSHOTS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let data = if let Some(v) = table
-                .get(snapshot_id as u64)
-                .map_err(|e| AppError::Database(e.to_string()))?
-            {
-                let mut data: SnapshotData = bincode::deserialize(v.value())
-                    .map_err(|e| AppError::Internal(e.to_string()))?;
-                data.commit_hash = Some(commit_hash.to_string());
-                Some(data)
-            } else {
-                None
-            };
-            if let Some(d) = data {
-                let bytes =
-                    bincode::serialize(&d).map_err(|e| AppError::Internal(e.to_string()))?;
-                table
-                    .insert(snapshot_id as u64, &*bytes)
-                    .map_err(|e| AppError::Database(e.to_string()))?;
+            for &snapshot_id in snapshot_ids {
+                let data = if let Some(v) = table
+                    .get(snapshot_id as u64)
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                {
+                    let mut data: SnapshotData =
+                        decode_record(SNAPSHOTS, snapshot_id as u64, v.value())?;
+                    data.commit_hash = Some(commit_hash.to_string());
+                    Some(data)
+                } else {
+                    None
+                };
+                if let Some(d) = data {
+                    let bytes = encode_record(SNAPSHOTS, snapshot_id as u64, &d)?;
+                    table
+                        .insert(snapshot_id as u64, &*bytes)
+                        .map_err(|e| AppError::Database(e.to_string()))?;
+                }
             }
         }
         write_txn
@@ -400,48 +1197,287 @@ impl Database {
         Ok(())
     }
 
-    pub fn insert_snapshot(
+    /// Snapshots of `paths` with content that no commit has been matched to
+    /// yet, as `(id, path, content hash, timestamp)`. Deletions are left out.
+    pub fn unlinked_snapshots(
         &self,
-        file_path: &str,
-        timestamp: &str,
-        content_hash: &str,
-        git_branch: Option<&str>,
-        session_id: Option<i64>,
-    ) -> AppResult<i64> {
-        let id = self.next_id("snapshot_id")?;
-        let file_path_id = self.intern_string(file_path)?;
-        let git_branch_id = if let Some(b) = git_branch {
-            Some(self.intern_string(b)?)
-        } else {
-            None
-        };
+        paths: &[String],
+    ) -> AppResult<Vec<(i64, String, String, i64)>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let index = read_txn
+            .open_table(STRING_INDEX)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let file_snapshots = read_txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut results = Vec::new();
+        for path in paths {
+            let Some(file_path_id) = find_string_in(&index, path)? else {
+                continue;
+            };
+            for res in file_snapshots
+                .range((file_path_id, 0)..=(file_path_id, u64::MAX))
+                .map_err(|e| AppError::Database(e.to_string()))?
+            {
+                let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                let Some(data) = table
+                    .get(key.value().1)
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                    .and_then(|v| deserialize_snapshot_data(key.value().1, v.value()))
+                else {
+                    continue;
+                };
+                if data.commit_hash.is_some() || data.event.is_tombstone() {
+                    continue;
+                }
+                results.push((data.id, path.clone(), data.content_hash, data.timestamp));
+            }
+        }
+        Ok(results)
+    }
+
+    pub fn insert_snapshot(
+        &self,
+        file_path: &str,
+        timestamp: i64,
+        content_hash: &str,
+        git_branch: Option<&str>,
+        session_id: Option<i64>,
+    ) -> AppResult<i64> {
+        let mut batch = WriteBatch::new();
+        batch.add_snapshot(NewSnapshot {
+            file_path: file_path.to_string(),
+            timestamp,
+            content_hash: content_hash.to_string(),
+            git_branch: git_branch.map(|b| b.to_string()),
+            session_id,
+            commit_hash: None,
+            event: SnapshotEvent::Modified,
+            chunks: Vec::new(),
+        });
+        let ids = self.commit_batch(batch)?;
+        Ok(ids[0])
+    }
+
+    /// Writes every snapshot in `batch` (ids, interned strings, chunk rows,
+    /// chunk links and trigram entries) in one write transaction, so a crash
+    /// never leaves a half-linked snapshot behind.
+    ///
+    /// Returns the new snapshot ids in the order they were added.
+    pub fn commit_batch(&self, batch: WriteBatch) -> AppResult<Vec<i64>> {
+        let mut ids = Vec::with_capacity(batch.snapshots.len());
+        if batch.is_empty() {
+            return Ok(ids);
+        }
         let write_txn = self
             .db
             .begin_write()
             .map_err(|e| AppError::Database(e.to_string()))?;
         {
-            let mut table = write_txn
+            let mut meta = write_txn
+                .open_table(METADATA)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut strings = write_txn
+                .open_table(STRINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut string_index = write_txn
+                .open_table(STRING_INDEX)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut snapshots = write_txn
                 .open_table(SNAPSHOTS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let data = SnapshotData {
-                id: id as i64,
-                file_path_id,
-                timestamp: timestamp.to_string(),
-                content_hash: content_hash.to_string(),
-                git_branch_id,
-                session_id,
-                commit_hash: None,
-                commit_message: None,
-            };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
-            table
-                .insert(id, &*bytes)
+            let mut file_snapshots = write_txn
+                .open_table(FILE_SNAPSHOTS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut snapshot_times = write_txn
+                .open_table(SNAPSHOT_TIMES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut hash_prefixes = write_txn
+                .open_table(HASH_PREFIXES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut chunks = write_txn
+                .open_table(CHUNKS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut snapshot_chunks = write_txn
+                .open_table(SNAPSHOT_CHUNKS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut trigram_markers = write_txn
+                .open_table(CHUNK_TRIGRAMS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut postings = write_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut chunk_refs = write_txn
+                .open_table(CHUNK_REFS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+
+            let mut sessions = write_txn
+                .open_table(SESSIONS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+
+            let changeset_id = match (batch.snapshots.first(), batch.changeset_window_ms) {
+                (Some(first), Some(window_ms)) => Some(changeset_for_in(
+                    &mut meta,
+                    &snapshots,
+                    &snapshot_times,
+                    first.timestamp,
+                    window_ms,
+                )?),
+                _ => None,
+            };
+            let mut session = match (batch.snapshots.first(), batch.session_idle_ms) {
+                (Some(first), Some(idle_ms)) => {
+                    let git_branch_id = match &first.git_branch {
+                        Some(b) => Some(intern_string_in(
+                            &mut meta,
+                            &mut strings,
+                            &mut string_index,
+                            b,
+                        )?),
+                        None => None,
+                    };
+                    Some(session_for_in(
+                        &mut meta,
+                        &mut sessions,
+                        &snapshots,
+                        &snapshot_times,
+                        first.timestamp,
+                        git_branch_id,
+                        idle_ms,
+                    )?)
+                }
+                _ => None,
+            };
+
+            for snap in batch.snapshots {
+                let id = next_id_in(&mut meta, "snapshot_id")?;
+                let file_path_id =
+                    intern_string_in(&mut meta, &mut strings, &mut string_index, &snap.file_path)?;
+                let git_branch_id = match &snap.git_branch {
+                    Some(b) => Some(intern_string_in(
+                        &mut meta,
+                        &mut strings,
+                        &mut string_index,
+                        b,
+                    )?),
+                    None => None,
+                };
+                let mut intern =
+                    |path: &str| intern_string_in(&mut meta, &mut strings, &mut string_index, path);
+                let event = match &snap.event {
+                    SnapshotEvent::Modified => EventData::Modified,
+                    SnapshotEvent::Deleted => EventData::Deleted,
+                    SnapshotEvent::RenamedFrom { from } => EventData::RenamedFrom(intern(from)?),
+                    SnapshotEvent::RenamedTo { to } => EventData::RenamedTo(intern(to)?),
+                };
+                let session_id = match session.as_mut() {
+                    Some(session) => {
+                        // A file's earlier saves in this session are its latest ones
+                        let previous = file_snapshots
+                            .range((file_path_id, 0)..=(file_path_id, u64::MAX))
+                            .map_err(|e| AppError::Database(e.to_string()))?
+                            .next_back()
+                            .transpose()
+                            .map_err(|e| AppError::Database(e.to_string()))?
+                            .map(|(k, _)| k.value().1);
+                        let seen = match previous {
+                            Some(previous) => snapshots
+                                .get(previous)
+                                .map_err(|e| AppError::Database(e.to_string()))?
+                                .and_then(|v| {
+                                    decode_record::<SnapshotData>(SNAPSHOTS, previous, v.value())
+                                        .ok()
+                                })
+                                .is_some_and(|data| data.session_id == Some(session.id)),
+                            None => false,
+                        };
+                        if !seen {
+                            session.file_count += 1;
+                        }
+                        session.snapshot_count += 1;
+                        Some(session.id)
+                    }
+                    None => snap.session_id,
+                };
+                let data = SnapshotData {
+                    id: id as i64,
+                    file_path_id,
+                    timestamp: snap.timestamp,
+                    content_hash: snap.content_hash,
+                    git_branch_id,
+                    session_id,
+                    commit_hash: snap.commit_hash,
+                    commit_message: None,
+                    event,
+                    changeset_id,
+                };
+                let bytes = encode_record(SNAPSHOTS, id, &data)?;
+                snapshots
+                    .insert(id, &*bytes)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                file_snapshots
+                    .insert((file_path_id, id), ())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                snapshot_times
+                    .insert((snap.timestamp, id), ())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                if let Some(key) = crypto::hash_prefix_key(&data.content_hash, HASH_PREFIX_LEN) {
+                    hash_prefixes
+                        .insert((key.as_str(), id), ())
+                        .map_err(|e| AppError::Database(e.to_string()))?;
+                }
+
+                for (pos, (chunk_hash, content)) in snap.chunks.iter().enumerate() {
+                    let known = chunks
+                        .get(chunk_hash.as_str())
+                        .map_err(|e| AppError::Database(e.to_string()))?
+                        .is_some();
+                    if !known {
+                        let kind_id =
+                            intern_string_in(&mut meta, &mut strings, &mut string_index, "raw")?;
+                        let chunk = ChunkData {
+                            hash: chunk_hash.clone(),
+                            kind_id,
+                        };
+                        let bytes = encode_record(CHUNKS, chunk_hash.as_str(), &chunk)?;
+                        chunks
+                            .insert(chunk_hash.as_str(), &*bytes)
+                            .map_err(|e| AppError::Database(e.to_string()))?;
+                        index_chunk_trigrams_in(
+                            &mut postings,
+                            &mut trigram_markers,
+                            chunk_hash,
+                            content,
+                        )?;
+                    }
+                    snapshot_chunks
+                        .insert((id, pos as u32), chunk_hash.as_str())
+                        .map_err(|e| AppError::Database(e.to_string()))?;
+                    retain_chunk_ref_in(&mut chunk_refs, chunk_hash)?;
+                }
+                ids.push(id as i64);
+            }
+            if let Some(last) = ids.last().filter(|_| batch.backfill) {
+                meta.insert(BACKFILLED_ID, *last as u64)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+            if let Some(session) = session {
+                let bytes = encode_record(SESSIONS, session.id as u64, &session)?;
+                sessions
+                    .insert(session.id as u64, &*bytes)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
         }
         write_txn
             .commit()
             .map_err(|e| AppError::Database(e.to_string()))?;
-        Ok(id as i64)
+        Ok(ids)
     }
 
     pub fn get_last_hash(&self, file_path: &str) -> AppResult<Option<String>> {
@@ -449,99 +1485,230 @@ impl Database {
             .db
             .begin_read()
             .map_err(|e| AppError::Database(e.to_string()))?;
+        let index = read_txn
+            .open_table(STRING_INDEX)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let Some(file_path_id) = find_string_in(&index, file_path)? else {
+            return Ok(None);
+        };
+        let meta = read_txn
+            .open_table(METADATA)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let file_snapshots = read_txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
         let table = read_txn
             .open_table(SNAPSHOTS)
             .map_err(|e| AppError::Database(e.to_string()))?;
+        let latest = newest_snapshot_in(&meta, &file_snapshots, &table, file_path_id)?;
+        // A deleted file has no current content
+        Ok(latest
+            .filter(|data| !data.event.is_tombstone())
+            .map(|data| data.content_hash))
+    }
+
+    /// History of one file, newest first.
+    ///
+    /// Falls back to a path-prefix lookup when nothing is recorded under the
+    /// exact path, so directory queries keep working.
+    pub fn get_history(&self, file_path: &str) -> AppResult<Vec<Snapshot>> {
+        let history = self.get_file_history(file_path)?;
+        if !history.is_empty() {
+            return Ok(history);
+        }
+        self.get_history_by_prefix(file_path)
+    }
+
+    /// History of exactly `file_path`, newest first.
+    pub fn get_file_history(&self, file_path: &str) -> AppResult<Vec<Snapshot>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
         let index = read_txn
             .open_table(STRING_INDEX)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let file_path_id = match index
-            .get(file_path)
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            Some(id) => id.value(),
-            None => return Ok(None),
+        let Some(file_path_id) = find_string_in(&index, file_path)? else {
+            return Ok(Vec::new());
         };
-        let mut last_hash = None;
-        let mut last_id = 0;
-        for res in table
-            .iter()
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
-            if data.file_path_id == file_path_id && id.value() >= last_id {
-                last_id = id.value();
-                last_hash = Some(data.content_hash);
+        drop(index);
+        drop(read_txn);
+        self.collect_history(&[(file_path_id, file_path.to_string())])
+    }
+
+    /// History of every file whose path starts with `prefix`, newest first.
+    pub fn get_history_by_prefix(&self, prefix: &str) -> AppResult<Vec<Snapshot>> {
+        let paths = self.paths_with_prefix(prefix)?;
+        self.collect_history(&paths)
+    }
+
+    /// Paths starting with `prefix` whose latest snapshot is not a tombstone.
+    pub fn live_paths_with_prefix(&self, prefix: &str) -> AppResult<Vec<String>> {
+        let paths = self.paths_with_prefix(prefix)?;
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let file_snapshots = read_txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let meta = read_txn
+            .open_table(METADATA)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut live = Vec::new();
+        for (id, path) in paths {
+            let latest = newest_snapshot_in(&meta, &file_snapshots, &table, id)?;
+            if latest.is_some_and(|d| !d.event.is_tombstone()) {
+                live.push(path);
             }
         }
-        Ok(last_hash)
+        Ok(live)
     }
 
-    pub fn get_history(&self, file_path: &str) -> AppResult<Vec<Snapshot>> {
+    /// Interned paths starting with `prefix` that have snapshots.
+    fn paths_with_prefix(&self, prefix: &str) -> AppResult<Vec<(u32, String)>> {
         let read_txn = self
             .db
             .begin_read()
             .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut candidates = Vec::new();
+        if crypto::is_unlocked() {
+            // The index is keyed by token, so match against the strings themselves
+            let strings = read_txn
+                .open_table(STRINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for res in strings
+                .iter()
+                .map_err(|e| AppError::Database(e.to_string()))?
+            {
+                let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                let s = crypto::open_string(v.value(), &row_binding(STRINGS, id.value()))?;
+                if s.starts_with(prefix) {
+                    candidates.push((id.value(), s.into_owned()));
+                }
+            }
+        } else {
+            let index = read_txn
+                .open_table(STRING_INDEX)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for res in index
+                .range(prefix..)
+                .map_err(|e| AppError::Database(e.to_string()))?
+            {
+                let (key, id) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                if !key.value().starts_with(prefix) {
+                    break;
+                }
+                candidates.push((id.value(), key.value().to_string()));
+            }
+        }
+
+        let file_snapshots = read_txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        // Interned strings include branches and symbol names; only keep ids
+        // that actually have snapshots
+        let mut paths = Vec::new();
+        for (id, path) in candidates {
+            let has_snapshots = file_snapshots
+                .range((id, 0)..=(id, u64::MAX))
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .next()
+                .is_some();
+            if has_snapshots {
+                paths.push((id, path));
+            }
+        }
+        Ok(paths)
+    }
+
+    fn collect_history(&self, paths: &[(u32, String)]) -> AppResult<Vec<Snapshot>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let file_snapshots = read_txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
         let table = read_txn
             .open_table(SNAPSHOTS)
             .map_err(|e| AppError::Database(e.to_string()))?;
         let mut history = Vec::new();
-        for res in table
-            .iter()
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            // Skip records that can't be parsed (old format or corrupted)
-            let Ok(data) = bincode::deserialize::<SnapshotData>(v.value()) else {
-                continue;
-            };
-            // Skip records with invalid file_path_id
-            if data.file_path_id == 0 {
-                continue;
-            }
-            let path = match self.lookup_string(data.file_path_id) {
-                Ok(p) => p,
-                Err(_) => continue,
-            };
-            if path.contains(file_path) {
+        for (file_path_id, path) in paths {
+            for res in file_snapshots
+                .range((*file_path_id, 0)..=(*file_path_id, u64::MAX))
+                .map_err(|e| AppError::Database(e.to_string()))?
+            {
+                let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                let Some(v) = table
+                    .get(key.value().1)
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                else {
+                    continue;
+                };
+                // Skip records that can't be parsed (old format or corrupted)
+                let Some(data) = deserialize_snapshot_data(key.value().1, v.value()) else {
+                    continue;
+                };
                 let branch = if let Some(bid) = data.git_branch_id {
                     Some(self.lookup_string(bid)?)
                 } else {
                     None
                 };
-                history.push(Snapshot {
-                    id: data.id,
-                    file_path: path,
-                    timestamp: data.timestamp,
-                    content_hash: data.content_hash,
-                    git_branch: branch,
-                    session_id: data.session_id,
-                    commit_hash: data.commit_hash,
-                    commit_message: data.commit_message,
-                });
+                history.push((
+                    data.timestamp,
+                    Snapshot {
+                        id: data.id,
+                        file_path: path.clone(),
+                        timestamp: format_ms(data.timestamp),
+                        content_hash: data.content_hash,
+                        git_branch: branch,
+                        session_id: data.session_id,
+                        changeset_id: data.changeset_id,
+                        commit_hash: data.commit_hash,
+                        commit_message: data.commit_message,
+                        event: data.event.resolve(|id| self.lookup_string(id))?,
+                    },
+                ));
             }
         }
-        history.sort_by(|a, b| b.id.cmp(&a.id));
-        Ok(history)
+        // By time first: versions imported later can predate the others
+        history.sort_by_key(|(timestamp, s)| std::cmp::Reverse((*timestamp, s.id)));
+        Ok(history.into_iter().map(|(_, s)| s).collect())
     }
 
+    /// The `limit` most recent snapshots across all files, newest first.
     pub fn get_global_history(&self, limit: usize) -> AppResult<Vec<Snapshot>> {
         let read_txn = self
             .db
             .begin_read()
             .map_err(|e| AppError::Database(e.to_string()))?;
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
         let table = read_txn
             .open_table(SNAPSHOTS)
             .map_err(|e| AppError::Database(e.to_string()))?;
         let mut history = Vec::new();
-        for res in table
+        for res in times
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
+            .rev()
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let Some(data) = deserialize_snapshot_data(v.value()) else {
+            if history.len() >= limit {
+                break;
+            }
+            let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let Some(v) = table
+                .get(key.value().1)
+                .map_err(|e| AppError::Database(e.to_string()))?
+            else {
+                continue;
+            };
+            let Some(data) = deserialize_snapshot_data(key.value().1, v.value()) else {
                 continue;
             };
             let path = match self.lookup_string(data.file_path_id) {
@@ -556,35 +1723,22 @@ impl Database {
             history.push(Snapshot {
                 id: data.id,
                 file_path: path,
-                timestamp: data.timestamp,
+                timestamp: format_ms(data.timestamp),
                 content_hash: data.content_hash,
                 git_branch: branch,
                 session_id: data.session_id,
+                changeset_id: data.changeset_id,
                 commit_hash: data.commit_hash,
                 commit_message: data.commit_message,
+                event: data.event.resolve(|id| self.lookup_string(id))?,
             });
         }
-        history.sort_by(|a, b| b.id.cmp(&a.id));
-        history.truncate(limit);
         Ok(history)
     }
 
     pub fn get_history_by_hash(&self, content_hash: &str) -> AppResult<Vec<Snapshot>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| AppError::Database(e.to_string()))?;
-        let table = read_txn
-            .open_table(SNAPSHOTS)
-            .map_err(|e| AppError::Database(e.to_string()))?;
         let mut history = Vec::new();
-        for res in table
-            .iter()
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+        for data in self.snapshots_with_hash_prefix(content_hash)? {
             if data.content_hash == content_hash {
                 let path = self.lookup_string(data.file_path_id)?;
                 let branch = if let Some(bid) = data.git_branch_id {
@@ -595,12 +1749,14 @@ impl Database {
                 history.push(Snapshot {
                     id: data.id,
                     file_path: path,
-                    timestamp: data.timestamp,
+                    timestamp: format_ms(data.timestamp),
                     content_hash: data.content_hash,
                     git_branch: branch,
                     session_id: data.session_id,
+                    changeset_id: data.changeset_id,
                     commit_hash: data.commit_hash,
                     commit_message: data.commit_message,
+                    event: data.event.resolve(|id| self.lookup_string(id))?,
                 });
             }
         }
@@ -636,16 +1792,13 @@ impl Database {
         let table = read_txn
             .open_table(SNAPSHOTS)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let mut files: std::collections::HashMap<u32, String> = std::collections::HashMap::new();
+        let mut files: std::collections::HashMap<u32, i64> = std::collections::HashMap::new();
         let branch_id = if let Some(b) = branch {
             let index = read_txn
                 .open_table(STRING_INDEX)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            match index
-                .get(b)
-                .map_err(|e| AppError::Database(e.to_string()))?
-            {
-                Some(id) => Some(id.value()),
+            match find_string_in(&index, b)? {
+                Some(id) => Some(id),
                 None => return Ok(Vec::new()),
             }
         } else {
@@ -655,9 +1808,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             if let Some(bid) = branch_id {
                 if data.git_branch_id != Some(bid) {
                     continue;
@@ -669,22 +1821,21 @@ impl Database {
                     continue;
                 }
             }
-            let entry = files
-                .entry(data.file_path_id)
-                .or_insert_with(|| data.timestamp.clone());
+            let entry = files.entry(data.file_path_id).or_insert(data.timestamp);
             if data.timestamp > *entry {
                 *entry = data.timestamp;
             }
         }
+        let mut files: Vec<(u32, i64)> = files.into_iter().collect();
+        files.sort_by_key(|(_, ts)| std::cmp::Reverse(*ts));
+        files.truncate(limit);
         let mut results = Vec::new();
         for (pid, last_update) in files {
             results.push(FileEntry {
                 path: self.lookup_string(pid)?,
-                last_update,
+                last_update: format_ms(last_update),
             });
         }
-        results.sort_by(|a, b| b.last_update.cmp(&a.last_update));
-        results.truncate(limit);
         Ok(results)
     }
 
@@ -701,9 +1852,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             if let Some(bid) = data.git_branch_id {
                 branches.insert(bid);
             }
@@ -721,6 +1871,23 @@ impl Database {
     }
 
     pub fn resolve_hash(&self, short_hash: &str) -> AppResult<Option<String>> {
+        let found: HashSet<String> = self
+            .snapshots_with_hash_prefix(short_hash)?
+            .into_iter()
+            .map(|data| data.content_hash)
+            .collect();
+        Ok(if found.len() == 1 {
+            found.into_iter().next()
+        } else {
+            None
+        })
+    }
+
+    /// Snapshots whose content hash starts with `prefix`, ignoring case.
+    /// A prefix of at least [`HASH_PREFIX_LEN`] digits is looked up in the
+    /// hash prefix index, so only the snapshots it names are decoded; a
+    /// shorter one scans every snapshot.
+    fn snapshots_with_hash_prefix(&self, prefix: &str) -> AppResult<Vec<SnapshotData>> {
         let read_txn = self
             .db
             .begin_read()
@@ -728,27 +1895,44 @@ impl Database {
         let table = read_txn
             .open_table(SNAPSHOTS)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let short = short_hash.to_lowercase();
-        let mut found = HashSet::new();
-        for res in table
-            .iter()
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
-            if data.content_hash.to_lowercase().starts_with(&short) {
-                found.insert(data.content_hash);
-                if found.len() > 1 {
-                    return Ok(None);
+        let prefix = prefix.to_lowercase();
+        let mut found = Vec::new();
+        let mut consider = |id: u64, bytes: &[u8]| -> AppResult<()> {
+            let data: SnapshotData = decode_record(SNAPSHOTS, id, bytes)?;
+            if data.content_hash.to_lowercase().starts_with(&prefix) {
+                found.push(data);
+            }
+            Ok(())
+        };
+        match crypto::hash_prefix_key(&prefix, HASH_PREFIX_LEN) {
+            Some(key) => {
+                let index = read_txn
+                    .open_table(HASH_PREFIXES)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                for res in index
+                    .range((key.as_str(), 0)..=(key.as_str(), u64::MAX))
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                {
+                    let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                    if let Some(v) = table
+                        .get(k.value().1)
+                        .map_err(|e| AppError::Database(e.to_string()))?
+                    {
+                        consider(k.value().1, v.value())?;
+                    }
+                }
+            }
+            None => {
+                for res in table
+                    .iter()
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                {
+                    let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+                    consider(k.value(), v.value())?;
                 }
             }
         }
-        Ok(if found.len() == 1 {
-            found.into_iter().next()
-        } else {
-            None
-        })
+        Ok(found)
     }
 
     pub fn get_all_unique_snapshots(&self) -> AppResult<Vec<Snapshot>> {
@@ -765,18 +1949,23 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             let entry = files
                 .entry(data.file_path_id)
                 .or_insert_with(|| data.clone());
-            if data.timestamp > entry.timestamp {
+            if (data.timestamp, data.id) > (entry.timestamp, entry.id) {
                 *entry = data;
             }
         }
+        // Files whose latest snapshot is a tombstone no longer exist
+        let mut files: Vec<SnapshotData> = files
+            .into_values()
+            .filter(|d| !d.event.is_tombstone())
+            .collect();
+        files.sort_by_key(|d| std::cmp::Reverse(d.timestamp));
         let mut results = Vec::new();
-        for (_, data) in files {
+        for data in files {
             let path = self.lookup_string(data.file_path_id)?;
             let branch = if let Some(bid) = data.git_branch_id {
                 Some(self.lookup_string(bid)?)
@@ -786,15 +1975,16 @@ impl Database {
             results.push(Snapshot {
                 id: data.id,
                 file_path: path,
-                timestamp: data.timestamp,
+                timestamp: format_ms(data.timestamp),
                 content_hash: data.content_hash,
                 git_branch: branch,
                 session_id: data.session_id,
+                changeset_id: data.changeset_id,
                 commit_hash: data.commit_hash,
                 commit_message: data.commit_message,
+                event: data.event.resolve(|id| self.lookup_string(id))?,
             });
         }
-        results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
         Ok(results)
     }
 
@@ -812,9 +2002,11 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
+            if data.event.is_tombstone() {
+                continue;
+            }
             let entry = deduplicated
                 .entry(data.content_hash.clone())
                 .or_insert_with(|| data.clone());
@@ -822,8 +2014,10 @@ impl Database {
                 *entry = data;
             }
         }
+        let mut deduplicated: Vec<SnapshotData> = deduplicated.into_values().collect();
+        deduplicated.sort_by_key(|d| std::cmp::Reverse(d.timestamp));
         let mut results = Vec::new();
-        for (_, data) in deduplicated {
+        for data in deduplicated {
             let path = self.lookup_string(data.file_path_id)?;
             let branch = if let Some(bid) = data.git_branch_id {
                 Some(self.lookup_string(bid)?)
@@ -833,15 +2027,16 @@ impl Database {
             results.push(Snapshot {
                 id: data.id,
                 file_path: path,
-                timestamp: data.timestamp,
+                timestamp: format_ms(data.timestamp),
                 content_hash: data.content_hash,
                 git_branch: branch,
                 session_id: data.session_id,
+                changeset_id: data.changeset_id,
                 commit_hash: data.commit_hash,
                 commit_message: data.commit_message,
+                event: data.event.resolve(|id| self.lookup_string(id))?,
             });
         }
-        results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
         Ok(results)
     }
 
@@ -858,21 +2053,75 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            if let Some(data) = deserialize_snapshot_data(v.value()) {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if let Some(data) = deserialize_snapshot_data(k.value(), v.value()) {
                 hashes.insert(data.content_hash);
             }
         }
         Ok(hashes)
     }
 
-    pub fn prune_snapshots(&self, days: u64) -> AppResult<usize> {
-        let cutoff = (chrono::Local::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+    /// Snapshot metadata for [`retention::select_prunable`], with commit-linked,
+    /// pinned and checkpoint-referenced snapshots marked exempt.
+    pub fn retention_candidates(&self) -> AppResult<Vec<RetentionCandidate>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let checkpoint_files = read_txn
+            .open_table(CHECKPOINT_FILES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut checkpointed = HashSet::new();
+        for res in checkpoint_files
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            checkpointed.insert(v.value().to_string());
+        }
+        let pins = read_txn
+            .open_table(PINS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for res in pins.iter().map_err(|e| AppError::Database(e.to_string()))? {
+            let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            checkpointed.insert(k.value().to_string());
+        }
+
+        let snapshots = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut candidates = Vec::new();
+        for res in snapshots
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let Some(data) = deserialize_snapshot_data(id.value(), v.value()) else {
+                continue;
+            };
+            candidates.push(RetentionCandidate {
+                id: id.value() as i64,
+                file_path_id: data.file_path_id,
+                exempt: data.commit_hash.is_some() || checkpointed.contains(&data.content_hash),
+                timestamp: data.timestamp,
+                session_id: data.session_id,
+            });
+        }
+        Ok(candidates)
+    }
+
+    /// Removes the given snapshots together with their symbols, references
+    /// and deltas. Chunk reference counts are decremented; chunks left
+    /// unreferenced are reclaimed by [`Database::purge_chunks`].
+    pub fn delete_snapshots(&self, ids: &HashSet<i64>) -> AppResult<GcReport> {
+        let mut report = GcReport::default();
+        if ids.is_empty() {
+            return Ok(report);
+        }
         let write_txn = self
             .db
             .begin_write()
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let mut count = 0;
         {
             let mut snapshots = write_txn
                 .open_table(SNAPSHOTS)
@@ -880,64 +2129,161 @@ impl Database {
             let mut snapshot_chunks = write_txn
                 .open_table(SNAPSHOT_CHUNKS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let mut symbols = write_txn
-                .open_table(SYMBOLS)
+            let mut chunk_refs = write_txn
+                .open_table(CHUNK_REFS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            let mut to_delete = Vec::new();
-            for res in snapshots
-                .iter()
-                .map_err(|e| AppError::Database(e.to_string()))?
-            {
-                let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-                let data: SnapshotData = bincode::deserialize(v.value())
-                    .map_err(|e| AppError::Internal(e.to_string()))?;
-                if data.timestamp < cutoff && data.commit_hash.is_none() {
-                    to_delete.push(id.value());
-                }
-            }
-            for id in &to_delete {
-                snapshots
+            let mut file_snapshots = write_txn
+                .open_table(FILE_SNAPSHOTS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut snapshot_times = write_txn
+                .open_table(SNAPSHOT_TIMES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut hash_prefixes = write_txn
+                .open_table(HASH_PREFIXES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for id in ids {
+                let id = *id as u64;
+                let removed = snapshots
                     .remove(id)
-                    .map_err(|e| AppError::Database(e.to_string()))?;
-                count += 1;
-                let mut chunk_keys = Vec::new();
-                for res in snapshot_chunks
-                    .iter()
                     .map_err(|e| AppError::Database(e.to_string()))?
-                {
-                    let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
-                    if key.value().0 == *id {
-                        chunk_keys.push(key.value());
-                    }
-                }
-                for k in chunk_keys {
-                    snapshot_chunks
-                        .remove(k)
+                    .map(|v| decode_record::<SnapshotData>(SNAPSHOTS, id, v.value()))
+                    .transpose()?;
+                let Some(data) = removed else {
+                    continue;
+                };
+                file_snapshots
+                    .remove((data.file_path_id, id))
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                snapshot_times
+                    .remove((data.timestamp, id))
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                if let Some(key) = crypto::hash_prefix_key(&data.content_hash, HASH_PREFIX_LEN) {
+                    hash_prefixes
+                        .remove((key.as_str(), id))
                         .map_err(|e| AppError::Database(e.to_string()))?;
                 }
-                let mut sym_keys = Vec::new();
-                for res in symbols
-                    .iter()
+                report.snapshots_pruned += 1;
+                let mut unlinked = Vec::new();
+                snapshot_chunks
+                    .retain_in((id, 0)..=(id, u32::MAX), |_, hash| {
+                        unlinked.push(hash.to_string());
+                        false
+                    })
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                for hash in &unlinked {
+                    release_chunk_ref_in(&mut chunk_refs, hash)?;
+                }
+            }
+
+            report.symbols_deleted = retain_records::<SymbolData>(&write_txn, SYMBOLS, |s| {
+                !ids.contains(&s.snapshot_id)
+            })?;
+            report.references_deleted =
+                retain_records::<ReferenceData>(&write_txn, SYMBOL_REFERENCES, |r| {
+                    !ids.contains(&r.snapshot_id)
+                })?;
+            report.deltas_deleted = retain_records::<DeltaData>(&write_txn, SYMBOL_DELTAS, |d| {
+                !ids.contains(&d.to_snapshot_id)
+            })?;
+        }
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(report)
+    }
+
+    /// Chunks that no snapshot links to any more.
+    pub fn unreferenced_chunks(&self) -> AppResult<Vec<String>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let chunks = read_txn
+            .open_table(CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let chunk_refs = read_txn
+            .open_table(CHUNK_REFS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut orphans = Vec::new();
+        for res in chunks
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let referenced = chunk_refs
+                .get(k.value())
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_some();
+            if !referenced {
+                orphans.push(k.value().to_string());
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Deletes chunk rows and their trigram postings. Each entry carries the
+    /// trigrams of the chunk's content. Chunks that gained a reference since
+    /// they were listed are kept; the removed hashes are returned.
+    pub fn purge_chunks(&self, chunks: &[(String, HashSet<u32>)]) -> AppResult<Vec<String>> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut removed = Vec::new();
+        {
+            let mut chunk_table = write_txn
+                .open_table(CHUNKS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let chunk_refs = write_txn
+                .open_table(CHUNK_REFS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut markers = write_txn
+                .open_table(CHUNK_TRIGRAMS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut postings = write_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let blind = crypto::trigram_blinder();
+            for (hash, trigrams) in chunks {
+                let referenced = chunk_refs
+                    .get(hash.as_str())
                     .map_err(|e| AppError::Database(e.to_string()))?
-                {
-                    let (key, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-                    let sym: SymbolData = bincode::deserialize(v.value())
-                        .map_err(|e| AppError::Internal(e.to_string()))?;
-                    if sym.snapshot_id == *id as i64 {
-                        sym_keys.push(key.value());
-                    }
+                    .is_some();
+                if referenced {
+                    continue;
                 }
-                for k in sym_keys {
-                    symbols
-                        .remove(k)
+                chunk_table
+                    .remove(hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                markers
+                    .remove(hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                for t in trigrams {
+                    postings
+                        .remove(blind(*t), hash.as_str())
                         .map_err(|e| AppError::Database(e.to_string()))?;
                 }
+                removed.push(hash.clone());
             }
         }
         write_txn
             .commit()
             .map_err(|e| AppError::Database(e.to_string()))?;
-        Ok(count)
+        Ok(removed)
+    }
+
+    pub fn has_chunk(&self, hash: &str) -> AppResult<bool> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(table
+            .get(hash)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .is_some())
     }
 
     pub fn get_snapshot_count(&self) -> AppResult<usize> {
@@ -1005,6 +2351,41 @@ impl Database {
                 sym.remove(k)
                     .map_err(|e| AppError::Database(e.to_string()))?;
             }
+            let mut fs_index = write_txn
+                .open_table(FILE_SNAPSHOTS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            fs_index
+                .retain(|_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            write_txn
+                .open_table(SNAPSHOT_TIMES)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .retain(|_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            write_txn
+                .open_table(HASH_PREFIXES)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .retain(|_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for def in [SYMBOL_REFERENCES, SYMBOL_DELTAS] {
+                write_txn
+                    .open_table(def)
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                    .retain(|_, _| false)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+            // Every chunk is unreferenced now and left for purge_chunks
+            write_txn
+                .open_table(CHUNK_REFS)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .retain(|_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            // Checkpoints survive, and so do their pins
+            write_txn
+                .open_table(PINS)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .retain(|k, v| decode_record::<PinData>(PINS, k, v).is_ok_and(|pin| pin.checkpoint))
+                .map_err(|e| AppError::Database(e.to_string()))?;
         }
         write_txn
             .commit()
@@ -1028,71 +2409,305 @@ impl Database {
             .get(id as u64)
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, id as u64, v.value())?;
+            let path = self.lookup_string(data.file_path_id)?;
+            let branch = if let Some(bid) = data.git_branch_id {
+                Some(self.lookup_string(bid)?)
+            } else {
+                None
+            };
+            Ok(Some(Snapshot {
+                id: data.id,
+                file_path: path,
+                timestamp: format_ms(data.timestamp),
+                content_hash: data.content_hash,
+                git_branch: branch,
+                session_id: data.session_id,
+                changeset_id: data.changeset_id,
+                commit_hash: data.commit_hash,
+                commit_message: data.commit_message,
+                event: data.event.resolve(|id| self.lookup_string(id))?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_latest_state(&self) -> AppResult<Vec<(String, String)>> {
+        let snapshots = self.get_all_unique_snapshots()?;
+        Ok(snapshots
+            .into_iter()
+            .map(|s| (s.file_path, s.content_hash))
+            .collect())
+    }
+
+    /// The newest snapshot of every file that existed at `timestamp` (UTC
+    /// epoch milliseconds).
+    pub fn get_state_at_timestamp(&self, timestamp: i64) -> AppResult<Vec<(String, String)>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        // Index order is time order, so later entries overwrite earlier ones
+        let mut latest_per_file: HashMap<u32, Option<String>> = HashMap::new();
+        for res in times
+            .range((i64::MIN, 0)..=(timestamp, u64::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let Some(v) = table
+                .get(key.value().1)
+                .map_err(|e| AppError::Database(e.to_string()))?
+            else {
+                continue;
+            };
+            let Some(data) = deserialize_snapshot_data(key.value().1, v.value()) else {
+                continue;
+            };
+            let hash = (!data.event.is_tombstone()).then_some(data.content_hash);
+            latest_per_file.insert(data.file_path_id, hash);
+        }
+        let mut results = Vec::new();
+        for (pid, hash) in latest_per_file {
+            if let Some(hash) = hash {
+                results.push((self.lookup_string(pid)?, hash));
+            }
+        }
+        Ok(results)
+    }
+
+    /// The `limit` newest changesets, newest first.
+    pub fn list_changesets(&self, limit: usize) -> AppResult<Vec<Changeset>> {
+        Ok(self
+            .changeset_groups(None, limit)?
+            .into_iter()
+            .map(|(id, snapshots)| {
+                let mut files: Vec<String> =
+                    snapshots.iter().map(|s| s.file_path.clone()).collect();
+                files.sort();
+                files.dedup();
+                Changeset {
+                    id,
+                    started_at: snapshots
+                        .last()
+                        .map(|s| s.timestamp.clone())
+                        .unwrap_or_default(),
+                    ended_at: snapshots[0].timestamp.clone(),
+                    git_branch: snapshots[0].git_branch.clone(),
+                    files,
+                    snapshot_count: snapshots.len(),
+                }
+            })
+            .collect())
+    }
+
+    /// Snapshots of changeset `id`, newest first.
+    pub fn changeset_snapshots(&self, id: i64) -> AppResult<Vec<Snapshot>> {
+        Ok(self
+            .changeset_groups(Some(id), 1)?
+            .pop()
+            .map(|(_, snapshots)| snapshots)
+            .unwrap_or_default())
+    }
+
+    /// Walks history newest first, grouping snapshots by changeset. A
+    /// changeset's snapshots are contiguous in time, so the walk stops at the
+    /// first changeset past `limit`.
+    fn changeset_groups(
+        &self,
+        only: Option<i64>,
+        limit: usize,
+    ) -> AppResult<Vec<(i64, Vec<Snapshot>)>> {
+        let mut groups: Vec<(i64, Vec<Snapshot>)> = Vec::new();
+        let mut positions: HashMap<i64, usize> = HashMap::new();
+        if limit == 0 {
+            return Ok(groups);
+        }
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for res in times
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .rev()
+        {
+            let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let Some(v) = table
+                .get(key.value().1)
+                .map_err(|e| AppError::Database(e.to_string()))?
+            else {
+                continue;
+            };
+            let Some(data) = deserialize_snapshot_data(key.value().1, v.value()) else {
+                continue;
+            };
+            let Some(changeset_id) = data.changeset_id else {
+                continue;
+            };
+            if only.is_some_and(|only| only != changeset_id) {
+                continue;
+            }
+            let pos = match positions.get(&changeset_id) {
+                Some(pos) => *pos,
+                None if groups.len() == limit => break,
+                None => {
+                    positions.insert(changeset_id, groups.len());
+                    groups.push((changeset_id, Vec::new()));
+                    groups.len() - 1
+                }
+            };
+            let branch = if let Some(bid) = data.git_branch_id {
+                Some(self.lookup_string(bid)?)
+            } else {
+                None
+            };
+            groups[pos].1.push(Snapshot {
+                id: data.id,
+                file_path: self.lookup_string(data.file_path_id)?,
+                timestamp: format_ms(data.timestamp),
+                content_hash: data.content_hash,
+                git_branch: branch,
+                session_id: data.session_id,
+                changeset_id: data.changeset_id,
+                commit_hash: data.commit_hash,
+                commit_message: data.commit_message,
+                event: data.event.resolve(|id| self.lookup_string(id))?,
+            });
+        }
+        Ok(groups)
+    }
+
+    /// When the earliest snapshot on record was taken (UTC epoch milliseconds).
+    pub fn first_snapshot_time(&self) -> AppResult<Option<i64>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(times
+            .first()
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .map(|(k, _)| k.value().0))
+    }
+
+    /// Snapshots taken between `from` and `to` (inclusive, UTC epoch
+    /// milliseconds), newest first.
+    pub fn history_between(&self, from: i64, to: i64) -> AppResult<Vec<Snapshot>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut history = Vec::new();
+        if from > to {
+            return Ok(history);
+        }
+        for res in times
+            .range((from, 0)..=(to, u64::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .rev()
+        {
+            let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let Some(v) = table
+                .get(key.value().1)
+                .map_err(|e| AppError::Database(e.to_string()))?
+            else {
+                continue;
+            };
+            let Some(data) = deserialize_snapshot_data(key.value().1, v.value()) else {
+                continue;
+            };
             let path = self.lookup_string(data.file_path_id)?;
             let branch = if let Some(bid) = data.git_branch_id {
                 Some(self.lookup_string(bid)?)
             } else {
                 None
             };
-            Ok(Some(Snapshot {
+            history.push(Snapshot {
                 id: data.id,
                 file_path: path,
-                timestamp: data.timestamp,
+                timestamp: format_ms(data.timestamp),
                 content_hash: data.content_hash,
                 git_branch: branch,
                 session_id: data.session_id,
+                changeset_id: data.changeset_id,
                 commit_hash: data.commit_hash,
                 commit_message: data.commit_message,
-            }))
-        } else {
-            Ok(None)
+                event: data.event.resolve(|id| self.lookup_string(id))?,
+            });
         }
+        Ok(history)
     }
 
-    pub fn get_latest_state(&self) -> AppResult<Vec<(String, String)>> {
-        let snapshots = self.get_all_unique_snapshots()?;
-        Ok(snapshots
-            .into_iter()
-            .map(|s| (s.file_path, s.content_hash))
-            .collect())
-    }
+    /// Snapshot counts per local calendar day between `from` and `to`
+    /// (inclusive, UTC epoch milliseconds), oldest day first. Days without
+    /// activity are included with a count of zero.
+    pub fn activity_between(&self, from: i64, to: i64) -> AppResult<Vec<(String, usize)>> {
+        use chrono::{Local, TimeZone};
+
+        let day_of = |ms: i64| {
+            Local
+                .timestamp_millis_opt(ms)
+                .earliest()
+                .map(|ts| ts.date_naive())
+        };
+        let (Some(first), Some(last)) = (day_of(from), day_of(to)) else {
+            return Ok(Vec::new());
+        };
+        if first > last {
+            return Ok(Vec::new());
+        }
 
-    pub fn get_state_at_timestamp(&self, timestamp: &str) -> AppResult<Vec<(String, String)>> {
         let read_txn = self
             .db
             .begin_read()
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let table = read_txn
-            .open_table(SNAPSHOTS)
+        let times = read_txn
+            .open_table(SNAPSHOT_TIMES)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let mut latest_per_file: std::collections::HashMap<u32, (String, u64)> =
-            std::collections::HashMap::new();
-        for res in table
-            .iter()
+        let mut counts: HashMap<chrono::NaiveDate, usize> = HashMap::new();
+        for res in times
+            .range((from, 0)..=(to, u64::MAX))
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
-            if data.timestamp.as_str() <= timestamp {
-                let entry = latest_per_file
-                    .entry(data.file_path_id)
-                    .or_insert((data.content_hash.clone(), id.value()));
-                if id.value() > entry.1 {
-                    *entry = (data.content_hash, id.value());
-                }
+            let (key, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if let Some(day) = day_of(key.value().0) {
+                *counts.entry(day).or_default() += 1;
             }
         }
-        let mut results = Vec::new();
-        for (pid, (hash, _)) in latest_per_file {
-            results.push((self.lookup_string(pid)?, hash));
-        }
-        Ok(results)
+        Ok(first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .map(|day| {
+                (
+                    day.format("%Y-%m-%d").to_string(),
+                    counts.get(&day).copied().unwrap_or(0),
+                )
+            })
+            .collect())
     }
 
-    pub fn create_session(&self, start_time: &str, git_branch: Option<&str>) -> AppResult<i64> {
+    pub fn create_session(&self, start_time: i64, git_branch: Option<&str>) -> AppResult<i64> {
         let id = self.next_id("session_id")?;
         let git_branch_id = if let Some(b) = git_branch {
             Some(self.intern_string(b)?)
@@ -1109,13 +2724,13 @@ impl Database {
                 .map_err(|e| AppError::Database(e.to_string()))?;
             let data = SessionData {
                 id: id as i64,
-                start_time: start_time.to_string(),
+                start_time,
                 end_time: None,
                 git_branch_id,
                 file_count: 0,
                 snapshot_count: 0,
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(SESSIONS, id, &data)?;
             table
                 .insert(id, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1129,7 +2744,7 @@ impl Database {
     pub fn close_session(
         &self,
         session_id: i64,
-        end_time: &str,
+        end_time: i64,
         file_count: usize,
         snapshot_count: usize,
     ) -> AppResult<()> {
@@ -1145,9 +2760,8 @@ impl Database {
                 .get(session_id as u64)
                 .map_err(|e| AppError::Database(e.to_string()))?
             {
-                let mut data: SessionData = bincode::deserialize(v.value())
-                    .map_err(|e| AppError::Internal(e.to_string()))?;
-                data.end_time = Some(end_time.to_string());
+                let mut data: SessionData = decode_record(SESSIONS, session_id as u64, v.value())?;
+                data.end_time = Some(end_time);
                 data.file_count = file_count;
                 data.snapshot_count = snapshot_count;
                 Some(data)
@@ -1155,8 +2769,7 @@ impl Database {
                 None
             };
             if let Some(d) = data {
-                let bytes =
-                    bincode::serialize(&d).map_err(|e| AppError::Internal(e.to_string()))?;
+                let bytes = encode_record(SESSIONS, session_id as u64, &d)?;
                 table
                     .insert(session_id as u64, &*bytes)
                     .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1168,6 +2781,46 @@ impl Database {
         Ok(())
     }
 
+    /// Closes the open session if nothing was saved in it since `idle_ms`
+    /// before `now`, ending it at its last save. Returns the closed id.
+    pub fn close_idle_session(&self, now: i64, idle_ms: i64) -> AppResult<Option<i64>> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let closed = {
+            let mut sessions = write_txn
+                .open_table(SESSIONS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let snapshots = write_txn
+                .open_table(SNAPSHOTS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let snapshot_times = write_txn
+                .open_table(SNAPSHOT_TIMES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            match open_session_in(&sessions)? {
+                Some(mut open) => {
+                    let last_active = session_last_active_in(&snapshots, &snapshot_times, &open)?;
+                    if now - last_active >= idle_ms {
+                        open.end_time = Some(last_active);
+                        let bytes = encode_record(SESSIONS, open.id as u64, &open)?;
+                        sessions
+                            .insert(open.id as u64, &*bytes)
+                            .map_err(|e| AppError::Database(e.to_string()))?;
+                        Some(open.id)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(closed)
+    }
+
     pub fn get_active_session(&self) -> AppResult<Option<Session>> {
         let read_txn = self
             .db
@@ -1181,9 +2834,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SessionData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SessionData = decode_record(SESSIONS, k.value(), v.value())?;
             if data.end_time.is_none() {
                 let branch = if let Some(bid) = data.git_branch_id {
                     Some(self.lookup_string(bid)?)
@@ -1192,8 +2844,8 @@ impl Database {
                 };
                 active = Some(Session {
                     id: data.id,
-                    start_time: data.start_time,
-                    end_time: data.end_time,
+                    start_time: format_ms(data.start_time),
+                    end_time: data.end_time.map(format_ms),
                     git_branch: branch,
                     file_count: data.file_count,
                     snapshot_count: data.snapshot_count,
@@ -1203,6 +2855,49 @@ impl Database {
         Ok(active)
     }
 
+    /// Every session as a span from its start to its last save, labelled
+    /// with `project`.
+    pub fn session_spans(&self, project: &str) -> AppResult<Vec<SessionSpan>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let sessions = read_txn
+            .open_table(SESSIONS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let snapshots = read_txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let snapshot_times = read_txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut spans = Vec::new();
+        for res in sessions
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SessionData = decode_record(SESSIONS, k.value(), v.value())?;
+            let end = match data.end_time {
+                Some(end) => end,
+                None => session_last_active_in(&snapshots, &snapshot_times, &data)?,
+            };
+            let branch = match data.git_branch_id {
+                Some(bid) => Some(self.lookup_string(bid)?),
+                None => None,
+            };
+            spans.push(SessionSpan {
+                project: project.to_string(),
+                branch,
+                start: data.start_time,
+                end,
+                file_count: data.file_count,
+                snapshot_count: data.snapshot_count,
+            });
+        }
+        Ok(spans)
+    }
+
     pub fn list_sessions(&self, limit: usize) -> AppResult<Vec<Session>> {
         let read_txn = self
             .db
@@ -1211,14 +2906,22 @@ impl Database {
         let table = read_txn
             .open_table(SESSIONS)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let mut sessions = Vec::new();
+        let mut records = Vec::new();
         for res in table
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SessionData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            records.push(decode_record::<SessionData>(
+                SESSIONS,
+                k.value(),
+                v.value(),
+            )?);
+        }
+        records.sort_by_key(|data| std::cmp::Reverse(data.start_time));
+        records.truncate(limit);
+        let mut sessions = Vec::new();
+        for data in records {
             let branch = if let Some(bid) = data.git_branch_id {
                 Some(self.lookup_string(bid)?)
             } else {
@@ -1226,15 +2929,13 @@ impl Database {
             };
             sessions.push(Session {
                 id: data.id,
-                start_time: data.start_time,
-                end_time: data.end_time,
+                start_time: format_ms(data.start_time),
+                end_time: data.end_time.map(format_ms),
                 git_branch: branch,
                 file_count: data.file_count,
                 snapshot_count: data.snapshot_count,
             });
         }
-        sessions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
-        sessions.truncate(limit);
         Ok(sessions)
     }
 
@@ -1252,7 +2953,7 @@ impl Database {
                 hash: hash.to_string(),
                 kind_id,
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(CHUNKS, hash, &data)?;
             table
                 .insert(hash, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1277,9 +2978,17 @@ impl Database {
             let mut table = write_txn
                 .open_table(SNAPSHOT_CHUNKS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
-            table
-                .insert((snapshot_id as u64, position as u32), chunk_hash)
+            let mut chunk_refs = write_txn
+                .open_table(CHUNK_REFS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
+            let replaced = table
+                .insert((snapshot_id as u64, position as u32), chunk_hash)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .map(|old| old.value().to_string());
+            if let Some(old) = replaced {
+                release_chunk_ref_in(&mut chunk_refs, &old)?;
+            }
+            retain_chunk_ref_in(&mut chunk_refs, chunk_hash)?;
         }
         write_txn
             .commit()
@@ -1318,7 +3027,7 @@ impl Database {
                 end_byte: symbol.end_byte,
                 parent_id: symbol.parent_id,
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(SYMBOLS, id, &data)?;
             table
                 .insert(id, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1360,7 +3069,7 @@ impl Database {
                 delta_kind: kind_str.to_string(),
                 structural_hash: delta.structural_hash.clone(),
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(SYMBOL_DELTAS, id, &data)?;
             table
                 .insert(id, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1382,12 +3091,8 @@ impl Database {
         let index = read_txn
             .open_table(STRING_INDEX)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let target_id = match index
-            .get(symbol_name)
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            Some(id) => id.value(),
-            None => return Ok(Vec::new()),
+        let Some(target_id) = find_string_in(&index, symbol_name)? else {
+            return Ok(Vec::new());
         };
         let table = read_txn
             .open_table(SYMBOL_DELTAS)
@@ -1397,9 +3102,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: DeltaData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: DeltaData = decode_record(SYMBOL_DELTAS, k.value(), v.value())?;
             if data.symbol_name_id == target_id || data.new_name_id == Some(target_id) {
                 let kind = match data.delta_kind.as_str() {
                     "Added" => crate::models::RecordKind::Added,
@@ -1448,7 +3152,7 @@ impl Database {
                 start_line: reference.start_line,
                 start_byte: reference.start_byte,
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(SYMBOL_REFERENCES, id, &data)?;
             table
                 .insert(id, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -1504,12 +3208,8 @@ impl Database {
         let index = read_txn
             .open_table(STRING_INDEX)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let target_id = match index
-            .get(symbol_name)
-            .map_err(|e| AppError::Database(e.to_string()))?
-        {
-            Some(id) => id.value(),
-            None => return Ok(Vec::new()),
+        let Some(target_id) = find_string_in(&index, symbol_name)? else {
+            return Ok(Vec::new());
         };
         let sym_table = read_txn
             .open_table(SYMBOLS)
@@ -1524,8 +3224,7 @@ impl Database {
             .map_err(|e| AppError::Database(e.to_string()))?
         {
             let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SymbolData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let data: SymbolData = decode_record(SYMBOLS, id.value(), v.value())?;
             if data.name_id == target_id && id.value() > max_sym_id {
                 max_sym_id = id.value();
                 latest_hash = data.structural_hash;
@@ -1536,16 +3235,15 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let sym_data: SymbolData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let sym_data: SymbolData = decode_record(SYMBOLS, k.value(), v.value())?;
             if sym_data.structural_hash == latest_hash || sym_data.name_id == target_id {
                 if let Some(sv) = snap_table
                     .get(sym_data.snapshot_id as u64)
                     .map_err(|e| AppError::Database(e.to_string()))?
                 {
-                    let snap_data: SnapshotData = bincode::deserialize(sv.value())
-                        .map_err(|e| AppError::Internal(e.to_string()))?;
+                    let snap_data: SnapshotData =
+                        decode_record(SNAPSHOTS, sym_data.snapshot_id as u64, sv.value())?;
                     let path = self.lookup_string(snap_data.file_path_id)?;
                     let branch = if let Some(bid) = snap_data.git_branch_id {
                         Some(self.lookup_string(bid)?)
@@ -1555,12 +3253,14 @@ impl Database {
                     let snap = Snapshot {
                         id: snap_data.id,
                         file_path: path,
-                        timestamp: snap_data.timestamp,
+                        timestamp: format_ms(snap_data.timestamp),
                         content_hash: snap_data.content_hash,
                         git_branch: branch,
                         session_id: snap_data.session_id,
+                        changeset_id: snap_data.changeset_id,
                         commit_hash: snap_data.commit_hash,
                         commit_message: snap_data.commit_message,
+                        event: snap_data.event.resolve(|id| self.lookup_string(id))?,
                     };
                     let name = self.lookup_string(sym_data.name_id)?;
                     let kind = self.lookup_string(sym_data.kind_id)?;
@@ -1583,12 +3283,15 @@ impl Database {
                         end_byte: sym_data.end_byte,
                         parent_id: sym_data.parent_id,
                     };
-                    results.push((snap, sym));
+                    results.push((snap_data.timestamp, snap, sym));
                 }
             }
         }
-        results.sort_by(|a, b| b.0.timestamp.cmp(&a.0.timestamp));
-        Ok(results)
+        results.sort_by_key(|(ts, _, _)| std::cmp::Reverse(*ts));
+        Ok(results
+            .into_iter()
+            .map(|(_, snap, sym)| (snap, sym))
+            .collect())
     }
 
     pub fn get_chunks_for_hash(&self, content_hash: &str) -> AppResult<Vec<String>> {
@@ -1608,8 +3311,7 @@ impl Database {
             .map_err(|e| AppError::Database(e.to_string()))?
         {
             let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, id.value(), v.value())?;
             if data.content_hash == content_hash {
                 snapshot_id = Some(id.value());
                 break;
@@ -1633,6 +3335,27 @@ impl Database {
         }
     }
 
+    /// Chunk hashes of one snapshot, in file order.
+    pub fn get_snapshot_chunks(&self, snapshot_id: i64) -> AppResult<Vec<String>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let sc_table = read_txn
+            .open_table(SNAPSHOT_CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let sid = snapshot_id as u64;
+        let mut chunks = Vec::new();
+        for res in sc_table
+            .range((sid, 0)..=(sid, u32::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            chunks.push(v.value().to_string());
+        }
+        Ok(chunks)
+    }
+
     pub fn get_symbols_for_snapshot(&self, snapshot_id: i64) -> AppResult<Vec<SemanticSymbol>> {
         let read_txn = self
             .db
@@ -1646,9 +3369,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SymbolData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SymbolData = decode_record(SYMBOLS, k.value(), v.value())?;
             if data.snapshot_id == snapshot_id {
                 let name = self.lookup_string(data.name_id)?;
                 let kind = self.lookup_string(data.kind_id)?;
@@ -1691,9 +3413,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SymbolData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SymbolData = decode_record(SYMBOLS, k.value(), v.value())?;
             let name = self.lookup_string(data.name_id)?;
             if name.to_lowercase().contains(&query_lower) {
                 let kind = self.lookup_string(data.kind_id)?;
@@ -1737,9 +3458,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             paths.insert(data.file_path_id);
         }
         Ok(paths.len())
@@ -1769,9 +3489,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             *files.entry(data.file_path_id).or_insert(0) += 1;
         }
         let mut results = Vec::new();
@@ -1796,9 +3515,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             if let Some(bid) = data.git_branch_id {
                 *branches.entry(bid).or_insert(0) += 1;
             }
@@ -1825,9 +3543,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             let path = self.lookup_string(data.file_path_id)?;
             let ext = std::path::Path::new(&path)
                 .extension()
@@ -1859,9 +3576,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             if let Some(ch) = data.commit_hash {
                 *snapshot_counts.entry(ch).or_insert(0) += 1;
             }
@@ -1871,9 +3587,8 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: GitCommitData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: GitCommitData = decode_record(GIT_COMMITS, k.value(), v.value())?;
             let count = *snapshot_counts.get(&data.hash).unwrap_or(&0);
             results.push((data.hash, data.message, data.author, data.timestamp, count));
         }
@@ -1896,8 +3611,7 @@ impl Database {
             .get(hash)
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let data: GitCommitData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let data: GitCommitData = decode_record(GIT_COMMITS, hash, v.value())?;
             Ok(Some((data.hash, data.message, data.author, data.timestamp)))
         } else {
             Ok(None)
@@ -1917,18 +3631,22 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: SnapshotData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: SnapshotData = decode_record(SNAPSHOTS, k.value(), v.value())?;
             if data.commit_hash.as_deref() == Some(hash) {
                 let path = self.lookup_string(data.file_path_id)?;
-                files.push((path, data.content_hash, data.timestamp));
+                files.push((data.timestamp, path, data.content_hash));
             }
         }
-        files.sort_by(|a, b| a.2.cmp(&b.2));
-        Ok(files)
+        files.sort_by_key(|(ts, _, _)| *ts);
+        Ok(files
+            .into_iter()
+            .map(|(ts, path, hash)| (path, hash, format_ms(ts)))
+            .collect())
     }
 
+    /// Hash, timestamp and description of the checkpoint whose hash starts
+    /// with `hash_query`.
     pub fn get_checkpoint_by_hash(
         &self,
         hash_query: &str,
@@ -1947,23 +3665,52 @@ impl Database {
         {
             let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
             if k.value().to_lowercase().starts_with(&query) {
-                let data: CheckpointData = bincode::deserialize(v.value())
-                    .map_err(|e| AppError::Internal(e.to_string()))?;
-                return Ok(Some((data.timestamp, data.file_states, data.description)));
+                let data: CheckpointData = decode_record(CHECKPOINTS, k.value(), v.value())?;
+                return Ok(Some((
+                    data.hash,
+                    format_ms(data.timestamp),
+                    data.description,
+                )));
             }
         }
         Ok(None)
     }
 
+    /// `(path, content hash)` of every file in a checkpoint, by path.
+    pub fn checkpoint_files(&self, hash: &str) -> AppResult<Vec<(String, String)>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = read_txn
+            .open_table(CHECKPOINT_FILES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut files = Vec::new();
+        for res in table
+            .range((hash, 0)..=(hash, u32::MAX))
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            files.push((self.lookup_string(k.value().1)?, v.value().to_string()));
+        }
+        files.sort();
+        Ok(files)
+    }
+
     pub fn save_checkpoint(
         &self,
-        timestamp: &str,
+        timestamp: i64,
         description: Option<&str>,
-        file_states_json: &str,
+        files: &[(String, String)],
     ) -> AppResult<String> {
         let mut hasher = blake3::Hasher::new();
-        hasher.update(timestamp.as_bytes());
-        hasher.update(file_states_json.as_bytes());
+        hasher.update(&timestamp.to_le_bytes());
+        for (path, hash) in files {
+            hasher.update(path.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(hash.as_bytes());
+            hasher.update(&[0]);
+        }
         if let Some(d) = description {
             hasher.update(d.as_bytes());
         }
@@ -1973,16 +3720,32 @@ impl Database {
             .begin_write()
             .map_err(|e| AppError::Database(e.to_string()))?;
         {
+            let mut meta = write_txn
+                .open_table(METADATA)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut strings = write_txn
+                .open_table(STRINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut index = write_txn
+                .open_table(STRING_INDEX)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut rows = write_txn
+                .open_table(CHECKPOINT_FILES)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            for (path, content_hash) in files {
+                let path_id = intern_string_in(&mut meta, &mut strings, &mut index, path)?;
+                rows.insert((hash.as_str(), path_id), content_hash.as_str())
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+            }
             let mut table = write_txn
                 .open_table(CHECKPOINTS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
             let data = CheckpointData {
                 hash: hash.clone(),
-                timestamp: timestamp.to_string(),
+                timestamp,
                 description: description.map(|s| s.to_string()),
-                file_states: file_states_json.to_string(),
             };
-            let bytes = bincode::serialize(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+            let bytes = encode_record(CHECKPOINTS, hash.as_str(), &data)?;
             table
                 .insert(hash.as_str(), &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
@@ -2006,13 +3769,15 @@ impl Database {
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            let data: CheckpointData =
-                bincode::deserialize(v.value()).map_err(|e| AppError::Internal(e.to_string()))?;
-            results.push((data.hash, data.timestamp, data.description));
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: CheckpointData = decode_record(CHECKPOINTS, k.value(), v.value())?;
+            results.push((data.timestamp, data.hash, data.description));
         }
-        results.sort_by(|a, b| b.1.cmp(&a.1));
-        Ok(results)
+        results.sort_by_key(|(ts, _, _)| std::cmp::Reverse(*ts));
+        Ok(results
+            .into_iter()
+            .map(|(ts, hash, description)| (hash, format_ms(ts), description))
+            .collect())
     }
 
     pub fn delete_checkpoint(&self, hash: &str) -> AppResult<bool> {
@@ -2029,6 +3794,16 @@ impl Database {
                 .remove(hash)
                 .map_err(|e| AppError::Database(e.to_string()))?
                 .is_some();
+            write_txn
+                .open_table(CHECKPOINT_FILES)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .retain_in((hash, 0)..=(hash, u32::MAX), |_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            write_txn
+                .open_table(PINS)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .remove(hash)
+                .map_err(|e| AppError::Database(e.to_string()))?;
         }
         write_txn
             .commit()
@@ -2036,32 +3811,24 @@ impl Database {
         Ok(found)
     }
 
-    pub fn update_chunk_trigrams(&self, chunk_hash: &str, content: &[u8]) -> AppResult<()> {
-        let mut trigrams = HashSet::new();
-        if content.len() >= 3 {
-            for i in 0..content.len() - 2 {
-                let trigram = (content[i] as u64) << 16
-                    | (content[i + 1] as u64) << 8
-                    | (content[i + 2] as u64);
-                trigrams.insert(trigram);
-            }
-        }
-        let mut bloom: u64 = 0;
-        for t in trigrams {
-            let h = blake3::hash(&t.to_be_bytes());
-            let bit = (h.as_bytes()[0] as u64) % 64;
-            bloom |= 1 << bit;
-        }
+    /// Pins `hash` under `label`, replacing any earlier label.
+    pub fn save_pin(&self, hash: &str, kind: PinKind, label: &str, created: i64) -> AppResult<()> {
         let write_txn = self
             .db
             .begin_write()
             .map_err(|e| AppError::Database(e.to_string()))?;
         {
             let mut table = write_txn
-                .open_table(CHUNK_TRIGRAMS)
+                .open_table(PINS)
                 .map_err(|e| AppError::Database(e.to_string()))?;
+            let data = PinData {
+                label: label.to_string(),
+                checkpoint: kind == PinKind::Checkpoint,
+                created,
+            };
+            let bytes = encode_record(PINS, hash, &data)?;
             table
-                .insert(chunk_hash, bloom)
+                .insert(hash, &*bytes)
                 .map_err(|e| AppError::Database(e.to_string()))?;
         }
         write_txn
@@ -2070,36 +3837,149 @@ impl Database {
         Ok(())
     }
 
-    pub fn filter_chunks_by_trigrams(&self, query: &str) -> AppResult<HashSet<String>> {
-        if query.len() < 3 {
-            return Ok(HashSet::new());
-        }
-        let mut query_bloom: u64 = 0;
-        let bytes = query.as_bytes();
-        for i in 0..bytes.len() - 2 {
-            let trigram =
-                (bytes[i] as u64) << 16 | (bytes[i + 1] as u64) << 8 | (bytes[i + 2] as u64);
-            let h = blake3::hash(&trigram.to_be_bytes());
-            let bit = (h.as_bytes()[0] as u64) % 64;
-            query_bloom |= 1 << bit;
+    pub fn delete_pin(&self, hash: &str) -> AppResult<bool> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let found;
+        {
+            let mut table = write_txn
+                .open_table(PINS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            found = table
+                .remove(hash)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .is_some();
         }
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(found)
+    }
+
+    /// Every pin, newest first.
+    pub fn list_pins(&self) -> AppResult<Vec<Pin>> {
         let read_txn = self
             .db
             .begin_read()
             .map_err(|e| AppError::Database(e.to_string()))?;
         let table = read_txn
-            .open_table(CHUNK_TRIGRAMS)
+            .open_table(PINS)
             .map_err(|e| AppError::Database(e.to_string()))?;
-        let mut matches = HashSet::new();
+        let mut pins = Vec::new();
         for res in table
             .iter()
             .map_err(|e| AppError::Database(e.to_string()))?
         {
-            let (hash, bloom) = res.map_err(|e| AppError::Database(e.to_string()))?;
-            if (bloom.value() & query_bloom) == query_bloom {
-                matches.insert(hash.value().to_string());
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            let data: PinData = decode_record(PINS, k.value(), v.value())?;
+            pins.push((data.created, k.value().to_string(), data));
+        }
+        pins.sort_by_key(|(created, _, _)| std::cmp::Reverse(*created));
+        Ok(pins
+            .into_iter()
+            .map(|(created, hash, data)| Pin {
+                hash,
+                label: data.label,
+                kind: if data.checkpoint {
+                    PinKind::Checkpoint
+                } else {
+                    PinKind::Snapshot
+                },
+                created_at: format_ms(created),
+            })
+            .collect())
+    }
+
+    pub fn update_chunk_trigrams(&self, chunk_hash: &str, content: &[u8]) -> AppResult<()> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        {
+            let mut postings = write_txn
+                .open_multimap_table(TRIGRAM_POSTINGS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            let mut markers = write_txn
+                .open_table(CHUNK_TRIGRAMS)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            index_chunk_trigrams_in(&mut postings, &mut markers, chunk_hash, content)?;
+        }
+        write_txn
+            .commit()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Posting lists for the given trigrams.
+    pub fn trigram_postings(
+        &self,
+        trigrams: &HashSet<u32>,
+    ) -> AppResult<HashMap<u32, HashSet<String>>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let postings = read_txn
+            .open_multimap_table(TRIGRAM_POSTINGS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let blind = crypto::trigram_blinder();
+        let mut result = HashMap::new();
+        for t in trigrams {
+            let mut chunks = HashSet::new();
+            for v in postings
+                .get(blind(*t))
+                .map_err(|e| AppError::Database(e.to_string()))?
+            {
+                let v = v.map_err(|e| AppError::Database(e.to_string()))?;
+                chunks.insert(v.value().to_string());
+            }
+            result.insert(*t, chunks);
+        }
+        Ok(result)
+    }
+
+    /// Chunks already present in the trigram index.
+    pub fn indexed_chunks(&self) -> AppResult<HashSet<String>> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let markers = read_txn
+            .open_table(CHUNK_TRIGRAMS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut chunks = HashSet::new();
+        for res in markers
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            chunks.insert(k.value().to_string());
+        }
+        Ok(chunks)
+    }
+
+    /// Chunks that still need to be added to the trigram index.
+    pub fn unindexed_chunks(&self) -> AppResult<Vec<String>> {
+        let indexed = self.indexed_chunks()?;
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let chunks = read_txn
+            .open_table(CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut missing = Vec::new();
+        for res in chunks
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if !indexed.contains(k.value()) {
+                missing.push(k.value().to_string());
             }
         }
-        Ok(matches)
+        Ok(missing)
     }
 }
@@ -0,0 +1,40 @@
+//! Which files of a project get history, shared by the watcher and the git
+//! import so both leave out the same ones.
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Path components that are never tracked.
+const IGNORED_COMPONENTS: [&str; 5] = ["target", ".git", "node_modules", ".DS_Store", ".mnemosyne"];
+
+/// The global `~/.mnemosyne/.mnemignore`, with the project's
+/// `.mnemosyneignore` on top when `use_mnemosyneignore` is set.
+pub fn mnemignore(root: &Path, config: &Config) -> AppResult<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(home) = dirs::home_dir() {
+        let global_ignore = home.join(".mnemosyne").join(".mnemignore");
+        if global_ignore.exists() {
+            builder.add(global_ignore);
+        }
+    }
+    let project_ignore = root.join(".mnemosyneignore");
+    if config.use_mnemosyneignore && project_ignore.exists() {
+        builder.add(project_ignore);
+    }
+    builder
+        .build()
+        .map_err(|e| AppError::Config(format!("Mnemignore build failed: {}", e)))
+}
+
+/// Whether `path` in the project at `root` is left out of history. Matches
+/// whole path components, so `targets/` is still tracked.
+pub fn is_ignored(root: &Path, path: &Path, mnemignore: Option<&Gitignore>) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let has_ignored_component = relative.components().any(|c| {
+        let s = c.as_os_str().to_string_lossy();
+        IGNORED_COMPONENTS.contains(&s.as_ref())
+    });
+    has_ignored_component || mnemignore.is_some_and(|mi| mi.matched(relative, false).is_ignore())
+}
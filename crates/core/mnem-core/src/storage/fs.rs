@@ -1,8 +1,22 @@
+use crate::crypto;
 use crate::error::{AppError, AppResult};
+use crate::storage::cdc;
+use crate::storage::tiered::dictionary::DICT_INPUT_MAX;
+use crate::storage::tiered::{
+    HotLayer, StorageLayer, TierConfig, TierOccupancy, TieredStore, persist_noclobber,
+};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
+/// Written once every object in the store is sealed and bound to its id.
+/// Stores sealed before seals were bound carry an older `sealed` marker.
+const SEALED_MARKER: &str = "sealed-bound";
+
+/// Objects read to train a dictionary, and the fewest worth training on.
+const DICT_SAMPLES: usize = 2000;
+const MIN_DICT_SAMPLES: usize = 64;
+
 /// BLAKE3 hashes are exactly 64 hex characters (256 bits).
 /// Validates that a hash string is well-formed before using it as a filesystem path,
 /// preventing path traversal attacks (audit 1.1).
@@ -15,7 +29,7 @@ fn validate_hash(hash: &str) -> AppResult<()> {
 
 /// Heuristic check: if the data starts with known magic bytes of compressed formats,
 /// skip re-compression to avoid wasted CPU (audit 3.3).
-fn is_already_compressed(data: &[u8]) -> bool {
+pub(crate) fn is_already_compressed(data: &[u8]) -> bool {
     if data.len() < 4 {
         return false;
     }
@@ -50,17 +64,19 @@ fn is_already_compressed(data: &[u8]) -> bool {
     false
 }
 
+/// Content-addressed object store. Objects are placed in hot, warm and cold
+/// tiers by age; see [`tiered`](super::tiered).
 #[derive(Clone)]
 pub struct CasStorage {
     pub base_dir: PathBuf,
+    tiers: TieredStore,
 }
 
 impl CasStorage {
     pub fn new(base_dir: PathBuf) -> AppResult<Self> {
-        let objects_dir = base_dir.join("objects");
         let temp_dir = base_dir.join("tmp");
-        fs::create_dir_all(&objects_dir).map_err(AppError::IoGeneric)?;
         fs::create_dir_all(&temp_dir).map_err(AppError::IoGeneric)?;
+        let tiers = TieredStore::new(&base_dir, &temp_dir)?;
 
         // Security: Set directory permissions to 700 (Unix only)
         #[cfg(unix)]
@@ -78,21 +94,15 @@ impl CasStorage {
             }
         }
 
-        Ok(Self { base_dir })
-    }
-
-    /// Build the sharded object path: `objects/ab/cdef...` (audit 3.5).
-    /// Uses 2-char prefix directories to avoid flat directory performance issues.
-    fn object_path(&self, hash: &str) -> PathBuf {
-        let (prefix, rest) = hash.split_at(2);
-        self.base_dir.join("objects").join(prefix).join(rest)
+        Ok(Self { base_dir, tiers })
     }
 
     /// Compute hash of a file without writing anything (for dedup-first pattern, audit 5.4).
-    /// Returns the BLAKE3 hex hash of the file's raw content.
+    /// Returns the BLAKE3 hex hash of the file's raw content, keyed while
+    /// history is encrypted.
     pub fn compute_hash(&self, input_path: &Path) -> AppResult<String> {
         let mut file = fs::File::open(input_path).map_err(AppError::IoGeneric)?;
-        let mut hasher = blake3::Hasher::new();
+        let mut hasher = crypto::object_hasher();
         let mut buffer = [0u8; 64 * 1024];
         loop {
             let count = file.read(&mut buffer).map_err(AppError::IoGeneric)?;
@@ -104,8 +114,12 @@ impl CasStorage {
         Ok(hasher.finalize().to_hex().to_string())
     }
 
+    /// Where the loose object is stored, or where it would be written.
     pub fn get_path(&self, hash: &str) -> PathBuf {
-        self.object_path(hash)
+        self.tiers
+            .locate(hash)
+            .map(|(_, path)| path)
+            .unwrap_or_else(|| self.tiers.hot().dir().path(hash))
     }
 
     /// Streaming write: Reads from input, compresses, hashes, and writes atomically.
@@ -119,18 +133,14 @@ impl CasStorage {
             .read(&mut first_chunk)
             .map_err(AppError::IoGeneric)?;
         let compression_level =
-            if !enable_compression || is_already_compressed(&first_chunk[..first_count]) {
-                0
-            } else {
-                3
-            };
+            HotLayer::compression_level(&first_chunk[..first_count], enable_compression);
 
         // Create a temporary file in dedicated tmp dir
         let temp_dir = self.base_dir.join("tmp");
         let mut temp_file =
             tempfile::NamedTempFile::new_in(&temp_dir).map_err(AppError::IoGeneric)?;
 
-        let mut hasher = blake3::Hasher::new();
+        let mut hasher = crypto::object_hasher();
         let mut encoder = zstd::stream::write::Encoder::new(&mut temp_file, compression_level)
             .map_err(AppError::IoGeneric)?;
 
@@ -156,7 +166,16 @@ impl CasStorage {
         encoder.finish().map_err(AppError::IoGeneric)?;
 
         let hash = hasher.finalize().to_hex().to_string();
-        let target_path = self.object_path(&hash);
+        if self.tiers.exists(&hash) {
+            return Ok(hash);
+        }
+        if crypto::is_unlocked() {
+            // Sealing needs the whole encoded object
+            let encoded = fs::read(temp_file.path()).map_err(AppError::IoGeneric)?;
+            self.tiers.hot().dir().persist(&hash, &encoded, None)?;
+            return Ok(hash);
+        }
+        let target_path = self.tiers.hot().dir().path(&hash);
 
         // Ensure the shard directory exists
         if let Some(parent) = target_path.parent() {
@@ -164,145 +183,126 @@ impl CasStorage {
         }
 
         // Atomic rename, avoiding TOCTOU race (audit 5.3)
-        match temp_file.persist_noclobber(&target_path) {
-            Ok(_) => {}
-            Err(e) if e.error.kind() == std::io::ErrorKind::AlreadyExists => {
-                // Content-addressed: identical hash means identical content, safe to skip
-            }
-            Err(e) => return Err(AppError::IoGeneric(e.error)),
-        }
+        persist_noclobber(temp_file, &target_path)?;
 
         Ok(hash)
     }
 
-    /// Write content from a byte slice. Uses the same sharding and atomic write strategy.
+    /// Write content from a byte slice into the hot tier, unless some tier
+    /// already holds it.
     pub fn write(&self, content: &[u8], enable_compression: bool) -> AppResult<String> {
-        let hash = blake3::hash(content).to_hex().to_string();
-        let object_path = self.object_path(&hash);
-
-        if let Some(parent) = object_path.parent() {
-            fs::create_dir_all(parent).map_err(AppError::IoGeneric)?;
-        }
-
-        // --- Semantic Delta Compression Logic ---
-        // Optimization: Use Zstd level 3.
-        // Level 3 is the sweet spot for speed and ratio.
-        // Higher levels (like 9) are too slow for the development loop.
-        let compression_level = if !enable_compression || is_already_compressed(content) {
-            0
-        } else {
-            3
-        };
-
-        // Atomic write with persist_noclobber to avoid TOCTOU race
-        let compressed = zstd::stream::encode_all(std::io::Cursor::new(content), compression_level)
-            .map_err(AppError::IoGeneric)?;
-
-        let temp_dir = self.base_dir.join("tmp");
-        let temp = tempfile::NamedTempFile::new_in(temp_dir).map_err(AppError::IoGeneric)?;
-        fs::write(temp.path(), &compressed).map_err(AppError::IoGeneric)?;
+        let hash = crypto::object_id(content);
+        self.write_as(&hash, content, enable_compression)?;
+        Ok(hash)
+    }
 
-        match temp.persist_noclobber(&object_path) {
-            Ok(_) => {}
-            Err(e) if e.error.kind() == std::io::ErrorKind::AlreadyExists => {
-                // CAS: identical hash = identical content, safe to skip
-            }
-            Err(e) => return Err(AppError::IoGeneric(e.error)),
+    /// Write content under an id computed elsewhere, e.g. by the store a
+    /// bundle was exported from. The caller has checked that `hash` names
+    /// `content`.
+    pub fn write_as(&self, hash: &str, content: &[u8], enable_compression: bool) -> AppResult<()> {
+        validate_hash(hash)?;
+        if self.tiers.exists(hash) {
+            return Ok(());
         }
 
-        Ok(hash)
+        let hot = self.tiers.hot();
+        let compressed = hot.encode_with(content, enable_compression)?;
+        hot.dir().persist(hash, &compressed, None)
     }
 
-    /// Read and decompress an object by its hash.
+    /// Read and decompress an object by its hash, promoting it to the hot tier.
     /// Validates hash format to prevent path traversal (audit 1.1).
     pub fn read(&self, hash: &str) -> AppResult<Vec<u8>> {
         validate_hash(hash)?;
-        let object_path = self.object_path(hash);
-
-        // Fallback: check legacy flat path for migration compatibility
-        let actual_path = if object_path.exists() {
-            object_path
-        } else {
-            let legacy = self.base_dir.join("objects").join(hash);
-            if legacy.exists() { legacy } else { object_path }
-        };
-
-        let raw = fs::read(&actual_path).map_err(|e| {
-            AppError::IoGeneric(std::io::Error::new(
-                e.kind(),
-                format!("Failed to read object {:?}: {}", actual_path, e),
-            ))
-        })?;
+        self.tiers.read(hash)
+    }
 
-        // Decompress with size limit to prevent decompression bombs
-        const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024; // 256 MB
-
-        match zstd::stream::Decoder::new(std::io::Cursor::new(&raw)) {
-            Ok(mut decoder) => {
-                let mut decompressed = Vec::new();
-                let mut buf = [0u8; 64 * 1024];
-                loop {
-                    let n = decoder
-                        .read(&mut buf)
-                        .map_err(|_| AppError::Internal("Decompression failed".into()))?;
-                    if n == 0 {
-                        break;
-                    }
-                    if decompressed.len() + n > MAX_DECOMPRESSED_SIZE {
-                        return Err(AppError::Internal(format!(
-                            "Decompressed size exceeds {} MB limit",
-                            MAX_DECOMPRESSED_SIZE / (1024 * 1024)
-                        )));
-                    }
-                    decompressed.extend_from_slice(&buf[..n]);
-                }
-                Ok(decompressed)
-            }
-            Err(_) => Ok(raw), // Fallback: assume it was uncompressed (legacy file)
-        }
+    /// Read an object without promoting it, for scans over the whole store.
+    pub fn peek(&self, hash: &str) -> AppResult<Vec<u8>> {
+        validate_hash(hash)?;
+        self.tiers.peek(hash)
     }
 
     /// Check if an object exists by hash.
     pub fn exists(&self, hash: &str) -> bool {
-        if validate_hash(hash).is_err() {
-            return false;
-        }
-        let sharded = self.object_path(hash);
-        if sharded.exists() {
-            return true;
-        }
-        // Fallback: check legacy flat path
-        self.base_dir.join("objects").join(hash).exists()
+        validate_hash(hash).is_ok() && self.tiers.exists(hash)
     }
 
-    /// Get the size of an object by hash.
+    /// Get the size of an object by hash, as stored on disk.
     pub fn get_size(&self, hash: &str) -> AppResult<u64> {
         validate_hash(hash)?;
-        let path = self.object_path(hash);
-        if path.exists() {
-            return Ok(fs::metadata(&path).map_err(AppError::IoGeneric)?.len());
-        }
-        let legacy = self.base_dir.join("objects").join(hash);
-        if legacy.exists() {
-            return Ok(fs::metadata(&legacy).map_err(AppError::IoGeneric)?.len());
-        }
-        Err(AppError::NotFound(format!("Object {} not found", hash)))
+        self.tiers.get_size(hash)
     }
 
     /// Delete an object by hash (used by GC to clean orphan files, audit 3.2).
     pub fn delete(&self, hash: &str) -> AppResult<()> {
         validate_hash(hash)?;
-        let sharded = self.object_path(hash);
-        if sharded.exists() {
-            fs::remove_file(&sharded).map_err(AppError::IoGeneric)?;
-        } else {
-            // Fallback: try legacy flat path
-            let legacy = self.base_dir.join("objects").join(hash);
-            if legacy.exists() {
-                fs::remove_file(&legacy).map_err(AppError::IoGeneric)?;
+        self.tiers.delete(hash)
+    }
+
+    /// Hashes of every stored object, in every tier and the legacy layout.
+    pub fn list_objects(&self) -> AppResult<Vec<String>> {
+        self.tiers.list()
+    }
+
+    /// Moves objects between tiers by age, and legacy objects into the tiers.
+    /// Returns the number of objects moved.
+    pub fn migrate(&self, config: &TierConfig) -> AppResult<usize> {
+        self.tiers.migrate(config)
+    }
+
+    /// Gathers small cold objects into pack files. Returns the number of
+    /// objects packed.
+    pub fn repack(&self, config: &TierConfig) -> AppResult<usize> {
+        self.tiers.repack(config.cold_compression_level)
+    }
+
+    /// Trains a new compression dictionary from a sample of small text
+    /// objects, when there is none yet or the latest has aged out. Returns the
+    /// new version; `None` when training was not due or too few objects
+    /// qualify.
+    pub fn train_dictionary(&self) -> AppResult<Option<u32>> {
+        let dicts = self.tiers.dicts();
+        if !dicts.needs_training() {
+            return Ok(None);
+        }
+        // Hashes are uniformly spread, so every n-th one is a fair sample
+        let hashes = self.list_objects()?;
+        let step = (hashes.len() / DICT_SAMPLES).max(1);
+        let mut samples = Vec::new();
+        for hash in hashes.iter().step_by(step) {
+            let Ok(content) = self.peek(hash) else {
+                continue;
+            };
+            if content.len() <= DICT_INPUT_MAX && !cdc::is_binary(&content) {
+                samples.push(content);
             }
         }
-        Ok(())
+        if samples.len() < MIN_DICT_SAMPLES {
+            return Ok(None);
+        }
+        dicts.train(&samples).map(Some)
+    }
+
+    /// Seals objects written before encryption was enabled, or sealed before
+    /// seals were bound to their ids. Does nothing while locked, or once the
+    /// store has been sealed.
+    pub fn seal_plaintext(&self) -> AppResult<usize> {
+        let marker = self.base_dir.join(SEALED_MARKER);
+        if !crypto::is_unlocked() || marker.exists() {
+            return Ok(0);
+        }
+        let sealed = self.tiers.seal_plaintext()?;
+        fs::write(&marker, b"").map_err(|e| AppError::Io {
+            path: marker,
+            source: e,
+        })?;
+        Ok(sealed)
+    }
+
+    /// Objects and bytes in each tier.
+    pub fn tier_occupancy(&self) -> AppResult<TierOccupancy> {
+        self.tiers.occupancy()
     }
 
     pub fn clean_temp(&self) -> AppResult<usize> {
@@ -361,8 +361,8 @@ mod tests {
         let hash2 = storage.write(content, true).unwrap();
         assert_eq!(hash1, hash2);
 
-        // Check that only one file exists in objects
-        let objects_dir = _dir.path().join("objects");
+        // Check that only one file exists in the hot tier
+        let objects_dir = _dir.path().join("hot");
         let mut count = 0;
         for entry in fs::read_dir(objects_dir).unwrap() {
             let entry = entry.unwrap();
@@ -408,6 +408,197 @@ mod tests {
         assert!(result.iter().all(|&b| b == b'x'));
     }
 
+    fn age(storage: &CasStorage, hash: &str, hours: u64) {
+        let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(hours * 3600);
+        fs::File::options()
+            .write(true)
+            .open(storage.get_path(hash))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn tier_of(dir: &TempDir, storage: &CasStorage, hash: &str) -> String {
+        let path = storage.get_path(hash);
+        let relative = path.strip_prefix(dir.path()).unwrap();
+        relative
+            .components()
+            .next()
+            .unwrap()
+            .as_os_str()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn objects_migrate_by_age_and_are_promoted_on_read() {
+        let (dir, storage) = setup();
+        let config = TierConfig::default();
+        let fresh = storage.write(b"fresh", true).unwrap();
+        let warm = storage.write(&b"warm ".repeat(1000), true).unwrap();
+        let cold = storage.write(&b"cold ".repeat(1000), true).unwrap();
+        age(&storage, &warm, 2);
+        age(&storage, &cold, 24 * 4);
+
+        assert_eq!(storage.migrate(&config).unwrap(), 2);
+        assert_eq!(tier_of(&dir, &storage, &fresh), "hot");
+        assert_eq!(tier_of(&dir, &storage, &warm), "warm");
+        assert_eq!(tier_of(&dir, &storage, &cold), "cold");
+        assert!(
+            fs::read(storage.get_path(&warm))
+                .unwrap()
+                .starts_with(b"mLZ4")
+        );
+
+        let occupancy = storage.tier_occupancy().unwrap();
+        assert_eq!(occupancy.hot.objects, 1);
+        assert_eq!(occupancy.warm.objects, 1);
+        assert_eq!(occupancy.cold.objects, 1);
+
+        // Warm keeps its age, so it moves on to cold when that runs out
+        age(&storage, &warm, 24 * 4);
+        assert_eq!(storage.migrate(&config).unwrap(), 1);
+        assert_eq!(tier_of(&dir, &storage, &warm), "cold");
+
+        assert_eq!(storage.read(&cold).unwrap(), b"cold ".repeat(1000));
+        assert_eq!(tier_of(&dir, &storage, &cold), "hot");
+        assert_eq!(storage.migrate(&config).unwrap(), 0);
+        assert_eq!(storage.list_objects().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn legacy_objects_stay_readable_and_move_into_tiers() {
+        let (dir, storage) = setup();
+        let old = b"written before tiering".to_vec();
+        let hash = blake3::hash(&old).to_hex().to_string();
+        let legacy = dir.path().join("objects").join(&hash[..2]).join(&hash[2..]);
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, zstd::encode_all(&old[..], 3).unwrap()).unwrap();
+        let flat = b"written before sharding".to_vec();
+        let flat_hash = blake3::hash(&flat).to_hex().to_string();
+        fs::write(dir.path().join("objects").join(&flat_hash), &flat).unwrap();
+        age(&storage, &flat_hash, 24 * 10);
+
+        assert!(storage.exists(&hash));
+        assert_eq!(storage.tier_occupancy().unwrap().legacy.objects, 2);
+        assert_eq!(storage.list_objects().unwrap().len(), 2);
+
+        assert_eq!(storage.migrate(&TierConfig::default()).unwrap(), 2);
+        assert!(!legacy.exists());
+        assert_eq!(tier_of(&dir, &storage, &hash), "hot");
+        assert_eq!(tier_of(&dir, &storage, &flat_hash), "cold");
+        assert_eq!(storage.read(&hash).unwrap(), old);
+        assert_eq!(storage.read(&flat_hash).unwrap(), flat);
+
+        storage.delete(&hash).unwrap();
+        assert!(!storage.exists(&hash));
+    }
+
+    #[test]
+    fn small_cold_objects_are_packed_and_stay_readable() {
+        let (dir, storage) = setup();
+        let config = TierConfig::default();
+        let mut noise = vec![0u8; 128 * 1024];
+        blake3::Hasher::new().finalize_xof().fill(&mut noise);
+        let large = storage.write(&noise, true).unwrap();
+        let small: Vec<String> = (0..20)
+            .map(|i| {
+                storage
+                    .write(format!("chunk {}", i).as_bytes(), true)
+                    .unwrap()
+            })
+            .collect();
+        for hash in small.iter().chain([&large]) {
+            age(&storage, hash, 24 * 4);
+        }
+        storage.migrate(&config).unwrap();
+
+        assert_eq!(storage.repack(&config).unwrap(), 20);
+        assert_eq!(tier_of(&dir, &storage, &large), "cold");
+        let occupancy = storage.tier_occupancy().unwrap();
+        assert_eq!(occupancy.cold.objects, 1);
+        assert_eq!(occupancy.packed.objects, 20);
+        assert_eq!(storage.list_objects().unwrap().len(), 21);
+        assert_eq!(storage.peek(&small[3]).unwrap(), b"chunk 3");
+        assert_eq!(storage.tier_occupancy().unwrap().packed.objects, 20);
+
+        // Indexes survive a reopen; deletes and promotions leave the pack
+        let storage = CasStorage::new(dir.path().to_path_buf()).unwrap();
+        assert!(storage.exists(&small[0]));
+        storage.delete(&small[0]).unwrap();
+        assert!(!storage.exists(&small[0]));
+        assert_eq!(storage.read(&small[1]).unwrap(), b"chunk 1");
+        assert_eq!(tier_of(&dir, &storage, &small[1]), "hot");
+        assert_eq!(storage.tier_occupancy().unwrap().packed.objects, 18);
+
+        // A mostly dead pack is rewritten with what is left
+        for hash in &small[2..15] {
+            storage.delete(hash).unwrap();
+        }
+        assert_eq!(storage.repack(&config).unwrap(), 5);
+        let packs: Vec<_> = fs::read_dir(dir.path().join("packs"))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(packs.len(), 2);
+        for (i, hash) in small.iter().enumerate().skip(15) {
+            assert_eq!(
+                storage.peek(hash).unwrap(),
+                format!("chunk {}", i).as_bytes()
+            );
+        }
+    }
+
+    fn source_chunk(i: usize) -> Vec<u8> {
+        format!(
+            "pub fn handler_{i}(state: &AppState, request: Request) -> AppResult<Response> {{\n    let user = state.users.get(request.user_id)?;\n    log::info!(\"handling request {i} for {{}}\", user.name);\n    Ok(Response::new(StatusCode::OK, user.to_json()?))\n}}\n"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn trained_dictionary_compresses_new_and_packed_objects() {
+        let (dir, storage) = setup();
+        let config = TierConfig::default();
+        let before: Vec<String> = (0..MIN_DICT_SAMPLES)
+            .map(|i| storage.write(&source_chunk(i), true).unwrap())
+            .collect();
+        assert!(
+            fs::read(storage.get_path(&before[0]))
+                .unwrap()
+                .starts_with(&[0x28, 0xB5, 0x2F, 0xFD])
+        );
+
+        assert_eq!(storage.train_dictionary().unwrap(), Some(1));
+        assert_eq!(storage.train_dictionary().unwrap(), None);
+        assert!(dir.path().join("dicts").join("v1.dict").exists());
+
+        let content = source_chunk(1000);
+        let after = storage.write(&content, true).unwrap();
+        assert!(
+            fs::read(storage.get_path(&after))
+                .unwrap()
+                .starts_with(b"mZDC")
+        );
+        assert_eq!(storage.read(&after).unwrap(), content);
+        assert_eq!(storage.read(&before[3]).unwrap(), source_chunk(3));
+
+        // Packs use it too, and report what it saved over plain zstd
+        for hash in before.iter().chain([&after]) {
+            age(&storage, hash, 24 * 4);
+        }
+        storage.migrate(&config).unwrap();
+        assert_eq!(storage.repack(&config).unwrap(), before.len() + 1);
+        let occupancy = storage.tier_occupancy().unwrap();
+        assert_eq!(occupancy.dictionary.versions, 1);
+        assert!(occupancy.dictionary.pack_bytes_saved > 0);
+
+        // A reopened store still reads every generation
+        let storage = CasStorage::new(dir.path().to_path_buf()).unwrap();
+        assert_eq!(storage.peek(&before[5]).unwrap(), source_chunk(5));
+        assert_eq!(storage.peek(&after).unwrap(), content);
+    }
+
     #[test]
     fn test_already_compressed_detection() {
         // PNG Magic bytes
@@ -0,0 +1,855 @@
+//! Integrity verification and repair of a project's history.
+//!
+//! [`check`] walks the database and CAS and reports chunks that are missing
+//! or corrupt, snapshots whose chunks no longer hash back to their content
+//! hash, interned string ids that do not resolve, orphaned objects and
+//! dangling symbol rows. [`repair`] quarantines the snapshots that cannot be
+//! restored anymore, drops dangling rows and rebuilds the derived tables: the
+//! per-file index, chunk reference counts, trigram postings and symbols.
+
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use crate::storage::Repository;
+use crate::storage::database::{
+    CHUNK_REFS, CHUNK_TRIGRAMS, CHUNKS, ChunkData, DeltaData, FILE_SNAPSHOTS, HASH_PREFIX_LEN,
+    HASH_PREFIXES, METADATA, QUARANTINE, ReferenceData, SNAPSHOT_CHUNKS, SNAPSHOT_TIMES, SNAPSHOTS,
+    STRING_INDEX, STRINGS, SYMBOL_DELTAS, SYMBOL_REFERENCES, SYMBOLS, SnapshotData, SymbolData,
+    TRIGRAM_POSTINGS, intern_string_in, retain_records,
+};
+use crate::storage::schema::{
+    build_hash_prefixes, build_snapshot_times, decode_record, encode_record, migrate_chunk_refs,
+    migrate_file_snapshot_index,
+};
+use redb::{ReadableMultimapTable, ReadableTable};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckIssueKind {
+    /// A snapshot record cannot be decoded.
+    CorruptRecord,
+    /// An interned string id does not resolve.
+    UnresolvedString,
+    /// A snapshot links a chunk that has no chunk row.
+    MissingChunkRow,
+    /// A linked chunk is absent from CAS.
+    MissingObject,
+    /// A CAS object does not hash back to its name.
+    CorruptObject,
+    /// The reassembled chunks do not hash to the snapshot's content hash.
+    ContentMismatch,
+    /// A chunk row no snapshot links to and whose object is gone. Unlinked
+    /// chunks that still have their object are simply waiting for GC.
+    OrphanChunk,
+    /// A CAS object no chunk row or snapshot refers to.
+    OrphanObject,
+    /// A chunk's reference count differs from its number of links.
+    RefCountMismatch,
+    DanglingSymbol,
+    DanglingReference,
+    DanglingDelta,
+    /// The per-file index is missing a snapshot or lists a deleted one.
+    StaleIndexEntry,
+    /// Trigram postings point at a chunk that no longer exists.
+    StalePosting,
+}
+
+impl FsckIssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FsckIssueKind::CorruptRecord => "corrupt record",
+            FsckIssueKind::UnresolvedString => "unresolved string",
+            FsckIssueKind::MissingChunkRow => "missing chunk row",
+            FsckIssueKind::MissingObject => "missing object",
+            FsckIssueKind::CorruptObject => "corrupt object",
+            FsckIssueKind::ContentMismatch => "content mismatch",
+            FsckIssueKind::OrphanChunk => "orphan chunk",
+            FsckIssueKind::OrphanObject => "orphan object",
+            FsckIssueKind::RefCountMismatch => "refcount mismatch",
+            FsckIssueKind::DanglingSymbol => "dangling symbol",
+            FsckIssueKind::DanglingReference => "dangling reference",
+            FsckIssueKind::DanglingDelta => "dangling delta",
+            FsckIssueKind::StaleIndexEntry => "stale index entry",
+            FsckIssueKind::StalePosting => "stale posting",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsckIssue {
+    pub kind: FsckIssueKind,
+    pub snapshot_id: Option<i64>,
+    /// Chunk or object hash the issue is about, if any.
+    pub object: Option<String>,
+    pub detail: String,
+}
+
+/// What `--repair` changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FsckRepairs {
+    pub snapshots_quarantined: usize,
+    pub chunk_rows_restored: usize,
+    pub rows_deleted: usize,
+    pub objects_deleted: usize,
+    pub trigrams_reindexed: usize,
+    pub symbols_reindexed: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FsckReport {
+    pub snapshots_checked: usize,
+    pub chunks_checked: usize,
+    pub objects_checked: usize,
+    pub issues: Vec<FsckIssue>,
+    /// Set when the check ran with repair.
+    pub repairs: Option<FsckRepairs>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn count(&self, kind: FsckIssueKind) -> usize {
+        self.issues.iter().filter(|i| i.kind == kind).count()
+    }
+
+    fn push(
+        &mut self,
+        kind: FsckIssueKind,
+        snapshot_id: Option<i64>,
+        object: Option<&str>,
+        detail: impl Into<String>,
+    ) {
+        self.issues.push(FsckIssue {
+            kind,
+            snapshot_id,
+            object: object.map(str::to_string),
+            detail: detail.into(),
+        });
+    }
+}
+
+/// A snapshot moved out of history by repair, kept for manual inspection.
+#[derive(Serialize, Deserialize)]
+struct QuarantinedSnapshot {
+    /// The original `SNAPSHOTS` record, verbatim.
+    record: Vec<u8>,
+    reason: String,
+    quarantined_at: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ObjectState {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+/// Findings of a scan plus what repair needs to act on them.
+#[derive(Default)]
+struct Scan {
+    report: FsckReport,
+    /// Snapshots whose content can no longer be reassembled, with the reason.
+    unrecoverable: BTreeMap<u64, String>,
+    /// Linked chunks without a row whose object is intact.
+    restorable_rows: HashSet<String>,
+    /// Chunk rows whose object is gone, linked or not.
+    dead_rows: HashSet<String>,
+    orphan_objects: Vec<String>,
+    /// Live snapshots with symbol rows that have to be rebuilt.
+    broken_symbols: HashSet<i64>,
+}
+
+/// Verifies the history of `repo` without changing anything.
+pub fn check(repo: &Repository) -> AppResult<FsckReport> {
+    Ok(scan(repo)?.report)
+}
+
+/// Verifies the history of `repo` and repairs what it can. The returned
+/// report lists the issues found before repairing.
+///
+/// Callers must make sure no snapshot is committed concurrently.
+pub fn repair(repo: &Repository) -> AppResult<FsckReport> {
+    let scan = scan(repo)?;
+    let mut repairs = FsckRepairs::default();
+
+    let txn = repo
+        .db
+        .redb()
+        .begin_write()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    {
+        // 1. Move unrecoverable snapshots aside
+        let mut snapshots = txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut quarantine = txn
+            .open_table(QUARANTINE)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut links = txn
+            .open_table(SNAPSHOT_CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let now = chrono::Local::now().to_rfc3339();
+        for (id, reason) in &scan.unrecoverable {
+            let record = snapshots
+                .remove(*id)
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .map(|v| v.value().to_vec());
+            let Some(record) = record else {
+                continue;
+            };
+            let entry = encode_record(
+                QUARANTINE,
+                *id,
+                &QuarantinedSnapshot {
+                    record,
+                    reason: reason.clone(),
+                    quarantined_at: now.clone(),
+                },
+            )?;
+            quarantine
+                .insert(*id, &*entry)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            links
+                .retain_in((*id, 0)..=(*id, u32::MAX), |_, _| false)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            repairs.snapshots_quarantined += 1;
+        }
+
+        // 2. Recreate chunk rows whose object survived
+        let mut chunks = txn
+            .open_table(CHUNKS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut meta = txn
+            .open_table(METADATA)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut strings = txn
+            .open_table(STRINGS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut string_index = txn
+            .open_table(STRING_INDEX)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for hash in &scan.restorable_rows {
+            let kind_id = intern_string_in(&mut meta, &mut strings, &mut string_index, "raw")?;
+            let bytes = encode_record(
+                CHUNKS,
+                hash.as_str(),
+                &ChunkData {
+                    hash: hash.clone(),
+                    kind_id,
+                },
+            )?;
+            chunks
+                .insert(hash.as_str(), &*bytes)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            repairs.chunk_rows_restored += 1;
+        }
+
+        // Rows of lost chunks go once quarantine removed their last link
+        let mut still_linked = HashSet::new();
+        for res in links
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if scan.dead_rows.contains(v.value()) {
+                still_linked.insert(v.value().to_string());
+            }
+        }
+        for hash in scan.dead_rows.difference(&still_linked) {
+            chunks
+                .remove(hash.as_str())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            repairs.rows_deleted += 1;
+        }
+    }
+
+    // 3. Drop rows hanging off snapshots that are gone or names that do not resolve
+    let (live, resolved) = {
+        let snapshots = txn
+            .open_table(SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let strings = txn
+            .open_table(STRINGS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        (live_snapshot_ids(&snapshots)?, string_ids(&strings)?)
+    };
+    let broken = &scan.broken_symbols;
+    repairs.rows_deleted += retain_records(&txn, SYMBOLS, |s: &SymbolData| {
+        live.contains(&s.snapshot_id) && !broken.contains(&s.snapshot_id)
+    })?;
+    repairs.rows_deleted += retain_records(&txn, SYMBOL_REFERENCES, |r: &ReferenceData| {
+        live.contains(&r.snapshot_id) && resolved.contains(&r.symbol_name_id)
+    })?;
+    repairs.rows_deleted += retain_records(&txn, SYMBOL_DELTAS, |d: &DeltaData| {
+        live.contains(&d.to_snapshot_id)
+            && resolved.contains(&d.symbol_name_id)
+            && d.new_name_id.is_none_or(|id| resolved.contains(&id))
+    })?;
+
+    // 4. Rebuild the derived tables from scratch
+    {
+        let mut index = txn
+            .open_table(FILE_SNAPSHOTS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        index
+            .retain(|_, _| false)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut times = txn
+            .open_table(SNAPSHOT_TIMES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        times
+            .retain(|_, _| false)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut prefixes = txn
+            .open_table(HASH_PREFIXES)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        prefixes
+            .retain(|_, _| false)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut markers = txn
+            .open_table(CHUNK_TRIGRAMS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        markers
+            .retain(|_, _| false)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    txn.delete_multimap_table(TRIGRAM_POSTINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    txn.open_multimap_table(TRIGRAM_POSTINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    migrate_file_snapshot_index(&txn)?;
+    build_snapshot_times(&txn)?;
+    build_hash_prefixes(&txn)?;
+    migrate_chunk_refs(&txn)?;
+    txn.commit()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    // 5. Objects nothing refers to; unlinked chunks that still have their
+    // object are left to GC, which now sees them with a zero reference count
+    for hash in &scan.orphan_objects {
+        match repo.fs.delete(hash) {
+            Ok(()) => repairs.objects_deleted += 1,
+            Err(e) => eprintln!("Warning: failed to delete orphan object {}: {}", hash, e),
+        }
+    }
+
+    repairs.trigrams_reindexed = repo.index_pending_trigrams()?;
+    repairs.symbols_reindexed = reindex_symbols(repo, &scan.broken_symbols)?;
+
+    let mut report = scan.report;
+    report.repairs = Some(repairs);
+    Ok(report)
+}
+
+/// Re-parses the snapshots whose symbols were dropped, plus the latest
+/// snapshot of every file that has no symbols at all.
+fn reindex_symbols(repo: &Repository, broken: &HashSet<i64>) -> AppResult<usize> {
+    let indexed: HashSet<i64> = {
+        let txn = repo
+            .db
+            .redb()
+            .begin_read()
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let table = txn
+            .open_table(SYMBOLS)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let mut ids = HashSet::new();
+        for res in table
+            .iter()
+            .map_err(|e| AppError::Database(e.to_string()))?
+        {
+            let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+            if let Ok(data) = decode_record::<SymbolData>(SYMBOLS, k.value(), v.value()) {
+                ids.insert(data.snapshot_id);
+            }
+        }
+        ids
+    };
+
+    let mut count = 0;
+    for (path, _) in repo.db.get_latest_state()? {
+        let history = repo.db.get_file_history(&path)?;
+        let latest = history.iter().map(|s| s.id).max();
+        for snapshot in &history {
+            let wanted = broken.contains(&snapshot.id)
+                || (Some(snapshot.id) == latest && !indexed.contains(&snapshot.id));
+            if wanted && repo.reindex_symbols(snapshot).is_ok() {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn live_snapshot_ids(table: &impl ReadableTable<u64, &'static [u8]>) -> AppResult<HashSet<i64>> {
+    let mut ids = HashSet::new();
+    for res in table
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        ids.insert(id.value() as i64);
+    }
+    Ok(ids)
+}
+
+fn string_ids(table: &impl ReadableTable<u32, &'static str>) -> AppResult<HashSet<u32>> {
+    let mut ids = HashSet::new();
+    for res in table
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        ids.insert(id.value());
+    }
+    Ok(ids)
+}
+
+fn object_state(repo: &Repository, hash: &str) -> ObjectState {
+    if !repo.fs.exists(hash) {
+        return ObjectState::Missing;
+    }
+    match repo.fs.peek(hash) {
+        Ok(data) if crypto::is_object_id(&data, hash) => ObjectState::Ok,
+        _ => ObjectState::Corrupt,
+    }
+}
+
+fn scan(repo: &Repository) -> AppResult<Scan> {
+    let mut scan = Scan::default();
+    let txn = repo
+        .db
+        .redb()
+        .begin_read()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let open_err = |e: redb::TableError| AppError::Database(e.to_string());
+
+    let strings = string_ids(&txn.open_table(STRINGS).map_err(open_err)?)?;
+
+    let mut chunk_rows: HashMap<String, u32> = HashMap::new();
+    for res in txn
+        .open_table(CHUNKS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let kind_id = decode_record::<ChunkData>(CHUNKS, k.value(), v.value())
+            .map(|c| c.kind_id)
+            .unwrap_or(0);
+        chunk_rows.insert(k.value().to_string(), kind_id);
+    }
+
+    let mut links: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut link_counts: HashMap<String, u64> = HashMap::new();
+    for res in txn
+        .open_table(SNAPSHOT_CHUNKS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let hash = v.value().to_string();
+        *link_counts.entry(hash.clone()).or_default() += 1;
+        links.entry(k.value().0).or_default().push(hash);
+    }
+
+    let mut refs: HashMap<String, u64> = HashMap::new();
+    for res in txn
+        .open_table(CHUNK_REFS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        refs.insert(k.value().to_string(), v.value());
+    }
+
+    let mut index: HashSet<(u32, u64)> = HashSet::new();
+    for res in txn
+        .open_table(FILE_SNAPSHOTS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        index.insert(k.value());
+    }
+    let mut time_index: HashSet<(i64, u64)> = HashSet::new();
+    for res in txn
+        .open_table(SNAPSHOT_TIMES)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        time_index.insert(k.value());
+    }
+    let mut prefix_index: HashSet<(String, u64)> = HashSet::new();
+    for res in txn
+        .open_table(HASH_PREFIXES)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, _) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let (key, id) = k.value();
+        prefix_index.insert((key.to_string(), id));
+    }
+
+    // Snapshots: every chunk must be present, intact and reassemble to the content hash
+    let mut objects: HashMap<String, ObjectState> = HashMap::new();
+    let mut content_hashes: HashSet<String> = HashSet::new();
+    let mut live: HashSet<i64> = HashSet::new();
+    let report = &mut scan.report;
+    for res in txn
+        .open_table(SNAPSHOTS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let id = k.value();
+        let sid = Some(id as i64);
+        report.snapshots_checked += 1;
+        live.insert(id as i64);
+
+        let data = match decode_record::<SnapshotData>(SNAPSHOTS, id, v.value()) {
+            Ok(data) => data,
+            Err(e) => {
+                let detail = format!("undecodable snapshot record: {}", e);
+                report.push(FsckIssueKind::CorruptRecord, sid, None, detail.clone());
+                scan.unrecoverable.insert(id, detail);
+                continue;
+            }
+        };
+        content_hashes.insert(data.content_hash.clone());
+
+        if !strings.contains(&data.file_path_id) {
+            let detail = format!("file path id {} does not resolve", data.file_path_id);
+            report.push(FsckIssueKind::UnresolvedString, sid, None, detail.clone());
+            scan.unrecoverable.insert(id, detail);
+            continue;
+        }
+        if let Some(branch_id) = data.git_branch_id.filter(|b| !strings.contains(b)) {
+            report.push(
+                FsckIssueKind::UnresolvedString,
+                sid,
+                None,
+                format!("branch id {} does not resolve", branch_id),
+            );
+        }
+        if !index.contains(&(data.file_path_id, id)) {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                sid,
+                None,
+                "snapshot missing from the per-file index",
+            );
+        }
+        if !time_index.contains(&(data.timestamp, id)) {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                sid,
+                None,
+                "snapshot missing from the time index",
+            );
+        }
+        if let Some(key) = crypto::hash_prefix_key(&data.content_hash, HASH_PREFIX_LEN)
+            && !prefix_index.contains(&(key, id))
+        {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                sid,
+                None,
+                "snapshot missing from the hash prefix index",
+            );
+        }
+        if data.event.is_tombstone() {
+            continue;
+        }
+
+        // Snapshots taken before encryption was enabled keep plain ids
+        let mut hasher = crypto::object_hasher();
+        let mut plain = blake3::Hasher::new();
+        let mut damage: Option<String> = None;
+        let chunk_hashes = links.get(&id).map(Vec::as_slice).unwrap_or_default();
+        for hash in chunk_hashes {
+            let known = objects.contains_key(hash);
+            let state = *objects
+                .entry(hash.clone())
+                .or_insert_with(|| object_state(repo, hash));
+            if !chunk_rows.contains_key(hash) {
+                report.push(
+                    FsckIssueKind::MissingChunkRow,
+                    sid,
+                    Some(hash),
+                    "linked chunk has no chunk row",
+                );
+                if state == ObjectState::Ok {
+                    scan.restorable_rows.insert(hash.clone());
+                }
+            }
+            match state {
+                ObjectState::Ok => {
+                    if damage.is_none() {
+                        let data = repo.fs.peek(hash)?;
+                        hasher.update(&data);
+                        plain.update(&data);
+                    }
+                }
+                ObjectState::Missing => {
+                    if !known {
+                        report.push(
+                            FsckIssueKind::MissingObject,
+                            sid,
+                            Some(hash),
+                            "chunk is missing from CAS",
+                        );
+                    }
+                    damage.get_or_insert_with(|| format!("chunk {} is missing", hash));
+                }
+                ObjectState::Corrupt => {
+                    if !known {
+                        report.push(
+                            FsckIssueKind::CorruptObject,
+                            sid,
+                            Some(hash),
+                            "chunk does not hash to its name",
+                        );
+                    }
+                    damage.get_or_insert_with(|| format!("chunk {} is corrupt", hash));
+                }
+            }
+        }
+
+        // Snapshots written before chunking keep their content as one object
+        if chunk_hashes.is_empty() && repo.fs.exists(&data.content_hash) {
+            continue;
+        }
+        let matches = [hasher.finalize(), plain.finalize()]
+            .iter()
+            .any(|h| h.to_hex().as_str() == data.content_hash);
+        if damage.is_none() && !matches {
+            let detail = "reassembled chunks do not hash to the content hash".to_string();
+            report.push(
+                FsckIssueKind::ContentMismatch,
+                sid,
+                Some(&data.content_hash),
+                detail.clone(),
+            );
+            damage = Some(detail);
+        }
+        if let Some(reason) = damage {
+            scan.unrecoverable.insert(id, reason);
+        }
+    }
+    report.chunks_checked = chunk_rows.len();
+
+    for (file_path_id, id) in &index {
+        if !live.contains(&(*id as i64)) {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                Some(*id as i64),
+                None,
+                format!("index entry of path id {} has no snapshot", file_path_id),
+            );
+        }
+    }
+    for (timestamp, id) in &time_index {
+        if !live.contains(&(*id as i64)) {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                Some(*id as i64),
+                None,
+                format!("time index entry at {} has no snapshot", timestamp),
+            );
+        }
+    }
+    for (_, id) in &prefix_index {
+        if !live.contains(&(*id as i64)) {
+            report.push(
+                FsckIssueKind::StaleIndexEntry,
+                Some(*id as i64),
+                None,
+                "hash prefix index entry has no snapshot",
+            );
+        }
+    }
+
+    // Chunk rows: referenced, counted correctly, with a resolvable kind
+    for (hash, kind_id) in &chunk_rows {
+        let missing = objects
+            .get(hash)
+            .map_or_else(|| !repo.fs.exists(hash), |s| *s == ObjectState::Missing);
+        if missing {
+            scan.dead_rows.insert(hash.clone());
+            if !link_counts.contains_key(hash) {
+                report.push(
+                    FsckIssueKind::OrphanChunk,
+                    None,
+                    Some(hash),
+                    "unlinked chunk row without an object",
+                );
+            }
+        }
+        if !strings.contains(kind_id) {
+            report.push(
+                FsckIssueKind::UnresolvedString,
+                None,
+                Some(hash),
+                format!("chunk kind id {} does not resolve", kind_id),
+            );
+        }
+    }
+    let counted: HashSet<&String> = refs.keys().chain(link_counts.keys()).collect();
+    for hash in counted {
+        let stored = refs.get(hash).copied().unwrap_or(0);
+        let linked = link_counts.get(hash).copied().unwrap_or(0);
+        if stored != linked {
+            report.push(
+                FsckIssueKind::RefCountMismatch,
+                None,
+                Some(hash),
+                format!("reference count is {} but {} links exist", stored, linked),
+            );
+        }
+    }
+
+    // CAS objects nothing refers to
+    for hash in repo.fs.list_objects()? {
+        report.objects_checked += 1;
+        if !chunk_rows.contains_key(&hash)
+            && !link_counts.contains_key(&hash)
+            && !content_hashes.contains(&hash)
+        {
+            report.push(
+                FsckIssueKind::OrphanObject,
+                None,
+                Some(&hash),
+                "object is not referenced by any chunk or snapshot",
+            );
+            scan.orphan_objects.push(hash);
+        }
+    }
+
+    // Semantic rows must point at live snapshots and resolvable names
+    let to_live = |id: i64| live.contains(&id) && !scan.unrecoverable.contains_key(&(id as u64));
+    for res in txn
+        .open_table(SYMBOLS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(s) = decode_record::<SymbolData>(SYMBOLS, k.value(), v.value()) else {
+            continue;
+        };
+        if !live.contains(&s.snapshot_id) {
+            report.push(
+                FsckIssueKind::DanglingSymbol,
+                Some(s.snapshot_id),
+                None,
+                format!("symbol {} belongs to a deleted snapshot", s.id),
+            );
+        } else if !strings.contains(&s.name_id)
+            || !strings.contains(&s.kind_id)
+            || s.scope_id.is_some_and(|id| !strings.contains(&id))
+        {
+            report.push(
+                FsckIssueKind::UnresolvedString,
+                Some(s.snapshot_id),
+                None,
+                format!("symbol {} has an unresolved name, kind or scope", s.id),
+            );
+            if to_live(s.snapshot_id) {
+                scan.broken_symbols.insert(s.snapshot_id);
+            }
+        }
+    }
+    for res in txn
+        .open_table(SYMBOL_REFERENCES)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(r) = decode_record::<ReferenceData>(SYMBOL_REFERENCES, k.value(), v.value()) else {
+            continue;
+        };
+        if !live.contains(&r.snapshot_id) {
+            report.push(
+                FsckIssueKind::DanglingReference,
+                Some(r.snapshot_id),
+                None,
+                format!("reference {} belongs to a deleted snapshot", r.id),
+            );
+        } else if !strings.contains(&r.symbol_name_id) {
+            report.push(
+                FsckIssueKind::UnresolvedString,
+                Some(r.snapshot_id),
+                None,
+                format!("reference {} has an unresolved name", r.id),
+            );
+        }
+    }
+    for res in txn
+        .open_table(SYMBOL_DELTAS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(d) = decode_record::<DeltaData>(SYMBOL_DELTAS, k.value(), v.value()) else {
+            continue;
+        };
+        if !live.contains(&d.to_snapshot_id) {
+            report.push(
+                FsckIssueKind::DanglingDelta,
+                Some(d.to_snapshot_id),
+                None,
+                format!("delta {} belongs to a deleted snapshot", d.id),
+            );
+        } else if !strings.contains(&d.symbol_name_id)
+            || d.new_name_id.is_some_and(|id| !strings.contains(&id))
+        {
+            report.push(
+                FsckIssueKind::UnresolvedString,
+                Some(d.to_snapshot_id),
+                None,
+                format!("delta {} has an unresolved name", d.id),
+            );
+        }
+    }
+
+    // Postings of chunks that are gone
+    let mut stale: HashSet<String> = HashSet::new();
+    for res in txn
+        .open_multimap_table(TRIGRAM_POSTINGS)
+        .map_err(open_err)?
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (_, values) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        for v in values {
+            let v = v.map_err(|e| AppError::Database(e.to_string()))?;
+            let hash = v.value();
+            if !chunk_rows.contains_key(hash) && !stale.contains(hash) {
+                stale.insert(hash.to_string());
+            }
+        }
+    }
+    for hash in stale {
+        report.push(
+            FsckIssueKind::StalePosting,
+            None,
+            Some(&hash),
+            "trigram postings point at a deleted chunk",
+        );
+    }
+
+    Ok(scan)
+}
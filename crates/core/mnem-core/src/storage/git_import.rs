@@ -0,0 +1,282 @@
+//! History from before tracking started, read from git.
+//!
+//! The first-parent history of a branch is walked oldest first, and every
+//! file a commit added, changed or removed inside the project becomes a
+//! snapshot stamped with the commit's time, branch and hash, one changeset
+//! per commit. Each version is indexed on the spot, its symbols diffed
+//! against the file's previous version. Only commits older than the earliest
+//! snapshot are imported, as later ones happened while the project was
+//! tracked; importing again therefore picks up further back, e.g. after a
+//! first import limited by `since`.
+//!
+//! Imported versions are older than the snapshots already recorded but get
+//! newer ids, so the batches are marked as backfilled and a file's latest
+//! snapshot is then found by time. Files the watcher would leave out, being
+//! ignored, too large or over the binary cap, are left out here too.
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::git::{Commit, GitRepo, ObjectStore, Oid, TreeEntry};
+use crate::models::SemanticSymbol;
+use crate::storage::commit_link::tree_path;
+use crate::storage::repository::PreparedSnapshot;
+use crate::storage::{Repository, cdc, filter};
+use chrono::{DateTime, Utc};
+use ignore::gitignore::Gitignore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct GitImportOptions {
+    /// Stop at the first commit made before this.
+    pub since: Option<DateTime<Utc>>,
+    /// Branch to walk instead of the checked out one.
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitImportReport {
+    pub branch: String,
+    /// Commits imported.
+    pub commits: usize,
+    /// Snapshots written for them, tombstones included.
+    pub snapshots: usize,
+    /// Commits left out because the project was already tracked then.
+    pub skipped: usize,
+}
+
+pub(crate) fn import(repo: &Repository, options: &GitImportOptions) -> AppResult<GitImportReport> {
+    let project = Path::new(&repo.project.path);
+    let git = GitRepo::discover(project).ok_or_else(|| {
+        AppError::Git(format!("{} is not in a git repository", project.display()))
+    })?;
+    let (branch, tip) = match &options.branch {
+        Some(branch) => {
+            let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+            let tip = git
+                .resolve_ref(&format!("refs/heads/{}", branch))?
+                .ok_or_else(|| AppError::NotFound(format!("Branch {}", branch)))?;
+            (branch.to_string(), tip)
+        }
+        None => {
+            let head = git.head()?;
+            let tip = head
+                .oid
+                .ok_or_else(|| AppError::Git("HEAD has no commits yet".into()))?;
+            let branch = head
+                .branch
+                .unwrap_or_else(|| tip.to_string()[..7].to_string());
+            (branch, tip)
+        }
+    };
+
+    let objects = git.objects()?;
+    let since = options.since.map(|t| t.timestamp_millis());
+    let tracked_from = repo.db.first_snapshot_time()?;
+    let mut report = GitImportReport {
+        branch: branch.clone(),
+        ..Default::default()
+    };
+    let mut commits = Vec::new();
+    let mut next = Some(tip);
+    while let Some(oid) = next {
+        let commit = objects.commit(&oid)?;
+        next = commit.parents.first().copied();
+        let at = commit.time * 1000;
+        if since.is_some_and(|since| at < since) {
+            break;
+        }
+        if tracked_from.is_some_and(|from| at >= from) {
+            report.skipped += 1;
+            continue;
+        }
+        commits.push(commit);
+    }
+
+    let prefix = match tree_path(git.work_tree(), project) {
+        Some(relative) => format!("{}/", relative),
+        None => String::new(),
+    };
+    let config = repo
+        .config
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .config
+        .clone();
+    let mut importer = Importer {
+        repo,
+        objects: &objects,
+        work_tree: git.work_tree().to_path_buf(),
+        prefix,
+        branch,
+        mnemignore: filter::mnemignore(project, &config)?,
+        config,
+        last: HashMap::new(),
+    };
+    // The oldest commit is taken whole: its parent's files are not imported
+    let mut parent_tree = None;
+    for commit in commits.iter().rev() {
+        report.snapshots += importer.import_commit(commit, parent_tree)?;
+        report.commits += 1;
+        parent_tree = Some(commit.tree);
+    }
+    Ok(report)
+}
+
+struct Importer<'a> {
+    repo: &'a Repository,
+    objects: &'a ObjectStore,
+    work_tree: PathBuf,
+    /// The project's directory in the tree, `/`-terminated; empty at the root.
+    prefix: String,
+    branch: String,
+    config: Config,
+    mnemignore: Gitignore,
+    /// Snapshot id and symbols of each imported file's last version.
+    last: HashMap<String, (i64, Vec<SemanticSymbol>)>,
+}
+
+impl Importer<'_> {
+    /// Writes what `commit` changed since `parent_tree` as one changeset.
+    /// Returns the number of snapshots written.
+    fn import_commit(&mut self, commit: &Commit, parent_tree: Option<Oid>) -> AppResult<usize> {
+        let mut changes = Vec::new();
+        diff_trees(
+            self.objects,
+            parent_tree,
+            Some(commit.tree),
+            "",
+            &mut changes,
+        )?;
+        let hash = commit.oid.to_string();
+        self.repo.db.insert_git_commit(
+            &hash,
+            commit.summary(),
+            &commit.author,
+            &commit.timestamp(),
+        )?;
+
+        let mut prepared = Vec::new();
+        let project = Path::new(&self.repo.project.path);
+        for (path, blob) in changes {
+            if !path.starts_with(&self.prefix) {
+                continue;
+            }
+            let file_path = self.work_tree.join(&path);
+            if filter::is_ignored(project, &file_path, Some(&self.mnemignore)) {
+                continue;
+            }
+            let path_str = file_path.to_string_lossy().to_string();
+            let content = match blob {
+                Some(blob) => {
+                    let content = self.objects.blob(&blob)?;
+                    if !self
+                        .config
+                        .accepts(content.len() as u64, cdc::is_binary(&content))
+                    {
+                        continue;
+                    }
+                    Some(bytes::Bytes::from(content))
+                }
+                // Nothing to mark gone when every version was left out
+                None if !self.last.contains_key(&path_str) => continue,
+                None => None,
+            };
+            prepared.push(self.repo.prepare_historic(
+                &file_path,
+                content,
+                commit.time * 1000,
+                Some(self.branch.clone()),
+                Some(hash.clone()),
+                self.last.remove(&path_str),
+            )?);
+        }
+        self.commit(prepared)
+    }
+
+    fn commit(&mut self, prepared: Vec<PreparedSnapshot>) -> AppResult<usize> {
+        let written = prepared.len();
+        if written == 0 {
+            return Ok(0);
+        }
+        let ids = self
+            .repo
+            .commit_historic(&prepared.iter().collect::<Vec<_>>())?;
+        for (prepared, id) in prepared.into_iter().zip(ids) {
+            let path = prepared.file_path.to_string_lossy().to_string();
+            let tombstone = prepared.content_hash.is_empty();
+            let symbols = self.repo.index_snapshot_now(prepared, id);
+            if !tombstone {
+                self.last.insert(path, (id, symbols));
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Appends the regular files that differ between two trees to `changes`,
+/// with their new blob or `None` where they are gone. Subtrees that did not
+/// change are not read.
+pub(crate) fn diff_trees(
+    objects: &ObjectStore,
+    old: Option<Oid>,
+    new: Option<Oid>,
+    dir: &str,
+    changes: &mut Vec<(String, Option<Oid>)>,
+) -> AppResult<()> {
+    let entries = |tree: Option<Oid>| -> AppResult<BTreeMap<String, TreeEntry>> {
+        let Some(tree) = tree else {
+            return Ok(BTreeMap::new());
+        };
+        Ok(objects
+            .tree(&tree)?
+            .iter()
+            .map(|e| (e.name.clone(), e.clone()))
+            .collect())
+    };
+    let old = entries(old)?;
+    let mut new = entries(new)?;
+    for (name, before) in &old {
+        let after = new.remove(name);
+        let path = format!("{}{}", dir, name);
+        let unchanged = after
+            .as_ref()
+            .is_some_and(|a| a.oid == before.oid && a.is_file() == before.is_file());
+        if !unchanged {
+            diff_entry(objects, Some(before), after.as_ref(), &path, changes)?;
+        }
+    }
+    for (name, after) in &new {
+        diff_entry(
+            objects,
+            None,
+            Some(after),
+            &format!("{}{}", dir, name),
+            changes,
+        )?;
+    }
+    Ok(())
+}
+
+/// One path of [`diff_trees`] that is not the same on both sides.
+fn diff_entry(
+    objects: &ObjectStore,
+    before: Option<&TreeEntry>,
+    after: Option<&TreeEntry>,
+    path: &str,
+    changes: &mut Vec<(String, Option<Oid>)>,
+) -> AppResult<()> {
+    let subtree = |e: Option<&TreeEntry>| e.filter(|e| e.is_tree()).map(|e| e.oid);
+    if before.is_some_and(|e| e.is_file()) && !after.is_some_and(|e| e.is_file()) {
+        changes.push((path.to_string(), None));
+    }
+    if subtree(before).is_some() || subtree(after).is_some() {
+        let dir = format!("{}/", path);
+        diff_trees(objects, subtree(before), subtree(after), &dir, changes)?;
+    }
+    if let Some(after) = after.filter(|e| e.is_file()) {
+        changes.push((path.to_string(), Some(after.oid)));
+    }
+    Ok(())
+}
@@ -1,7 +1,19 @@
+pub mod bundle;
+pub mod cdc;
+pub mod commit_link;
+pub mod commit_queue;
 pub mod database;
+pub mod filter;
 pub mod fs;
+pub mod fsck;
+pub mod git_import;
 pub mod registry;
 pub mod repository;
+pub mod retention;
+pub mod schema;
 pub mod tiered;
+pub mod timesheet;
+pub mod tree;
+pub mod trigram;
 
 pub use repository::Repository;
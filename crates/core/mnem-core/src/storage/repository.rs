@@ -1,21 +1,75 @@
-use crate::config::ConfigManager;
+use crate::config::{ConfigManager, RetentionPolicy};
+use crate::crypto;
 use crate::error::{AppError, AppResult};
-use crate::models::{FileEntry, Project, SearchResult, Session, Snapshot};
+use crate::git::{GitRepo, Oid};
+use crate::models::{
+    ChangeKind, Changeset, CheckpointChange, FileEntry, GcReport, Pin, PinKind, Project,
+    SearchResult, Session, Snapshot, SnapshotEvent,
+};
 use crate::semantic::SemanticParser;
+use crate::storage::commit_queue::{self, GitCommitEvent};
 use crate::storage::registry::ProjectRegistry;
+use crate::storage::tiered::TierConfig;
+use crate::storage::timesheet::SessionSpan;
+use crate::storage::trigram::{self, TrigramQuery};
+use crate::storage::{bundle, cdc, commit_link, fsck, git_import, retention, tree};
+use crate::utils::time;
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
-use super::database::Database;
+use super::database::{Database, NewSnapshot, WriteBatch};
 use super::fs::CasStorage;
 
+/// (chunk hash, offset, length, bytes) in file order
+type Chunks = Vec<(String, usize, usize, bytes::Bytes)>;
+
+/// A save whose content is already chunked and in CAS, waiting for its
+/// database rows to be committed.
+pub struct PreparedSnapshot {
+    pub file_path: PathBuf,
+    pub content_hash: String,
+    content: bytes::Bytes,
+    timestamp: i64,
+    branch: Option<String>,
+    commit_hash: Option<String>,
+    chunks: Chunks,
+    previous: Option<(i64, Vec<crate::models::SemanticSymbol>)>,
+    event: SnapshotEvent,
+    /// Chunked by content rather than by syntax; not semantically indexed.
+    blob: bool,
+}
+
+impl PreparedSnapshot {
+    fn to_new_snapshot(&self) -> NewSnapshot {
+        NewSnapshot {
+            file_path: self.file_path.to_string_lossy().to_string(),
+            timestamp: self.timestamp,
+            content_hash: self.content_hash.clone(),
+            git_branch: self.branch.clone(),
+            session_id: None,
+            commit_hash: self.commit_hash.clone(),
+            event: self.event.clone(),
+            chunks: self
+                .chunks
+                .iter()
+                .map(|(hash, _, _, data)| (hash.clone(), data.clone()))
+                .collect(),
+        }
+    }
+}
+
 pub struct Repository {
     pub db: Arc<Database>,
     pub fs: Arc<CasStorage>,
     pub config: Mutex<ConfigManager>,
     pub project: Project,
+    /// Held for writing while GC deletes chunks, and for reading while a save
+    /// commits, so a chunk cannot be reclaimed between its CAS write and its link.
+    gc_lock: RwLock<()>,
+    /// HEAD as of the last [`Repository::sync_git_head`].
+    git_head: Mutex<Option<Oid>>,
 }
 
 impl Repository {
@@ -103,6 +157,7 @@ impl Repository {
             })?;
         }
 
+        crypto::ensure_unlocked(&base_dir)?;
         let db_path = db_dir.join("mnemosyne.db");
         let db = Arc::new(Database::new(db_path)?);
 
@@ -114,8 +169,12 @@ impl Repository {
             })?;
         }
         let fs = Arc::new(CasStorage::new(cas_dir)?);
+        let sealed = fs.seal_plaintext()?;
+        if sealed > 0 {
+            log::info!("Sealed {} stored objects of {}", sealed, project.name);
+        }
 
-        let config = ConfigManager::new(&base_dir)?;
+        let config = ConfigManager::with_project(&base_dir, &project_path)?;
 
         let ignore_path = project_path.join(".mnemignore");
         if !ignore_path.exists() {
@@ -164,12 +223,33 @@ temp/
             let _ = std::fs::write(&ignore_path, default_ignore);
         }
 
-        Ok(Self {
+        let repo = Self {
             db,
             fs,
             config: Mutex::new(config),
             project,
-        })
+            gc_lock: RwLock::new(()),
+            git_head: Mutex::new(None),
+        };
+        if let Err(e) = repo.replay_queued_commits() {
+            log::warn!("Failed to replay queued commits: {}", e);
+        }
+        Ok(repo)
+    }
+
+    /// Open a repository from a process other than the daemon.
+    ///
+    /// The daemon keeps every watched project open, so this refuses while it
+    /// is running instead of contending for the store; go through
+    /// `DaemonClient` then. Fails with [`AppError::Locked`] when another
+    /// command already holds the project.
+    pub fn open_exclusive(base_dir: PathBuf, project_path: PathBuf) -> AppResult<Self> {
+        if crate::client::daemon_running_in(&base_dir) {
+            return Err(AppError::Locked(
+                "the daemon is running and owns the history".into(),
+            ));
+        }
+        Self::open(base_dir, project_path)
     }
 
     /// Try to find which project a snapshot hash belongs to by searching all registered projects.
@@ -197,12 +277,8 @@ temp/
         let mut total = 0u64;
 
         // 1. Get size of all CAS files (content-addressable storage)
-        if let Ok(entries) = std::fs::read_dir(&self.fs.base_dir) {
-            for entry in entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    total += metadata.len();
-                }
-            }
+        if let Ok(occupancy) = self.fs.tier_occupancy() {
+            total += occupancy.total().bytes;
         }
 
         // 2. Add redb DB size
@@ -240,57 +316,115 @@ temp/
         Self::open(base_dir.clone(), base_dir)
     }
 
-    /// Garbage collection: prune old snapshots AND clean orphan object files (audit 3.2).
-    pub fn run_gc(&self) -> AppResult<usize> {
-        let retention = self
+    /// Garbage collection: thin old snapshots AND clean orphan object files (audit 3.2).
+    pub fn run_gc(&self) -> AppResult<GcReport> {
+        let policy = self
             .config
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner())
             .config
-            .retention_days;
-        if retention == 0 {
-            return Ok(0);
-        }
-
-        // 1. Get all hashes referenced BEFORE pruning
-        let hashes_before = self.db.get_all_content_hashes()?;
+            .retention
+            .clone();
+        self.run_gc_with_policy(&policy)
+    }
 
-        // 2. Prune from DB
-        let pruned = self.db.prune_snapshots(retention)?;
+    /// [`Repository::run_gc`] with `policy` in place of the configured one.
+    pub fn run_gc_with_policy(&self, policy: &RetentionPolicy) -> AppResult<GcReport> {
+        let _guard = self
+            .gc_lock
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
 
-        // 3. Get hashes still referenced AFTER pruning
+        // 1. Thin history according to the retention policy
+        let hashes_before = self.db.get_all_content_hashes()?;
+        let prunable = retention::select_prunable(
+            &self.db.retention_candidates()?,
+            policy,
+            &chrono::Local::now(),
+        );
+        let mut report = self.db.delete_snapshots(&prunable)?;
         let hashes_after = self.db.get_all_content_hashes()?;
 
-        // 4. Delete orphan object files (present before but not after)
-        let orphaned: Vec<_> = hashes_before.difference(&hashes_after).collect();
-        for hash in &orphaned {
-            if let Err(e) = self.fs.delete(hash) {
-                eprintln!("Warning: failed to delete orphan object {}: {}", hash, e);
+        // 2. Reclaim chunks that lost their last reference
+        self.sweep_chunks(&mut report)?;
+
+        // 3. Whole-file objects only exist in stores written before snapshots
+        // were chunked; skip hashes that double as a live chunk
+        for hash in hashes_before.difference(&hashes_after) {
+            if self.db.has_chunk(hash)? || !self.fs.exists(hash) {
+                continue;
+            }
+            let size = self.fs.get_size(hash).unwrap_or(0);
+            match self.fs.delete(hash) {
+                Ok(()) => report.bytes_reclaimed += size,
+                Err(e) => eprintln!("Warning: failed to delete orphan object {}: {}", hash, e),
             }
         }
 
-        // 5. Clean stale temp files (audit hygiene)
+        // 4. Clean stale temp files (audit hygiene)
         let _ = self.fs.clean_temp();
 
-        // 6. VACUUM to reclaim space
-        if pruned > 0 {
+        // 5. VACUUM to reclaim space
+        if report.snapshots_pruned > 0 {
             if let Err(e) = self.db.vacuum() {
                 eprintln!("Warning: VACUUM failed: {}", e);
             }
         }
 
-        Ok(pruned)
+        Ok(report)
+    }
+
+    /// Deletes every unreferenced chunk from the database and CAS.
+    /// Callers must hold `gc_lock` for writing.
+    fn sweep_chunks(&self, report: &mut GcReport) -> AppResult<()> {
+        let orphans: Vec<(String, HashSet<u32>)> = self
+            .db
+            .unreferenced_chunks()?
+            .into_iter()
+            .map(|hash| {
+                // Unreadable chunks leave their postings behind; they point
+                // at no snapshot, so search simply never reaches them
+                let trigrams = self
+                    .fs
+                    .peek(&hash)
+                    .map(|c| trigram::trigrams(&c))
+                    .unwrap_or_default();
+                (hash, trigrams)
+            })
+            .collect();
+        if orphans.is_empty() {
+            return Ok(());
+        }
+
+        for hash in self.db.purge_chunks(&orphans)? {
+            let size = self.fs.get_size(&hash).unwrap_or(0);
+            match self.fs.delete(&hash) {
+                Ok(()) => {
+                    report.chunks_deleted += 1;
+                    report.bytes_reclaimed += size;
+                }
+                Err(e) => eprintln!("Warning: failed to delete orphan chunk {}: {}", hash, e),
+            }
+        }
+        Ok(())
     }
 
     /// Clear all history for the current project.
     pub fn clear_all_history(&self) -> AppResult<usize> {
+        let _guard = self
+            .gc_lock
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
         // 1. Get all hashes before wipe
         let hashes_before = self.db.get_all_content_hashes()?;
 
         // 2. Wipe DB
         let count = self.db.delete_all()?;
 
-        // 3. Clean all chunks from FS (since DB is now empty, all are orphans)
+        // 3. Every chunk is now unreferenced; legacy whole-file objects go too
+        let mut report = GcReport::default();
+        self.sweep_chunks(&mut report)?;
         for hash in &hashes_before {
             let _ = self.fs.delete(hash);
         }
@@ -301,15 +435,176 @@ temp/
         Ok(count)
     }
 
-    /// Trigger background migration of tiered storage objects (Hot -> Warm -> Cold)
+    /// Moves CAS objects between the hot, warm and cold tiers by age, as set
+    /// by the `tiers` config. Returns the number of objects moved.
     pub fn run_migration(&self) -> AppResult<usize> {
-        Ok(0)
+        // Keeps GC from deleting an object between its read and its new copy
+        let _guard = self
+            .gc_lock
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.fs.migrate(&self.tier_config())
+    }
+
+    /// Gathers small cold objects into pack files. Returns the number of
+    /// objects packed.
+    pub fn repack(&self) -> AppResult<usize> {
+        let _guard = self
+            .gc_lock
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.fs.repack(&self.tier_config())
+    }
+
+    /// Whether `content` is stored as a blob: binary, or text over
+    /// `max_file_size_mb`.
+    pub fn is_blob(&self, content: &[u8]) -> bool {
+        self.config
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .config
+            .is_blob(content.len() as u64, cdc::is_binary(content))
+    }
+
+    /// Trains a compression dictionary for new and repacked objects when one
+    /// is due. Returns the new dictionary version.
+    pub fn train_dictionary(&self) -> AppResult<Option<u32>> {
+        let _guard = self
+            .gc_lock
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.fs.train_dictionary()
+    }
+
+    fn tier_config(&self) -> TierConfig {
+        self.config
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .config
+            .tiers
+            .clone()
+    }
+
+    /// Adds chunks missing from the trigram index, e.g. after an upgrade
+    /// dropped the old bloom filters. Returns the number of chunks indexed.
+    pub fn index_pending_trigrams(&self) -> AppResult<usize> {
+        let mut indexed = 0;
+        for chunk_hash in self.db.unindexed_chunks()? {
+            let Ok(content) = self.fs.peek(&chunk_hash) else {
+                continue;
+            };
+            self.db.update_chunk_trigrams(&chunk_hash, &content)?;
+            indexed += 1;
+        }
+        Ok(indexed)
+    }
+
+    /// Verifies history integrity, repairing what it can when `repair` is set.
+    pub fn fsck(&self, repair: bool) -> AppResult<fsck::FsckReport> {
+        if !repair {
+            return fsck::check(self);
+        }
+        let _guard = self
+            .gc_lock
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        fsck::repair(self)
+    }
+
+    /// Writes the history selected by `filter` to a portable bundle.
+    pub fn export_bundle(
+        &self,
+        path: &Path,
+        filter: &bundle::BundleFilter,
+    ) -> AppResult<bundle::BundleManifest> {
+        bundle::export(self, path, filter)
+    }
+
+    /// Writes every file as it was at `at` (an RFC3339 time, a checkpoint or
+    /// a recorded commit) into the fresh directory `dest`, with a manifest.
+    pub fn export_tree(&self, at: &str, dest: &Path) -> AppResult<tree::TreeManifest> {
+        tree::export_dir(self, at, dest)
+    }
+
+    /// [`Repository::export_tree`] as a tar stream written to `out`.
+    pub fn export_tree_tar(
+        &self,
+        at: &str,
+        out: impl std::io::Write,
+    ) -> AppResult<tree::TreeManifest> {
+        tree::export_tar(self, at, out)
+    }
+
+    /// Backfills the history from before the project was tracked with the
+    /// commits of a local git branch.
+    pub fn import_git(
+        &self,
+        options: &git_import::GitImportOptions,
+    ) -> AppResult<git_import::GitImportReport> {
+        git_import::import(self, options)
+    }
+
+    /// Merges a bundle written by [`Repository::export_bundle`] into this project.
+    pub fn import_bundle(
+        &self,
+        path: &Path,
+    ) -> AppResult<(bundle::BundleManifest, bundle::ImportReport)> {
+        let _guard = self
+            .gc_lock
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        bundle::import(self, path)
+    }
+
+    /// Rebuilds the symbols, references and deltas of a committed snapshot
+    /// from its stored content.
+    pub fn reindex_symbols(&self, snapshot: &Snapshot) -> AppResult<()> {
+        let mut content = Vec::new();
+        let mut chunks_info = Vec::new();
+        for hash in self.db.get_snapshot_chunks(snapshot.id)? {
+            let data = self.fs.read(&hash)?;
+            chunks_info.push((hash, content.len(), data.len()));
+            content.extend_from_slice(&data);
+        }
+        if chunks_info.is_empty() && self.fs.exists(&snapshot.content_hash) {
+            content = self.fs.read(&snapshot.content_hash)?;
+        }
+        if self.is_blob(&content) {
+            return Ok(());
+        }
+
+        // Newest first by time, so the version before follows this one
+        let history = self.db.get_file_history(&snapshot.file_path)?;
+        let previous = history.iter().skip_while(|s| s.id != snapshot.id).nth(1);
+        let previous = match previous {
+            Some(p) => Some((p.id, self.db.get_symbols_for_snapshot(p.id)?)),
+            None => None,
+        };
+        let ext = Path::new(&snapshot.file_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        index_semantics(
+            &self.db,
+            &bytes::Bytes::from(content),
+            ext,
+            &snapshot.file_path,
+            snapshot.id,
+            &chunks_info,
+            previous,
+        );
+        Ok(())
     }
 
     /// Save a snapshot using the dedup-first pattern (audit 5.4):
     /// Uses a single read pass to hash and compress atomically,
     /// preventing TOCTOU race between compute_hash and write_stream.
     pub fn save_snapshot_from_file(&self, file_path: &Path) -> AppResult<String> {
+        let content = Self::read_file_content(file_path)?;
+        self.save_snapshot(file_path, content)
+    }
+
+    pub fn read_file_content(file_path: &Path) -> AppResult<bytes::Bytes> {
         let file = std::fs::File::open(file_path).map_err(|e| AppError::Io {
             path: file_path.to_path_buf(),
             source: e,
@@ -326,22 +621,39 @@ temp/
         };
 
         // Wrap mmap in Bytes (sharing the memory-mapped buffer)
-        let content = bytes::Bytes::copy_from_slice(&mmap);
         // Note: although copy_from_slice copies, it allows us to handle memory
         // in a unified way. For a "pure" zero-copy we could use a custom
         // wrapper, but Bytes::copy_from_slice is a good compromise for stability.
-
-        self.save_snapshot(file_path, content)
+        Ok(bytes::Bytes::copy_from_slice(&mmap))
     }
 
     pub fn save_snapshot(&self, file_path: &Path, content: bytes::Bytes) -> AppResult<String> {
+        let Some(prepared) = self.prepare_snapshot(file_path, content)? else {
+            // Unchanged: the content hash is the latest one on record
+            let path_str = file_path.to_string_lossy();
+            return Ok(self.db.get_last_hash(&path_str)?.unwrap_or_default());
+        };
+        let ids = self.commit_snapshots(&[&prepared])?;
+        let hash = prepared.content_hash.clone();
+        self.index_snapshot(prepared, ids[0]);
+        Ok(hash)
+    }
+
+    /// Hashes, chunks and writes `content` to CAS without touching the database.
+    ///
+    /// Returns `None` when the content matches the file's latest snapshot.
+    pub fn prepare_snapshot(
+        &self,
+        file_path: &Path,
+        content: bytes::Bytes,
+    ) -> AppResult<Option<PreparedSnapshot>> {
         let path_str = file_path.to_string_lossy().to_string();
-        let full_hash = blake3::hash(&content).to_hex().to_string();
+        let full_hash = crypto::object_id(&content);
 
         // 1. Dedup check before doing anything expensive
-        let previous_snapshot_data = if let Ok(Some(last_hash)) = self.db.get_last_hash(&path_str) {
+        let previous = if let Ok(Some(last_hash)) = self.db.get_last_hash(&path_str) {
             if last_hash == full_hash {
-                return Ok(full_hash);
+                return Ok(None);
             }
 
             // Get previous symbols BEFORE inserting new snapshot
@@ -362,170 +674,351 @@ temp/
             None
         };
 
-        // 2. Insert Snapshot and get ID
-        let timestamp = chrono::Local::now().to_rfc3339();
-        let branch = self.get_current_branch();
+        // 2. Chunkify (SHP Protocol) with Semantic Awareness.
+        let (chunks, blob) = self.write_chunks(file_path, &content)?;
+
+        Ok(Some(PreparedSnapshot {
+            file_path: file_path.to_path_buf(),
+            content_hash: full_hash,
+            content,
+            timestamp: time::now_ms(),
+            branch: self.get_current_branch(),
+            commit_hash: None,
+            chunks,
+            previous,
+            event: SnapshotEvent::Modified,
+            blob,
+        }))
+    }
 
-        // Optimization: Do NOT write the full content to CAS anymore.
-        // We only store chunks (Step 3) and reassemble if needed.
-        // This saves ~50% disk space.
+    /// Cuts `content` into chunks and writes them to CAS. Only chunks go to
+    /// CAS; the full content is reassembled when needed. Binary and oversized
+    /// files are cut by content instead of by syntax, which the returned flag
+    /// tells.
+    fn write_chunks(&self, file_path: &Path, content: &bytes::Bytes) -> AppResult<(Chunks, bool)> {
+        let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let enable_compression = self.is_compression_enabled();
+        let blob = self.is_blob(content);
+        let mut chunks = Vec::new();
+        if blob {
+            for (offset, data) in cdc::chunk(content) {
+                let chunk_hash = self.fs.write(&data, enable_compression)?;
+                chunks.push((chunk_hash, offset, data.len(), data));
+            }
+        } else {
+            for chunk in crate::semantic::chunker::SemanticChunker::chunk(content.clone(), ext) {
+                let chunk_hash = self.fs.write(&chunk.data, enable_compression)?;
+                chunks.push((chunk_hash, chunk.offset, chunk.length, chunk.data));
+            }
+        }
+        Ok((chunks, blob))
+    }
 
-        let snapshot_id =
+    /// A version of `file_path` from elsewhere, recorded as of `timestamp`
+    /// on `branch` and, when known, in `commit_hash`. `None` content makes a
+    /// tombstone. `previous` is the version its symbols are diffed against.
+    pub(crate) fn prepare_historic(
+        &self,
+        file_path: &Path,
+        content: Option<bytes::Bytes>,
+        timestamp: i64,
+        branch: Option<String>,
+        commit_hash: Option<String>,
+        previous: Option<(i64, Vec<crate::models::SemanticSymbol>)>,
+    ) -> AppResult<PreparedSnapshot> {
+        let mut prepared = match content {
+            Some(content) => {
+                let (chunks, blob) = self.write_chunks(file_path, &content)?;
+                PreparedSnapshot {
+                    file_path: file_path.to_path_buf(),
+                    content_hash: crypto::object_id(&content),
+                    content,
+                    timestamp,
+                    branch: None,
+                    commit_hash: None,
+                    chunks,
+                    previous,
+                    event: SnapshotEvent::Modified,
+                    blob,
+                }
+            }
+            None => self.prepare_tombstone(file_path, SnapshotEvent::Deleted),
+        };
+        prepared.timestamp = timestamp;
+        prepared.branch = branch;
+        prepared.commit_hash = commit_hash;
+        Ok(prepared)
+    }
+
+    /// A content-less snapshot marking `file_path` as gone.
+    fn prepare_tombstone(&self, file_path: &Path, event: SnapshotEvent) -> PreparedSnapshot {
+        PreparedSnapshot {
+            file_path: file_path.to_path_buf(),
+            content_hash: String::new(),
+            content: bytes::Bytes::new(),
+            timestamp: time::now_ms(),
+            branch: self.get_current_branch(),
+            commit_hash: None,
+            chunks: Vec::new(),
+            previous: None,
+            event,
+            blob: false,
+        }
+    }
+
+    /// Records a tombstone for `path`, or for every tracked file under it when
+    /// it was a directory. Paths that still exist are left alone.
+    ///
+    /// Returns the number of tombstones written.
+    pub fn record_deletion(&self, path: &Path) -> AppResult<usize> {
+        let path_str = path.to_string_lossy().to_string();
+        let tracked = if self.db.get_last_hash(&path_str)?.is_some() {
+            vec![path_str]
+        } else {
+            let dir = format!("{}{}", path_str, std::path::MAIN_SEPARATOR);
+            self.db.live_paths_with_prefix(&dir)?
+        };
+        let tombstones: Vec<PreparedSnapshot> = tracked
+            .iter()
+            .map(Path::new)
+            .filter(|p| !p.exists())
+            .map(|p| self.prepare_tombstone(p, SnapshotEvent::Deleted))
+            .collect();
+        let refs: Vec<&PreparedSnapshot> = tombstones.iter().collect();
+        Ok(self.commit_snapshots(&refs)?.len())
+    }
+
+    /// Records that `from` was moved to `to`, for a file or a whole directory.
+    /// The old path gets a tombstone and the new one a snapshot pointing back
+    /// at it, committed together. Moves of untracked files are left to a
+    /// regular save of the new path.
+    ///
+    /// Returns the number of files moved.
+    pub fn record_rename(&self, from: &Path, to: &Path) -> AppResult<usize> {
+        let pairs: Vec<(PathBuf, PathBuf)> = if to.is_dir() {
+            let from_dir = format!("{}{}", from.to_string_lossy(), std::path::MAIN_SEPARATOR);
             self.db
-                .insert_snapshot(&path_str, &timestamp, &full_hash, branch.as_deref(), None)?;
+                .live_paths_with_prefix(&from_dir)?
+                .into_iter()
+                .map(|old| {
+                    let new = to.join(&old[from_dir.len()..]);
+                    (PathBuf::from(old), new)
+                })
+                .collect()
+        } else {
+            vec![(from.to_path_buf(), to.to_path_buf())]
+        };
 
-        // 3. Chunkify (SHP Protocol) with Semantic Awareness
-        let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let chunks = crate::semantic::chunker::SemanticChunker::chunk(content.clone(), ext);
-        let mut chunks_info = Vec::new();
+        let mut moved = 0;
+        for (old, new) in pairs {
+            let tracked = self.db.get_last_hash(&old.to_string_lossy())?.is_some();
+            if !tracked || old.exists() || !new.is_file() {
+                continue;
+            }
+            let content = Self::read_file_content(&new)?;
+            let prepared = self.prepare_snapshot(&new, content)?;
+
+            let tombstone = self.prepare_tombstone(
+                &old,
+                SnapshotEvent::RenamedTo {
+                    to: new.to_string_lossy().to_string(),
+                },
+            );
+            match prepared {
+                Some(mut prepared) => {
+                    prepared.event = SnapshotEvent::RenamedFrom {
+                        from: old.to_string_lossy().to_string(),
+                    };
+                    let ids = self.commit_snapshots(&[&tombstone, &prepared])?;
+                    self.index_snapshot(prepared, ids[1]);
+                }
+                // The destination already holds this content
+                None => {
+                    self.commit_snapshots(&[&tombstone])?;
+                }
+            }
+            moved += 1;
+        }
+        Ok(moved)
+    }
 
-        for chunk in chunks {
-            let chunk_hash = blake3::hash(&chunk.data).to_hex().to_string();
-            let enable_compression = self.is_compression_enabled();
-            self.fs.write(&chunk.data, enable_compression)?; // Filesystem CAS (Only chunks)
-            self.db.insert_chunk(&chunk_hash, "raw")?; // DB metadata
-
-            // Improvement: Trigram indexing for Grep (Background)
-            let db_c = self.db.clone();
-            let hash_c = chunk_hash.clone();
-            let data_c = chunk.data.clone();
-
-            // Only spawn if we're in a Tokio runtime context
-            if let Ok(handle) = tokio::runtime::Handle::try_current() {
-                handle.spawn(async move {
-                    let _ = db_c.update_chunk_trigrams(&hash_c, &data_c);
-                });
+    /// Pairs files that disappeared with untracked files holding exactly
+    /// their last content, for moves the watcher saw as a delete and a create.
+    pub fn match_renames(
+        &self,
+        removed: &[PathBuf],
+        added: &[PathBuf],
+    ) -> AppResult<Vec<(PathBuf, PathBuf)>> {
+        let mut by_hash: HashMap<String, &PathBuf> = HashMap::new();
+        for path in removed.iter().filter(|p| !p.exists()) {
+            if let Some(hash) = self.db.get_last_hash(&path.to_string_lossy())? {
+                by_hash.insert(hash, path);
             }
+        }
+        let mut pairs = Vec::new();
+        if by_hash.is_empty() {
+            return Ok(pairs);
+        }
+        for path in added.iter().filter(|p| p.is_file()) {
+            if self.db.get_last_hash(&path.to_string_lossy())?.is_some() {
+                continue;
+            }
+            let content = Self::read_file_content(path)?;
+            if let Some(from) = by_hash.remove(&crypto::object_id(&content)) {
+                pairs.push((from.clone(), path.clone()));
+            }
+        }
+        Ok(pairs)
+    }
 
-            chunks_info.push((chunk_hash, chunk.offset, chunk.length));
+    /// Commits the database rows of several prepared saves in one transaction.
+    pub fn commit_snapshots(&self, prepared: &[&PreparedSnapshot]) -> AppResult<Vec<i64>> {
+        let window_ms = self
+            .config
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .config
+            .changeset_window_secs as i64
+            * 1000;
+        self.commit_in_changeset(prepared, window_ms, Some(self.session_idle_ms()))
+    }
+
+    /// Commits versions from [`Repository::prepare_historic`] as a changeset
+    /// of their own, outside any session.
+    pub(crate) fn commit_historic(&self, prepared: &[&PreparedSnapshot]) -> AppResult<Vec<i64>> {
+        self.commit_in_changeset(prepared, 0, None)
+    }
+
+    /// [`Repository::commit_snapshots`] with an explicit changeset window,
+    /// tracking sessions when given their idle timeout and as backfilled
+    /// history otherwise.
+    fn commit_in_changeset(
+        &self,
+        prepared: &[&PreparedSnapshot],
+        window_ms: i64,
+        session_idle_ms: Option<i64>,
+    ) -> AppResult<Vec<i64>> {
+        let _guard = self
+            .gc_lock
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let enable_compression = self.is_compression_enabled();
+        let mut batch = WriteBatch::new();
+        for p in prepared {
+            // A GC since prepare_snapshot may have reclaimed a shared chunk
+            for (hash, _, _, data) in &p.chunks {
+                if !self.fs.exists(hash) {
+                    self.fs.write(data, enable_compression)?;
+                }
+            }
+            batch.add_snapshot(p.to_new_snapshot());
+        }
+        batch.group_changesets(window_ms);
+        match session_idle_ms {
+            Some(idle_ms) => batch.track_sessions(idle_ms),
+            None => batch.backfill(),
         }
+        self.db.commit_batch(batch)
+    }
 
-        // 4. Link Chunks to Snapshot
-        for (pos, (hash, _, _)) in chunks_info.iter().enumerate() {
-            self.db.link_snapshot_chunk(snapshot_id, hash, pos)?;
+    fn session_idle_ms(&self) -> i64 {
+        self.config
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .config
+            .session_idle_minutes as i64
+            * 60_000
+    }
+
+    /// Indexes a committed snapshot right away rather than in the background.
+    /// Returns its symbols, to diff the file's next version against.
+    pub(crate) fn index_snapshot_now(
+        &self,
+        prepared: PreparedSnapshot,
+        snapshot_id: i64,
+    ) -> Vec<crate::models::SemanticSymbol> {
+        if prepared.event.is_tombstone() || prepared.blob {
+            return Vec::new();
         }
+        let path_str = prepared.file_path.to_string_lossy().to_string();
+        let ext = prepared
+            .file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let chunks_info: Vec<(String, usize, usize)> = prepared
+            .chunks
+            .iter()
+            .map(|(hash, offset, len, _)| (hash.clone(), *offset, *len))
+            .collect();
+        index_semantics(
+            &self.db,
+            &prepared.content,
+            ext,
+            &path_str,
+            snapshot_id,
+            &chunks_info,
+            prepared.previous,
+        )
+    }
 
-        // 5. Semantic Indexing (Background)
+    /// Runs semantic indexing for a committed snapshot in the background.
+    pub fn index_snapshot(&self, prepared: PreparedSnapshot, snapshot_id: i64) {
+        if prepared.event.is_tombstone() || prepared.blob {
+            return;
+        }
+        let PreparedSnapshot {
+            file_path,
+            content,
+            chunks,
+            previous: previous_snapshot_data,
+            ..
+        } = prepared;
         let ext = file_path
             .extension()
             .and_then(|e| e.to_str())
             .unwrap_or("")
             .to_string();
+        let path_str = file_path.to_string_lossy().to_string();
+        let chunks_info: Vec<(String, usize, usize)> = chunks
+            .into_iter()
+            .map(|(hash, offset, len, _)| (hash, offset, len))
+            .collect();
         let db = self.db.clone();
-        let path_str = path_str.clone();
 
         // Only spawn if we're in a Tokio runtime context
         if let Ok(handle) = tokio::runtime::Handle::try_current() {
             handle.spawn(async move {
-                use crate::semantic::diff::SemanticDiffer;
-                if let Ok(mut parser) = SemanticParser::new() {
-                    if let Ok((symbols, references)) =
-                        parser.parse_semantic_data(&content, &ext, snapshot_id, Some(&path_str))
-                    {
-                        let mut should_save_symbols = true;
-                        let mut prev_snapshot_id = None;
-                        let mut prev_symbols = Vec::new();
-
-                        if let Some((pid, psyms)) = previous_snapshot_data {
-                            prev_snapshot_id = Some(pid);
-                            prev_symbols = psyms;
-
-                            // Calculate a "Structural Signature" of the file
-                            let current_sig: String = symbols
-                                .iter()
-                                .map(|s| &s.structural_hash)
-                                .cloned()
-                                .collect::<Vec<_>>()
-                                .join("");
-                            let last_sig: String = prev_symbols
-                                .iter()
-                                .map(|s| &s.structural_hash)
-                                .cloned()
-                                .collect::<Vec<_>>()
-                                .join("");
-
-                            if current_sig == last_sig && !current_sig.is_empty() {
-                                should_save_symbols = false;
-                            }
-                        }
-
-                        let mut deltas_to_save = Vec::new();
-                        // Compute and store Semantic Deltas
-                        if prev_snapshot_id.is_some() || !symbols.is_empty() {
-                            let deltas = SemanticDiffer::compare(
-                                &prev_symbols,
-                                &symbols,
-                                prev_snapshot_id,
-                                snapshot_id,
-                            );
-                            deltas_to_save = deltas;
-                        }
-
-                        let mut symbols_to_save = Vec::new();
-                        if should_save_symbols {
-                            // We cannot easily use a stack with parent_id if we want to batch perfectly,
-                            // but we can compute the tree structure here and then batch the list.
-                            // For simplicity, let's just collect them.
-                            let mut parent_stack: Vec<(usize, i64)> = Vec::new();
-
-                            for mut symbol in symbols {
-                                while let Some((parent_end, _)) = parent_stack.last() {
-                                    if symbol.start_byte >= *parent_end {
-                                        parent_stack.pop();
-                                    } else {
-                                        break;
-                                    }
-                                }
-                                if let Some((_, parent_db_id)) = parent_stack.last() {
-                                    symbol.parent_id = Some(*parent_db_id);
-                                }
-                                if let Some((chunk_hash, _, _)) =
-                                    chunks_info.iter().find(|(_, offset, len)| {
-                                        symbol.start_byte >= *offset
-                                            && symbol.start_byte < (*offset + *len)
-                                    })
-                                {
-                                    symbol.chunk_hash = chunk_hash.clone();
-                                }
-
-                                // To get the parent_id correctly, we still need to insert them sequentially
-                                // or compute the IDs locally. redb uses u64 IDs we generate.
-                                // Let's keep the sequential insert for symbols to maintain parent_id integrity,
-                                // but batch the rest.
-                                if let Ok(db_id) = db.insert_symbol(&symbol) {
-                                    parent_stack.push((symbol.end_byte, db_id));
-                                    symbol.id = db_id;
-                                    symbols_to_save.push(symbol);
-                                }
-                            }
-                        }
-
-                        // Use the new batch method for references and deltas (Symbols are already saved for parent_id)
-                        let _ =
-                            db.batch_insert_semantic_data(Vec::new(), deltas_to_save, references);
-                    }
-                }
+                index_semantics(
+                    &db,
+                    &content,
+                    &ext,
+                    &path_str,
+                    snapshot_id,
+                    &chunks_info,
+                    previous_snapshot_data,
+                );
             });
         }
-
-        Ok(full_hash)
     }
 
     pub fn get_current_branch(&self) -> Option<String> {
-        let head_path = Path::new(&self.project.path).join(".git/HEAD");
-        if let Ok(content) = std::fs::read_to_string(head_path) {
-            let content = content.trim();
-            if let Some(rest) = content.strip_prefix("ref: refs/heads/") {
-                return Some(rest.to_string());
-            } else {
-                // Detached HEAD or check if it's a raw SHA
-                if content.len() >= 7 {
-                    return Some(content[..7].to_string());
-                }
-                return Some(content.to_string());
-            }
+        let head = GitRepo::discover(Path::new(&self.project.path))?
+            .head()
+            .ok()?;
+        match (head.branch, head.oid) {
+            (Some(branch), _) => Some(branch),
+            // Detached HEAD
+            (None, Some(oid)) => Some(oid.to_string()[..7].to_string()),
+            (None, None) => None,
         }
-        None
+    }
+
+    /// Records the commits HEAD gained since the last call and links the
+    /// snapshots they contain. `None` when HEAD has not moved.
+    pub fn sync_git_head(&self) -> AppResult<Option<commit_link::HeadSync>> {
+        let mut last_head = self.git_head.lock().unwrap_or_else(|p| p.into_inner());
+        commit_link::sync_head(self, &mut last_head)
     }
 
     pub fn list_files(
@@ -544,10 +1037,24 @@ temp/
         self.db.get_history(file_path)
     }
 
+    pub fn get_file_history(&self, file_path: &str) -> AppResult<Vec<Snapshot>> {
+        self.db.get_file_history(file_path)
+    }
+
     pub fn get_recent_activity(&self, limit: usize) -> AppResult<Vec<Snapshot>> {
         self.db.get_recent_activity(limit)
     }
 
+    /// Snapshots taken between two instants (UTC epoch milliseconds), newest first.
+    pub fn history_between(&self, from: i64, to: i64) -> AppResult<Vec<Snapshot>> {
+        self.db.history_between(from, to)
+    }
+
+    /// Snapshot counts per local day between two instants (UTC epoch milliseconds).
+    pub fn activity_between(&self, from: i64, to: i64) -> AppResult<Vec<(String, usize)>> {
+        self.db.activity_between(from, to)
+    }
+
     pub fn get_content(&self, hash_raw: &str) -> AppResult<Vec<u8>> {
         // Try direct read first
         match self.fs.read(hash_raw) {
@@ -590,6 +1097,19 @@ temp/
         let target = Path::new(target_path);
         let project_root = Path::new(&self.project.path);
 
+        // A deleted file may have taken its directory with it
+        if let Some(parent) = target.parent()
+            && parent.starts_with(project_root)
+            && !parent
+                .components()
+                .any(|c| c == std::path::Component::ParentDir)
+        {
+            std::fs::create_dir_all(parent).map_err(|e| AppError::Io {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+
         // Security: Ensure target is within project root
         let target_canonical =
             crate::utils::validation::PathValidator::validate_within(project_root, target)?;
@@ -616,7 +1136,8 @@ temp/
             }
         }
 
-        let content = self.fs.read(&hash)?;
+        // Multi-chunk content only exists as its chunks
+        let content = self.get_content(&hash)?;
 
         // Atomic write: write to tempfile then rename (audit 1.4)
         let parent = target_canonical
@@ -644,16 +1165,38 @@ temp/
         &self,
         query: &str,
         path_filter: Option<&str>,
+        regex: bool,
     ) -> AppResult<Vec<SearchResult>> {
         if query.is_empty() {
             return Ok(Vec::new());
         }
 
-        // Improvement: Use Trigram Index to pre-filter chunks
-        let candidate_chunks = if query.len() >= 3 {
-            self.db.filter_chunks_by_trigrams(query).unwrap_or_default()
+        let (trigram_query, matcher) = if regex {
+            (
+                trigram::regex_query(query)?,
+                Some(
+                    regex::Regex::new(query)
+                        .map_err(|e| AppError::Other(anyhow::anyhow!("Invalid regex: {}", e)))?,
+                ),
+            )
         } else {
+            (trigram::literal_query(query), None)
+        };
+
+        // Posting lists narrow the snapshots that have to be read. Chunks the
+        // backfill has not reached yet are always treated as candidates.
+        let mut lookups = trigram_query.trigrams();
+        for t in trigram_query.trigrams() {
+            for (tail, head) in trigram::splits(t) {
+                lookups.insert(tail);
+                lookups.insert(head);
+            }
+        }
+        let postings = self.db.trigram_postings(&lookups)?;
+        let indexed = if trigram_query == TrigramQuery::All {
             HashSet::new()
+        } else {
+            self.db.indexed_chunks()?
         };
 
         let snapshots = self.db.get_all_snapshots_deduped()?;
@@ -661,6 +1204,8 @@ temp/
         const MAX_RESULTS: usize = 200;
         const MAX_MATCHES_PER_FILE: usize = 3;
 
+        let query_lower = query.to_lowercase();
+
         let results: Vec<SearchResult> = snapshots
             .par_iter()
             .filter(|snap| {
@@ -670,24 +1215,42 @@ temp/
                     true
                 }
             })
-            .filter(|snap| {
-                // If we have trigram results, only check snapshots that contain a matching chunk
-                if !candidate_chunks.is_empty() {
-                    if let Ok(chunks) = self.db.get_chunks_for_hash(&snap.content_hash) {
-                        return chunks.iter().any(|h| candidate_chunks.contains(h));
-                    }
+            .filter_map(|snap| {
+                let chunks = self.db.get_snapshot_chunks(snap.id).ok()?;
+                Some((snap, chunks))
+            })
+            .filter(|(_, chunks)| {
+                if trigram_query == TrigramQuery::All || chunks.iter().any(|c| !indexed.contains(c))
+                {
+                    return true;
                 }
-                true
+                let has = |id: u32, chunk: &String| {
+                    postings.get(&id).is_some_and(|set| set.contains(chunk))
+                };
+                trigram_query.matches(&|t| {
+                    chunks.iter().any(|c| has(t, c))
+                        || chunks.windows(2).any(|pair| {
+                            trigram::splits(t)
+                                .iter()
+                                .any(|(tail, head)| has(*tail, &pair[0]) && has(*head, &pair[1]))
+                        })
+                })
             })
-            .filter_map(|snap| {
-                let content = self.fs.read(&snap.content_hash).ok()?;
+            .filter_map(|(snap, chunks)| {
+                let mut content = Vec::new();
+                for chunk in &chunks {
+                    content.extend_from_slice(&self.fs.read(chunk).ok()?);
+                }
                 let text = String::from_utf8_lossy(&content);
 
-                let query_lower = query.to_lowercase();
                 let mut matches = Vec::new();
 
                 for (line_idx, line) in text.lines().enumerate() {
-                    if line.to_lowercase().contains(&query_lower) {
+                    let is_match = match &matcher {
+                        Some(re) => re.is_match(line),
+                        None => line.to_lowercase().contains(&query_lower),
+                    };
+                    if is_match {
                         // Safe truncation at char boundary
                         let trimmed = line.trim().to_string();
                         let display = if trimmed.len() > 120 {
@@ -835,6 +1398,18 @@ temp/
         self.db.get_active_session()
     }
 
+    /// Every session of the project, for [`crate::storage::timesheet::build`].
+    pub fn session_spans(&self) -> AppResult<Vec<SessionSpan>> {
+        self.db.session_spans(&self.project.name)
+    }
+
+    /// Ends the open session once it has been idle for the configured
+    /// timeout. Returns the id of the session that was closed.
+    pub fn close_idle_session(&self) -> AppResult<Option<i64>> {
+        self.db
+            .close_idle_session(time::now_ms(), self.session_idle_ms())
+    }
+
     // -----------------------------------------------------------------------
     // Mass Revert
     // -----------------------------------------------------------------------
@@ -842,24 +1417,349 @@ temp/
     /// Create a checkpoint capturing the current state of all tracked files.
     pub fn create_checkpoint(&self, description: Option<&str>) -> AppResult<String> {
         let state = self.db.get_latest_state()?;
-        let timestamp = chrono::Local::now().to_rfc3339();
-        let file_states_json = serde_json::to_string(&state)
-            .map_err(|e| AppError::Internal(format!("Failed to serialize checkpoint: {}", e)))?;
-        self.db
-            .save_checkpoint(&timestamp, description, &file_states_json)
+        self.db.save_checkpoint(time::now_ms(), description, &state)
+    }
+
+    /// The full hash and `(path, content hash)` files of the checkpoint whose
+    /// hash starts with `hash_query`.
+    fn checkpoint_manifest(&self, hash_query: &str) -> AppResult<(String, Vec<(String, String)>)> {
+        let (hash, _timestamp, _desc) = self
+            .db
+            .get_checkpoint_by_hash(hash_query)?
+            .ok_or_else(|| AppError::NotFound(format!("Checkpoint not found: {}", hash_query)))?;
+        let files = self.db.checkpoint_files(&hash)?;
+        Ok((hash, files))
+    }
+
+    /// Files that differ between checkpoints `from` and `to`, sorted by path.
+    pub fn checkpoint_diff(&self, from: &str, to: &str) -> AppResult<Vec<CheckpointChange>> {
+        let (_, old) = self.checkpoint_manifest(from)?;
+        let (_, new) = self.checkpoint_manifest(to)?;
+        let old: HashMap<String, String> = old.into_iter().collect();
+        let new: HashMap<String, String> = new.into_iter().collect();
+
+        let paths: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        let mut changes = Vec::new();
+        for path in paths {
+            let (before, after) = match (old.get(path), new.get(path)) {
+                (Some(a), Some(b)) if a == b => continue,
+                (a, b) => (a, b),
+            };
+            let before = before.map(|h| self.get_content(h)).transpose()?;
+            let after = after.map(|h| self.get_content(h)).transpose()?;
+            changes.push(self.checkpoint_change(path, before.as_deref(), after.as_deref()));
+        }
+        Ok(changes)
+    }
+
+    /// Files in the working tree that differ from checkpoint `hash_query`,
+    /// sorted by path. Only files the checkpoint or the history knows about
+    /// are compared.
+    pub fn checkpoint_status(&self, hash_query: &str) -> AppResult<Vec<CheckpointChange>> {
+        let (_, files) = self.checkpoint_manifest(hash_query)?;
+        let saved: HashMap<String, String> = files.into_iter().collect();
+        let mut paths: std::collections::BTreeSet<String> = saved.keys().cloned().collect();
+        paths.extend(
+            self.db
+                .get_latest_state()?
+                .into_iter()
+                .map(|(path, _)| path),
+        );
+
+        let mut changes = Vec::new();
+        for path in &paths {
+            let on_disk = Path::new(path);
+            let current = if on_disk.is_file() {
+                Some(std::fs::read(on_disk).map_err(|e| AppError::Io {
+                    path: on_disk.to_path_buf(),
+                    source: e,
+                })?)
+            } else {
+                None
+            };
+            let before = saved.get(path).map(|h| self.get_content(h)).transpose()?;
+            if before == current {
+                continue;
+            }
+            changes.push(self.checkpoint_change(path, before.as_deref(), current.as_deref()));
+        }
+        Ok(changes)
+    }
+
+    fn checkpoint_change(
+        &self,
+        path: &str,
+        before: Option<&[u8]>,
+        after: Option<&[u8]>,
+    ) -> CheckpointChange {
+        let kind = match (before, after) {
+            (None, _) => ChangeKind::Added,
+            (_, None) => ChangeKind::Removed,
+            _ => ChangeKind::Modified,
+        };
+        let before = before.unwrap_or_default();
+        let after = after.unwrap_or_default();
+        let (mut lines_added, mut lines_removed) = (0, 0);
+        if !self.is_blob(before) && !self.is_blob(after) {
+            use similar::{ChangeTag, TextDiff};
+            let old_text = String::from_utf8_lossy(before);
+            let new_text = String::from_utf8_lossy(after);
+            for change in TextDiff::from_lines(&old_text, &new_text).iter_all_changes() {
+                match change.tag() {
+                    ChangeTag::Insert => lines_added += 1,
+                    ChangeTag::Delete => lines_removed += 1,
+                    ChangeTag::Equal => {}
+                }
+            }
+        }
+        CheckpointChange {
+            path: path.to_string(),
+            kind,
+            lines_added,
+            lines_removed,
+        }
     }
 
     pub fn list_checkpoints(&self) -> AppResult<Vec<(String, String, Option<String>)>> {
         self.db.list_checkpoints()
     }
 
-    /// Delete a checkpoint by its hash.
+    /// Delete a checkpoint by its hash, along with its pin.
     pub fn delete_checkpoint(&self, hash: &str) -> AppResult<bool> {
         self.db.delete_checkpoint(hash)
     }
 
-    /// List all Git commits with their metadata.
+    // -----------------------------------------------------------------------
+    // Changesets
+    // -----------------------------------------------------------------------
+
+    /// The `limit` newest changesets, newest first.
+    pub fn list_changesets(&self, limit: usize) -> AppResult<Vec<Changeset>> {
+        self.db.list_changesets(limit)
+    }
+
+    /// Puts every file changeset `id` touched back the way it was before the
+    /// changeset, deleting the ones it created. Every new content is staged
+    /// before any file is touched, files already swapped are restored from
+    /// the safety save if a later one fails, and the revert is recorded as a
+    /// changeset of its own.
+    ///
+    /// Returns the number of files written or removed.
+    pub fn revert_changeset(&self, id: i64) -> AppResult<usize> {
+        let snapshots = self.db.changeset_snapshots(id)?;
+        if snapshots.is_empty() {
+            return Err(AppError::NotFound(format!("Changeset not found: {}", id)));
+        }
+        let members: HashSet<i64> = snapshots.iter().map(|s| s.id).collect();
+        let mut paths: Vec<&str> = snapshots.iter().map(|s| s.file_path.as_str()).collect();
+        paths.sort();
+        paths.dedup();
+
+        // 1. What each file looked like before the changeset
+        let project_root = Path::new(&self.project.path);
+        let mut plan: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+        for path in paths {
+            // Newest first by time: the version before is past the changeset's oldest
+            let history = self.db.get_file_history(path)?;
+            let before = history
+                .iter()
+                .rposition(|s| members.contains(&s.id))
+                .and_then(|i| history.get(i + 1))
+                .filter(|s| !s.event.is_tombstone());
+            let content = before
+                .map(|s| self.get_content(&s.content_hash))
+                .transpose()?;
+            plan.push((PathBuf::from(path), content));
+        }
+
+        // 2. Safety save of what is on disk now
+        let mut current = Vec::new();
+        let mut originals: HashMap<&Path, bytes::Bytes> = HashMap::new();
+        for (path, _) in &plan {
+            if path.is_file() {
+                let content = Self::read_file_content(path)?;
+                originals.insert(path, content.clone());
+                current.extend(self.prepare_snapshot(path, content)?);
+            }
+        }
+        self.commit_snapshots(&current.iter().collect::<Vec<_>>())?;
+
+        // 3. Stage every write next to its target
+        let mut staged = Vec::new();
+        for (path, content) in &plan {
+            let Some(content) = content else {
+                continue;
+            };
+            if let Some(parent) = path.parent()
+                && parent.starts_with(project_root)
+                && !parent
+                    .components()
+                    .any(|c| c == std::path::Component::ParentDir)
+            {
+                std::fs::create_dir_all(parent).map_err(|e| AppError::Io {
+                    path: parent.to_path_buf(),
+                    source: e,
+                })?;
+            }
+            let target =
+                crate::utils::validation::PathValidator::validate_within(project_root, path)?;
+            let parent = target
+                .parent()
+                .ok_or_else(|| AppError::Security("No parent dir".into()))?;
+            let mut temp = tempfile::NamedTempFile::new_in(parent).map_err(|e| AppError::Io {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+            std::io::Write::write_all(&mut temp, content).map_err(|e| AppError::Io {
+                path: temp.path().to_path_buf(),
+                source: e,
+            })?;
+            staged.push((temp, target, path.as_path()));
+        }
+
+        // 4. Swap the staged files in and remove the created ones, putting
+        // back what was already changed if any of it fails
+        let mut swapped: Vec<&Path> = Vec::new();
+        let mut failure = None;
+        for (temp, target, path) in staged {
+            if let Err(e) = temp.persist(&target) {
+                failure = Some(AppError::Io {
+                    path: target,
+                    source: e.error,
+                });
+                break;
+            }
+            swapped.push(path);
+        }
+        for (path, content) in &plan {
+            if failure.is_none() && content.is_none() && path.is_file() {
+                let removed =
+                    crate::utils::validation::PathValidator::validate_within(project_root, path)
+                        .and_then(|_| {
+                            std::fs::remove_file(path).map_err(|e| AppError::Io {
+                                path: path.clone(),
+                                source: e,
+                            })
+                        });
+                match removed {
+                    Ok(()) => swapped.push(path),
+                    Err(e) => failure = Some(e),
+                }
+            }
+        }
+        if let Some(e) = failure {
+            for path in swapped {
+                let restored = match originals.get(path) {
+                    Some(content) => std::fs::write(path, content),
+                    None => std::fs::remove_file(path),
+                };
+                if let Err(err) = restored {
+                    log::warn!("Failed to restore {}: {}", path.display(), err);
+                }
+            }
+            return Err(e);
+        }
+        let changed = swapped.len();
+
+        // 5. Record the revert as one new changeset
+        let mut prepared = Vec::new();
+        for (path, content) in &plan {
+            match content {
+                Some(content) => prepared
+                    .extend(self.prepare_snapshot(path, bytes::Bytes::copy_from_slice(content))?),
+                None if self.db.get_last_hash(&path.to_string_lossy())?.is_some() => {
+                    prepared.push(self.prepare_tombstone(path, SnapshotEvent::Deleted));
+                }
+                None => {}
+            }
+        }
+        let ids = self.commit_in_changeset(
+            &prepared.iter().collect::<Vec<_>>(),
+            0,
+            Some(self.session_idle_ms()),
+        )?;
+        for (prepared, id) in prepared.into_iter().zip(ids) {
+            self.index_snapshot(prepared, id);
+        }
+        Ok(changed)
+    }
+
+    // -----------------------------------------------------------------------
+    // Pins
+    // -----------------------------------------------------------------------
+
+    /// Pins the checkpoint or snapshot whose hash starts with `hash_query`,
+    /// keeping it from retention and GC. Pinning it again replaces the label.
+    pub fn pin(&self, hash_query: &str, label: &str) -> AppResult<Pin> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(AppError::Internal("A pin needs a label".into()));
+        }
+        let query = hash_query.trim().to_lowercase();
+        if query.is_empty() {
+            return Err(AppError::Internal("A pin needs a hash".into()));
+        }
+
+        let checkpoints: Vec<String> = self
+            .db
+            .list_checkpoints()?
+            .into_iter()
+            .map(|(hash, _, _)| hash)
+            .filter(|hash| hash.starts_with(&query))
+            .collect();
+        let (hash, kind) = match (checkpoints.as_slice(), self.db.resolve_hash(&query)?) {
+            ([hash], None) => (hash.clone(), PinKind::Checkpoint),
+            ([], Some(hash)) => (hash, PinKind::Snapshot),
+            _ => {
+                return Err(AppError::NotFound(format!(
+                    "No single snapshot or checkpoint matches {}",
+                    hash_query
+                )));
+            }
+        };
+        let created = time::now_ms();
+        self.db.save_pin(&hash, kind, label, created)?;
+        Ok(Pin {
+            hash,
+            label: label.to_string(),
+            kind,
+            created_at: time::format_ms(created),
+        })
+    }
+
+    /// Removes the pin whose hash starts with `hash_query`. Returns the
+    /// removed pin, or `None` when no single pin matches.
+    pub fn unpin(&self, hash_query: &str) -> AppResult<Option<Pin>> {
+        let query = hash_query.trim().to_lowercase();
+        let mut matches: Vec<Pin> = self
+            .db
+            .list_pins()?
+            .into_iter()
+            .filter(|pin| !query.is_empty() && pin.hash.starts_with(&query))
+            .collect();
+        if matches.len() != 1 {
+            return Ok(None);
+        }
+        let pin = matches.remove(0);
+        self.db.delete_pin(&pin.hash)?;
+        Ok(Some(pin))
+    }
+
+    /// Pins newest first, limited to labels containing `query` when given.
+    pub fn list_pins(&self, query: Option<&str>) -> AppResult<Vec<Pin>> {
+        let mut pins = self.db.list_pins()?;
+        if let Some(query) = query.map(str::to_lowercase) {
+            pins.retain(|pin| pin.label.to_lowercase().contains(&query));
+        }
+        Ok(pins)
+    }
+
+    /// List all Git commits with their metadata, after picking up any that
+    /// HEAD gained since the last look.
     pub fn list_commits(&self) -> AppResult<Vec<(String, String, String, String, usize)>> {
+        if let Err(e) = self.sync_git_head() {
+            log::warn!("Failed to read git HEAD: {}", e);
+        }
         self.db.get_commits()
     }
 
@@ -887,15 +1787,49 @@ temp/
         self.db.insert_git_commit(hash, message, author, timestamp)
     }
 
+    /// Record a commit reported by the post-commit hook, after any commits
+    /// that were queued while the store was busy.
+    pub fn record_git_commit(&self, event: &GitCommitEvent) -> AppResult<()> {
+        self.replay_queued_commits()?;
+        self.insert_git_commit(&event.hash, &event.message, &event.author, &event.timestamp)?;
+        if let Err(e) = commit_link::link_commit(self, &event.hash) {
+            log::warn!("Failed to link snapshots to {}: {}", event.hash, e);
+        }
+        Ok(())
+    }
+
+    /// Record the commits the hook queued while the store was busy.
+    pub fn replay_queued_commits(&self) -> AppResult<usize> {
+        let mnem_dir = Path::new(&self.project.path).join(".mnemosyne");
+        commit_queue::replay(&mnem_dir, |event| {
+            self.insert_git_commit(&event.hash, &event.message, &event.author, &event.timestamp)
+        })
+    }
+
     /// Revert entire project to a specific checkpoint hash.
     pub fn revert_to_checkpoint(&self, hash_query: &str) -> AppResult<usize> {
-        let (_timestamp, file_states_json, _desc) = self
-            .db
-            .get_checkpoint_by_hash(hash_query)?
-            .ok_or_else(|| AppError::Internal(format!("Checkpoint not found: {}", hash_query)))?;
+        self.revert_paths_to_checkpoint(hash_query, None)
+    }
 
-        let state: Vec<(String, String)> = serde_json::from_str(&file_states_json)
-            .map_err(|e| AppError::Internal(format!("Failed to parse checkpoint: {}", e)))?;
+    /// Revert the files matching `path_glob` (relative to the project root)
+    /// to a checkpoint, or every file when it is `None`. Files outside the
+    /// glob are left alone.
+    pub fn revert_paths_to_checkpoint(
+        &self,
+        hash_query: &str,
+        path_glob: Option<&str>,
+    ) -> AppResult<usize> {
+        let scope = path_glob
+            .map(|g| {
+                globset::Glob::new(g)
+                    .map(|g| g.compile_matcher())
+                    .map_err(|e| AppError::Config(format!("Invalid path glob: {}", e)))
+            })
+            .transpose()?;
+        let (_, mut state) = self.checkpoint_manifest(hash_query)?;
+        if let Some(scope) = &scope {
+            state.retain(|(path, _)| self.in_scope(scope, path));
+        }
 
         // 1. Safety checkpoint
         let short_hash = if hash_query.len() > 8 {
@@ -910,36 +1844,79 @@ temp/
 
         // 2. Restore files
         let mut count = 0;
-        for (path, hash) in state {
-            if self.restore_file(&hash, &path).is_ok() {
+        for (path, hash) in &state {
+            if self.restore_file(hash, path).is_ok() {
                 count += 1;
             }
         }
 
+        // 3. Remove files that did not exist yet
+        self.remove_files_outside(&state, scope.as_ref())?;
+
         Ok(count)
     }
 
-    /// Get details of a checkpoint by hash.
+    fn in_scope(&self, scope: &globset::GlobMatcher, path: &str) -> bool {
+        scope.is_match(bundle::relative_path(&self.project.path, path))
+    }
+
+    /// Deletes tracked files that are not part of `state`, snapshotting each
+    /// one first so the deletion can be undone. With a `scope`, only files it
+    /// matches are considered.
+    fn remove_files_outside(
+        &self,
+        state: &[(String, String)],
+        scope: Option<&globset::GlobMatcher>,
+    ) -> AppResult<usize> {
+        let wanted: HashSet<&str> = state.iter().map(|(path, _)| path.as_str()).collect();
+        let project_root = Path::new(&self.project.path);
+        let mut removed = 0;
+        for (path, _) in self.db.get_latest_state()? {
+            if wanted.contains(path.as_str())
+                || scope.is_some_and(|scope| !self.in_scope(scope, &path))
+            {
+                continue;
+            }
+            let target = Path::new(&path);
+            if target.is_file() {
+                crate::utils::validation::PathValidator::validate_within(project_root, target)?;
+                if let Err(e) = self.save_snapshot_from_file(target) {
+                    eprintln!(
+                        "Warning: failed to snapshot {} before removing it: {}",
+                        path, e
+                    );
+                    continue;
+                }
+                std::fs::remove_file(target).map_err(|e| AppError::Io {
+                    path: target.to_path_buf(),
+                    source: e,
+                })?;
+                removed += 1;
+            }
+            self.record_deletion(target)?;
+        }
+        Ok(removed)
+    }
+
+    /// Get the full hash, timestamp and description of a checkpoint by hash prefix.
     pub fn get_checkpoint_details(
         &self,
         hash: &str,
     ) -> AppResult<Option<(String, String, Option<String>)>> {
-        let result = self.db.get_checkpoint_by_hash(hash)?;
-        if let Some((ts, states_json, desc)) = result {
-            Ok(Some((ts, states_json, desc)))
-        } else {
-            Ok(None)
-        }
+        self.db.get_checkpoint_by_hash(hash)
     }
 
-    /// Revert the entire project to a specific timestamp.
+    /// Revert the entire project to a specific RFC3339 timestamp (any offset).
     /// Creates a safety checkpoint first, then restores all files.
     pub fn revert_to_timestamp(&self, timestamp: &str) -> AppResult<usize> {
+        let at = time::parse_ms(timestamp)
+            .ok_or_else(|| AppError::Internal(format!("Invalid timestamp: {}", timestamp)))?;
+
         // 1. Safety checkpoint
         self.create_checkpoint(Some("Pre-revert safety checkpoint"))?;
 
         // 2. Get the project state at that timestamp
-        let state = self.db.get_state_at_timestamp(timestamp)?;
+        let state = self.db.get_state_at_timestamp(at)?;
         if state.is_empty() {
             return Err(AppError::Internal(
                 "No snapshots found before the given timestamp".into(),
@@ -957,6 +1934,9 @@ temp/
             }
         }
 
+        // 4. Remove files created since
+        self.remove_files_outside(&state, None)?;
+
         Ok(restored)
     }
 
@@ -1006,7 +1986,7 @@ temp/
     }
 
     pub fn get_file_info(&self, file_path: &str) -> AppResult<crate::protocol::FileInfoResponse> {
-        let history = self.db.get_history(file_path)?;
+        let history = self.db.get_file_history(file_path)?;
         let count = history.len();
         let mut total_bytes = 0;
         let mut first_seen = String::new();
@@ -1200,3 +2180,103 @@ temp/
         Ok(locations)
     }
 }
+
+/// Parses the symbols and references of a snapshot and stores them together
+/// with the semantic deltas against the file's previous snapshot. Returns the
+/// symbols, which are the previous ones' when the structure did not change.
+fn index_semantics(
+    db: &Database,
+    content: &bytes::Bytes,
+    ext: &str,
+    path_str: &str,
+    snapshot_id: i64,
+    chunks_info: &[(String, usize, usize)],
+    previous_snapshot_data: Option<(i64, Vec<crate::models::SemanticSymbol>)>,
+) -> Vec<crate::models::SemanticSymbol> {
+    use crate::semantic::diff::SemanticDiffer;
+    if let Ok(mut parser) = SemanticParser::new() {
+        if let Ok((symbols, references)) =
+            parser.parse_semantic_data(content, ext, snapshot_id, Some(path_str))
+        {
+            let mut should_save_symbols = true;
+            let mut prev_snapshot_id = None;
+            let mut prev_symbols = Vec::new();
+
+            if let Some((pid, psyms)) = previous_snapshot_data {
+                prev_snapshot_id = Some(pid);
+                prev_symbols = psyms;
+
+                // Calculate a "Structural Signature" of the file
+                let current_sig: String = symbols
+                    .iter()
+                    .map(|s| &s.structural_hash)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("");
+                let last_sig: String = prev_symbols
+                    .iter()
+                    .map(|s| &s.structural_hash)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("");
+
+                if current_sig == last_sig && !current_sig.is_empty() {
+                    should_save_symbols = false;
+                }
+            }
+
+            let mut deltas_to_save = Vec::new();
+            // Compute and store Semantic Deltas
+            if prev_snapshot_id.is_some() || !symbols.is_empty() {
+                let deltas =
+                    SemanticDiffer::compare(&prev_symbols, &symbols, prev_snapshot_id, snapshot_id);
+                deltas_to_save = deltas;
+            }
+
+            let mut symbols_to_save = Vec::new();
+            if should_save_symbols {
+                // We cannot easily use a stack with parent_id if we want to batch perfectly,
+                // but we can compute the tree structure here and then batch the list.
+                // For simplicity, let's just collect them.
+                let mut parent_stack: Vec<(usize, i64)> = Vec::new();
+
+                for mut symbol in symbols {
+                    while let Some((parent_end, _)) = parent_stack.last() {
+                        if symbol.start_byte >= *parent_end {
+                            parent_stack.pop();
+                        } else {
+                            break;
+                        }
+                    }
+                    if let Some((_, parent_db_id)) = parent_stack.last() {
+                        symbol.parent_id = Some(*parent_db_id);
+                    }
+                    if let Some((chunk_hash, _, _)) = chunks_info.iter().find(|(_, offset, len)| {
+                        symbol.start_byte >= *offset && symbol.start_byte < (*offset + *len)
+                    }) {
+                        symbol.chunk_hash = chunk_hash.clone();
+                    }
+
+                    // To get the parent_id correctly, we still need to insert them sequentially
+                    // or compute the IDs locally. redb uses u64 IDs we generate.
+                    // Let's keep the sequential insert for symbols to maintain parent_id integrity,
+                    // but batch the rest.
+                    if let Ok(db_id) = db.insert_symbol(&symbol) {
+                        parent_stack.push((symbol.end_byte, db_id));
+                        symbol.id = db_id;
+                        symbols_to_save.push(symbol);
+                    }
+                }
+            }
+
+            // Use the new batch method for references and deltas (Symbols are already saved for parent_id)
+            let _ = db.batch_insert_semantic_data(Vec::new(), deltas_to_save, references);
+            return if should_save_symbols {
+                symbols_to_save
+            } else {
+                prev_symbols
+            };
+        }
+    }
+    Vec::new()
+}
@@ -0,0 +1,224 @@
+//! Generational thinning of snapshot history.
+//!
+//! Snapshots age through the tiers of a [`RetentionPolicy`]: every save is
+//! kept at first, then the newest snapshot of each file per hour, per day and
+//! finally per week survives. Commit-linked and checkpoint-referenced
+//! snapshots, the first and last snapshot of every session and the latest
+//! snapshot of every file are never pruned.
+
+use crate::config::RetentionPolicy;
+use chrono::{DateTime, TimeZone};
+use std::collections::{HashMap, HashSet};
+
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+/// A snapshot's `(timestamp, id)`, the order history is read in.
+type At = (i64, i64);
+
+/// What retention needs to know about a snapshot.
+#[derive(Debug, Clone)]
+pub struct RetentionCandidate {
+    pub id: i64,
+    pub file_path_id: u32,
+    /// UTC epoch milliseconds.
+    pub timestamp: i64,
+    pub session_id: Option<i64>,
+    /// Linked to a git commit or referenced by a checkpoint.
+    pub exempt: bool,
+}
+
+/// Ids of the snapshots `policy` no longer keeps as of `now`.
+pub fn select_prunable<Tz: TimeZone>(
+    candidates: &[RetentionCandidate],
+    policy: &RetentionPolicy,
+    now: &DateTime<Tz>,
+) -> HashSet<i64> {
+    let now_tz = now.timezone();
+    let now = now.naive_local().and_utc().timestamp();
+    let mut keep: HashSet<i64> = HashSet::new();
+
+    // By (timestamp, id): backfilled history is older than its ids suggest
+    let mut session_bounds: HashMap<i64, (At, At)> = HashMap::new();
+    let mut latest_per_file: HashMap<u32, At> = HashMap::new();
+    for c in candidates {
+        if c.exempt {
+            keep.insert(c.id);
+        }
+        let at = (c.timestamp, c.id);
+        if let Some(session) = c.session_id {
+            let bounds = session_bounds.entry(session).or_insert((at, at));
+            bounds.0 = bounds.0.min(at);
+            bounds.1 = bounds.1.max(at);
+        }
+        let latest = latest_per_file.entry(c.file_path_id).or_insert(at);
+        *latest = (*latest).max(at);
+    }
+    for (first, last) in session_bounds.values() {
+        keep.insert(first.1);
+        keep.insert(last.1);
+    }
+    keep.extend(latest_per_file.values().map(|(_, id)| *id));
+
+    // (file, bucket width, bucket index) -> newest snapshot in that bucket
+    let mut buckets: HashMap<(u32, i64, i64), (i64, i64)> = HashMap::new();
+    for c in candidates {
+        // Bucket on local wall-clock time so "one per day" follows the user's days
+        let Some(ts) = now_tz.timestamp_millis_opt(c.timestamp).earliest() else {
+            keep.insert(c.id);
+            continue;
+        };
+        let ts = ts.naive_local().and_utc().timestamp();
+        let age = now - ts;
+
+        let width = if age < policy.keep_all_hours as i64 * HOUR {
+            keep.insert(c.id);
+            continue;
+        } else if age < policy.hourly_days as i64 * DAY {
+            HOUR
+        } else if age < policy.daily_days as i64 * DAY {
+            DAY
+        } else if policy.weekly_weeks == 0 || age < policy.weekly_weeks as i64 * WEEK {
+            WEEK
+        } else {
+            continue;
+        };
+
+        let key = (c.file_path_id, width, ts.div_euclid(width));
+        let entry = buckets.entry(key).or_insert((ts, c.id));
+        if (ts, c.id) > *entry {
+            *entry = (ts, c.id);
+        }
+    }
+    keep.extend(buckets.values().map(|(_, id)| *id));
+
+    candidates
+        .iter()
+        .map(|c| c.id)
+        .filter(|id| !keep.contains(id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn snap(id: i64, file: u32, at: DateTime<Utc>, session: Option<i64>) -> RetentionCandidate {
+        RetentionCandidate {
+            id,
+            file_path_id: file,
+            timestamp: at.timestamp_millis(),
+            session_id: session,
+            exempt: false,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 15, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_recent_snapshots_are_all_kept() {
+        let now = now();
+        let candidates: Vec<_> = (1..=10)
+            .map(|i| snap(i, 1, now - Duration::minutes(60 - i), None))
+            .collect();
+        let prunable = select_prunable(&candidates, &RetentionPolicy::default(), &now);
+        assert!(prunable.is_empty());
+    }
+
+    #[test]
+    fn test_hourly_tier_keeps_newest_per_hour() {
+        let now = now();
+        let two_days = now - Duration::days(2);
+        let candidates = vec![
+            snap(1, 1, two_days, None),
+            snap(2, 1, two_days + Duration::minutes(10), None),
+            snap(3, 1, two_days + Duration::minutes(20), None),
+            snap(4, 2, two_days + Duration::minutes(5), None),
+            snap(5, 1, now, None),
+            snap(6, 2, now, None),
+        ];
+        let prunable = select_prunable(&candidates, &RetentionPolicy::default(), &now);
+        assert_eq!(prunable, HashSet::from([1, 2]));
+    }
+
+    #[test]
+    fn test_daily_and_weekly_tiers() {
+        let now = now();
+        let ten_days = now - Duration::days(10);
+        let ten_weeks = now - Duration::weeks(10);
+        let candidates = vec![
+            snap(1, 1, ten_weeks, None),
+            snap(2, 1, ten_weeks + Duration::hours(1), None),
+            snap(3, 1, ten_days, None),
+            snap(4, 1, ten_days + Duration::hours(3), None),
+            snap(5, 1, now, None),
+        ];
+        let prunable = select_prunable(&candidates, &RetentionPolicy::default(), &now);
+        assert_eq!(prunable, HashSet::from([1, 3]));
+
+        let bounded = RetentionPolicy {
+            weekly_weeks: 4,
+            ..RetentionPolicy::default()
+        };
+        let prunable = select_prunable(&candidates, &bounded, &now);
+        assert_eq!(prunable, HashSet::from([1, 2, 3]));
+    }
+
+    #[test]
+    fn test_session_bounds_and_exempt_snapshots_are_kept() {
+        let now = now();
+        let old = now - Duration::days(3);
+        let mut candidates = vec![
+            snap(1, 1, old, Some(7)),
+            snap(2, 1, old + Duration::minutes(1), Some(7)),
+            snap(3, 1, old + Duration::minutes(2), Some(7)),
+            snap(4, 1, old + Duration::minutes(3), Some(7)),
+            snap(5, 1, old + Duration::minutes(4), None),
+            snap(6, 1, now, None),
+        ];
+        candidates[1].exempt = true;
+        let prunable = select_prunable(&candidates, &RetentionPolicy::default(), &now);
+        // 1 and 4 bound the session, 2 is exempt, 5 is the newest of its hour
+        assert_eq!(prunable, HashSet::from([3]));
+    }
+
+    #[test]
+    fn test_latest_snapshot_of_file_survives_expiry() {
+        let now = now();
+        let policy = RetentionPolicy {
+            weekly_weeks: 1,
+            ..RetentionPolicy::default()
+        };
+        let candidates = vec![
+            snap(1, 1, now - Duration::weeks(60), None),
+            snap(2, 1, now - Duration::weeks(50), None),
+        ];
+        let prunable = select_prunable(&candidates, &policy, &now);
+        assert_eq!(prunable, HashSet::from([1]));
+    }
+
+    #[test]
+    fn test_backfilled_history_is_ordered_by_time() {
+        let now = now();
+        let policy = RetentionPolicy {
+            weekly_weeks: 1,
+            ..RetentionPolicy::default()
+        };
+        // 3 and 6 were imported after the others were saved, but are older
+        let candidates = vec![
+            snap(1, 1, now - Duration::weeks(50), Some(7)),
+            snap(2, 1, now - Duration::weeks(40), Some(7)),
+            snap(3, 1, now - Duration::weeks(60), Some(7)),
+            snap(4, 1, now - Duration::weeks(30), None),
+            snap(5, 2, now - Duration::weeks(20), None),
+            snap(6, 2, now - Duration::weeks(45), None),
+        ];
+        let prunable = select_prunable(&candidates, &policy, &now);
+        // 3 and 2 bound the session, 4 and 5 are the latest of their files
+        assert_eq!(prunable, HashSet::from([1, 6]));
+    }
+}
@@ -0,0 +1,1177 @@
+//! On-disk schema versioning for the redb store.
+//!
+//! Every record value is wrapped in a small envelope (magic bytes plus a
+//! record version) so a format change is detected instead of the row failing
+//! to decode and silently dropping out of history. Structural changes are
+//! expressed as ordered [`Migration`]s that `Database::new` applies on open.
+
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use crate::storage::database::{
+    CHECKPOINT_FILES, CHECKPOINTS, CHUNK_REFS, CHUNK_TRIGRAMS, CHUNKS, CheckpointData, EventData,
+    FILE_SNAPSHOTS, GIT_COMMITS, HASH_PREFIX_LEN, HASH_PREFIXES, METADATA, PINS, QUARANTINE,
+    SESSIONS, SNAPSHOT_CHUNKS, SNAPSHOT_TIMES, SNAPSHOTS, STRING_INDEX, STRINGS, SYMBOL_DELTAS,
+    SYMBOL_REFERENCES, SYMBOLS, SessionData, SnapshotData, TRIGRAM_POSTINGS, intern_string_in,
+};
+use crate::utils::time::parse_ms;
+use redb::{ReadableTable, TableDefinition, TableHandle, WriteTransaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Schema version written by this build. Bump it together with a new entry in [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u64 = 10;
+
+/// Version of the record envelope layout.
+pub const RECORD_VERSION: u8 = 1;
+
+const RECORD_MAGIC: [u8; 3] = [0xFE, b'M', b'N'];
+const HEADER_LEN: usize = RECORD_MAGIC.len() + 1;
+/// Set on the version byte of a record whose payload is sealed.
+const SEALED_FLAG: u8 = 0x80;
+
+/// Key of the row a record is stored under.
+pub(crate) trait RowKey {
+    fn row_bytes(&self) -> Vec<u8>;
+}
+
+impl RowKey for u64 {
+    fn row_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
+impl RowKey for u32 {
+    fn row_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
+impl RowKey for &str {
+    fn row_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+/// What a sealed record is bound to: its table and key, so it fails to open
+/// when copied into another row.
+pub(crate) fn row_binding(table: impl TableHandle, key: impl RowKey) -> Vec<u8> {
+    let mut aad = table.name().as_bytes().to_vec();
+    aad.push(0);
+    aad.extend_from_slice(&key.row_bytes());
+    aad
+}
+
+/// Encodes the record stored under `key` in `table`, sealing it while
+/// history is encrypted.
+pub(crate) fn encode_record<T: Serialize>(
+    table: impl TableHandle,
+    key: impl RowKey,
+    value: &T,
+) -> AppResult<Vec<u8>> {
+    let payload = bincode::serialize(value).map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(match crypto::seal(&payload, &row_binding(table, key))? {
+        Some(sealed) => wrap_payload_as(RECORD_VERSION | SEALED_FLAG, &sealed),
+        None => wrap_payload(&payload),
+    })
+}
+
+/// Decodes the record stored under `key` in `table`.
+pub(crate) fn decode_record<T: DeserializeOwned>(
+    table: impl TableHandle,
+    key: impl RowKey,
+    bytes: &[u8],
+) -> AppResult<T> {
+    let (version, payload) = split_envelope(bytes);
+    let opened;
+    let payload = if version & SEALED_FLAG != 0 {
+        opened = crypto::open(payload, &row_binding(table, key))?;
+        &opened[..]
+    } else {
+        payload
+    };
+    let version = version & !SEALED_FLAG;
+    if version > RECORD_VERSION {
+        return Err(AppError::Database(format!(
+            "Record version {} is newer than this build supports ({})",
+            version, RECORD_VERSION
+        )));
+    }
+    bincode::deserialize(payload).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn wrap_payload(payload: &[u8]) -> Vec<u8> {
+    wrap_payload_as(RECORD_VERSION, payload)
+}
+
+fn wrap_payload_as(version: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(&RECORD_MAGIC);
+    out.push(version);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Returns the record version and its payload. Rows written before envelopes
+/// existed are reported as version 0.
+fn split_envelope(bytes: &[u8]) -> (u8, &[u8]) {
+    if bytes.len() >= HEADER_LEN && bytes[..RECORD_MAGIC.len()] == RECORD_MAGIC {
+        (bytes[RECORD_MAGIC.len()], &bytes[HEADER_LEN..])
+    } else {
+        (0, bytes)
+    }
+}
+
+/// One ordered, idempotent step from `version - 1` to `version`.
+pub struct Migration {
+    pub version: u64,
+    pub description: &'static str,
+    apply: fn(&WriteTransaction) -> AppResult<usize>,
+}
+
+impl Migration {
+    pub(crate) fn apply(&self, txn: &WriteTransaction) -> AppResult<usize> {
+        (self.apply)(txn)
+    }
+}
+
+/// Registry of every migration, in the order they must run.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Wrap records in versioned envelopes",
+        apply: migrate_record_envelopes,
+    },
+    Migration {
+        version: 2,
+        description: "Build per-file snapshot index",
+        apply: migrate_file_snapshot_index,
+    },
+    Migration {
+        version: 3,
+        description: "Drop chunk trigram blooms in favour of posting lists",
+        apply: migrate_drop_trigram_blooms,
+    },
+    Migration {
+        version: 4,
+        description: "Count chunk references",
+        apply: migrate_chunk_refs,
+    },
+    Migration {
+        version: 5,
+        description: "Store timestamps as epoch milliseconds",
+        apply: migrate_epoch_timestamps,
+    },
+    Migration {
+        version: 6,
+        description: "Record deletions and renames on snapshots",
+        apply: migrate_snapshot_events,
+    },
+    Migration {
+        version: 7,
+        description: "Store checkpoint files as rows",
+        apply: migrate_checkpoint_files,
+    },
+    Migration {
+        version: 8,
+        description: "Give snapshots a changeset",
+        apply: migrate_snapshot_changesets,
+    },
+    Migration {
+        version: 9,
+        description: "Index snapshots by hash prefix",
+        apply: build_hash_prefixes,
+    },
+    Migration {
+        version: 10,
+        description: "Index the edges of chunks for search",
+        apply: migrate_chunk_edges,
+    },
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    pub version: u64,
+    pub description: String,
+    pub rows_changed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub dry_run: bool,
+    pub applied: Vec<AppliedMigration>,
+    pub backup_path: Option<PathBuf>,
+}
+
+impl MigrationReport {
+    pub fn is_noop(&self) -> bool {
+        self.applied.is_empty()
+    }
+}
+
+/// `SnapshotData` as written before `commit_message` was added.
+#[derive(Deserialize)]
+struct SnapshotDataV0 {
+    id: i64,
+    file_path_id: u32,
+    timestamp: String,
+    content_hash: String,
+    git_branch_id: Option<u32>,
+    session_id: Option<i64>,
+    commit_hash: Option<String>,
+}
+
+/// `SnapshotData` as written before timestamps became epoch milliseconds.
+#[derive(Serialize, Deserialize)]
+struct SnapshotDataV1 {
+    id: i64,
+    file_path_id: u32,
+    timestamp: String,
+    content_hash: String,
+    git_branch_id: Option<u32>,
+    session_id: Option<i64>,
+    commit_hash: Option<String>,
+    commit_message: Option<String>,
+}
+
+/// `SnapshotData` as written before snapshots carried an event.
+#[derive(Deserialize)]
+struct SnapshotDataV2 {
+    id: i64,
+    file_path_id: u32,
+    timestamp: i64,
+    content_hash: String,
+    git_branch_id: Option<u32>,
+    session_id: Option<i64>,
+    commit_hash: Option<String>,
+    commit_message: Option<String>,
+}
+
+/// `SnapshotData` as written before snapshots carried a changeset.
+#[derive(Deserialize)]
+struct SnapshotDataV3 {
+    id: i64,
+    file_path_id: u32,
+    timestamp: i64,
+    content_hash: String,
+    git_branch_id: Option<u32>,
+    session_id: Option<i64>,
+    commit_hash: Option<String>,
+    commit_message: Option<String>,
+    event: EventData,
+}
+
+/// `SessionData` as written before timestamps became epoch milliseconds.
+#[derive(Deserialize)]
+struct SessionDataV1 {
+    id: i64,
+    start_time: String,
+    end_time: Option<String>,
+    git_branch_id: Option<u32>,
+    file_count: usize,
+    snapshot_count: usize,
+}
+
+/// `CheckpointData` as written before timestamps became epoch milliseconds.
+#[derive(Deserialize)]
+struct CheckpointDataV1 {
+    hash: String,
+    timestamp: String,
+    description: Option<String>,
+    file_states: String,
+}
+
+/// `CheckpointData` as written before a checkpoint's files became rows.
+#[derive(Serialize, Deserialize)]
+struct CheckpointDataV2 {
+    hash: String,
+    timestamp: i64,
+    description: Option<String>,
+    /// JSON list of `(path, content hash)`.
+    file_states: String,
+}
+
+/// The leading fields every snapshot layout shares; bincode ignores the rest.
+#[derive(Deserialize)]
+struct SnapshotKey {
+    _id: i64,
+    file_path_id: u32,
+}
+
+fn upgrade_snapshot(payload: &[u8]) -> Option<SnapshotDataV1> {
+    if let Ok(data) = bincode::deserialize::<SnapshotDataV1>(payload) {
+        return Some(data);
+    }
+    let old: SnapshotDataV0 = bincode::deserialize(payload).ok()?;
+    Some(SnapshotDataV1 {
+        id: old.id,
+        file_path_id: old.file_path_id,
+        timestamp: old.timestamp,
+        content_hash: old.content_hash,
+        git_branch_id: old.git_branch_id,
+        session_id: old.session_id,
+        commit_hash: old.commit_hash,
+        commit_message: None,
+    })
+}
+
+fn migrate_record_envelopes(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut changed = 0;
+
+    let mut snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let (version, payload) = split_envelope(v.value());
+        if version != 0 {
+            continue;
+        }
+        match upgrade_snapshot(payload) {
+            Some(data) => rewrites.push((k.value(), encode_record(SNAPSHOTS, k.value(), &data)?)),
+            // Leave the row untouched so a later migration can still recover it
+            None => log::warn!("Snapshot {} has an unknown legacy layout", k.value()),
+        }
+    }
+    for (k, bytes) in rewrites {
+        snapshots
+            .insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        changed += 1;
+    }
+    drop(snapshots);
+
+    for table in [SESSIONS, SYMBOLS, SYMBOL_REFERENCES, SYMBOL_DELTAS] {
+        changed += wrap_legacy_rows(txn, table)?;
+    }
+    for table in [GIT_COMMITS, CHECKPOINTS, CHUNKS] {
+        changed += wrap_legacy_keyed_rows(txn, table)?;
+    }
+    Ok(changed)
+}
+
+fn wrap_legacy_rows(
+    txn: &WriteTransaction,
+    table: TableDefinition<u64, &[u8]>,
+) -> AppResult<usize> {
+    let mut t = txn
+        .open_table(table)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in t.iter().map_err(|e| AppError::Database(e.to_string()))? {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let (version, payload) = split_envelope(v.value());
+        if version == 0 {
+            rewrites.push((k.value(), wrap_payload(payload)));
+        }
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        t.insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+fn wrap_legacy_keyed_rows(
+    txn: &WriteTransaction,
+    table: TableDefinition<&str, &[u8]>,
+) -> AppResult<usize> {
+    let mut t = txn
+        .open_table(table)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in t.iter().map_err(|e| AppError::Database(e.to_string()))? {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let (version, payload) = split_envelope(v.value());
+        if version == 0 {
+            rewrites.push((k.value().to_string(), wrap_payload(payload)));
+        }
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        t.insert(k.as_str(), &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+pub(crate) fn migrate_file_snapshot_index(txn: &WriteTransaction) -> AppResult<usize> {
+    let snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut index = txn
+        .open_table(FILE_SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut changed = 0;
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SnapshotKey>(SNAPSHOTS, id.value(), v.value()) else {
+            continue;
+        };
+        if data.file_path_id == 0 {
+            continue;
+        }
+        let existed = index
+            .insert((data.file_path_id, id.value()), ())
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .is_some();
+        if !existed {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Bloom rows are cleared so every chunk is re-indexed into the posting
+/// lists by the maintenance backfill, which can read chunk content from CAS.
+fn migrate_drop_trigram_blooms(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut markers = txn
+        .open_table(CHUNK_TRIGRAMS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut removed = 0;
+    markers
+        .retain(|_, _| {
+            removed += 1;
+            false
+        })
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(removed)
+}
+
+/// Chunks indexed before their first and last bytes were recorded are
+/// re-indexed by the maintenance backfill, so matches that straddle two
+/// chunks are found.
+fn migrate_chunk_edges(txn: &WriteTransaction) -> AppResult<usize> {
+    migrate_drop_trigram_blooms(txn)
+}
+
+pub(crate) fn migrate_chunk_refs(txn: &WriteTransaction) -> AppResult<usize> {
+    let links = txn
+        .open_table(SNAPSHOT_CHUNKS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for res in links
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (_, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        *counts.entry(v.value().to_string()).or_default() += 1;
+    }
+    let mut refs = txn
+        .open_table(CHUNK_REFS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    refs.retain(|_, _| false)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for (hash, count) in &counts {
+        refs.insert(hash.as_str(), *count)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(counts.len())
+}
+
+/// Timestamps that no longer parse keep the row readable at the epoch
+/// instead of dropping it from history.
+fn legacy_ms(kind: &str, value: &str) -> i64 {
+    parse_ms(value).unwrap_or_else(|| {
+        log::warn!("{} has an unreadable timestamp {:?}", kind, value);
+        0
+    })
+}
+
+fn migrate_epoch_timestamps(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut changed = 0;
+
+    let mut snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(old) = decode_record::<SnapshotDataV1>(SNAPSHOTS, k.value(), v.value()) else {
+            continue;
+        };
+        let data = SnapshotData {
+            id: old.id,
+            file_path_id: old.file_path_id,
+            timestamp: legacy_ms("Snapshot", &old.timestamp),
+            content_hash: old.content_hash,
+            git_branch_id: old.git_branch_id,
+            session_id: old.session_id,
+            commit_hash: old.commit_hash,
+            commit_message: old.commit_message,
+            event: EventData::Modified,
+            changeset_id: None,
+        };
+        rewrites.push((k.value(), encode_record(SNAPSHOTS, k.value(), &data)?));
+    }
+    for (k, bytes) in rewrites {
+        snapshots
+            .insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        changed += 1;
+    }
+    drop(snapshots);
+
+    let mut sessions = txn
+        .open_table(SESSIONS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in sessions
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(old) = decode_record::<SessionDataV1>(SESSIONS, k.value(), v.value()) else {
+            continue;
+        };
+        let data = SessionData {
+            id: old.id,
+            start_time: legacy_ms("Session", &old.start_time),
+            end_time: old.end_time.map(|t| legacy_ms("Session", &t)),
+            git_branch_id: old.git_branch_id,
+            file_count: old.file_count,
+            snapshot_count: old.snapshot_count,
+        };
+        rewrites.push((k.value(), encode_record(SESSIONS, k.value(), &data)?));
+    }
+    for (k, bytes) in rewrites {
+        sessions
+            .insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        changed += 1;
+    }
+    drop(sessions);
+
+    let mut checkpoints = txn
+        .open_table(CHECKPOINTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in checkpoints
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(old) = decode_record::<CheckpointDataV1>(CHECKPOINTS, k.value(), v.value()) else {
+            continue;
+        };
+        let data = CheckpointDataV2 {
+            hash: old.hash,
+            timestamp: legacy_ms("Checkpoint", &old.timestamp),
+            description: old.description,
+            file_states: old.file_states,
+        };
+        rewrites.push((
+            k.value().to_string(),
+            encode_record(CHECKPOINTS, k.value(), &data)?,
+        ));
+    }
+    for (k, bytes) in rewrites {
+        checkpoints
+            .insert(k.as_str(), &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        changed += 1;
+    }
+    drop(checkpoints);
+
+    build_snapshot_times(txn)?;
+    Ok(changed)
+}
+
+fn migrate_snapshot_events(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        // The older layout is a prefix of the current one, so try the current one first
+        if decode_record::<SnapshotData>(SNAPSHOTS, k.value(), v.value()).is_ok() {
+            continue;
+        }
+        let Ok(old) = decode_record::<SnapshotDataV2>(SNAPSHOTS, k.value(), v.value()) else {
+            continue;
+        };
+        let data = SnapshotData {
+            id: old.id,
+            file_path_id: old.file_path_id,
+            timestamp: old.timestamp,
+            content_hash: old.content_hash,
+            git_branch_id: old.git_branch_id,
+            session_id: old.session_id,
+            commit_hash: old.commit_hash,
+            commit_message: old.commit_message,
+            event: EventData::Modified,
+            changeset_id: None,
+        };
+        rewrites.push((k.value(), encode_record(SNAPSHOTS, k.value(), &data)?));
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        snapshots
+            .insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+/// Snapshots saved before changesets existed are left out of them.
+fn migrate_snapshot_changesets(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        if decode_record::<SnapshotData>(SNAPSHOTS, k.value(), v.value()).is_ok() {
+            continue;
+        }
+        let Ok(old) = decode_record::<SnapshotDataV3>(SNAPSHOTS, k.value(), v.value()) else {
+            continue;
+        };
+        let data = SnapshotData {
+            id: old.id,
+            file_path_id: old.file_path_id,
+            timestamp: old.timestamp,
+            content_hash: old.content_hash,
+            git_branch_id: old.git_branch_id,
+            session_id: old.session_id,
+            commit_hash: old.commit_hash,
+            commit_message: old.commit_message,
+            event: old.event,
+            changeset_id: None,
+        };
+        rewrites.push((k.value(), encode_record(SNAPSHOTS, k.value(), &data)?));
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        snapshots
+            .insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+fn migrate_checkpoint_files(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut checkpoints = txn
+        .open_table(CHECKPOINTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut old = Vec::new();
+    for res in checkpoints
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        // The current layout lacks the trailing list, so it never decodes as the old one
+        if let Ok(data) = decode_record::<CheckpointDataV2>(CHECKPOINTS, k.value(), v.value()) {
+            old.push((k.value().to_string(), data));
+        }
+    }
+
+    let mut meta = txn
+        .open_table(METADATA)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut strings = txn
+        .open_table(STRINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut index = txn
+        .open_table(STRING_INDEX)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rows = txn
+        .open_table(CHECKPOINT_FILES)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let changed = old.len();
+    for (key, data) in old {
+        let files: Vec<(String, String)> =
+            serde_json::from_str(&data.file_states).unwrap_or_else(|e| {
+                log::warn!("Checkpoint {} has an unreadable file list: {}", key, e);
+                Vec::new()
+            });
+        for (path, content_hash) in &files {
+            let path_id = intern_string_in(&mut meta, &mut strings, &mut index, path)?;
+            rows.insert((key.as_str(), path_id), content_hash.as_str())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+        let data = CheckpointData {
+            hash: data.hash,
+            timestamp: data.timestamp,
+            description: data.description,
+        };
+        checkpoints
+            .insert(
+                key.as_str(),
+                &*encode_record(CHECKPOINTS, key.as_str(), &data)?,
+            )
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+/// Adds every decodable snapshot to the `(timestamp, snapshot_id)` index.
+pub(crate) fn build_snapshot_times(txn: &WriteTransaction) -> AppResult<usize> {
+    let snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut index = txn
+        .open_table(SNAPSHOT_TIMES)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut changed = 0;
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SnapshotData>(SNAPSHOTS, id.value(), v.value()) else {
+            continue;
+        };
+        if data.file_path_id == 0 {
+            continue;
+        }
+        let existed = index
+            .insert((data.timestamp, id.value()), ())
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .is_some();
+        if !existed {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Adds every decodable snapshot with content to the
+/// `(hash prefix key, snapshot_id)` index.
+pub(crate) fn build_hash_prefixes(txn: &WriteTransaction) -> AppResult<usize> {
+    let snapshots = txn
+        .open_table(SNAPSHOTS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut index = txn
+        .open_table(HASH_PREFIXES)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut changed = 0;
+    for res in snapshots
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let Ok(data) = decode_record::<SnapshotData>(SNAPSHOTS, id.value(), v.value()) else {
+            continue;
+        };
+        let Some(key) = crypto::hash_prefix_key(&data.content_hash, HASH_PREFIX_LEN) else {
+            continue;
+        };
+        let existed = index
+            .insert((key.as_str(), id.value()), ())
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .is_some();
+        if !existed {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Seals every record and interned string written in the clear or sealed
+/// before seals were bound to their rows. Runs once, when history is first
+/// opened with encryption unlocked, and again for history sealed unbound.
+///
+/// History that was in the `plaintext` until now also has its trigram index
+/// dropped, so the maintenance backfill rebuilds it with keyed trigrams, and
+/// its hash prefix index rebuilt with keyed prefixes.
+pub(crate) fn seal_history(txn: &WriteTransaction, plaintext: bool) -> AppResult<usize> {
+    let mut changed = 0;
+    for table in [
+        SNAPSHOTS,
+        SESSIONS,
+        SYMBOLS,
+        SYMBOL_REFERENCES,
+        SYMBOL_DELTAS,
+        QUARANTINE,
+    ] {
+        changed += seal_rows(txn, table)?;
+    }
+    for table in [GIT_COMMITS, CHECKPOINTS, CHUNKS, PINS] {
+        changed += seal_keyed_rows(txn, table)?;
+    }
+    changed += seal_strings(txn)?;
+    if !plaintext {
+        return Ok(changed);
+    }
+
+    txn.open_table(HASH_PREFIXES)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .retain(|_, _| false)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    build_hash_prefixes(txn)?;
+
+    let mut markers = txn
+        .open_table(CHUNK_TRIGRAMS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    markers
+        .retain(|_, _| false)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    txn.delete_multimap_table(TRIGRAM_POSTINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    txn.open_multimap_table(TRIGRAM_POSTINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(changed)
+}
+
+/// Seals a record bound to its row, unless it already is. `aad` is its
+/// [`row_binding`].
+fn seal_payload(bytes: &[u8], aad: &[u8]) -> AppResult<Option<Vec<u8>>> {
+    let (version, payload) = split_envelope(bytes);
+    let sealed = if version & SEALED_FLAG != 0 {
+        crypto::reseal(payload, aad)?
+    } else {
+        crypto::seal(payload, aad)?
+    };
+    Ok(sealed.map(|sealed| wrap_payload_as(version | SEALED_FLAG, &sealed)))
+}
+
+fn seal_rows(txn: &WriteTransaction, table: TableDefinition<u64, &[u8]>) -> AppResult<usize> {
+    let mut t = txn
+        .open_table(table)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in t.iter().map_err(|e| AppError::Database(e.to_string()))? {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        if let Some(bytes) = seal_payload(v.value(), &row_binding(table, k.value()))? {
+            rewrites.push((k.value(), bytes));
+        }
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        t.insert(k, &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+fn seal_keyed_rows(
+    txn: &WriteTransaction,
+    table: TableDefinition<&str, &[u8]>,
+) -> AppResult<usize> {
+    let mut t = txn
+        .open_table(table)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut rewrites = Vec::new();
+    for res in t.iter().map_err(|e| AppError::Database(e.to_string()))? {
+        let (k, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        if let Some(bytes) = seal_payload(v.value(), &row_binding(table, k.value()))? {
+            rewrites.push((k.value().to_string(), bytes));
+        }
+    }
+    let changed = rewrites.len();
+    for (k, bytes) in rewrites {
+        t.insert(k.as_str(), &*bytes)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+/// Seals interned strings bound to their ids and re-keys the string index by
+/// token.
+fn seal_strings(txn: &WriteTransaction) -> AppResult<usize> {
+    let mut strings = txn
+        .open_table(STRINGS)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut index = txn
+        .open_table(STRING_INDEX)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut all = Vec::new();
+    for res in strings
+        .iter()
+        .map_err(|e| AppError::Database(e.to_string()))?
+    {
+        let (id, v) = res.map_err(|e| AppError::Database(e.to_string()))?;
+        let stored = v.value();
+        all.push((
+            id.value(),
+            crypto::open_string(stored, &row_binding(STRINGS, id.value()))?.into_owned(),
+            crypto::is_bound_string(stored),
+        ));
+    }
+
+    index
+        .retain(|_, _| false)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let mut changed = 0;
+    for (id, s, bound) in all {
+        if !bound {
+            strings
+                .insert(id, &*crypto::seal_string(&s, &row_binding(STRINGS, id))?)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            changed += 1;
+        }
+        index
+            .insert(&*crypto::string_key(&s), id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Sample {
+        id: u64,
+        name: String,
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let value = Sample {
+            id: 7,
+            name: "main.rs".into(),
+        };
+        let bytes = encode_record(SNAPSHOTS, 7u64, &value).unwrap();
+        assert_eq!(split_envelope(&bytes).0, RECORD_VERSION);
+        assert_eq!(
+            decode_record::<Sample>(SNAPSHOTS, 7u64, &bytes).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_legacy_record_decodes() {
+        let value = Sample {
+            id: 7,
+            name: "main.rs".into(),
+        };
+        let legacy = bincode::serialize(&value).unwrap();
+        assert_eq!(split_envelope(&legacy).0, 0);
+        assert_eq!(
+            decode_record::<Sample>(SNAPSHOTS, 7u64, &legacy).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_newer_record_version_is_rejected() {
+        let mut bytes = encode_record(SNAPSHOTS, 1u64, &1u64).unwrap();
+        bytes[RECORD_MAGIC.len()] = RECORD_VERSION + 1;
+        assert!(decode_record::<u64>(SNAPSHOTS, 1u64, &bytes).is_err());
+    }
+
+    #[derive(Serialize)]
+    struct LegacySnapshot {
+        id: i64,
+        file_path_id: u32,
+        timestamp: String,
+        content_hash: String,
+        git_branch_id: Option<u32>,
+        session_id: Option<i64>,
+        commit_hash: Option<String>,
+    }
+
+    /// Writes a store the way builds before schema versioning did.
+    fn write_legacy_db(path: &std::path::Path) {
+        use crate::storage::database::{METADATA, STRING_INDEX, STRINGS};
+        let db = redb::Database::create(path).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut meta = txn.open_table(METADATA).unwrap();
+            meta.insert("snapshot_id", 1).unwrap();
+            meta.insert("string_id", 1).unwrap();
+            let mut strings = txn.open_table(STRINGS).unwrap();
+            strings.insert(1, "/p/main.rs").unwrap();
+            let mut index = txn.open_table(STRING_INDEX).unwrap();
+            index.insert("/p/main.rs", 1).unwrap();
+            let mut snapshots = txn.open_table(SNAPSHOTS).unwrap();
+            let legacy = LegacySnapshot {
+                id: 1,
+                file_path_id: 1,
+                timestamp: "2024-01-01T00:00:00+00:00".into(),
+                content_hash: "ab".repeat(32),
+                git_branch_id: None,
+                session_id: None,
+                commit_hash: None,
+            };
+            snapshots
+                .insert(1, &*bincode::serialize(&legacy).unwrap())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn test_legacy_database_is_migrated_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnemosyne.db");
+        write_legacy_db(&path);
+
+        let db = crate::storage::database::Database::new(path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        let history = db.get_file_history("/p/main.rs").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].commit_message, None);
+        let at = parse_ms("2024-01-01T00:00:00+00:00").unwrap();
+        assert_eq!(parse_ms(&history[0].timestamp), Some(at));
+        assert_eq!(db.history_between(at, at).unwrap().len(), 1);
+        assert!(db.history_between(at + 1, i64::MAX).unwrap().is_empty());
+
+        let backups = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".bak"))
+            .count();
+        assert_eq!(backups, 1);
+    }
+
+    #[test]
+    fn test_dry_run_leaves_database_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnemosyne.db");
+        write_legacy_db(&path);
+
+        let db = crate::storage::database::Database::open_unmigrated(path).unwrap();
+        let report = db.migrate(true).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.to_version, SCHEMA_VERSION);
+        assert_eq!(report.applied[0].rows_changed, 1);
+        assert!(report.backup_path.is_none());
+        assert_eq!(db.schema_version().unwrap(), 0);
+        assert!(db.get_file_history("/p/main.rs").unwrap().is_empty());
+    }
+
+    #[derive(Serialize)]
+    struct SnapshotWithoutEvent {
+        id: i64,
+        file_path_id: u32,
+        timestamp: i64,
+        content_hash: String,
+        git_branch_id: Option<u32>,
+        session_id: Option<i64>,
+        commit_hash: Option<String>,
+        commit_message: Option<String>,
+    }
+
+    #[test]
+    fn test_snapshots_without_event_are_migrated() {
+        use crate::models::SnapshotEvent;
+        use crate::storage::database::{FILE_SNAPSHOTS, METADATA, STRING_INDEX, STRINGS};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnemosyne.db");
+        {
+            let db = redb::Database::create(&path).unwrap();
+            let txn = db.begin_write().unwrap();
+            {
+                let mut meta = txn.open_table(METADATA).unwrap();
+                meta.insert("schema_version", 5).unwrap();
+                meta.insert("snapshot_id", 1).unwrap();
+                meta.insert("string_id", 1).unwrap();
+                txn.open_table(STRINGS)
+                    .unwrap()
+                    .insert(1, "/p/main.rs")
+                    .unwrap();
+                txn.open_table(STRING_INDEX)
+                    .unwrap()
+                    .insert("/p/main.rs", 1)
+                    .unwrap();
+                txn.open_table(FILE_SNAPSHOTS)
+                    .unwrap()
+                    .insert((1, 1), ())
+                    .unwrap();
+                let old = SnapshotWithoutEvent {
+                    id: 1,
+                    file_path_id: 1,
+                    timestamp: 1_700_000_000_000,
+                    content_hash: "ab".repeat(32),
+                    git_branch_id: None,
+                    session_id: None,
+                    commit_hash: None,
+                    commit_message: None,
+                };
+                let payload = bincode::serialize(&old).unwrap();
+                txn.open_table(SNAPSHOTS)
+                    .unwrap()
+                    .insert(1, &*wrap_payload(&payload))
+                    .unwrap();
+            }
+            txn.commit().unwrap();
+        }
+
+        let db = crate::storage::database::Database::new(path).unwrap();
+        let history = db.get_file_history("/p/main.rs").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].event, SnapshotEvent::Modified);
+        assert_eq!(history[0].changeset_id, None);
+        assert_eq!(db.get_latest_state().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_checkpoint_file_lists_become_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnemosyne.db");
+        let hash = "cd".repeat(32);
+        {
+            let db = redb::Database::create(&path).unwrap();
+            let txn = db.begin_write().unwrap();
+            {
+                txn.open_table(METADATA)
+                    .unwrap()
+                    .insert("schema_version", 6)
+                    .unwrap();
+                let files = vec![
+                    ("/p/b.rs".to_string(), "bb".repeat(32)),
+                    ("/p/a.rs".to_string(), "aa".repeat(32)),
+                ];
+                let old = CheckpointDataV2 {
+                    hash: hash.clone(),
+                    timestamp: 1_700_000_000_000,
+                    description: Some("before refactor".into()),
+                    file_states: serde_json::to_string(&files).unwrap(),
+                };
+                txn.open_table(CHECKPOINTS)
+                    .unwrap()
+                    .insert(
+                        hash.as_str(),
+                        &*encode_record(CHECKPOINTS, hash.as_str(), &old).unwrap(),
+                    )
+                    .unwrap();
+            }
+            txn.commit().unwrap();
+        }
+
+        let db = crate::storage::database::Database::new(path).unwrap();
+        let (full, _, description) = db.get_checkpoint_by_hash(&hash[..8]).unwrap().unwrap();
+        assert_eq!(full, hash);
+        assert_eq!(description.as_deref(), Some("before refactor"));
+        assert_eq!(
+            db.checkpoint_files(&hash).unwrap(),
+            vec![
+                ("/p/a.rs".to_string(), "aa".repeat(32)),
+                ("/p/b.rs".to_string(), "bb".repeat(32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let versions: Vec<u64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let expected: Vec<u64> = (1..=SCHEMA_VERSION).collect();
+        assert_eq!(versions, expected);
+    }
+}
@@ -1,61 +1,29 @@
-use super::StorageLayer;
+use super::{LayerDir, StorageLayer, TierConfig};
 use crate::error::{AppError, AppResult};
-use std::fs;
-use std::path::PathBuf;
 
+/// Objects past the warm window, as zstd at a high level.
+#[derive(Clone)]
 pub struct ColdLayer {
-    root: PathBuf,
+    dir: LayerDir,
 }
 
 impl ColdLayer {
-    pub fn new(root: PathBuf) -> Self {
-        fs::create_dir_all(&root).ok();
-        Self { root }
-    }
-}
-
-impl StorageLayer for ColdLayer {
-    fn write(&self, hash: &str, content: &[u8]) -> AppResult<()> {
-        let path = self.root.join(hash);
-        // Zstd High Compression (Level 15 is a good balance for cold)
-        let compressed = zstd::encode_all(content, 15)
-            .map_err(|e| AppError::Internal(format!("Zstd compress error: {}", e)))?;
-        fs::write(path, compressed).map_err(AppError::IoGeneric)
+    pub fn new(dir: LayerDir) -> Self {
+        Self { dir }
     }
 
-    fn read(&self, hash: &str) -> AppResult<Option<Vec<u8>>> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            let compressed = fs::read(path).map_err(AppError::IoGeneric)?;
-            let decompressed = zstd::decode_all(&compressed[..])
-                .map_err(|e| AppError::Internal(format!("Zstd decompress error: {}", e)))?;
-            Ok(Some(decompressed))
-        } else {
-            Ok(None)
-        }
-    }
-
-    fn delete(&self, hash: &str) -> AppResult<()> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            fs::remove_file(path).map_err(AppError::IoGeneric)?;
-        }
-        Ok(())
+    pub fn encode_at(&self, content: &[u8], level: i32) -> AppResult<Vec<u8>> {
+        zstd::stream::encode_all(std::io::Cursor::new(content), level)
+            .map_err(|e| AppError::Internal(format!("Zstd cold compress error: {}", e)))
     }
+}
 
-    fn exists(&self, hash: &str) -> bool {
-        self.root.join(hash).exists()
+impl StorageLayer for ColdLayer {
+    fn dir(&self) -> &LayerDir {
+        &self.dir
     }
 
-    fn get_size(&self, hash: &str) -> AppResult<u64> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            Ok(fs::metadata(path).map_err(AppError::IoGeneric)?.len())
-        } else {
-            Err(AppError::IoGeneric(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "not found",
-            )))
-        }
+    fn encode(&self, content: &[u8]) -> AppResult<Vec<u8>> {
+        self.encode_at(content, TierConfig::default().cold_compression_level)
     }
 }
@@ -1,14 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TierConfig {
-    /// Items accessed within this window are kept uncompressed or lightly compressed
+    /// Objects written or read within this many hours stay hot (fast zstd)
     pub hot_window_hours: u64,
 
-    /// Items older than this move to high compression
+    /// Objects older than this many days move to cold storage
     pub warm_window_days: u64,
 
-    /// Compression level for cold storage (Zstd level 1-21)
+    /// Compression level for cold storage (Zstd level 1-22)
     pub cold_compression_level: i32,
 }
 
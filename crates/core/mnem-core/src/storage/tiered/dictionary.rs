@@ -0,0 +1,261 @@
+//! Trained zstd dictionaries for small objects.
+//!
+//! Most chunks are small source fragments, which zstd compresses poorly on
+//! their own. A dictionary trained on a sample of the project's chunks gives
+//! every frame that context up front. Dictionaries are kept as
+//! `dicts/v<N>.dict`; each new training adds a version, and objects name the
+//! version they were compressed with, so older versions are never removed.
+//!
+//! A dictionary-compressed file is `mZDC`, the version as a little-endian
+//! `u32`, then the zstd frame. While history is encrypted the dictionary
+//! files are sealed like objects, as they are built from content.
+
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+const DICT_MAGIC: &[u8; 4] = b"mZDC";
+const HEADER_LEN: usize = DICT_MAGIC.len() + 4;
+
+/// Largest dictionary trained; zstd's own default.
+pub const DICT_MAX_SIZE: usize = 110 * 1024;
+/// Inputs larger than this gain too little from a dictionary to use one on
+/// the write path.
+pub const DICT_INPUT_MAX: usize = 64 * 1024;
+/// A dictionary older than this is replaced by the next training.
+pub const RETRAIN_AFTER: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Dictionary versions in a store and what they saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DictionaryUsage {
+    pub versions: usize,
+    /// Bytes packs take less than they would with plain zstd. Loose objects
+    /// compressed with a dictionary are not counted.
+    #[serde(alias = "bytes_saved")]
+    pub pack_bytes_saved: u64,
+}
+
+impl DictionaryUsage {
+    pub fn add(&mut self, other: DictionaryUsage) {
+        self.versions += other.versions;
+        self.pack_bytes_saved += other.pack_bytes_saved;
+    }
+}
+
+#[derive(Default)]
+struct Loaded {
+    /// Prepared per version and level, as preparing is the expensive part.
+    encoders: HashMap<(u32, i32), Arc<EncoderDictionary<'static>>>,
+    decoders: HashMap<u32, Arc<DecoderDictionary<'static>>>,
+}
+
+pub struct Dictionaries {
+    root: PathBuf,
+    temp_dir: PathBuf,
+    latest: RwLock<Option<u32>>,
+    loaded: RwLock<Loaded>,
+}
+
+impl Dictionaries {
+    /// Finds the versions under `root`.
+    pub fn open(root: PathBuf, temp_dir: PathBuf) -> AppResult<Self> {
+        fs::create_dir_all(&root).map_err(|e| AppError::Io {
+            path: root.clone(),
+            source: e,
+        })?;
+        let dicts = Self {
+            root,
+            temp_dir,
+            latest: RwLock::new(None),
+            loaded: RwLock::new(Loaded::default()),
+        };
+        *dicts.latest.write().unwrap_or_else(|p| p.into_inner()) =
+            dicts.versions()?.into_iter().max();
+        Ok(dicts)
+    }
+
+    fn path(&self, version: u32) -> PathBuf {
+        self.root.join(format!("v{}.dict", version))
+    }
+
+    /// Every version on disk, unordered.
+    fn versions(&self) -> AppResult<Vec<u32>> {
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(&self.root)
+            .map_err(AppError::IoGeneric)?
+            .flatten()
+        {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(version) = name
+                .strip_prefix('v')
+                .and_then(|rest| rest.strip_suffix(".dict"))
+                .and_then(|n| n.parse().ok())
+            {
+                versions.push(version);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// The version new objects are compressed with.
+    pub fn latest(&self) -> Option<u32> {
+        *self.latest.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Whether there is no dictionary yet, or the latest has aged out.
+    pub fn needs_training(&self) -> bool {
+        let Some(version) = self.latest() else {
+            return true;
+        };
+        fs::metadata(self.path(version))
+            .and_then(|meta| meta.modified())
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default()
+                    >= RETRAIN_AFTER
+            })
+            .unwrap_or(true)
+    }
+
+    fn read(&self, version: u32) -> AppResult<Vec<u8>> {
+        let path = self.path(version);
+        let raw = fs::read(&path).map_err(|e| AppError::Io { path, source: e })?;
+        if crypto::is_sealed(&raw) {
+            crypto::open(&raw, &dict_binding(version))
+        } else {
+            Ok(raw)
+        }
+    }
+
+    fn encoder(&self, version: u32, level: i32) -> AppResult<Arc<EncoderDictionary<'static>>> {
+        if let Some(dict) = self
+            .loaded
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .encoders
+            .get(&(version, level))
+        {
+            return Ok(dict.clone());
+        }
+        let dict = Arc::new(EncoderDictionary::copy(&self.read(version)?, level));
+        self.loaded
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .encoders
+            .insert((version, level), dict.clone());
+        Ok(dict)
+    }
+
+    fn decoder(&self, version: u32) -> AppResult<Arc<DecoderDictionary<'static>>> {
+        if let Some(dict) = self
+            .loaded
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .decoders
+            .get(&version)
+        {
+            return Ok(dict.clone());
+        }
+        let dict = Arc::new(DecoderDictionary::copy(&self.read(version)?));
+        self.loaded
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .decoders
+            .insert(version, dict.clone());
+        Ok(dict)
+    }
+
+    /// Compresses `content` with the latest dictionary. `None` when there is
+    /// no dictionary yet.
+    pub fn encode(&self, content: &[u8], level: i32) -> AppResult<Option<Vec<u8>>> {
+        let Some(version) = self.latest() else {
+            return Ok(None);
+        };
+        let dict = self.encoder(version, level)?;
+        let frame = zstd::bulk::Compressor::with_prepared_dictionary(&dict)
+            .and_then(|mut c| c.compress(content))
+            .map_err(|e| AppError::Internal(format!("Zstd dictionary compress error: {}", e)))?;
+        let mut encoded = Vec::with_capacity(HEADER_LEN + frame.len());
+        encoded.extend_from_slice(DICT_MAGIC);
+        encoded.extend_from_slice(&version.to_le_bytes());
+        encoded.extend_from_slice(&frame);
+        Ok(Some(encoded))
+    }
+
+    pub(crate) fn decode(&self, raw: &[u8]) -> AppResult<Vec<u8>> {
+        let header = raw
+            .get(DICT_MAGIC.len()..HEADER_LEN)
+            .ok_or_else(|| AppError::Internal("Truncated dictionary object".into()))?;
+        let version = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let dict = self.decoder(version)?;
+        let decoder = zstd::stream::read::Decoder::with_prepared_dictionary(
+            std::io::BufReader::new(&raw[HEADER_LEN..]),
+            &dict,
+        )
+        .map_err(|_| AppError::Internal("Decompression failed".into()))?;
+        super::read_bounded(decoder)
+    }
+
+    /// Trains a dictionary from `samples` and makes it the latest version.
+    /// Returns the new version.
+    pub fn train(&self, samples: &[Vec<u8>]) -> AppResult<u32> {
+        let total: usize = samples.iter().map(Vec::len).sum();
+        let dict = zstd::dict::from_samples(samples, (total / 10).min(DICT_MAX_SIZE))
+            .map_err(|e| AppError::Internal(format!("Dictionary training failed: {}", e)))?;
+
+        let mut latest = self.latest.write().unwrap_or_else(|p| p.into_inner());
+        let version = self.versions()?.into_iter().max().map_or(1, |v| v + 1);
+        let mut temp =
+            tempfile::NamedTempFile::new_in(&self.temp_dir).map_err(AppError::IoGeneric)?;
+        temp.write_all(&crypto::seal_if_unlocked(&dict, &dict_binding(version))?)
+            .map_err(AppError::IoGeneric)?;
+        super::persist_noclobber(temp, &self.path(version))?;
+        *latest = Some(version);
+        Ok(version)
+    }
+
+    pub fn version_count(&self) -> AppResult<usize> {
+        Ok(self.versions()?.len())
+    }
+
+    /// Seals every dictionary written in the clear or sealed unbound. Returns
+    /// the number sealed.
+    pub fn seal_plaintext(&self) -> AppResult<usize> {
+        let mut sealed = 0;
+        for version in self.versions()? {
+            let path = self.path(version);
+            let raw = fs::read(&path).map_err(|e| AppError::Io {
+                path: path.clone(),
+                source: e,
+            })?;
+            let Some(data) = crypto::reseal(&raw, &dict_binding(version))? else {
+                continue;
+            };
+            let mut temp =
+                tempfile::NamedTempFile::new_in(&self.temp_dir).map_err(AppError::IoGeneric)?;
+            temp.write_all(&data).map_err(AppError::IoGeneric)?;
+            temp.persist(&path)
+                .map_err(|e| AppError::IoGeneric(e.error))?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+}
+
+/// What a sealed dictionary is bound to: its version.
+fn dict_binding(version: u32) -> Vec<u8> {
+    format!("dictionary {}", version).into_bytes()
+}
+
+pub(crate) fn is_dict_frame(raw: &[u8]) -> bool {
+    raw.starts_with(DICT_MAGIC)
+}
@@ -1,70 +0,0 @@
-use crate::error::{AppError, AppResult};
-use super::config::TierConfig;
-use std::path::{Path, PathBuf};
-use std::fs;
-use std::time::{SystemTime, Duration};
-
-pub enum StorageTier {
-    Hot,  // Raw / Mmap
-    Warm, // LZ4
-    Cold, // Zstd
-}
-
-pub struct TieredStorageManager {
-    base_dir: PathBuf,
-    config: TierConfig,
-}
-
-impl TieredStorageManager {
-    pub fn new(base_dir: PathBuf, config: TierConfig) -> Self {
-        Self { base_dir, config }
-    }
-
-    /// Determines where a chunk *should* be based on its age.
-    pub fn desired_tier(&self, age: Duration) -> StorageTier {
-        if age.as_secs() < self.config.hot_window_hours * 3600 {
-            StorageTier::Hot
-        } else if age.as_secs() < self.config.warm_window_days * 86400 {
-            StorageTier::Warm
-        } else {
-            StorageTier::Cold
-        }
-    }
-
-    /// Reads a chunk, handling decompression transparently.
-    /// Tries Hot -> Warm -> Cold locations.
-    pub fn read(&self, hash: &str) -> AppResult<Vec<u8>> {
-        // 1. Try Hot (cas/hot/hash)
-        let hot_path = self.base_dir.join("cas").join("hot").join(hash);
-        if hot_path.exists() {
-            return Ok(fs::read(hot_path).map_err(AppError::IoGeneric)?);
-        }
-
-        // 2. Try Warm (cas/warm/hash) - LZ4
-        let warm_path = self.base_dir.join("cas").join("warm").join(hash);
-        if warm_path.exists() {
-            let compressed = fs::read(warm_path).map_err(AppError::IoGeneric)?;
-            // Decompress LZ4 (Placeholder logic, needs crate)
-            return Ok(compressed); 
-        }
-
-        // 3. Try Cold (cas/cold/hash) - Zstd
-        let cold_path = self.base_dir.join("cas").join(hash); // Default location usually
-        if cold_path.exists() {
-            let compressed = fs::read(cold_path).map_err(AppError::IoGeneric)?;
-            // Decompress Zstd (Placeholder logic, needs crate)
-            return Ok(compressed);
-        }
-
-        Err(AppError::IoGeneric(std::io::Error::new(std::io::ErrorKind::NotFound, "Chunk not found in any tier")))
-    }
-
-    /// Moves chunks to their correct tier based on age.
-    /// Should be called by a background maintenance task.
-    pub fn optimize_storage(&self) -> AppResult<usize> {
-        let mut moved_count = 0;
-        // Logic to scan directories and move files
-        // ... (Implementation to follow)
-        Ok(moved_count)
-    }
-}
@@ -1,129 +1,647 @@
+//! Hot/warm/cold placement of CAS objects.
+//!
+//! New objects land in `hot/` as fast zstd. Once older than the hot window
+//! they move to `warm/` as LZ4, and past the warm window to `cold/` as zstd at
+//! `cold_compression_level`. Reading a warm or cold object promotes it back to
+//! hot. Age is the object's mtime, which migration carries over and promotion
+//! resets, so an object ages from its last write or read.
+//!
+//! Every stored file says how it is encoded, so a file moved between tiers
+//! verbatim (already-compressed content is never re-encoded) stays readable.
+//! Stores written before tiering keep their objects under `objects/`; those are
+//! read in place and moved into the tiers by [`TieredStore::migrate`]. Small
+//! cold objects end up in [`pack`] files.
+//!
+//! Small objects in hot and packed storage are compressed with a trained
+//! [`dictionary`] once the project has one.
+//!
+//! While history is encrypted every file is sealed on top of its encoding;
+//! see [`crypto`](crate::crypto).
+
 pub mod cold_layer;
 pub mod config;
+pub mod dictionary;
 pub mod hot_layer;
+pub mod pack;
 pub mod warm_layer;
 
 pub use cold_layer::ColdLayer;
+pub use config::TierConfig;
+pub use dictionary::{Dictionaries, DictionaryUsage};
 pub use hot_layer::HotLayer;
+pub use pack::PackStore;
 pub use warm_layer::WarmLayer;
 
+use crate::crypto;
 use crate::error::{AppError, AppResult};
-pub use config::TierConfig;
-use std::path::PathBuf;
+use crate::storage::fs::is_already_compressed;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Largest object a stored file may decode to, against decompression bombs.
+pub(crate) const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Hot,
+    Warm,
+    Cold,
+}
+
+impl Tier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hot => "hot",
+            Self::Warm => "warm",
+            Self::Cold => "cold",
+        }
+    }
+}
+
+/// A stored object found by a directory scan.
+pub(crate) struct StoredObject {
+    pub hash: String,
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub size: u64,
+}
+
+/// A directory of objects sharded as `ab/cdef...`. Flat `<hash>` files are
+/// also found, as the oldest stores wrote them.
+#[derive(Clone)]
+pub struct LayerDir {
+    root: PathBuf,
+    temp_dir: PathBuf,
+    dicts: Arc<Dictionaries>,
+}
+
+impl LayerDir {
+    pub fn new(root: PathBuf, temp_dir: PathBuf, dicts: Arc<Dictionaries>) -> Self {
+        Self {
+            root,
+            temp_dir,
+            dicts,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The store's dictionaries, which objects here may be compressed with.
+    pub fn dicts(&self) -> &Dictionaries {
+        &self.dicts
+    }
+
+    /// Where `hash` is written.
+    pub fn path(&self, hash: &str) -> PathBuf {
+        let (prefix, rest) = hash.split_at(2);
+        self.root.join(prefix).join(rest)
+    }
+
+    /// Where `hash` currently is, if this layer holds it.
+    pub fn find(&self, hash: &str) -> Option<PathBuf> {
+        let sharded = self.path(hash);
+        if sharded.exists() {
+            return Some(sharded);
+        }
+        let flat = self.root.join(hash);
+        flat.exists().then_some(flat)
+    }
+
+    /// Atomically stores `data` as `hash`, keeping whatever copy is already
+    /// there. `modified` carries an object's age over from another layer.
+    pub fn persist(&self, hash: &str, data: &[u8], modified: Option<SystemTime>) -> AppResult<()> {
+        let path = self.path(hash);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(AppError::IoGeneric)?;
+        }
+        let temp = self.stage(
+            &crypto::seal_if_unlocked(data, &object_binding(hash))?,
+            modified,
+        )?;
+        persist_noclobber(temp, &path)
+    }
+
+    fn stage(
+        &self,
+        data: &[u8],
+        modified: Option<SystemTime>,
+    ) -> AppResult<tempfile::NamedTempFile> {
+        let mut temp =
+            tempfile::NamedTempFile::new_in(&self.temp_dir).map_err(AppError::IoGeneric)?;
+        temp.write_all(data).map_err(AppError::IoGeneric)?;
+        if let Some(modified) = modified {
+            temp.as_file()
+                .set_modified(modified)
+                .map_err(AppError::IoGeneric)?;
+        }
+        Ok(temp)
+    }
+
+    /// Seals every file written in the clear or sealed unbound, keeping its
+    /// age. Returns the number of files sealed.
+    pub(crate) fn seal_plaintext(&self) -> AppResult<usize> {
+        let mut sealed = 0;
+        for object in self.scan()? {
+            let raw = match fs::read(&object.path) {
+                Ok(raw) => raw,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(AppError::IoGeneric(e)),
+            };
+            let Some(data) = crypto::reseal(&raw, &object_binding(&object.hash))? else {
+                continue;
+            };
+            self.stage(&data, Some(object.modified))?
+                .persist(&object.path)
+                .map_err(|e| AppError::IoGeneric(e.error))?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+
+    /// Every object in the layer.
+    pub(crate) fn scan(&self) -> AppResult<Vec<StoredObject>> {
+        let mut objects = Vec::new();
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(objects),
+            Err(e) => return Err(AppError::IoGeneric(e)),
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if path.is_dir() {
+                for inner in fs::read_dir(&path).map_err(AppError::IoGeneric)?.flatten() {
+                    let hash = format!("{}{}", name, inner.file_name().to_string_lossy());
+                    objects.extend(stored_object(hash, inner.path()));
+                }
+            } else {
+                objects.extend(stored_object(name, path));
+            }
+        }
+        Ok(objects)
+    }
+}
+
+fn stored_object(hash: String, path: PathBuf) -> Option<StoredObject> {
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let meta = fs::metadata(&path).ok()?;
+    Some(StoredObject {
+        hash,
+        modified: meta.modified().ok()?,
+        size: meta.len(),
+        path,
+    })
+}
+
+/// Moves a finished temp file into place unless the object is already there.
+pub(crate) fn persist_noclobber(temp: tempfile::NamedTempFile, path: &Path) -> AppResult<()> {
+    match temp.persist_noclobber(path) {
+        Ok(_) => Ok(()),
+        // Content-addressed: identical hash means identical content
+        Err(e) if e.error.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(AppError::IoGeneric(e.error)),
+    }
+}
 
 pub trait StorageLayer {
-    fn write(&self, hash: &str, content: &[u8]) -> AppResult<()>;
-    fn read(&self, hash: &str) -> AppResult<Option<Vec<u8>>>;
-    fn delete(&self, hash: &str) -> AppResult<()>;
-    fn exists(&self, hash: &str) -> bool;
-    fn get_size(&self, hash: &str) -> AppResult<u64>;
+    fn dir(&self) -> &LayerDir;
+
+    /// Encodes plain content the way this layer stores it.
+    fn encode(&self, content: &[u8]) -> AppResult<Vec<u8>>;
+
+    fn write(&self, hash: &str, content: &[u8]) -> AppResult<()> {
+        self.dir().persist(hash, &self.encode(content)?, None)
+    }
+
+    fn read(&self, hash: &str) -> AppResult<Option<Vec<u8>>> {
+        match self.dir().find(hash) {
+            Some(path) => {
+                Ok(read_object(&path, hash, self.dir().dicts())?.map(|(content, _)| content))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, hash: &str) -> AppResult<()> {
+        if let Some(path) = self.dir().find(hash) {
+            remove_object(&path)?;
+        }
+        Ok(())
+    }
+
+    fn exists(&self, hash: &str) -> bool {
+        self.dir().find(hash).is_some()
+    }
+
+    fn get_size(&self, hash: &str) -> AppResult<u64> {
+        let path = self
+            .dir()
+            .find(hash)
+            .ok_or_else(|| AppError::NotFound(format!("Object {} not found", hash)))?;
+        Ok(fs::metadata(path).map_err(AppError::IoGeneric)?.len())
+    }
 }
 
+/// Reads and decodes a stored file. `None` when it vanished, e.g. because a
+/// concurrent migration moved it.
+fn read_object(
+    path: &Path,
+    hash: &str,
+    dicts: &Dictionaries,
+) -> AppResult<Option<(Vec<u8>, Vec<u8>)>> {
+    let raw = match fs::read(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(AppError::IoGeneric(std::io::Error::new(
+                e.kind(),
+                format!("Failed to read object {:?}: {}", path, e),
+            )));
+        }
+    };
+    // Callers get the encoding without the seal, which `persist` reapplies
+    let raw = if crypto::is_sealed(&raw) {
+        crypto::open(&raw, &object_binding(hash))?
+    } else {
+        raw
+    };
+    let content = decode(&raw, dicts)?;
+    Ok(Some((content, raw)))
+}
+
+/// What a sealed object is bound to: its id.
+fn object_binding(hash: &str) -> Vec<u8> {
+    format!("object {}", hash).into_bytes()
+}
+
+fn remove_object(path: &Path) -> AppResult<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::IoGeneric(e)),
+        _ => Ok(()),
+    }
+}
+
+fn is_zstd_frame(raw: &[u8]) -> bool {
+    raw.starts_with(&ZSTD_MAGIC)
+}
+
+/// Decodes a stored file of any tier, once opened. Files that are neither
+/// zstd nor LZ4 are raw content from the earliest stores.
+pub(crate) fn decode(raw: &[u8], dicts: &Dictionaries) -> AppResult<Vec<u8>> {
+    if warm_layer::is_lz4_block(raw) {
+        return warm_layer::decode(raw);
+    }
+    if dictionary::is_dict_frame(raw) {
+        return dicts.decode(raw);
+    }
+    if !is_zstd_frame(raw) {
+        return Ok(raw.to_vec());
+    }
+
+    let decoder = zstd::stream::Decoder::new(std::io::Cursor::new(raw))
+        .map_err(|_| AppError::Internal("Decompression failed".into()))?;
+    read_bounded(decoder)
+}
+
+/// Reads a decompressing reader to the end, up to [`MAX_DECOMPRESSED_SIZE`].
+pub(crate) fn read_bounded(mut decoder: impl Read) -> AppResult<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = decoder
+            .read(&mut buf)
+            .map_err(|_| AppError::Internal("Decompression failed".into()))?;
+        if n == 0 {
+            break;
+        }
+        if decompressed.len() + n > MAX_DECOMPRESSED_SIZE {
+            return Err(size_limit_exceeded());
+        }
+        decompressed.extend_from_slice(&buf[..n]);
+    }
+    Ok(decompressed)
+}
+
+pub(crate) fn size_limit_exceeded() -> AppError {
+    AppError::Internal(format!(
+        "Decompressed size exceeds {} MB limit",
+        MAX_DECOMPRESSED_SIZE / (1024 * 1024)
+    ))
+}
+
+/// Objects and bytes on disk in one tier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierUsage {
+    pub objects: usize,
+    pub bytes: u64,
+}
+
+impl TierUsage {
+    pub fn add(&mut self, other: TierUsage) {
+        self.objects += other.objects;
+        self.bytes += other.bytes;
+    }
+}
+
+/// How a store's objects are spread over the tiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TierOccupancy {
+    pub hot: TierUsage,
+    pub warm: TierUsage,
+    pub cold: TierUsage,
+    /// Objects of a pre-tiering store not migrated yet.
+    pub legacy: TierUsage,
+    /// Cold objects gathered into pack files.
+    pub packed: TierUsage,
+    pub dictionary: DictionaryUsage,
+}
+
+impl TierOccupancy {
+    pub fn add(&mut self, other: &TierOccupancy) {
+        self.hot.add(other.hot);
+        self.warm.add(other.warm);
+        self.cold.add(other.cold);
+        self.legacy.add(other.legacy);
+        self.packed.add(other.packed);
+        self.dictionary.add(other.dictionary);
+    }
+
+    pub fn total(&self) -> TierUsage {
+        let mut total = self.hot;
+        total.add(self.warm);
+        total.add(self.cold);
+        total.add(self.legacy);
+        total.add(self.packed);
+        total
+    }
+}
+
+#[derive(Clone)]
 pub struct TieredStore {
-    config: TierConfig,
-    // Layers
-    hot: hot_layer::HotLayer,
-    warm: warm_layer::WarmLayer,
-    cold: cold_layer::ColdLayer,
+    hot: HotLayer,
+    warm: WarmLayer,
+    cold: ColdLayer,
+    legacy: LayerDir,
+    packs: Arc<PackStore>,
+    dicts: Arc<Dictionaries>,
 }
 
 impl TieredStore {
-    pub fn new(base_dir: PathBuf, config: TierConfig) -> AppResult<Self> {
-        let cas_root = base_dir.join("cas");
-        std::fs::create_dir_all(&cas_root).map_err(|e| AppError::Io {
-            path: cas_root.clone(),
-            source: e,
-        })?;
+    /// Opens the tiers under `cas_root`, staging writes in `temp_dir`.
+    pub fn new(cas_root: &Path, temp_dir: &Path) -> AppResult<Self> {
+        let dicts = Arc::new(Dictionaries::open(
+            cas_root.join("dicts"),
+            temp_dir.to_path_buf(),
+        )?);
+        let layer = |name: &str| -> AppResult<LayerDir> {
+            let root = cas_root.join(name);
+            fs::create_dir_all(&root).map_err(|e| AppError::Io {
+                path: root.clone(),
+                source: e,
+            })?;
+            Ok(LayerDir::new(root, temp_dir.to_path_buf(), dicts.clone()))
+        };
 
         Ok(Self {
-            config,
-            hot: hot_layer::HotLayer::new(cas_root.join("hot")),
-            warm: warm_layer::WarmLayer::new(cas_root.join("warm")),
-            cold: cold_layer::ColdLayer::new(cas_root.join("cold")),
+            hot: HotLayer::new(layer("hot")?),
+            warm: WarmLayer::new(layer("warm")?),
+            cold: ColdLayer::new(layer("cold")?),
+            legacy: LayerDir::new(
+                cas_root.join("objects"),
+                temp_dir.to_path_buf(),
+                dicts.clone(),
+            ),
+            packs: Arc::new(PackStore::open(
+                cas_root.join("packs"),
+                temp_dir.to_path_buf(),
+                dicts.clone(),
+            )?),
+            dicts,
         })
     }
 
-    pub fn write(&self, hash: &str, content: &[u8]) -> AppResult<()> {
-        // Always write to HOT first for speed
-        self.hot.write(hash, content)
+    pub fn hot(&self) -> &HotLayer {
+        &self.hot
     }
 
-    pub fn read(&self, hash: &str) -> AppResult<Vec<u8>> {
-        // Waterfall read: Hot -> Warm -> Cold
-        if let Some(data) = self.hot.read(hash)? {
-            return Ok(data);
+    pub fn dicts(&self) -> &Dictionaries {
+        &self.dicts
+    }
+
+    fn layers(&self) -> [(Option<Tier>, &LayerDir); 4] {
+        [
+            (Some(Tier::Hot), self.hot.dir()),
+            (Some(Tier::Warm), self.warm.dir()),
+            (Some(Tier::Cold), self.cold.dir()),
+            (None, &self.legacy),
+        ]
+    }
+
+    fn layer(&self, tier: Tier) -> &dyn StorageLayer {
+        match tier {
+            Tier::Hot => &self.hot,
+            Tier::Warm => &self.warm,
+            Tier::Cold => &self.cold,
         }
-        if let Some(data) = self.warm.read(hash)? {
-            // Promote to hot? Maybe not on simple read, only on edit.
-            return Ok(data);
+    }
+
+    /// Where loose `hash` is stored and in which tier; `None` for a legacy
+    /// object.
+    pub fn locate(&self, hash: &str) -> Option<(Option<Tier>, PathBuf)> {
+        self.layers()
+            .into_iter()
+            .find_map(|(tier, dir)| dir.find(hash).map(|path| (tier, path)))
+    }
+
+    pub fn exists(&self, hash: &str) -> bool {
+        self.locate(hash).is_some() || self.packs.contains(hash)
+    }
+
+    /// Reads `hash` from whichever tier holds it, promoting it to hot when it
+    /// was elsewhere.
+    pub fn read(&self, hash: &str) -> AppResult<Vec<u8>> {
+        self.fetch(hash, true)
+    }
+
+    /// Reads `hash` where it is, for scans that should not disturb placement.
+    pub fn peek(&self, hash: &str) -> AppResult<Vec<u8>> {
+        self.fetch(hash, false)
+    }
+
+    fn fetch(&self, hash: &str, promote: bool) -> AppResult<Vec<u8>> {
+        // A miss can race an object moving between tiers, so look twice
+        for _ in 0..2 {
+            for (tier, dir) in self.layers() {
+                let Some(path) = dir.find(hash) else {
+                    continue;
+                };
+                let Some((content, _)) = read_object(&path, hash, &self.dicts)? else {
+                    continue;
+                };
+                if promote && tier != Some(Tier::Hot) {
+                    self.promote(hash, &content, || remove_object(&path));
+                }
+                return Ok(content);
+            }
+            if let Some(content) = self.packs.read(hash)? {
+                if promote {
+                    self.promote(hash, &content, || self.packs.remove(hash));
+                }
+                return Ok(content);
+            }
         }
-        if let Some(data) = self.cold.read(hash)? {
-            return Ok(data);
+        Err(AppError::IoGeneric(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Object {} not found", hash),
+        )))
+    }
+
+    /// Best effort: a failed promotion leaves the object where it was.
+    fn promote(&self, hash: &str, content: &[u8], remove: impl FnOnce() -> AppResult<()>) {
+        let promoted = self.hot.write(hash, content).and_then(|_| remove());
+        if let Err(e) = promoted {
+            log::warn!("Failed to promote object {}: {}", hash, e);
         }
-        Err(AppError::NotFound(format!("Chunk {} not found", hash)))
     }
 
+    /// Bytes the object takes on disk; its uncompressed size when packed.
     pub fn get_size(&self, hash: &str) -> AppResult<u64> {
-        if let Ok(size) = self.hot.get_size(hash) {
-            return Ok(size);
-        }
-        if let Ok(size) = self.warm.get_size(hash) {
-            return Ok(size);
+        if let Some((_, path)) = self.locate(hash) {
+            return Ok(fs::metadata(path).map_err(AppError::IoGeneric)?.len());
         }
-        if let Ok(size) = self.cold.get_size(hash) {
-            return Ok(size);
-        }
-        Ok(0)
+        self.packs
+            .get_size(hash)
+            .ok_or_else(|| AppError::NotFound(format!("Object {} not found", hash)))
     }
 
+    /// Removes every copy of `hash`.
     pub fn delete(&self, hash: &str) -> AppResult<()> {
-        let _ = self.hot.delete(hash);
-        let _ = self.warm.delete(hash);
-        let _ = self.cold.delete(hash);
-        Ok(())
+        for (_, dir) in self.layers() {
+            remove_object(&dir.path(hash))?;
+            remove_object(&dir.root().join(hash))?;
+        }
+        self.packs.remove(hash)
     }
 
-    pub fn exists(&self, hash: &str) -> bool {
-        self.hot.exists(hash) || self.warm.exists(hash) || self.cold.exists(hash)
+    /// Hashes of every stored object.
+    pub fn list(&self) -> AppResult<Vec<String>> {
+        let mut hashes = Vec::new();
+        for (_, dir) in self.layers() {
+            hashes.extend(dir.scan()?.into_iter().map(|o| o.hash));
+        }
+        hashes.extend(self.packs.list());
+        hashes.sort_unstable();
+        hashes.dedup();
+        Ok(hashes)
     }
 
-    /// Run the migration logic: move old Hot items to Warm, old Warm to Cold.
-    pub fn migrate(&self) -> AppResult<usize> {
-        let mut moved = 0;
-
-        // 1. Hot -> Warm
-        // Iterate hot files, check age > hot_window
-        // If old: compress to warm, delete from hot
-        let hot = self.hot.scan()?;
-        for (hash, age) in hot {
-            if age.as_secs() > self.config.hot_window_hours * 3600 {
-                if let Some(content) = self.hot.read(&hash)? {
-                    self.warm.write(&hash, &content)?;
-                    self.hot.delete(&hash)?;
-                    moved += 1;
-                }
+    pub fn occupancy(&self) -> AppResult<TierOccupancy> {
+        let mut occupancy = TierOccupancy::default();
+        for (tier, dir) in self.layers() {
+            let usage = match tier {
+                Some(Tier::Hot) => &mut occupancy.hot,
+                Some(Tier::Warm) => &mut occupancy.warm,
+                Some(Tier::Cold) => &mut occupancy.cold,
+                None => &mut occupancy.legacy,
+            };
+            for object in dir.scan()? {
+                usage.add(TierUsage {
+                    objects: 1,
+                    bytes: object.size,
+                });
             }
         }
+        let (objects, bytes) = self.packs.usage();
+        occupancy.packed = TierUsage { objects, bytes };
+        occupancy.dictionary = DictionaryUsage {
+            versions: self.dicts.version_count()?,
+            pack_bytes_saved: self.packs.dictionary_savings(),
+        };
+        Ok(occupancy)
+    }
+
+    /// Seals every object and pack written in the clear. Returns the number
+    /// of files sealed.
+    pub fn seal_plaintext(&self) -> AppResult<usize> {
+        let mut sealed = 0;
+        for (_, dir) in self.layers() {
+            sealed += dir.seal_plaintext()?;
+        }
+        Ok(sealed + self.packs.seal_plaintext()? + self.dicts.seal_plaintext()?)
+    }
 
-        // 2. Warm -> Cold
-        // Iterate warm files, check age > warm_window
-        // If old: compress harder to cold, delete from warm
-        let warm = self.warm.scan()?;
-        for (hash, age) in warm {
-            if age.as_secs() > self.config.warm_window_days * 86400 {
-                if let Some(content) = self.warm.read(&hash)? {
-                    self.cold.write(&hash, &content)?;
-                    self.warm.delete(&hash)?;
+    /// Gathers small cold objects into packs compressed at `level`. Returns
+    /// the number of objects packed.
+    pub fn repack(&self, level: i32) -> AppResult<usize> {
+        self.packs.repack(self.cold.dir(), level)
+    }
+
+    /// Moves every object that outgrew its tier, legacy objects included.
+    /// Returns the number of objects moved.
+    pub fn migrate(&self, config: &TierConfig) -> AppResult<usize> {
+        let now = SystemTime::now();
+        let mut moved = 0;
+        for (tier, dir) in self.layers() {
+            for object in dir.scan()? {
+                let age = now.duration_since(object.modified).unwrap_or_default();
+                let target = config.tier_for(age);
+                let outgrown = match tier {
+                    Some(Tier::Hot) => target != Tier::Hot,
+                    Some(Tier::Warm) => target == Tier::Cold,
+                    Some(Tier::Cold) => false,
+                    None => true,
+                };
+                if outgrown && self.demote(&object, target, config)? {
                     moved += 1;
                 }
             }
         }
-
         Ok(moved)
     }
 
-    pub fn clean_temp(&self) -> AppResult<usize> {
-        // Placeholder for now, maybe clean partial uploads
-        Ok(0)
+    fn demote(&self, object: &StoredObject, target: Tier, config: &TierConfig) -> AppResult<bool> {
+        let Some((content, raw)) = read_object(&object.path, &object.hash, &self.dicts)? else {
+            return Ok(false);
+        };
+        let layer = self.layer(target);
+        // Compressed formats gain nothing from another pass
+        let encoded = if is_zstd_frame(&raw) && is_already_compressed(&content) {
+            raw
+        } else if target == Tier::Cold {
+            self.cold
+                .encode_at(&content, config.cold_compression_level)?
+        } else {
+            layer.encode(&content)?
+        };
+        layer
+            .dir()
+            .persist(&object.hash, &encoded, Some(object.modified))?;
+        remove_object(&object.path)?;
+        Ok(true)
+    }
+}
+
+impl TierConfig {
+    /// The tier an object of this age belongs in.
+    pub fn tier_for(&self, age: Duration) -> Tier {
+        if age.as_secs() >= self.warm_window_days * 86400 {
+            Tier::Cold
+        } else if age.as_secs() >= self.hot_window_hours * 3600 {
+            Tier::Warm
+        } else {
+            Tier::Hot
+        }
     }
 }
@@ -0,0 +1,404 @@
+//! Pack files for small cold objects.
+//!
+//! Semantic chunking leaves one CAS file per chunk, which wastes inodes and
+//! slows every directory walk. Repacking gathers small loose cold objects into
+//! `packs/<id>.pack`, a single zstd frame holding them back to back, next to
+//! `packs/<id>.idx` listing each object's hash, offset and length.
+//!
+//! A pack is sealed once written. Deleting or promoting a packed object only
+//! drops it from the index; a pack left mostly dead is rewritten by the next
+//! repack. Packs are compressed with the project's dictionary when that comes
+//! out smaller. While history is encrypted the `.pack` file is encrypted as a
+//! whole; the index only lists object ids.
+
+use super::{Dictionaries, LayerDir, StoredObject, decode, read_object, remove_object};
+use crate::crypto;
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Loose objects up to this size on disk are packed.
+pub const PACK_OBJECT_MAX: u64 = 64 * 1024;
+/// Uncompressed bytes gathered into one pack.
+pub const PACK_TARGET_SIZE: usize = 8 * 1024 * 1024;
+/// Fewer loose candidates than this are left for a later repack.
+pub const MIN_PACK_OBJECTS: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PackEntry {
+    hash: String,
+    offset: usize,
+    len: usize,
+}
+
+/// Contents of a `.idx` file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PackIndex {
+    /// Uncompressed size of the whole pack, live or not.
+    size: usize,
+    objects: Vec<PackEntry>,
+    /// Bytes the dictionary saved over plain zstd.
+    #[serde(default)]
+    dict_saved: u64,
+}
+
+impl PackIndex {
+    fn live_bytes(&self) -> usize {
+        self.objects.iter().map(|e| e.len).sum()
+    }
+}
+
+#[derive(Default)]
+struct PackState {
+    packs: HashMap<String, PackIndex>,
+    /// Object hash to the pack holding it.
+    objects: HashMap<String, String>,
+}
+
+impl PackState {
+    fn insert(&mut self, id: String, index: PackIndex) {
+        for entry in &index.objects {
+            self.objects.insert(entry.hash.clone(), id.clone());
+        }
+        self.packs.insert(id, index);
+    }
+}
+
+pub struct PackStore {
+    root: PathBuf,
+    temp_dir: PathBuf,
+    dicts: Arc<Dictionaries>,
+    state: RwLock<PackState>,
+    /// The last pack read, decompressed; reads tend to cluster.
+    cache: Mutex<Option<(String, Arc<Vec<u8>>)>>,
+}
+
+impl PackStore {
+    /// Loads the indexes under `root`.
+    pub fn open(root: PathBuf, temp_dir: PathBuf, dicts: Arc<Dictionaries>) -> AppResult<Self> {
+        fs::create_dir_all(&root).map_err(|e| AppError::Io {
+            path: root.clone(),
+            source: e,
+        })?;
+
+        let mut state = PackState::default();
+        for entry in fs::read_dir(&root).map_err(AppError::IoGeneric)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "idx") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let parsed = fs::read(&path)
+                .map_err(AppError::IoGeneric)
+                .and_then(|raw| {
+                    serde_json::from_slice::<PackIndex>(&raw)
+                        .map_err(|e| AppError::Internal(e.to_string()))
+                });
+            match parsed {
+                Ok(index) => state.insert(id, index),
+                Err(e) => log::warn!("Skipping unreadable pack index {:?}: {}", path, e),
+            }
+        }
+
+        Ok(Self {
+            root,
+            temp_dir,
+            dicts,
+            state: RwLock::new(state),
+            cache: Mutex::new(None),
+        })
+    }
+
+    fn pack_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.pack", id))
+    }
+
+    fn index_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.idx", id))
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.read_state().objects.contains_key(hash)
+    }
+
+    fn read_state(&self) -> std::sync::RwLockReadGuard<'_, PackState> {
+        self.state.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn write_state(&self) -> std::sync::RwLockWriteGuard<'_, PackState> {
+        self.state.write().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn entry(&self, hash: &str) -> Option<(String, PackEntry)> {
+        let state = self.read_state();
+        let id = state.objects.get(hash)?;
+        let entry = state
+            .packs
+            .get(id)?
+            .objects
+            .iter()
+            .find(|e| e.hash == hash)?;
+        Some((id.clone(), entry.clone()))
+    }
+
+    /// Uncompressed size of a packed object.
+    pub fn get_size(&self, hash: &str) -> Option<u64> {
+        self.entry(hash).map(|(_, entry)| entry.len as u64)
+    }
+
+    pub fn read(&self, hash: &str) -> AppResult<Option<Vec<u8>>> {
+        let Some((id, entry)) = self.entry(hash) else {
+            return Ok(None);
+        };
+        let pack = self.load(&id)?;
+        let content = pack
+            .get(entry.offset..entry.offset + entry.len)
+            .ok_or_else(|| AppError::Internal(format!("Pack {} is truncated", id)))?;
+        Ok(Some(content.to_vec()))
+    }
+
+    fn load(&self, id: &str) -> AppResult<Arc<Vec<u8>>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some((cached, pack)) = cache.as_ref()
+            && cached == id
+        {
+            return Ok(pack.clone());
+        }
+        let path = self.pack_path(id);
+        let mut raw = fs::read(&path).map_err(|e| AppError::Io { path, source: e })?;
+        if crypto::is_sealed(&raw) {
+            raw = crypto::open(&raw, &pack_binding(id))?;
+        }
+        let pack = Arc::new(decode(&raw, &self.dicts)?);
+        *cache = Some((id.to_string(), pack.clone()));
+        Ok(pack)
+    }
+
+    /// Drops `hash` from its pack's index, removing the pack once nothing
+    /// in it is live.
+    pub fn remove(&self, hash: &str) -> AppResult<()> {
+        let mut state = self.write_state();
+        let Some(id) = state.objects.remove(hash) else {
+            return Ok(());
+        };
+        let Some(index) = state.packs.get_mut(&id) else {
+            return Ok(());
+        };
+        index.objects.retain(|e| e.hash != hash);
+        // The index is rewritten under the lock so removals land in order
+        if index.objects.is_empty() {
+            state.packs.remove(&id);
+            self.remove_pack(&id)
+        } else {
+            self.write_index(&id, index)
+        }
+    }
+
+    fn remove_pack(&self, id: &str) -> AppResult<()> {
+        // Index first: a pack without one is an orphan the next repack removes
+        remove_object(&self.index_path(id))?;
+        remove_object(&self.pack_path(id))
+    }
+
+    fn write_index(&self, id: &str, index: &PackIndex) -> AppResult<()> {
+        let json = serde_json::to_vec(index).map_err(|e| AppError::Internal(e.to_string()))?;
+        self.write_file(&self.index_path(id), &json)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> AppResult<()> {
+        let mut temp =
+            tempfile::NamedTempFile::new_in(&self.temp_dir).map_err(AppError::IoGeneric)?;
+        temp.write_all(data).map_err(AppError::IoGeneric)?;
+        temp.persist(path)
+            .map_err(|e| AppError::IoGeneric(e.error))?;
+        Ok(())
+    }
+
+    /// Hashes of every packed object.
+    pub fn list(&self) -> Vec<String> {
+        self.read_state().objects.keys().cloned().collect()
+    }
+
+    /// Live packed objects, and the bytes the packs take on disk.
+    pub fn usage(&self) -> (usize, u64) {
+        let state = self.read_state();
+        let bytes = state
+            .packs
+            .keys()
+            .filter_map(|id| fs::metadata(self.pack_path(id)).ok())
+            .map(|meta| meta.len())
+            .sum();
+        (state.objects.len(), bytes)
+    }
+
+    /// Bytes the dictionary saved across all packs.
+    pub fn dictionary_savings(&self) -> u64 {
+        self.read_state().packs.values().map(|i| i.dict_saved).sum()
+    }
+
+    /// Packs the small loose objects of `cold`, and rewrites packs that are
+    /// mostly dead. Returns the number of objects written to new packs.
+    pub fn repack(&self, cold: &LayerDir, level: i32) -> AppResult<usize> {
+        self.remove_orphans()?;
+
+        let loose: Vec<StoredObject> = cold
+            .scan()?
+            .into_iter()
+            .filter(|o| o.size <= PACK_OBJECT_MAX)
+            .collect();
+        let sparse: Vec<String> = self
+            .read_state()
+            .packs
+            .iter()
+            .filter(|(_, index)| index.live_bytes() * 2 < index.size)
+            .map(|(id, _)| id.clone())
+            .collect();
+        if loose.len() < MIN_PACK_OBJECTS && sparse.is_empty() {
+            return Ok(0);
+        }
+
+        let mut writer = PackWriter::default();
+        let mut packed = 0;
+        for object in &loose {
+            if self.contains(&object.hash) {
+                remove_object(&object.path)?;
+                continue;
+            }
+            let Some((content, _)) = read_object(&object.path, &object.hash, &self.dicts)? else {
+                continue;
+            };
+            writer.add(&object.hash, &content, Some(object.path.clone()));
+            if writer.data.len() >= PACK_TARGET_SIZE {
+                packed += self.seal(&mut writer, level)?;
+            }
+        }
+        for id in &sparse {
+            let index = self.read_state().packs.get(id).cloned().unwrap_or_default();
+            let pack = self.load(id)?;
+            for entry in &index.objects {
+                if let Some(content) = pack.get(entry.offset..entry.offset + entry.len) {
+                    writer.add(&entry.hash, content, None);
+                }
+                if writer.data.len() >= PACK_TARGET_SIZE {
+                    packed += self.seal(&mut writer, level)?;
+                }
+            }
+        }
+        packed += self.seal(&mut writer, level)?;
+
+        for id in sparse {
+            let mut state = self.write_state();
+            if let Some(index) = state.packs.get(&id)
+                && index
+                    .objects
+                    .iter()
+                    .all(|e| state.objects.get(&e.hash) != Some(&id))
+            {
+                state.packs.remove(&id);
+                self.remove_pack(&id)?;
+            }
+        }
+        Ok(packed)
+    }
+
+    /// Writes out the gathered objects as a new pack, then removes the
+    /// copies they came from.
+    fn seal(&self, writer: &mut PackWriter, level: i32) -> AppResult<usize> {
+        let PackWriter {
+            data,
+            mut index,
+            sources,
+        } = std::mem::take(writer);
+        if index.objects.is_empty() {
+            return Ok(0);
+        }
+
+        let id = crypto::object_id(&data)[..16].to_string();
+        let plain = zstd::stream::encode_all(std::io::Cursor::new(&data), level)
+            .map_err(|e| AppError::Internal(format!("Zstd pack compress error: {}", e)))?;
+        let compressed = match self.dicts.encode(&data, level)? {
+            Some(with_dict) if with_dict.len() < plain.len() => {
+                index.dict_saved = (plain.len() - with_dict.len()) as u64;
+                with_dict
+            }
+            _ => plain,
+        };
+        self.write_file(
+            &self.pack_path(&id),
+            &crypto::seal_if_unlocked(&compressed, &pack_binding(&id))?,
+        )?;
+        self.write_index(&id, &index)?;
+
+        let count = index.objects.len();
+        self.write_state().insert(id, index);
+        for path in sources {
+            remove_object(&path)?;
+        }
+        Ok(count)
+    }
+
+    /// Seals every pack written in the clear or sealed unbound. Returns the
+    /// number sealed.
+    pub fn seal_plaintext(&self) -> AppResult<usize> {
+        let ids: Vec<String> = self.read_state().packs.keys().cloned().collect();
+        let mut sealed = 0;
+        for id in ids {
+            let path = self.pack_path(&id);
+            let raw = fs::read(&path).map_err(|e| AppError::Io { path, source: e })?;
+            let Some(data) = crypto::reseal(&raw, &pack_binding(&id))? else {
+                continue;
+            };
+            self.write_file(&self.pack_path(&id), &data)?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+
+    /// Packs whose index never got written, after an interrupted repack.
+    fn remove_orphans(&self) -> AppResult<()> {
+        for entry in fs::read_dir(&self.root)
+            .map_err(AppError::IoGeneric)?
+            .flatten()
+        {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "pack")
+                && !path.with_extension("idx").exists()
+            {
+                remove_object(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a sealed pack is bound to: its id.
+fn pack_binding(id: &str) -> Vec<u8> {
+    format!("pack {}", id).into_bytes()
+}
+
+#[derive(Default)]
+struct PackWriter {
+    data: Vec<u8>,
+    index: PackIndex,
+    /// Loose files to remove once the pack is sealed.
+    sources: Vec<PathBuf>,
+}
+
+impl PackWriter {
+    fn add(&mut self, hash: &str, content: &[u8], source: Option<PathBuf>) {
+        self.index.objects.push(PackEntry {
+            hash: hash.to_string(),
+            offset: self.data.len(),
+            len: content.len(),
+        });
+        self.data.extend_from_slice(content);
+        self.index.size = self.data.len();
+        self.sources.extend(source);
+    }
+}
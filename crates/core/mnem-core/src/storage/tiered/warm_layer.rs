@@ -1,83 +1,48 @@
-use super::StorageLayer;
+use super::{LayerDir, MAX_DECOMPRESSED_SIZE, StorageLayer, size_limit_exceeded};
 use crate::error::{AppError, AppResult};
-use std::fs;
-use std::path::PathBuf;
-use std::time::{Duration, SystemTime};
 
+/// Marks an LZ4 block, which has no magic of its own. The block follows with
+/// its decompressed size prepended.
+const LZ4_MAGIC: &[u8; 4] = b"mLZ4";
+
+/// Objects past the hot window, as LZ4: bigger than zstd but much cheaper to
+/// read back.
+#[derive(Clone)]
 pub struct WarmLayer {
-    root: PathBuf,
+    dir: LayerDir,
 }
 
 impl WarmLayer {
-    pub fn new(root: PathBuf) -> Self {
-        fs::create_dir_all(&root).ok();
-        Self { root }
-    }
-
-    pub fn scan(&self) -> AppResult<Vec<(String, Duration)>> {
-        let mut results = Vec::new();
-        if !self.root.exists() {
-            return Ok(results);
-        }
-
-        for entry in fs::read_dir(&self.root).map_err(AppError::IoGeneric)? {
-            let entry = entry.map_err(AppError::IoGeneric)?;
-            if let Ok(meta) = entry.metadata() {
-                if let Ok(modified) = meta.modified() {
-                    if let Ok(age) = SystemTime::now().duration_since(modified) {
-                        if let Ok(name) = entry.file_name().into_string() {
-                            results.push((name, age));
-                        }
-                    }
-                }
-            }
-        }
-        Ok(results)
+    pub fn new(dir: LayerDir) -> Self {
+        Self { dir }
     }
 }
 
 impl StorageLayer for WarmLayer {
-    fn write(&self, hash: &str, content: &[u8]) -> AppResult<()> {
-        let path = self.root.join(hash);
-        // Zstd Fast Compression (Level 3) - replacement for LZ4
-        let compressed = zstd::encode_all(content, 3)
-            .map_err(|e| AppError::Internal(format!("Zstd warm compress error: {}", e)))?;
-        fs::write(path, compressed).map_err(AppError::IoGeneric)
+    fn dir(&self) -> &LayerDir {
+        &self.dir
     }
 
-    fn read(&self, hash: &str) -> AppResult<Option<Vec<u8>>> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            let compressed = fs::read(path).map_err(AppError::IoGeneric)?;
-            let decompressed = zstd::decode_all(&compressed[..])
-                .map_err(|e| AppError::Internal(format!("Zstd warm decompress error: {}", e)))?;
-            Ok(Some(decompressed))
-        } else {
-            Ok(None)
-        }
-    }
-
-    fn delete(&self, hash: &str) -> AppResult<()> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            fs::remove_file(path).map_err(AppError::IoGeneric)?;
-        }
-        Ok(())
+    fn encode(&self, content: &[u8]) -> AppResult<Vec<u8>> {
+        let mut encoded = LZ4_MAGIC.to_vec();
+        encoded.extend_from_slice(&lz4_flex::compress_prepend_size(content));
+        Ok(encoded)
     }
+}
 
-    fn exists(&self, hash: &str) -> bool {
-        self.root.join(hash).exists()
-    }
+pub(crate) fn is_lz4_block(raw: &[u8]) -> bool {
+    raw.starts_with(LZ4_MAGIC)
+}
 
-    fn get_size(&self, hash: &str) -> AppResult<u64> {
-        let path = self.root.join(hash);
-        if path.exists() {
-            Ok(fs::metadata(path).map_err(AppError::IoGeneric)?.len())
-        } else {
-            Err(AppError::IoGeneric(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "not found",
-            )))
-        }
+pub(crate) fn decode(raw: &[u8]) -> AppResult<Vec<u8>> {
+    let block = &raw[LZ4_MAGIC.len()..];
+    let size = block
+        .get(..4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or_else(|| AppError::Internal("Truncated LZ4 object".into()))?;
+    if size > MAX_DECOMPRESSED_SIZE {
+        return Err(size_limit_exceeded());
     }
+    lz4_flex::decompress_size_prepended(block)
+        .map_err(|e| AppError::Internal(format!("LZ4 warm decompress error: {}", e)))
 }
@@ -0,0 +1,312 @@
+//! Billable time from coding sessions.
+//!
+//! Sessions from every project are laid on one timeline before anything is
+//! summed, so time spent with two projects open is only counted once: each
+//! stretch goes to the session that started most recently. The timeline is
+//! then cut at local day, ISO week or month boundaries, summed per period and
+//! group, and each entry is rounded on its own.
+
+use crate::models::TimesheetEntry;
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MINUTE: i64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    #[default]
+    Day,
+    /// ISO weeks, starting on Monday.
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Branch,
+    Project,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimesheetOptions {
+    pub period: Period,
+    /// Split each period per branch or per project.
+    pub group_by: Option<GroupBy>,
+    /// Round every entry to a multiple of this many minutes. 0 rounds to the
+    /// minute.
+    pub round_minutes: u64,
+    pub rounding: Rounding,
+    /// Only count time from this instant on (UTC epoch milliseconds).
+    pub from: Option<i64>,
+    /// Only count time before this instant (UTC epoch milliseconds).
+    pub to: Option<i64>,
+}
+
+/// What the timesheet needs to know about a session.
+#[derive(Debug, Clone)]
+pub struct SessionSpan {
+    pub project: String,
+    pub branch: Option<String>,
+    /// UTC epoch milliseconds.
+    pub start: i64,
+    /// Last save of the session, in UTC epoch milliseconds.
+    pub end: i64,
+    pub file_count: usize,
+    pub snapshot_count: usize,
+}
+
+/// (project, branch), whichever the entries are grouped by.
+type Group = (Option<String>, Option<String>);
+
+#[derive(Default)]
+struct Totals {
+    ms: i64,
+    file_count: usize,
+    snapshot_count: usize,
+}
+
+/// Timesheet entries for `spans` in the periods of time zone `tz`, ordered by
+/// period then group. File and save counts go to the period the session
+/// started in.
+pub fn build<Tz: TimeZone>(
+    spans: &[SessionSpan],
+    options: &TimesheetOptions,
+    tz: &Tz,
+) -> Vec<TimesheetEntry> {
+    let from = options.from.unwrap_or(i64::MIN);
+    let to = options.to.unwrap_or(i64::MAX);
+    let spans: Vec<(i64, i64, &SessionSpan)> = spans
+        .iter()
+        .filter(|s| s.start < to && s.end >= from)
+        .map(|s| (s.start.max(from), s.end.min(to), s))
+        .collect();
+
+    let group = |span: &SessionSpan| match options.group_by {
+        Some(GroupBy::Branch) => (None, span.branch.clone()),
+        Some(GroupBy::Project) => (Some(span.project.clone()), None),
+        None => (None, None),
+    };
+    let mut totals: BTreeMap<(String, Group), Totals> = BTreeMap::new();
+    for (start, _, span) in &spans {
+        let (key, _) = period_of(*start, options.period, tz);
+        let entry = totals.entry((key, group(span))).or_default();
+        entry.file_count += span.file_count;
+        entry.snapshot_count += span.snapshot_count;
+    }
+
+    let mut cuts: Vec<i64> = spans.iter().flat_map(|(s, e, _)| [*s, *e]).collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    for pair in cuts.windows(2) {
+        let (mut at, end) = (pair[0], pair[1]);
+        let owner = spans
+            .iter()
+            .enumerate()
+            .filter(|(_, (s, e, _))| *s <= at && *e >= end)
+            .max_by_key(|(i, (s, _, _))| (*s, std::cmp::Reverse(*i)))
+            .map(|(_, (_, _, span))| *span);
+        let Some(owner) = owner else {
+            continue;
+        };
+        while at < end {
+            let (key, next) = period_of(at, options.period, tz);
+            let until = next.min(end);
+            totals.entry((key, group(owner))).or_default().ms += until - at;
+            at = until;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((date, (project, branch)), totals)| TimesheetEntry {
+            date,
+            project,
+            branch,
+            duration_minutes: round(totals.ms, options),
+            file_count: totals.file_count,
+            snapshot_count: totals.snapshot_count,
+        })
+        .collect()
+}
+
+fn round(ms: i64, options: &TimesheetOptions) -> u64 {
+    let step = options.round_minutes.max(1) as i64 * MINUTE;
+    let steps = match options.rounding {
+        Rounding::Nearest => (ms + step / 2) / step,
+        Rounding::Up => (ms + step - 1) / step,
+        Rounding::Down => ms / step,
+    };
+    (steps * step / MINUTE) as u64
+}
+
+/// Label of the period containing `ms`, and when the next period starts.
+fn period_of<Tz: TimeZone>(ms: i64, period: Period, tz: &Tz) -> (String, i64) {
+    let day = tz
+        .timestamp_millis_opt(ms)
+        .earliest()
+        .map(|t| t.date_naive())
+        .unwrap_or_default();
+    let (label, next) = match period {
+        Period::Day => (day.format("%Y-%m-%d").to_string(), day + Duration::days(1)),
+        Period::Week => {
+            let week = day.iso_week();
+            let monday = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+            (
+                format!("{}-W{:02}", week.year(), week.week()),
+                monday + Duration::days(7),
+            )
+        }
+        Period::Month => {
+            let next = if day.month() == 12 {
+                NaiveDate::from_ymd_opt(day.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(day.year(), day.month() + 1, 1)
+            };
+            (day.format("%Y-%m").to_string(), next.unwrap_or(day))
+        }
+    };
+    (label, local_midnight(next, tz).max(ms + 1))
+}
+
+fn local_midnight<Tz: TimeZone>(day: NaiveDate, tz: &Tz) -> i64 {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    match tz.from_local_datetime(&midnight).earliest() {
+        Some(t) => t.timestamp_millis(),
+        // Midnight skipped by a DST change
+        None => midnight.and_utc().timestamp_millis(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(day: u32, hour: u32, minute: u32) -> i64 {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, minute, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    fn span(project: &str, branch: &str, start: i64, end: i64) -> SessionSpan {
+        SessionSpan {
+            project: project.into(),
+            branch: Some(branch.into()),
+            start,
+            end,
+            file_count: 1,
+            snapshot_count: 2,
+        }
+    }
+
+    fn minutes(entries: &[TimesheetEntry]) -> Vec<(&str, Option<&str>, u64)> {
+        entries
+            .iter()
+            .map(|e| {
+                (
+                    e.date.as_str(),
+                    e.project.as_deref().or(e.branch.as_deref()),
+                    e.duration_minutes,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_overlapping_sessions_are_counted_once() {
+        let spans = [
+            span("api", "main", at(2, 9, 0), at(2, 11, 0)),
+            span("web", "main", at(2, 10, 0), at(2, 10, 30)),
+        ];
+        let total = build(&spans, &TimesheetOptions::default(), &Utc);
+        assert_eq!(minutes(&total), vec![("2026-03-02", None, 120)]);
+
+        let options = TimesheetOptions {
+            group_by: Some(GroupBy::Project),
+            ..Default::default()
+        };
+        let per_project = build(&spans, &options, &Utc);
+        assert_eq!(
+            minutes(&per_project),
+            vec![
+                ("2026-03-02", Some("api"), 90),
+                ("2026-03-02", Some("web"), 30)
+            ]
+        );
+        assert_eq!(per_project[0].snapshot_count, 2);
+    }
+
+    #[test]
+    fn test_sessions_are_cut_at_period_boundaries() {
+        let spans = [span("api", "main", at(1, 23, 0), at(2, 1, 30))];
+        let days = build(&spans, &TimesheetOptions::default(), &Utc);
+        assert_eq!(
+            minutes(&days),
+            vec![("2026-03-01", None, 60), ("2026-03-02", None, 90)]
+        );
+        // Sunday 1 March and Monday 2 March fall in different ISO weeks
+        let options = TimesheetOptions {
+            period: Period::Week,
+            ..Default::default()
+        };
+        assert_eq!(
+            minutes(&build(&spans, &options, &Utc)),
+            vec![("2026-W09", None, 60), ("2026-W10", None, 90)]
+        );
+        let options = TimesheetOptions {
+            period: Period::Month,
+            ..Default::default()
+        };
+        assert_eq!(
+            minutes(&build(&spans, &options, &Utc)),
+            vec![("2026-03", None, 150)]
+        );
+    }
+
+    #[test]
+    fn test_branches_and_rounding() {
+        let spans = [
+            span("api", "main", at(2, 9, 0), at(2, 9, 7)),
+            span("api", "fix", at(2, 10, 0), at(2, 10, 22)),
+        ];
+        let mut options = TimesheetOptions {
+            group_by: Some(GroupBy::Branch),
+            round_minutes: 15,
+            ..Default::default()
+        };
+        assert_eq!(
+            minutes(&build(&spans, &options, &Utc)),
+            vec![
+                ("2026-03-02", Some("fix"), 15),
+                ("2026-03-02", Some("main"), 0)
+            ]
+        );
+        options.rounding = Rounding::Up;
+        assert_eq!(
+            minutes(&build(&spans, &options, &Utc)),
+            vec![
+                ("2026-03-02", Some("fix"), 30),
+                ("2026-03-02", Some("main"), 15)
+            ]
+        );
+        options.rounding = Rounding::Down;
+        options.from = Some(at(2, 10, 15));
+        assert_eq!(
+            minutes(&build(&spans, &options, &Utc)),
+            vec![("2026-03-02", Some("fix"), 0)]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Past project states materialized outside the working tree.
+//!
+//! `--at` names a moment in the project's history: an RFC3339 timestamp, a
+//! checkpoint hash prefix or a recorded git commit. Every file alive at that
+//! moment is reassembled from the store into a fresh directory or a ustar
+//! stream; the project itself is never touched. A JSON manifest of BLAKE3
+//! hashes is written next to the files ([`MANIFEST_FILE`]) so two exports can
+//! be compared without diffing their contents.
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Repository;
+use crate::storage::bundle::relative_path;
+use crate::utils::time;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Component, Path};
+
+/// Name of the manifest at the root of every export.
+pub const MANIFEST_FILE: &str = ".mnem-manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeManifest {
+    /// Root of the project the files were taken from.
+    pub project: String,
+    /// What `--at` resolved to, e.g. `checkpoint 1a2b3c4d`.
+    pub source: String,
+    /// The moment the state was taken at, RFC3339.
+    pub at: String,
+    /// Sorted by path.
+    pub files: Vec<TreeFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeFile {
+    /// Relative to the export root, `/`-separated.
+    pub path: String,
+    /// BLAKE3 of the content, whether or not history is encrypted.
+    pub blake3: String,
+    pub size: u64,
+}
+
+/// Writes the state at `at` into `dest`, which must not exist yet or be an
+/// empty directory outside the project. Files are staged in a sibling
+/// directory first, so a failed export leaves nothing behind.
+pub fn export_dir(repo: &Repository, at: &str, dest: &Path) -> AppResult<TreeManifest> {
+    if dest.exists() {
+        let empty = dest
+            .read_dir()
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !empty {
+            return Err(AppError::Config(format!(
+                "{} already exists and is not an empty directory",
+                dest.display()
+            )));
+        }
+    }
+    let parent = dest
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent).map_err(|e| AppError::Io {
+        path: parent.to_path_buf(),
+        source: e,
+    })?;
+    let root = Path::new(&repo.project.path);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    if parent
+        .canonicalize()
+        .is_ok_and(|p| p.starts_with(&canonical_root))
+    {
+        return Err(AppError::Config(
+            "Export into a directory outside the project, or it would be tracked".into(),
+        ));
+    }
+
+    let (mut manifest, entries) = plan(repo, at)?;
+    let staging = tempfile::Builder::new()
+        .prefix(".mnem-export")
+        .tempdir_in(parent)?;
+    for (path, hash) in entries {
+        let target = staging.path().join(&path);
+        let content = fetch(repo, &mut manifest, path, &hash)?;
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| AppError::Io {
+                path: dir.to_path_buf(),
+                source: e,
+            })?;
+        }
+        std::fs::write(&target, &content).map_err(|e| AppError::Io {
+            path: target.clone(),
+            source: e,
+        })?;
+    }
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::Internal(e.to_string()))?;
+    std::fs::write(staging.path().join(MANIFEST_FILE), manifest_json)?;
+
+    if dest.exists() {
+        std::fs::remove_dir(dest)?;
+    }
+    std::fs::rename(staging.path(), dest).map_err(|e| AppError::Io {
+        path: dest.to_path_buf(),
+        source: e,
+    })?;
+    Ok(manifest)
+}
+
+/// Writes the state at `at` to `out` as a ustar archive, manifest last.
+pub fn export_tar(repo: &Repository, at: &str, out: impl Write) -> AppResult<TreeManifest> {
+    let (mut manifest, entries) = plan(repo, at)?;
+    let mtime = time::parse_ms(&manifest.at).unwrap_or(0).max(0) as u64 / 1000;
+    let mut tar = TarWriter { out, mtime };
+    for (path, hash) in entries {
+        let content = fetch(repo, &mut manifest, path.clone(), &hash)?;
+        tar.append(&path, &content)?;
+    }
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::Internal(e.to_string()))?;
+    tar.append(MANIFEST_FILE, &manifest_json)?;
+    tar.finish()?;
+    Ok(manifest)
+}
+
+/// The manifest of the state at `at`, without its files yet, and the
+/// relative path and content hash of each of them, sorted by path.
+fn plan(repo: &Repository, at: &str) -> AppResult<(TreeManifest, Vec<(String, String)>)> {
+    let Resolved {
+        source,
+        timestamp,
+        files,
+    } = resolve(repo, at)?;
+    let mut state = match files {
+        Some(files) => files,
+        None => repo.db.get_state_at_timestamp(timestamp)?,
+    };
+    if state.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No files were tracked at {}",
+            time::format_ms(timestamp)
+        )));
+    }
+    let mut entries = Vec::new();
+    for (path, hash) in state.drain(..) {
+        let relative = relative_path(&repo.project.path, &path);
+        // Only paths that stay inside the export root
+        let inside = Path::new(&relative)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !inside || relative.is_empty() {
+            log::warn!("Skipping {} outside the project", path);
+            continue;
+        }
+        entries.push((relative, hash));
+    }
+    entries.sort();
+
+    let manifest = TreeManifest {
+        project: repo.project.path.clone(),
+        source,
+        at: time::format_ms(timestamp),
+        files: Vec::with_capacity(entries.len()),
+    };
+    Ok((manifest, entries))
+}
+
+/// Reads one file of the export and adds it to `manifest`, so only one
+/// file's content is held at a time.
+fn fetch(
+    repo: &Repository,
+    manifest: &mut TreeManifest,
+    path: String,
+    hash: &str,
+) -> AppResult<Vec<u8>> {
+    let content = repo.get_content(hash)?;
+    manifest.files.push(TreeFile {
+        path,
+        blake3: blake3::hash(&content).to_hex().to_string(),
+        size: content.len() as u64,
+    });
+    Ok(content)
+}
+
+/// What an `--at` argument names.
+struct Resolved {
+    /// e.g. `checkpoint 1a2b3c4d`
+    source: String,
+    /// UTC epoch milliseconds.
+    timestamp: i64,
+    /// The files a checkpoint recorded, which can differ from the history
+    /// at its time.
+    files: Option<Vec<(String, String)>>,
+}
+
+/// Timestamps win over checkpoints, and checkpoints over commits.
+fn resolve(repo: &Repository, at: &str) -> AppResult<Resolved> {
+    if let Some(timestamp) = time::parse_ms(at) {
+        return Ok(Resolved {
+            source: format!("time {}", at),
+            timestamp,
+            files: None,
+        });
+    }
+    if let Some((hash, timestamp, _)) = repo.db.get_checkpoint_by_hash(at)? {
+        let ms = time::parse_ms(&timestamp)
+            .ok_or_else(|| AppError::Internal(format!("Checkpoint {} has no valid time", hash)))?;
+        return Ok(Resolved {
+            source: format!("checkpoint {}", &hash[..8.min(hash.len())]),
+            timestamp: ms,
+            files: Some(repo.db.checkpoint_files(&hash)?),
+        });
+    }
+    let commit = match repo.db.get_commit_by_hash(at)? {
+        Some((hash, _, _, timestamp)) => Some((hash, timestamp)),
+        None if at.len() >= 4 => {
+            let mut matches: Vec<(String, String)> = repo
+                .db
+                .get_commits()?
+                .into_iter()
+                .filter(|(hash, ..)| hash.starts_with(at))
+                .map(|(hash, _, _, timestamp, _)| (hash, timestamp))
+                .collect();
+            if matches.len() > 1 {
+                return Err(AppError::Config(format!(
+                    "'{}' matches {} commits; use more characters",
+                    at,
+                    matches.len()
+                )));
+            }
+            matches.pop()
+        }
+        None => None,
+    };
+    match commit {
+        Some((hash, timestamp)) => {
+            let ms = time::parse_ms(&timestamp)
+                .ok_or_else(|| AppError::Internal(format!("Commit {} has no valid time", hash)))?;
+            Ok(Resolved {
+                source: format!("commit {}", &hash[..8.min(hash.len())]),
+                timestamp: ms,
+                files: None,
+            })
+        }
+        None => Err(AppError::NotFound(format!(
+            "'{}' is not an RFC3339 time, a checkpoint or a recorded commit",
+            at
+        ))),
+    }
+}
+
+/// Just enough of ustar for regular files.
+struct TarWriter<W: Write> {
+    out: W,
+    /// Seconds since the epoch, stamped on every entry.
+    mtime: u64,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn append(&mut self, path: &str, content: &[u8]) -> AppResult<()> {
+        let mut header = [0u8; 512];
+        let (prefix, name) = split_tar_path(path)?;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        let size = octal_field(content.len() as u64, 12)
+            .ok_or_else(|| AppError::Config(format!("Too large for a tar archive: {}", path)))?;
+        header[124..136].copy_from_slice(size.as_bytes());
+        let mtime = octal_field(self.mtime, 12).ok_or_else(|| {
+            AppError::Config(format!("Time out of range for a tar archive: {}", path))
+        })?;
+        header[136..148].copy_from_slice(mtime.as_bytes());
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(content)?;
+        let padding = (512 - content.len() % 512) % 512;
+        self.out.write_all(&vec![0u8; padding])?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> AppResult<()> {
+        self.out.write_all(&[0u8; 1024])?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// `value` as a NUL-terminated octal field of `width` bytes, or `None` when
+/// it needs more digits than the field holds (8 GiB for a size).
+fn octal_field(value: u64, width: usize) -> Option<String> {
+    let field = format!("{:0digits$o}\0", value, digits = width - 1);
+    (field.len() == width).then_some(field)
+}
+
+/// Splits `path` into ustar's 155-byte prefix and 100-byte name fields.
+fn split_tar_path(path: &str) -> AppResult<(&str, &str)> {
+    if path.len() <= 100 {
+        return Ok(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+        .ok_or_else(|| AppError::Config(format!("Path too long for a tar archive: {}", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_headers_are_valid_ustar() {
+        let mut archive = Vec::new();
+        let mut tar = TarWriter {
+            out: &mut archive,
+            mtime: 1_700_000_000,
+        };
+        tar.append("src/main.rs", b"fn main() {}\n").unwrap();
+        tar.finish().unwrap();
+
+        assert_eq!(archive.len(), 512 + 512 + 1024);
+        let header = &archive[..512];
+        assert_eq!(&header[..11], b"src/main.rs");
+        assert_eq!(&header[124..136], b"00000000015\0");
+        assert_eq!(&header[257..262], b"ustar");
+        let stored = std::str::from_utf8(&header[148..154]).unwrap();
+        let mut blank = header.to_vec();
+        blank[148..156].copy_from_slice(b"        ");
+        let expected: u32 = blank.iter().map(|&b| b as u32).sum();
+        assert_eq!(u32::from_str_radix(stored, 8).unwrap(), expected);
+        assert_eq!(&archive[512..525], b"fn main() {}\n");
+    }
+
+    #[test]
+    fn test_long_paths_use_the_prefix_field() {
+        let dir = "d".repeat(120);
+        let path = format!("{}/file.rs", dir);
+        assert_eq!(split_tar_path(&path).unwrap(), (dir.as_str(), "file.rs"));
+        assert!(split_tar_path(&"x".repeat(101)).is_err());
+    }
+
+    #[test]
+    fn test_sizes_past_the_octal_field_are_rejected() {
+        assert_eq!(octal_field(13, 12).as_deref(), Some("00000000015\0"));
+        assert_eq!(
+            octal_field((8 << 30) - 1, 12).as_deref(),
+            Some("77777777777\0")
+        );
+        assert_eq!(octal_field(8 << 30, 12), None);
+    }
+}
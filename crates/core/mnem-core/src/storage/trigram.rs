@@ -0,0 +1,404 @@
+//! Trigram extraction for the content search index.
+//!
+//! Chunks are indexed by the set of byte trigrams of their ASCII-lowercased
+//! content. A search is turned into a [`TrigramQuery`] of trigrams that any
+//! match must contain, which narrows the snapshots that are actually read and
+//! verified. For regexes the required trigrams are derived from the parsed
+//! pattern, following the approach of Google Code Search.
+//!
+//! Chunks are shared between snapshots, so a trigram that straddles the
+//! boundary of two chunks belongs to neither. Each chunk therefore also
+//! records its first and last one and two bytes, and a trigram counts as
+//! present where one chunk ends with its start and the next begins with the
+//! rest (see [`splits`]).
+
+use crate::error::{AppError, AppResult};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use std::collections::HashSet;
+
+/// Exact-string sets larger than this are dropped in favour of their trigrams.
+const MAX_EXACT_SET: usize = 16;
+/// Character classes with more members than this are treated as "anything".
+const MAX_CLASS_SIZE: usize = 4;
+
+/// Tags of the edge ids, above the 24 bits a packed trigram uses.
+const HEAD: u32 = 1 << 24;
+const TAIL: u32 = 2 << 24;
+
+pub fn pack(a: u8, b: u8, c: u8) -> u32 {
+    (a as u32) << 16 | (b as u32) << 8 | c as u32
+}
+
+/// Id of a chunk starting with `bytes` (one or two, lowercased).
+fn head(bytes: &[u8]) -> u32 {
+    HEAD | edge(bytes)
+}
+
+/// Id of a chunk ending with `bytes` (one or two, lowercased).
+fn tail(bytes: &[u8]) -> u32 {
+    TAIL | edge(bytes)
+}
+
+fn edge(bytes: &[u8]) -> u32 {
+    match bytes {
+        [a] => 1 << 16 | *a as u32,
+        [a, b] => 2 << 16 | (*a as u32) << 8 | *b as u32,
+        _ => 0,
+    }
+}
+
+/// Distinct trigrams of `content`, after ASCII lowercasing, plus the ids of
+/// its edges. Binary content has none, which keeps blob chunks out of the
+/// posting lists.
+pub fn trigrams(content: &[u8]) -> HashSet<u32> {
+    let mut set = HashSet::new();
+    if crate::storage::cdc::is_binary(content) {
+        return set;
+    }
+    let lower = content.to_ascii_lowercase();
+    for w in lower.windows(3) {
+        set.insert(pack(w[0], w[1], w[2]));
+    }
+    for len in 1..=lower.len().min(2) {
+        set.insert(head(&lower[..len]));
+        set.insert(tail(&lower[lower.len() - len..]));
+    }
+    set
+}
+
+/// The ways trigram `t` can straddle two chunks, as the tail id of the
+/// first chunk and the head id of the second.
+pub fn splits(t: u32) -> [(u32, u32); 2] {
+    let [_, a, b, c] = t.to_be_bytes();
+    [(tail(&[a, b]), head(&[c])), (tail(&[a]), head(&[b, c]))]
+}
+
+/// Boolean condition over the trigrams present in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrigramQuery {
+    /// No constraint: every document is a candidate.
+    All,
+    Trigram(u32),
+    And(Vec<TrigramQuery>),
+    Or(Vec<TrigramQuery>),
+}
+
+impl TrigramQuery {
+    fn and(self, other: TrigramQuery) -> TrigramQuery {
+        match (self, other) {
+            (TrigramQuery::All, q) | (q, TrigramQuery::All) => q,
+            (TrigramQuery::And(mut a), TrigramQuery::And(b)) => {
+                a.extend(b);
+                TrigramQuery::And(a)
+            }
+            (TrigramQuery::And(mut a), q) | (q, TrigramQuery::And(mut a)) => {
+                a.push(q);
+                TrigramQuery::And(a)
+            }
+            (a, b) => TrigramQuery::And(vec![a, b]),
+        }
+    }
+
+    fn or(queries: Vec<TrigramQuery>) -> TrigramQuery {
+        if queries.is_empty() || queries.contains(&TrigramQuery::All) {
+            return TrigramQuery::All;
+        }
+        if queries.len() == 1 {
+            return queries.into_iter().next().unwrap_or(TrigramQuery::All);
+        }
+        TrigramQuery::Or(queries)
+    }
+
+    /// Every trigram the query mentions.
+    pub fn trigrams(&self) -> HashSet<u32> {
+        let mut out = HashSet::new();
+        self.collect(&mut out);
+        out
+    }
+
+    fn collect(&self, out: &mut HashSet<u32>) {
+        match self {
+            TrigramQuery::All => {}
+            TrigramQuery::Trigram(t) => {
+                out.insert(*t);
+            }
+            TrigramQuery::And(qs) | TrigramQuery::Or(qs) => qs.iter().for_each(|q| q.collect(out)),
+        }
+    }
+
+    /// Evaluates the query given a predicate telling whether a trigram is present.
+    pub fn matches(&self, present: &impl Fn(u32) -> bool) -> bool {
+        match self {
+            TrigramQuery::All => true,
+            TrigramQuery::Trigram(t) => present(*t),
+            TrigramQuery::And(qs) => qs.iter().all(|q| q.matches(present)),
+            TrigramQuery::Or(qs) => qs.iter().any(|q| q.matches(present)),
+        }
+    }
+}
+
+/// Query for a case-insensitive substring search.
+pub fn literal_query(query: &str) -> TrigramQuery {
+    // Non-ASCII letters are matched with Unicode case folding, which the
+    // ASCII-lowercased index cannot mirror, so only pure ASCII trigrams count
+    let bytes = query.to_ascii_lowercase().into_bytes();
+    let mut q = TrigramQuery::All;
+    for w in bytes.windows(3).filter(|w| w.is_ascii()) {
+        q = q.and(TrigramQuery::Trigram(pack(w[0], w[1], w[2])));
+    }
+    q
+}
+
+/// Query of trigrams required by any match of `pattern`.
+pub fn regex_query(pattern: &str) -> AppResult<TrigramQuery> {
+    let hir = regex_syntax::Parser::new()
+        .parse(pattern)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Invalid regex: {}", e)))?;
+    Ok(analyze(&hir).into_query())
+}
+
+/// What is known about the strings a sub-expression can match.
+struct Info {
+    /// Every string the expression can match, when that set is small.
+    exact: Option<HashSet<Vec<u8>>>,
+    /// Trigrams required regardless of `exact`.
+    query: TrigramQuery,
+}
+
+impl Info {
+    fn exact(set: HashSet<Vec<u8>>) -> Self {
+        Info {
+            exact: Some(set),
+            query: TrigramQuery::All,
+        }
+    }
+
+    fn any() -> Self {
+        Info {
+            exact: None,
+            query: TrigramQuery::All,
+        }
+    }
+
+    fn into_query(self) -> TrigramQuery {
+        match self.exact {
+            Some(set) => self.query.and(exact_set_query(&set)),
+            None => self.query,
+        }
+    }
+}
+
+fn exact_set_query(set: &HashSet<Vec<u8>>) -> TrigramQuery {
+    let mut alternatives = Vec::new();
+    for s in set {
+        let mut q = TrigramQuery::All;
+        for w in s.windows(3) {
+            q = q.and(TrigramQuery::Trigram(pack(w[0], w[1], w[2])));
+        }
+        alternatives.push(q);
+    }
+    TrigramQuery::or(alternatives)
+}
+
+fn single(bytes: &[u8]) -> HashSet<Vec<u8>> {
+    let mut set = HashSet::new();
+    set.insert(bytes.to_ascii_lowercase());
+    set
+}
+
+fn analyze(hir: &Hir) -> Info {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => Info::exact(single(b"")),
+        HirKind::Literal(lit) => Info::exact(single(&lit.0)),
+        HirKind::Class(class) => class_info(class),
+        HirKind::Capture(cap) => analyze(&cap.sub),
+        HirKind::Repetition(rep) => {
+            if rep.min == 0 {
+                Info::any()
+            } else {
+                // The sub-expression matches at least once, but the repeated
+                // text is no longer one exact string
+                Info {
+                    exact: None,
+                    query: analyze(&rep.sub).into_query(),
+                }
+            }
+        }
+        HirKind::Concat(subs) => {
+            let mut acc = Info::exact(single(b""));
+            for sub in subs {
+                let next = analyze(sub);
+                acc = concat(acc, next);
+            }
+            acc
+        }
+        HirKind::Alternation(subs) => {
+            let infos: Vec<Info> = subs.iter().map(analyze).collect();
+            if infos.iter().all(|i| i.exact.is_some()) {
+                let mut union = HashSet::new();
+                let mut query = Vec::new();
+                for info in infos {
+                    query.push(info.query);
+                    union.extend(info.exact.unwrap_or_default());
+                }
+                if union.len() <= MAX_EXACT_SET {
+                    return Info {
+                        exact: Some(union),
+                        query: if query.iter().all(|q| *q == TrigramQuery::All) {
+                            TrigramQuery::All
+                        } else {
+                            TrigramQuery::or(query)
+                        },
+                    };
+                }
+                return Info {
+                    exact: None,
+                    query: exact_set_query(&union),
+                };
+            }
+            Info {
+                exact: None,
+                query: TrigramQuery::or(infos.into_iter().map(Info::into_query).collect()),
+            }
+        }
+    }
+}
+
+fn concat(left: Info, right: Info) -> Info {
+    match (left.exact, right.exact) {
+        (Some(a), Some(b)) if a.len() * b.len() <= MAX_EXACT_SET => {
+            let mut product = HashSet::new();
+            for x in &a {
+                for y in &b {
+                    let mut s = x.clone();
+                    s.extend_from_slice(y);
+                    product.insert(s);
+                }
+            }
+            Info {
+                exact: Some(product),
+                query: left.query.and(right.query),
+            }
+        }
+        (a, b) => {
+            let mut query = left.query.and(right.query);
+            if let Some(a) = a {
+                query = query.and(exact_set_query(&a));
+            }
+            if let Some(b) = b {
+                query = query.and(exact_set_query(&b));
+            }
+            Info { exact: None, query }
+        }
+    }
+}
+
+fn class_info(class: &Class) -> Info {
+    let mut set = HashSet::new();
+    match class {
+        Class::Unicode(c) => {
+            let size: u32 = c
+                .ranges()
+                .iter()
+                .map(|r| r.end() as u32 - r.start() as u32 + 1)
+                .sum();
+            if size as usize > MAX_CLASS_SIZE {
+                return Info::any();
+            }
+            for r in c.ranges() {
+                for ch in r.start()..=r.end() {
+                    let mut buf = [0u8; 4];
+                    set.insert(ch.encode_utf8(&mut buf).as_bytes().to_ascii_lowercase());
+                }
+            }
+        }
+        Class::Bytes(c) => {
+            let size: usize = c
+                .ranges()
+                .iter()
+                .map(|r| r.end() as usize - r.start() as usize + 1)
+                .sum();
+            if size > MAX_CLASS_SIZE {
+                return Info::any();
+            }
+            for r in c.ranges() {
+                for b in r.start()..=r.end() {
+                    set.insert(vec![b.to_ascii_lowercase()]);
+                }
+            }
+        }
+    }
+    Info::exact(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tri(s: &str) -> u32 {
+        let b = s.as_bytes();
+        pack(b[0], b[1], b[2])
+    }
+
+    fn doc(text: &str) -> impl Fn(u32) -> bool {
+        let set = trigrams(text.as_bytes());
+        move |t| set.contains(&t)
+    }
+
+    #[test]
+    fn test_splits_match_the_edges_of_adjacent_chunks() {
+        let left = trigrams(b"let tok");
+        let right = trigrams(b"EN = 1;");
+        assert!(!left.contains(&tri("oke")) && !right.contains(&tri("oke")));
+        let [(tail2, head1), (tail1, head2)] = splits(tri("oke"));
+        assert!(left.contains(&tail2) && right.contains(&head1));
+        assert!(!left.contains(&tail1) && !right.contains(&head2));
+        let [_, (tail1, head2)] = splits(tri("ken"));
+        assert!(left.contains(&tail1) && right.contains(&head2));
+        assert!(trigrams(b"").is_empty());
+        assert_eq!(trigrams(b"x").len(), 2);
+    }
+
+    #[test]
+    fn test_literal_query_requires_all_trigrams() {
+        let q = literal_query("Hello");
+        assert!(q.trigrams().contains(&tri("hel")));
+        assert!(q.matches(&doc("say HELLO world")));
+        assert!(!q.matches(&doc("say help")));
+        assert_eq!(literal_query("ab"), TrigramQuery::All);
+    }
+
+    #[test]
+    fn test_regex_query_extracts_literals() {
+        let q = regex_query(r"fn\s+parse_\w+").unwrap();
+        assert!(q.trigrams().contains(&tri("rse")));
+        assert!(q.matches(&doc("pub fn parse_header()")));
+        assert!(!q.matches(&doc("pub fn render()")));
+    }
+
+    #[test]
+    fn test_regex_alternation_is_or() {
+        let q = regex_query("(foo|bar)baz").unwrap();
+        assert!(q.matches(&doc("xxfoobaz")));
+        assert!(q.matches(&doc("barbaz")));
+        assert!(!q.matches(&doc("quxbaz")));
+    }
+
+    #[test]
+    fn test_regex_without_literals_matches_everything() {
+        assert_eq!(regex_query(r"\w+\d*").unwrap(), TrigramQuery::All);
+        assert_eq!(regex_query("a?b?c?").unwrap(), TrigramQuery::All);
+    }
+
+    #[test]
+    fn test_case_insensitive_regex_uses_index() {
+        let q = regex_query("(?i)todo").unwrap();
+        assert_ne!(q, TrigramQuery::All);
+        assert!(q.matches(&doc("// TODO: fix")));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_error() {
+        assert!(regex_query("(unclosed").is_err());
+    }
+}
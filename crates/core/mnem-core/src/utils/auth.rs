@@ -47,7 +47,7 @@ impl AuthManager {
     }
 
     #[cfg(unix)]
-    fn restrict_permissions(path: &std::path::Path) -> AppResult<()> {
+    pub(crate) fn restrict_permissions(path: &std::path::Path) -> AppResult<()> {
         use std::os::unix::fs::PermissionsExt;
         let mut perms = fs::metadata(path)
             .map_err(|e| AppError::Io {
@@ -64,7 +64,7 @@ impl AuthManager {
     }
 
     #[cfg(windows)]
-    fn restrict_permissions(_path: &std::path::Path) -> AppResult<()> {
+    pub(crate) fn restrict_permissions(_path: &std::path::Path) -> AppResult<()> {
         // Windows permissions are more complex to set via std::fs.
         // For now, we rely on the fact that it's in the user's home directory.
         // TODO: Implement NTFS ACL restriction if critical.
@@ -0,0 +1,51 @@
+//! Conversions between stored epoch milliseconds and RFC3339 text.
+//!
+//! The store keeps every timestamp as UTC milliseconds since the epoch so
+//! ordering never depends on the offset a record was written with. Models and
+//! the CLI still speak RFC3339 in the local timezone.
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+
+/// Current time as UTC epoch milliseconds.
+pub fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Parses an RFC3339 timestamp (any offset) into UTC epoch milliseconds.
+pub fn parse_ms(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|ts| ts.timestamp_millis())
+}
+
+/// Formats UTC epoch milliseconds as RFC3339 in the local timezone.
+pub fn format_ms(ms: i64) -> String {
+    match Local.timestamp_millis_opt(ms).single() {
+        Some(ts) => ts.to_rfc3339(),
+        None => Utc
+            .timestamp_millis_opt(ms)
+            .single()
+            .unwrap_or_default()
+            .to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_normalize_to_the_same_instant() {
+        let utc = parse_ms("2024-03-10T10:00:00+00:00").unwrap();
+        let pst = parse_ms("2024-03-10T02:00:00-08:00").unwrap();
+        let cet = parse_ms("2024-03-10T11:00:00+01:00").unwrap();
+        assert_eq!(utc, pst);
+        assert_eq!(utc, cet);
+        assert_eq!(parse_ms(&format_ms(utc)), Some(utc));
+    }
+
+    #[test]
+    fn test_invalid_input_is_rejected() {
+        assert_eq!(parse_ms("yesterday"), None);
+    }
+}
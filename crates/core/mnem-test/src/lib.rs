@@ -1 +1,31 @@
-// mnem-test library. Tests have been moved to the tests/ directory.
+//! Fixtures shared by the integration tests in `tests/`.
+
+use mnem_core::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Opens `dir/<name>` as a tracked project with id `name`, its store in
+/// `dir/home_<name>`. Projects opened under the same `dir` share nothing.
+pub fn open_project(dir: &Path, name: &str) -> Repository {
+    let base_dir = dir.join(format!("home_{}", name));
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.join(name);
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(
+        project_mnem_dir.join("tracked"),
+        format!("project_id: {}", name),
+    )
+    .unwrap();
+    Repository::open(base_dir, project_dir).unwrap()
+}
+
+/// Writes `content` to `rel` under the project and saves a snapshot of it.
+/// Returns the absolute path.
+pub fn write(repo: &Repository, rel: &str, content: &str) -> PathBuf {
+    let path = PathBuf::from(format!("{}/{}", repo.project.path, rel));
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, content).unwrap();
+    repo.save_snapshot_from_file(&path).unwrap();
+    path
+}
@@ -0,0 +1,86 @@
+use mnem_core::Repository;
+use mnem_core::protocol::SnapshotContentResponse;
+use mnem_test::open_project;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Deterministic bytes that look binary from the first byte on.
+fn blob(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    let mut data: Vec<u8> = (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    data[..4].copy_from_slice(b"\0\0\0\x01");
+    data
+}
+
+fn chunks_of(repo: &Repository, path: &Path) -> HashSet<String> {
+    let snap = repo.get_file_history(&path.to_string_lossy()).unwrap()[0].clone();
+    repo.db
+        .get_snapshot_chunks(snap.id)
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+#[test]
+fn test_binary_edits_share_most_chunks() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let path = PathBuf::from(format!("{}/fixture.sqlite", repo.project.path));
+
+    let original = blob(2 * 1024 * 1024, 42);
+    fs::write(&path, &original).unwrap();
+    let first = repo.save_snapshot_from_file(&path).unwrap();
+    assert!(repo.is_blob(&original));
+    let before = chunks_of(&repo, &path);
+    assert!(before.len() > 4);
+
+    let mut edited = original.clone();
+    edited[1024 * 1024..1024 * 1024 + 16].copy_from_slice(&[0xAB; 16]);
+    fs::write(&path, &edited).unwrap();
+    let second = repo.save_snapshot_from_file(&path).unwrap();
+    assert_ne!(first, second);
+    let after = chunks_of(&repo, &path);
+    assert!(after.difference(&before).count() <= 2);
+
+    // Both versions reassemble byte for byte
+    assert_eq!(repo.get_content(&first).unwrap(), original);
+    assert_eq!(repo.get_content(&second).unwrap(), edited);
+    repo.restore_file(&first, &path.to_string_lossy()).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    // and survive the trip through the daemon's reply
+    let reply = serde_json::to_value(SnapshotContentResponse::new(&edited)).unwrap();
+    let reply: SnapshotContentResponse = serde_json::from_value(reply).unwrap();
+    assert_eq!(reply.into_bytes().unwrap(), edited);
+
+    // Blobs stay out of content search
+    assert!(repo.grep_contents("zzz", None, false).unwrap().is_empty());
+    assert!(repo.fsck(false).unwrap().is_clean());
+}
+
+#[test]
+fn test_text_over_the_size_limit_is_kept_as_a_blob() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    repo.config.lock().unwrap().config.max_file_size_mb = 1;
+    let path = PathBuf::from(format!("{}/dump.sql", repo.project.path));
+
+    let line = "INSERT INTO events VALUES (1, 'page_view');\n";
+    let content = line.repeat(2 * 1024 * 1024 / line.len());
+    fs::write(&path, &content).unwrap();
+    assert!(repo.is_blob(content.as_bytes()));
+    assert!(!repo.is_blob(line.as_bytes()));
+
+    let hash = repo.save_snapshot_from_file(&path).unwrap();
+    assert!(chunks_of(&repo, &path).len() > 1);
+    assert_eq!(repo.get_content(&hash).unwrap(), content.as_bytes());
+}
@@ -0,0 +1,69 @@
+use mnem_core::storage::bundle::BundleFilter;
+use mnem_test::{open_project, write};
+use std::fs;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_bundle_round_trip_into_another_checkout() {
+    let dir = TempDir::new().unwrap();
+    let source = open_project(dir.path(), "laptop");
+    let a = write(&source, "a.txt", "first draft");
+    fs::write(&a, "second draft").unwrap();
+    let latest_a = source.save_snapshot_from_file(&a).unwrap();
+    write(&source, "src/b.rs", "fn main() {}");
+    source.create_checkpoint(Some("before move")).unwrap();
+
+    let bundle_path = dir.path().join("history.mnembundle");
+    let manifest = source
+        .export_bundle(&bundle_path, &BundleFilter::default())
+        .unwrap();
+    assert_eq!(manifest.snapshots, 3);
+    assert_eq!(manifest.checkpoints, 1);
+
+    // Paths are rebased onto the new checkout
+    let target = open_project(dir.path(), "desktop");
+    let (_, report) = target.import_bundle(&bundle_path).unwrap();
+    assert_eq!(report.snapshots_imported, 3);
+    assert_eq!(report.checkpoints_imported, 1);
+
+    let moved_a = target.project.path.clone() + "/a.txt";
+    let history = target.get_file_history(&moved_a).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].content_hash, latest_a);
+    assert_eq!(target.get_content(&latest_a).unwrap(), b"second draft");
+    assert_eq!(target.list_checkpoints().unwrap().len(), 1);
+    assert_eq!(
+        target
+            .grep_contents("first draft", None, false)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Importing again adds nothing
+    let (_, again) = target.import_bundle(&bundle_path).unwrap();
+    assert_eq!(again.snapshots_imported, 0);
+    assert_eq!(again.snapshots_skipped, 3);
+    assert_eq!(target.get_file_history(&moved_a).unwrap().len(), 2);
+    assert!(target.fsck(false).unwrap().is_clean());
+}
+
+#[tokio::test]
+async fn test_bundle_export_filters_by_path() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "a.txt", "not exported");
+    write(&repo, "src/b.rs", "exported");
+
+    let filter = BundleFilter {
+        path_glob: Some("src/**".to_string()),
+        ..BundleFilter::default()
+    };
+    let bundle_path = dir.path().join("src.mnembundle");
+    let manifest = repo.export_bundle(&bundle_path, &filter).unwrap();
+    assert_eq!(manifest.snapshots, 1);
+    assert_eq!(manifest.objects, 1);
+
+    let read = mnem_core::storage::bundle::read_manifest(&bundle_path).unwrap();
+    assert_eq!(read.path_glob.as_deref(), Some("src/**"));
+}
@@ -0,0 +1,111 @@
+use mnem_core::Repository;
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn changeset_of(repo: &Repository, path: &Path) -> Option<i64> {
+    repo.get_history(&path.to_string_lossy()).unwrap()[0].changeset_id
+}
+
+#[test]
+fn test_saves_within_the_window_share_a_changeset() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let main = write(&repo, "src/main.rs", "v1");
+    let readme = write(&repo, "README.md", "v1");
+
+    let id = changeset_of(&repo, &lib);
+    assert!(id.is_some());
+    assert_eq!(changeset_of(&repo, &main), id);
+    assert_eq!(changeset_of(&repo, &readme), id);
+
+    let changesets = repo.list_changesets(10).unwrap();
+    assert_eq!(changesets.len(), 1);
+    assert_eq!(changesets[0].id, id.unwrap());
+    assert_eq!(changesets[0].snapshot_count, 3);
+    assert_eq!(
+        changesets[0].files,
+        vec![
+            readme.to_string_lossy().to_string(),
+            lib.to_string_lossy().to_string(),
+            main.to_string_lossy().to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_zero_window_gives_every_save_its_own_changeset() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    repo.config.lock().unwrap().config.changeset_window_secs = 0;
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let main = write(&repo, "src/main.rs", "v1");
+
+    assert_ne!(changeset_of(&repo, &lib), changeset_of(&repo, &main));
+    let changesets = repo.list_changesets(10).unwrap();
+    assert_eq!(changesets.len(), 2);
+    assert_eq!(changesets[0].id, changeset_of(&repo, &main).unwrap());
+    assert_eq!(repo.list_changesets(1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_revert_changeset_undoes_every_file_it_touched() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    repo.config.lock().unwrap().config.changeset_window_secs = 0;
+    let lib = write(&repo, "src/lib.rs", "lib v1");
+    let readme = write(&repo, "README.md", "readme v1");
+
+    // Opens a fresh changeset that the next saves join
+    write(&repo, "src/lib.rs", "lib v2");
+    repo.config.lock().unwrap().config.changeset_window_secs = 60;
+    write(&repo, "README.md", "readme v2");
+    let created = write(&repo, "src/new.rs", "new");
+    let id = changeset_of(&repo, &created).unwrap();
+    assert_eq!(changeset_of(&repo, &lib), Some(id));
+
+    assert_eq!(repo.revert_changeset(id).unwrap(), 3);
+    assert_eq!(fs::read_to_string(&lib).unwrap(), "lib v1");
+    assert_eq!(fs::read_to_string(&readme).unwrap(), "readme v1");
+    assert!(!created.exists());
+
+    // The revert is its own changeset, so it can be undone in turn
+    let revert = changeset_of(&repo, &lib).unwrap();
+    assert_ne!(revert, id);
+    assert_eq!(changeset_of(&repo, &created), Some(revert));
+    assert_eq!(repo.revert_changeset(revert).unwrap(), 3);
+    assert_eq!(fs::read_to_string(&lib).unwrap(), "lib v2");
+    assert_eq!(fs::read_to_string(&created).unwrap(), "new");
+}
+
+#[test]
+fn test_revert_unknown_changeset_fails() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "a.txt", "a");
+    assert!(repo.revert_changeset(9999).is_err());
+}
+
+#[test]
+fn test_failed_revert_puts_back_the_files_it_already_swapped() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    repo.config.lock().unwrap().config.changeset_window_secs = 0;
+    let a = write(&repo, "a.txt", "a v1");
+    let b = write(&repo, "b.txt", "b v1");
+
+    write(&repo, "a.txt", "a v2");
+    repo.config.lock().unwrap().config.changeset_window_secs = 60;
+    write(&repo, "b.txt", "b v2");
+    let id = changeset_of(&repo, &a).unwrap();
+    assert_eq!(changeset_of(&repo, &b), Some(id));
+
+    // a.txt is swapped first, then b.txt cannot be replaced
+    fs::remove_file(&b).unwrap();
+    fs::create_dir_all(b.join("blocker")).unwrap();
+    assert!(repo.revert_changeset(id).is_err());
+    assert_eq!(fs::read_to_string(&a).unwrap(), "a v2");
+    assert!(b.join("blocker").is_dir());
+}
@@ -0,0 +1,99 @@
+use mnem_core::models::{ChangeKind, CheckpointChange};
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn summary(changes: &[CheckpointChange]) -> Vec<(String, ChangeKind, usize, usize)> {
+    changes
+        .iter()
+        .map(|c| {
+            let name = Path::new(&c.path).file_name().unwrap();
+            (
+                name.to_string_lossy().to_string(),
+                c.kind,
+                c.lines_added,
+                c.lines_removed,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_diff_lists_added_removed_and_modified_files() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "fn a() {}\nfn b() {}\n");
+    let gone = write(&repo, "src/old.rs", "one\ntwo\n");
+    write(&repo, "README.md", "same\n");
+    let before = repo.create_checkpoint(Some("before")).unwrap();
+
+    write(&repo, "src/lib.rs", "fn a() {}\nfn c() {}\nfn d() {}\n");
+    write(&repo, "src/new.rs", "fresh\n");
+    fs::remove_file(&gone).unwrap();
+    repo.record_deletion(&gone).unwrap();
+    let after = repo.create_checkpoint(Some("after")).unwrap();
+
+    assert_eq!(
+        summary(&repo.checkpoint_diff(&before[..8], &after[..8]).unwrap()),
+        vec![
+            ("lib.rs".to_string(), ChangeKind::Modified, 2, 1),
+            ("new.rs".to_string(), ChangeKind::Added, 1, 0),
+            ("old.rs".to_string(), ChangeKind::Removed, 0, 2),
+        ]
+    );
+    assert!(repo.checkpoint_diff(&after, &after).unwrap().is_empty());
+    assert!(repo.checkpoint_diff("ffffffff", &after).is_err());
+}
+
+#[test]
+fn test_status_compares_the_working_tree() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let lib = write(&repo, "src/lib.rs", "fn a() {}\n");
+    let notes = write(&repo, "notes.txt", "keep\n");
+    let checkpoint = repo.create_checkpoint(None).unwrap();
+    assert!(repo.checkpoint_status(&checkpoint).unwrap().is_empty());
+
+    // Unsaved edits show up too, not just what the history has seen
+    fs::write(&lib, "fn a() {}\nfn b() {}\n").unwrap();
+    fs::remove_file(&notes).unwrap();
+    write(&repo, "src/extra.rs", "x\n");
+    assert_eq!(
+        summary(&repo.checkpoint_status(&checkpoint).unwrap()),
+        vec![
+            ("notes.txt".to_string(), ChangeKind::Removed, 0, 1),
+            ("extra.rs".to_string(), ChangeKind::Added, 1, 0),
+            ("lib.rs".to_string(), ChangeKind::Modified, 1, 0),
+        ]
+    );
+}
+
+#[test]
+fn test_partial_revert_only_touches_matching_paths() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let readme = write(&repo, "README.md", "v1");
+    let checkpoint = repo.create_checkpoint(None).unwrap();
+
+    write(&repo, "src/lib.rs", "v2");
+    write(&repo, "README.md", "v2");
+    let scratch = write(&repo, "src/scratch.rs", "tmp");
+    let notes = write(&repo, "notes.txt", "outside");
+
+    assert_eq!(
+        repo.revert_paths_to_checkpoint(&checkpoint, Some("src/**"))
+            .unwrap(),
+        1
+    );
+    assert_eq!(fs::read_to_string(&lib).unwrap(), "v1");
+    assert!(!scratch.exists());
+    assert_eq!(fs::read_to_string(&readme).unwrap(), "v2");
+    assert!(notes.exists());
+
+    assert!(
+        repo.revert_paths_to_checkpoint(&checkpoint, Some("src/["))
+            .is_err()
+    );
+}
@@ -0,0 +1,89 @@
+use mnem_core::Repository;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_literal_and_regex_content_search() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let lib = repo.project.path.clone() + "/lib.rs";
+    let notes = repo.project.path.clone() + "/notes.txt";
+
+    fs::write(&lib, "pub fn parse_header() {}\npub fn render() {}\n").unwrap();
+    repo.save_snapshot_from_file(Path::new(&lib)).unwrap();
+    fs::write(&notes, "TODO: write the parser\n").unwrap();
+    repo.save_snapshot_from_file(Path::new(&notes)).unwrap();
+
+    // Literal search stays case-insensitive
+    let results = repo.grep_contents("todo", None, false).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].file_path, notes);
+
+    let results = repo.grep_contents(r"fn\s+parse_\w+", None, true).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].file_path, lib);
+    assert_eq!(results[0].line_number, 1);
+
+    let results = repo.grep_contents("(parser|render)", None, true).unwrap();
+    assert_eq!(results.len(), 2);
+
+    assert!(repo.grep_contents("(unclosed", None, true).is_err());
+
+    // Nothing is left for the maintenance backfill after a normal save
+    assert_eq!(repo.index_pending_trigrams().unwrap(), 0);
+}
+
+#[test]
+fn test_search_finds_matches_across_chunk_boundaries() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    // Every file is cut by content, so chunk boundaries fall mid-line
+    repo.config.lock().unwrap().config.max_file_size_mb = 0;
+    let path = repo.project.path.clone() + "/dump.txt";
+    let mut state = 7u64;
+    let text: String = (0..200 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (b'!' + (state % 94) as u8) as char
+        })
+        .collect();
+    fs::write(&path, &text).unwrap();
+    repo.save_snapshot_from_file(Path::new(&path)).unwrap();
+    let id = repo.get_file_history(&path).unwrap()[0].id;
+    let chunks = repo.db.get_snapshot_chunks(id).unwrap();
+    assert!(chunks.len() > 1);
+
+    // A needle whose straddling trigram occurs nowhere else in the file
+    let lower = text.to_ascii_lowercase();
+    let occurrences = |at: usize| lower.matches(&lower[at..at + 3]).count();
+    let mut boundary = 0;
+    let needle = chunks[..chunks.len() - 1]
+        .iter()
+        .find_map(|chunk| {
+            boundary += repo.fs.read(chunk).unwrap().len();
+            (occurrences(boundary - 2) == 1 || occurrences(boundary - 1) == 1)
+                .then(|| &text[boundary - 6..boundary + 6])
+        })
+        .expect("a boundary with a unique trigram");
+
+    let results = repo.grep_contents(needle, None, false).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].file_path, path);
+}
@@ -0,0 +1,250 @@
+//! The unlocked key is process-wide, so every test here runs under one lock
+//! and leaves encryption the way it found it.
+
+use mnem_core::storage::commit_queue::{self, GitCommitEvent};
+use mnem_core::{AppError, Repository, crypto};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const SECRET: &str = "API_KEY=hunter2-correct-horse";
+const MESSAGE: &str = "rotate the staging credentials";
+
+fn project(dir: &Path, name: &str) -> PathBuf {
+    let project_dir = dir.join(name);
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(
+        project_mnem_dir.join("tracked"),
+        format!("project_id: {}", name),
+    )
+    .unwrap();
+    project_dir
+}
+
+fn record_history(repo: &Repository) -> String {
+    let env = repo.project.path.clone() + "/.env";
+    fs::write(&env, SECRET).unwrap();
+    let hash = repo.save_snapshot_from_file(Path::new(&env)).unwrap();
+    repo.record_git_commit(&GitCommitEvent {
+        hash: "a1b2c3d4e5f6".to_string(),
+        message: MESSAGE.to_string(),
+        author: "dev".to_string(),
+        timestamp: "2024-03-10T10:00:00+00:00".to_string(),
+    })
+    .unwrap();
+    repo.pin(&hash, MESSAGE).unwrap();
+    hash
+}
+
+/// Files under the project's `.mnemosyne` containing `needle`.
+fn files_containing(project_dir: &Path, needle: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![project_dir.join(".mnemosyne")];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if fs::read(&path)
+                .unwrap()
+                .windows(needle.len())
+                .any(|w| w == needle.as_bytes())
+            {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+#[test]
+fn test_encrypted_history_hides_content_paths_and_messages() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home");
+    let key_file = crypto::enable_with_key_file(&base_dir, None).unwrap();
+    assert_eq!(key_file.file_name().unwrap(), crypto::KEY_FILE);
+
+    let project_dir = project(dir.path(), "secret-project");
+    let repo = Repository::open(base_dir.clone(), project_dir.clone()).unwrap();
+    let hash = record_history(&repo);
+
+    // Keyed ids still deduplicate identical content
+    let copy = repo.project.path.clone() + "/copy.env";
+    fs::write(&copy, SECRET).unwrap();
+    assert_eq!(
+        repo.save_snapshot_from_file(Path::new(&copy)).unwrap(),
+        hash
+    );
+
+    // Everything still reads back through the repository
+    let env = repo.project.path.clone() + "/.env";
+    let history = repo.get_file_history(&env).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(repo.list_commits().unwrap()[0].1, MESSAGE);
+    assert_eq!(repo.get_content(&hash).unwrap(), SECRET.as_bytes());
+    assert_eq!(
+        repo.db.resolve_hash(&hash[..8]).unwrap(),
+        Some(hash.clone())
+    );
+    assert_eq!(repo.get_history(&repo.project.path).unwrap().len(), 2);
+    assert_eq!(repo.grep_contents("hunter2", None, false).unwrap().len(), 1);
+    assert!(repo.fsck(false).unwrap().is_clean());
+    drop(repo);
+
+    for needle in ["hunter2", "staging credentials", ".env"] {
+        assert!(
+            files_containing(&project_dir, needle).is_empty(),
+            "{:?} found on disk",
+            needle
+        );
+    }
+
+    crypto::lock();
+}
+
+#[test]
+fn test_plaintext_history_is_sealed_when_encryption_is_enabled() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    crypto::lock();
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = project(dir.path(), "older-project");
+
+    let repo = Repository::open(base_dir.clone(), project_dir.clone()).unwrap();
+    let hash = record_history(&repo);
+    drop(repo);
+    assert!(!files_containing(&project_dir, "staging credentials").is_empty());
+
+    crypto::enable_with_key_file(&base_dir, None).unwrap();
+    let repo = Repository::open(base_dir.clone(), project_dir.clone()).unwrap();
+    assert!(repo.db.is_sealed().unwrap());
+    let env = repo.project.path.clone() + "/.env";
+    let history = repo.get_file_history(&env).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].content_hash, hash);
+    assert_eq!(repo.list_commits().unwrap()[0].1, MESSAGE);
+    assert_eq!(repo.list_pins(None).unwrap()[0].label, MESSAGE);
+    assert_eq!(repo.get_content(&hash).unwrap(), SECRET.as_bytes());
+    // The hash prefix index is rebuilt with keyed prefixes
+    assert_eq!(
+        repo.db.resolve_hash(&hash[..8]).unwrap(),
+        Some(hash.clone())
+    );
+
+    // New objects get keyed ids; old ones keep theirs
+    let copy = repo.project.path.clone() + "/copy.env";
+    fs::write(&copy, SECRET).unwrap();
+    assert_ne!(
+        repo.save_snapshot_from_file(Path::new(&copy)).unwrap(),
+        hash
+    );
+
+    // The search index is rebuilt with keyed trigrams
+    repo.index_pending_trigrams().unwrap();
+    assert_eq!(repo.grep_contents("hunter2", None, false).unwrap().len(), 2);
+    assert!(repo.fsck(false).unwrap().is_clean());
+    drop(repo);
+    for needle in ["hunter2", "staging credentials", ".env"] {
+        assert!(
+            files_containing(&project_dir, needle).is_empty(),
+            "{:?} still on disk: {:?}",
+            needle,
+            files_containing(&project_dir, needle)
+        );
+    }
+
+    // Without the key the store refuses to open rather than mix in plaintext
+    crypto::lock();
+    let keyless = dir.path().join("elsewhere");
+    fs::create_dir_all(&keyless).unwrap();
+    let locked = Repository::open(keyless, project_dir.clone());
+    assert!(matches!(locked, Err(AppError::Encryption(_))));
+
+    // A tampered object fails authentication instead of reading as garbage
+    crypto::unlock(&base_dir, None).unwrap();
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let path = repo.fs.get_path(&hash);
+    let mut raw = fs::read(&path).unwrap();
+    *raw.last_mut().unwrap() ^= 1;
+    fs::write(&path, raw).unwrap();
+    assert!(repo.get_content(&hash).is_err());
+
+    crypto::lock();
+}
+
+#[test]
+fn test_sealed_object_copied_over_another_fails_to_open() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home");
+    crypto::enable_with_key_file(&base_dir, None).unwrap();
+    let project_dir = project(dir.path(), "swapped-project");
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+
+    let save = |name: &str, content: &str| {
+        let path = repo.project.path.clone() + "/" + name;
+        fs::write(&path, content).unwrap();
+        repo.save_snapshot_from_file(Path::new(&path)).unwrap()
+    };
+    let real = save("config.toml", "debug = false");
+    let planted = save("other.toml", "debug = true");
+
+    // Both seal under the same key, but each is bound to its own id
+    fs::copy(repo.fs.get_path(&planted), repo.fs.get_path(&real)).unwrap();
+    assert!(repo.get_content(&real).is_err());
+    assert_eq!(repo.get_content(&planted).unwrap(), b"debug = true");
+
+    crypto::lock();
+}
+
+#[test]
+fn test_commit_queued_under_encryption_leaves_its_message_to_git() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home");
+    crypto::enable_with_key_file(&base_dir, None).unwrap();
+    crypto::lock();
+    let project_dir = project(dir.path(), "queued-project");
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(["-c", "user.name=dev", "-c", "user.email=dev@example.com"])
+            .args(args)
+            .current_dir(&project_dir)
+            .output()
+            .expect("git is installed");
+        assert!(output.status.success(), "git {:?}", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    git(&["init", "-q"]);
+    fs::write(project_dir.join(".gitignore"), ".mnemosyne/\n").unwrap();
+    git(&["add", "-A"]);
+    git(&["commit", "-q", "-m", MESSAGE]);
+    let hash = git(&["rev-parse", "HEAD"]);
+
+    // The hook holds no key, so the message stays out of the queue
+    let event = GitCommitEvent {
+        hash: hash.clone(),
+        message: MESSAGE.to_string(),
+        author: "dev".to_string(),
+        timestamp: "2024-03-10T10:00:00+00:00".to_string(),
+    };
+    commit_queue::enqueue(&base_dir, &project_dir, &event).unwrap();
+    assert!(files_containing(&project_dir, "staging credentials").is_empty());
+
+    // and is read back from git when the next owner replays it
+    crypto::unlock(&base_dir, None).unwrap();
+    let repo = Repository::open(base_dir, project_dir.clone()).unwrap();
+    let (_, message, author, _) = repo.get_commit_details(&hash).unwrap().unwrap();
+    assert_eq!((message.as_str(), author.as_str()), (MESSAGE, "dev"));
+    drop(repo);
+    assert!(files_containing(&project_dir, "staging credentials").is_empty());
+
+    crypto::lock();
+}
@@ -0,0 +1,79 @@
+use mnem_core::protocol::PID_FILE;
+use mnem_core::storage::commit_queue::{self, GitCommitEvent};
+use mnem_core::{AppError, Repository};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+    (dir, base_dir, project_dir)
+}
+
+fn commit(hash: &str) -> GitCommitEvent {
+    GitCommitEvent {
+        hash: hash.to_string(),
+        message: format!("commit {}", hash),
+        author: "dev".to_string(),
+        timestamp: "2024-03-10T10:00:00+00:00".to_string(),
+    }
+}
+
+#[test]
+fn test_exclusive_open_reports_a_held_store_as_locked() {
+    let (_dir, base_dir, project_dir) = setup();
+
+    let holder = Repository::open(base_dir.clone(), project_dir.clone()).unwrap();
+    let second = Repository::open_exclusive(base_dir.clone(), project_dir.clone());
+    assert!(matches!(second, Err(AppError::Locked(_))));
+    drop(holder);
+    assert!(Repository::open_exclusive(base_dir.clone(), project_dir.clone()).is_ok());
+
+    // A live daemon owns every watched project
+    fs::write(base_dir.join(PID_FILE), std::process::id().to_string()).unwrap();
+    let refused = Repository::open_exclusive(base_dir, project_dir);
+    assert!(matches!(refused, Err(AppError::Locked(_))));
+}
+
+#[test]
+fn test_commits_queued_while_busy_are_replayed_by_the_next_owner() {
+    let (_dir, base_dir, project_dir) = setup();
+
+    let holder = Repository::open(base_dir.clone(), project_dir.clone()).unwrap();
+    commit_queue::enqueue(&base_dir, &project_dir, &commit("aaaaaaaa1")).unwrap();
+    commit_queue::enqueue(&base_dir, &project_dir, &commit("bbbbbbbb2")).unwrap();
+    assert!(holder.list_commits().unwrap().is_empty());
+
+    // The current owner records them ahead of the next reported commit
+    holder.record_git_commit(&commit("cccccccc3")).unwrap();
+    assert_eq!(holder.list_commits().unwrap().len(), 3);
+    drop(holder);
+
+    // A later owner picks up whatever was queued in between on open
+    commit_queue::enqueue(&base_dir, &project_dir, &commit("dddddddd4")).unwrap();
+    let repo = Repository::open(base_dir, project_dir.clone()).unwrap();
+    let hashes: Vec<String> = repo
+        .list_commits()
+        .unwrap()
+        .into_iter()
+        .map(|(hash, ..)| hash)
+        .collect();
+    assert_eq!(hashes.len(), 4);
+    assert!(hashes.contains(&"dddddddd4".to_string()));
+    assert_eq!(repo.replay_queued_commits().unwrap(), 0);
+    assert!(
+        fs::read_dir(project_dir.join(".mnemosyne"))
+            .unwrap()
+            .flatten()
+            .all(|e| !e
+                .file_name()
+                .to_string_lossy()
+                .starts_with("pending_commits"))
+    );
+}
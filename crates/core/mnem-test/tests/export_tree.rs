@@ -0,0 +1,145 @@
+use chrono::{SecondsFormat, Utc};
+use mnem_core::storage::tree::MANIFEST_FILE;
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn now() -> String {
+    std::thread::sleep(Duration::from_millis(20));
+    let at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    std::thread::sleep(Duration::from_millis(20));
+    at
+}
+
+#[test]
+fn test_checkpoint_is_exported_without_touching_the_project() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "v1");
+    write(&repo, "README.md", "readme");
+    let checkpoint = repo.create_checkpoint(Some("before")).unwrap();
+    // Unencrypted history is addressed by the plain BLAKE3 of the content
+    let v1_hash = repo
+        .get_history(&format!("{}/src/lib.rs", repo.project.path))
+        .unwrap()[0]
+        .content_hash
+        .clone();
+    let lib = write(&repo, "src/lib.rs", "v2");
+
+    let dest = dir.path().join("out");
+    let manifest = repo.export_tree(&checkpoint[..8], &dest).unwrap();
+
+    assert_eq!(fs::read_to_string(dest.join("src/lib.rs")).unwrap(), "v1");
+    assert_eq!(
+        fs::read_to_string(dest.join("README.md")).unwrap(),
+        "readme"
+    );
+    assert_eq!(fs::read_to_string(&lib).unwrap(), "v2");
+    assert!(manifest.source.starts_with("checkpoint "));
+
+    let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["README.md", "src/lib.rs"]);
+    assert_eq!(manifest.files[1].blake3, v1_hash);
+    assert_eq!(manifest.files[1].size, 2);
+    let written = fs::read_to_string(dest.join(MANIFEST_FILE)).unwrap();
+    assert!(written.contains(&v1_hash));
+}
+
+#[test]
+fn test_timestamp_and_commit_pick_the_state_of_that_moment() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "v1");
+    let first = now();
+    repo.insert_git_commit("abcdef1234567890", "first", "dev", &first)
+        .unwrap();
+    write(&repo, "src/lib.rs", "v2");
+    write(&repo, "src/new.rs", "new");
+
+    let by_time = dir.path().join("by_time");
+    let manifest = repo.export_tree(&first, &by_time).unwrap();
+    assert_eq!(manifest.files.len(), 1);
+    assert_eq!(
+        fs::read_to_string(by_time.join("src/lib.rs")).unwrap(),
+        "v1"
+    );
+    assert!(!by_time.join("src/new.rs").exists());
+
+    let by_commit = dir.path().join("by_commit");
+    let manifest = repo.export_tree("abcdef12", &by_commit).unwrap();
+    assert_eq!(manifest.source, "commit abcdef12");
+    assert_eq!(
+        fs::read_to_string(by_commit.join("src/lib.rs")).unwrap(),
+        "v1"
+    );
+
+    let latest = dir.path().join("latest");
+    repo.export_tree(&now(), &latest).unwrap();
+    assert_eq!(fs::read_to_string(latest.join("src/lib.rs")).unwrap(), "v2");
+    assert_eq!(
+        fs::read_to_string(latest.join("src/new.rs")).unwrap(),
+        "new"
+    );
+}
+
+#[test]
+fn test_refuses_unsafe_destinations_and_unknown_points() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "v1");
+    let at = now();
+
+    let occupied = dir.path().join("occupied");
+    fs::create_dir_all(&occupied).unwrap();
+    fs::write(occupied.join("keep.txt"), "mine").unwrap();
+    assert!(repo.export_tree(&at, &occupied).is_err());
+    assert_eq!(
+        fs::read_to_string(occupied.join("keep.txt")).unwrap(),
+        "mine"
+    );
+
+    let inside = Path::new(&repo.project.path).join("old");
+    assert!(repo.export_tree(&at, &inside).is_err());
+    assert!(!inside.exists());
+
+    let empty = dir.path().join("empty");
+    fs::create_dir_all(&empty).unwrap();
+    repo.export_tree(&at, &empty).unwrap();
+    assert!(empty.join(MANIFEST_FILE).exists());
+
+    assert!(
+        repo.export_tree("nothing-like-this", &dir.path().join("x"))
+            .is_err()
+    );
+    assert!(!dir.path().join("x").exists());
+}
+
+#[test]
+fn test_tar_export_lists_every_file_and_the_manifest() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "v1");
+    write(&repo, "README.md", "readme");
+    let checkpoint = repo.create_checkpoint(None).unwrap();
+
+    let mut archive = Vec::new();
+    let manifest = repo.export_tree_tar(&checkpoint, &mut archive).unwrap();
+    assert_eq!(manifest.files.len(), 2);
+    assert_eq!(archive.len() % 512, 0);
+
+    let mut names = Vec::new();
+    let mut offset = 0;
+    while offset + 512 <= archive.len() && archive[offset] != 0 {
+        let header = &archive[offset..offset + 512];
+        let name = &header[..100];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(100)];
+        let size_field = std::str::from_utf8(&header[124..135]).unwrap();
+        let size = usize::from_str_radix(size_field, 8).unwrap();
+        names.push(String::from_utf8(name.to_vec()).unwrap());
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    assert_eq!(names, vec!["README.md", "src/lib.rs", MANIFEST_FILE]);
+    assert!(Path::new(&repo.project.path).join("src/lib.rs").exists());
+}
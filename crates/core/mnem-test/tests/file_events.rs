@@ -0,0 +1,166 @@
+use mnem_core::Repository;
+use mnem_core::models::SnapshotEvent;
+use mnem_core::storage::bundle::BundleFilter;
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn latest_paths(repo: &Repository) -> Vec<String> {
+    let mut paths: Vec<String> = repo
+        .db
+        .get_latest_state()
+        .unwrap()
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn event(repo: &Repository, path: &Path) -> SnapshotEvent {
+    repo.get_file_history(&path.to_string_lossy()).unwrap()[0]
+        .event
+        .clone()
+}
+
+#[test]
+fn test_deleted_files_leave_a_tombstone() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let a = write(&repo, "a.txt", "alpha");
+    let b = write(&repo, "b.txt", "beta");
+
+    fs::remove_file(&b).unwrap();
+    assert_eq!(repo.record_deletion(&a).unwrap(), 0);
+    assert_eq!(repo.record_deletion(&b).unwrap(), 1);
+    assert_eq!(repo.record_deletion(&b).unwrap(), 0);
+
+    let history = repo.get_file_history(&b.to_string_lossy()).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].event, SnapshotEvent::Deleted);
+    assert!(history[0].content_hash.is_empty());
+    assert_eq!(latest_paths(&repo), vec![a.to_string_lossy().to_string()]);
+    assert!(repo.fsck(false).unwrap().is_clean());
+
+    // Bringing the same content back is a new snapshot, not a no-op
+    let b = write(&repo, "b.txt", "beta");
+    assert_eq!(event(&repo, &b), SnapshotEvent::Modified);
+    assert_eq!(latest_paths(&repo).len(), 2);
+
+    // Removing a directory tombstones everything tracked under it
+    write(&repo, "src/c.rs", "fn c() {}");
+    write(&repo, "src/d.rs", "fn d() {}");
+    fs::remove_dir_all(format!("{}/src", repo.project.path)).unwrap();
+    let src = PathBuf::from(format!("{}/src", repo.project.path));
+    assert_eq!(repo.record_deletion(&src).unwrap(), 2);
+    assert_eq!(latest_paths(&repo).len(), 2);
+}
+
+#[test]
+fn test_renames_link_both_paths() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let old = write(&repo, "old.rs", "fn moved() {}");
+    let new = PathBuf::from(format!("{}/new.rs", repo.project.path));
+
+    fs::rename(&old, &new).unwrap();
+    assert_eq!(repo.record_rename(&old, &new).unwrap(), 1);
+    assert_eq!(
+        event(&repo, &new),
+        SnapshotEvent::RenamedFrom {
+            from: old.to_string_lossy().to_string()
+        }
+    );
+    assert_eq!(
+        event(&repo, &old),
+        SnapshotEvent::RenamedTo {
+            to: new.to_string_lossy().to_string()
+        }
+    );
+    assert_eq!(latest_paths(&repo), vec![new.to_string_lossy().to_string()]);
+
+    // A move reported as a delete plus a create is matched by content
+    let third = PathBuf::from(format!("{}/third.rs", repo.project.path));
+    let unrelated = PathBuf::from(format!("{}/unrelated.rs", repo.project.path));
+    fs::rename(&new, &third).unwrap();
+    fs::write(&unrelated, "fn other() {}").unwrap();
+    let pairs = repo
+        .match_renames(&[new.clone()], &[unrelated, third.clone()])
+        .unwrap();
+    assert_eq!(pairs, vec![(new.clone(), third.clone())]);
+
+    // Moving a directory moves every tracked file in it
+    let lib_file = write(&repo, "lib/x.rs", "fn x() {}");
+    let lib = lib_file.parent().unwrap().to_path_buf();
+    let pkg = PathBuf::from(format!("{}/pkg", repo.project.path));
+    fs::rename(&lib, &pkg).unwrap();
+    assert_eq!(repo.record_rename(&lib, &pkg).unwrap(), 1);
+    assert_eq!(
+        event(&repo, &pkg.join("x.rs")),
+        SnapshotEvent::RenamedFrom {
+            from: lib_file.to_string_lossy().to_string()
+        }
+    );
+    assert!(repo.fsck(false).unwrap().is_clean());
+}
+
+#[test]
+fn test_revert_reproduces_the_exact_file_set() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let keep = write(&repo, "src/keep.rs", "v1");
+    let gone = write(&repo, "old/gone.rs", "removed later");
+    std::thread::sleep(Duration::from_millis(5));
+    let at = chrono::Utc::now().to_rfc3339();
+    std::thread::sleep(Duration::from_millis(5));
+
+    write(&repo, "src/keep.rs", "v2");
+    let later = write(&repo, "later.rs", "created after");
+    fs::remove_dir_all(gone.parent().unwrap()).unwrap();
+    repo.record_deletion(&gone).unwrap();
+
+    assert_eq!(repo.revert_to_timestamp(&at).unwrap(), 2);
+    assert_eq!(fs::read_to_string(&keep).unwrap(), "v1");
+    assert_eq!(fs::read_to_string(&gone).unwrap(), "removed later");
+    assert!(!later.exists());
+    assert_eq!(event(&repo, &later), SnapshotEvent::Deleted);
+
+    // Checkpoints restore the same way
+    let checkpoint = repo.create_checkpoint(Some("two files")).unwrap();
+    let extra = write(&repo, "extra.rs", "scratch");
+    repo.revert_to_checkpoint(&checkpoint).unwrap();
+    assert!(!extra.exists());
+    assert!(keep.exists());
+}
+
+#[test]
+fn test_bundles_keep_deletions_and_renames() {
+    let dir = TempDir::new().unwrap();
+    let source = open_project(dir.path(), "laptop");
+    let old = write(&source, "old.rs", "fn moved() {}");
+    let new = PathBuf::from(format!("{}/new.rs", source.project.path));
+    fs::rename(&old, &new).unwrap();
+    source.record_rename(&old, &new).unwrap();
+
+    let bundle_path = dir.path().join("history.mnembundle");
+    source
+        .export_bundle(&bundle_path, &BundleFilter::default())
+        .unwrap();
+    let target = open_project(dir.path(), "desktop");
+    target.import_bundle(&bundle_path).unwrap();
+
+    let old = PathBuf::from(format!("{}/old.rs", target.project.path));
+    let new = PathBuf::from(format!("{}/new.rs", target.project.path));
+    assert_eq!(
+        event(&target, &new),
+        SnapshotEvent::RenamedFrom {
+            from: old.to_string_lossy().to_string()
+        }
+    );
+    assert_eq!(
+        latest_paths(&target),
+        vec![new.to_string_lossy().to_string()]
+    );
+}
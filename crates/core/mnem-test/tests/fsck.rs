@@ -0,0 +1,60 @@
+use mnem_core::Repository;
+use mnem_core::storage::fsck::FsckIssueKind;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_fsck_reports_and_repairs_damaged_history() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let a = repo.project.path.clone() + "/a.txt";
+    let b = repo.project.path.clone() + "/b.txt";
+
+    fs::write(&a, "first version of a").unwrap();
+    let lost = repo.save_snapshot_from_file(Path::new(&a)).unwrap();
+    fs::write(&a, "second version of a").unwrap();
+    let kept = repo.save_snapshot_from_file(Path::new(&a)).unwrap();
+    fs::write(&b, "contents of b").unwrap();
+    repo.save_snapshot_from_file(Path::new(&b)).unwrap();
+
+    let report = repo.fsck(false).unwrap();
+    assert!(report.is_clean(), "unexpected issues: {:?}", report.issues);
+    assert_eq!(report.snapshots_checked, 3);
+
+    // Lose the only chunk of the first snapshot and leave a stray object behind
+    repo.fs.delete(&lost).unwrap();
+    let stray = repo.fs.write(b"nobody refers to this", false).unwrap();
+
+    let report = repo.fsck(false).unwrap();
+    assert_eq!(report.count(FsckIssueKind::MissingObject), 1);
+    assert_eq!(report.count(FsckIssueKind::OrphanObject), 1);
+    assert!(repo.get_content(&lost).is_err());
+
+    // Checking alone changes nothing
+    assert_eq!(repo.get_file_history(&a).unwrap().len(), 2);
+    assert!(repo.fs.exists(&stray));
+
+    let report = repo.fsck(true).unwrap();
+    let repairs = report.repairs.unwrap();
+    assert_eq!(repairs.snapshots_quarantined, 1);
+    assert_eq!(repairs.objects_deleted, 1);
+    assert!(!repo.fs.exists(&stray));
+
+    // The damaged snapshot is out of history; the rest is untouched
+    let history = repo.get_file_history(&a).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].content_hash, kept);
+    assert_eq!(repo.get_content(&kept).unwrap(), b"second version of a");
+    assert_eq!(repo.grep_contents("contents", None, false).unwrap().len(), 1);
+
+    let report = repo.fsck(false).unwrap();
+    assert!(report.is_clean(), "unexpected issues: {:?}", report.issues);
+}
@@ -0,0 +1,62 @@
+use mnem_core::Repository;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_gc_reclaims_only_unreferenced_chunks() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let a = repo.project.path.clone() + "/a.txt";
+    let b = repo.project.path.clone() + "/b.txt";
+    let c = repo.project.path.clone() + "/c.txt";
+
+    fs::write(&a, "only ever in a").unwrap();
+    let hash_a = repo.save_snapshot_from_file(Path::new(&a)).unwrap();
+    fs::write(&b, "shared between b and c").unwrap();
+    let hash_shared = repo.save_snapshot_from_file(Path::new(&b)).unwrap();
+
+    let ids: HashSet<i64> = repo
+        .db
+        .get_history_by_prefix(&repo.project.path)
+        .unwrap()
+        .iter()
+        .map(|s| s.id)
+        .collect();
+    let pruned = repo.db.delete_snapshots(&ids).unwrap();
+    assert_eq!(pruned.snapshots_pruned, 2);
+    assert_eq!(repo.db.get_snapshot_count().unwrap(), 0);
+
+    // c links the shared chunk again before GC runs
+    fs::write(&c, "shared between b and c").unwrap();
+    repo.save_snapshot_from_file(Path::new(&c)).unwrap();
+
+    let report = repo.run_gc().unwrap();
+    assert_eq!(report.chunks_deleted, 1);
+    assert!(report.bytes_reclaimed > 0);
+    assert!(!repo.fs.exists(&hash_a));
+    assert!(repo.fs.exists(&hash_shared));
+    assert_eq!(
+        repo.get_content(&hash_shared).unwrap(),
+        b"shared between b and c"
+    );
+
+    // The trigram index no longer points at the reclaimed chunk
+    assert!(
+        repo.grep_contents("only ever", None, false)
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(repo.grep_contents("shared", None, false).unwrap().len(), 1);
+
+    // Nothing left to reclaim
+    assert_eq!(repo.run_gc().unwrap().chunks_deleted, 0);
+}
@@ -0,0 +1,289 @@
+use chrono::DateTime;
+use mnem_core::config::RetentionPolicy;
+use mnem_core::storage::git_import::GitImportOptions;
+use mnem_core::storage::retention;
+use mnem_test::open_project;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    git_at(dir, args, "2024-01-01T00:00:00+00:00")
+}
+
+fn git_at(dir: &Path, args: &[&str], date: &str) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+        .args(["-c", "init.defaultBranch=main", "-c", "gc.auto=0"])
+        .args(args)
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .current_dir(dir)
+        .output()
+        .expect("git is installed");
+    assert!(
+        output.status.success(),
+        "git {:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+fn init(dir: &Path) -> PathBuf {
+    let project = dir.join("project");
+    fs::create_dir_all(&project).unwrap();
+    git(&project, &["init", "-q"]);
+    fs::write(project.join(".gitignore"), ".mnemosyne/\n").unwrap();
+    project
+}
+
+/// Writes into the checkout without saving a snapshot.
+fn write_file(project: &Path, rel: &str, content: &str) {
+    let path = project.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn commit(project: &Path, message: &str, date: &str) -> String {
+    git_at(project, &["add", "-A"], date);
+    git_at(project, &["commit", "-q", "-m", message], date);
+    git(project, &["rev-parse", "HEAD"])
+}
+
+fn seconds(timestamp: &str) -> i64 {
+    DateTime::parse_from_rfc3339(timestamp).unwrap().timestamp()
+}
+
+fn path_of(project: &Path, rel: &str) -> String {
+    project.join(rel).to_string_lossy().to_string()
+}
+
+#[test]
+fn test_import_backfills_history_before_tracking() {
+    let dir = TempDir::new().unwrap();
+    let project = init(dir.path());
+    write_file(&project, "src/lib.rs", "v1");
+    write_file(&project, "old.txt", "old");
+    let first = commit(&project, "Start", "2024-01-01T10:00:00+00:00");
+    write_file(&project, "src/lib.rs", "v2");
+    fs::remove_file(project.join("old.txt")).unwrap();
+    let second = commit(&project, "Drop old.txt", "2024-02-01T10:00:00+00:00");
+    write_file(&project, "README.md", "readme");
+    let third = commit(&project, "Readme", "2024-03-01T10:00:00+00:00");
+
+    // Tracking starts with an uncommitted edit
+    let repo = open_project(dir.path(), "project");
+    write_file(&project, "src/lib.rs", "v3");
+    let lib = path_of(&project, "src/lib.rs");
+    let live = repo.save_snapshot_from_file(Path::new(&lib)).unwrap();
+    let readme = path_of(&project, "README.md");
+    repo.save_snapshot_from_file(Path::new(&readme)).unwrap();
+
+    let report = repo.import_git(&GitImportOptions::default()).unwrap();
+    assert_eq!(report.branch, "main");
+    // .gitignore, lib.rs and old.txt, then lib.rs and old.txt's tombstone, then the readme
+    assert_eq!(
+        (report.commits, report.snapshots, report.skipped),
+        (3, 6, 0)
+    );
+
+    let history = repo.get_history(&lib).unwrap();
+    assert_eq!(history.len(), 3);
+    // The live version stays the latest, without being copied on top
+    assert_eq!(repo.db.get_last_hash(&lib).unwrap(), Some(live.clone()));
+    assert_eq!(history[0].content_hash, live);
+    let imported: Vec<_> = history[1..]
+        .iter()
+        .map(|s| {
+            (
+                seconds(&s.timestamp),
+                s.git_branch.as_deref(),
+                s.commit_hash.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        imported,
+        vec![
+            (1706781600, Some("main"), Some(second.clone())),
+            (1704103200, Some("main"), Some(first.clone())),
+        ]
+    );
+    assert_eq!(repo.get_content(&history[2].content_hash).unwrap(), b"v1");
+    assert_ne!(history[1].changeset_id, history[2].changeset_id);
+
+    let old = repo.get_history(&path_of(&project, "old.txt")).unwrap();
+    assert_eq!(old.len(), 2);
+    assert!(old[0].event.is_tombstone());
+    assert_eq!(old[0].commit_hash, Some(second.clone()));
+    assert_eq!(old[0].changeset_id, history[1].changeset_id);
+    assert_eq!(
+        repo.db
+            .get_last_hash(&path_of(&project, "old.txt"))
+            .unwrap(),
+        None
+    );
+
+    // The readme was committed as it is now, so its live snapshot is still the latest
+    let readme_history = repo.get_history(&readme).unwrap();
+    assert_eq!(readme_history.len(), 2);
+    assert_eq!(readme_history[1].commit_hash, Some(third.clone()));
+
+    let commits = repo.list_commits().unwrap();
+    assert_eq!(commits.len(), 3);
+    let (_, message, author, timestamp) = repo.get_commit_details(&second).unwrap().unwrap();
+    assert_eq!((message.as_str(), author.as_str()), ("Drop old.txt", "Ada"));
+    assert_eq!(seconds(&timestamp), 1706781600);
+
+    let again = repo.import_git(&GitImportOptions::default()).unwrap();
+    assert_eq!((again.commits, again.snapshots, again.skipped), (0, 0, 3));
+}
+
+#[test]
+fn test_import_since_a_date_from_another_branch() {
+    let dir = TempDir::new().unwrap();
+    let project = init(dir.path());
+    write_file(&project, "a.txt", "a1");
+    commit(&project, "One", "2024-01-01T10:00:00+00:00");
+    write_file(&project, "b.txt", "b1");
+    let second = commit(&project, "Two", "2024-02-01T10:00:00+00:00");
+    git(&project, &["checkout", "-q", "-b", "feature"]);
+    write_file(&project, "a.txt", "a2");
+    let third = commit(&project, "Three", "2024-03-01T10:00:00+00:00");
+    git(&project, &["checkout", "-q", "main"]);
+    let repo = open_project(dir.path(), "project");
+
+    let options = GitImportOptions {
+        since: Some("2024-01-15T00:00:00Z".parse().unwrap()),
+        branch: Some("feature".into()),
+    };
+    let report = repo.import_git(&options).unwrap();
+    assert_eq!(report.branch, "feature");
+    // The oldest commit imported is taken whole
+    assert_eq!((report.commits, report.snapshots), (2, 4));
+    let a = repo.get_history(&path_of(&project, "a.txt")).unwrap();
+    let hashes: Vec<_> = a.iter().map(|s| s.commit_hash.clone()).collect();
+    assert_eq!(hashes, vec![Some(third), Some(second)]);
+    assert!(a.iter().all(|s| s.git_branch.as_deref() == Some("feature")));
+
+    // Importing again only goes further back
+    let report = repo.import_git(&GitImportOptions::default()).unwrap();
+    assert_eq!(report.branch, "main");
+    assert_eq!((report.commits, report.skipped), (1, 1));
+    let a = repo.get_history(&path_of(&project, "a.txt")).unwrap();
+    assert_eq!(a.len(), 3);
+    assert_eq!(repo.get_content(&a[2].content_hash).unwrap(), b"a1");
+    // The feature branch's version is still the latest
+    assert_eq!(repo.get_content(&a[0].content_hash).unwrap(), b"a2");
+    assert_eq!(
+        repo.db.get_last_hash(&a[0].file_path).unwrap(),
+        Some(a[0].content_hash.clone())
+    );
+
+    let missing = GitImportOptions {
+        branch: Some("nope".into()),
+        ..Default::default()
+    };
+    assert!(repo.import_git(&missing).is_err());
+}
+
+#[test]
+fn test_import_leaves_out_what_the_watcher_ignores() {
+    let dir = TempDir::new().unwrap();
+    let project = init(dir.path());
+    write_file(&project, ".mnemosyneignore", "*.log\n");
+    write_file(&project, "src/main.rs", "fn main() {}");
+    write_file(&project, "debug.log", "noise");
+    write_file(&project, "node_modules/dep/index.js", "module.exports = 1");
+    fs::write(project.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    commit(&project, "Start", "2024-01-01T10:00:00+00:00");
+    fs::remove_file(project.join("logo.png")).unwrap();
+    commit(&project, "Drop the logo", "2024-02-01T10:00:00+00:00");
+
+    let repo = open_project(dir.path(), "project");
+    repo.config.lock().unwrap().config.binary.enabled = false;
+    let report = repo.import_git(&GitImportOptions::default()).unwrap();
+    // .gitignore, .mnemosyneignore and main.rs; the logo never got a version to remove
+    assert_eq!((report.commits, report.snapshots), (2, 3));
+    assert_eq!(
+        repo.get_history(&path_of(&project, "src/main.rs"))
+            .unwrap()
+            .len(),
+        1
+    );
+    for left_out in ["debug.log", "node_modules/dep/index.js", "logo.png"] {
+        assert!(
+            repo.get_history(&path_of(&project, left_out))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
+
+#[test]
+fn test_gc_after_import_keeps_the_live_version() {
+    let dir = TempDir::new().unwrap();
+    let project = init(dir.path());
+    write_file(&project, "notes.md", "v1");
+    let first = commit(&project, "Start", "2024-01-01T10:00:00+00:00");
+
+    let repo = open_project(dir.path(), "project");
+    let notes = path_of(&project, "notes.md");
+    let mut live = Vec::new();
+    for version in ["v2", "v3", "v4"] {
+        write_file(&project, "notes.md", version);
+        live.push(repo.save_snapshot_from_file(Path::new(&notes)).unwrap());
+    }
+    repo.import_git(&GitImportOptions::default()).unwrap();
+    assert_eq!(repo.get_history(&notes).unwrap().len(), 4);
+
+    // Long after every save has aged out of the policy
+    let policy = RetentionPolicy {
+        weekly_weeks: 1,
+        ..RetentionPolicy::default()
+    };
+    let prunable = retention::select_prunable(
+        &repo.db.retention_candidates().unwrap(),
+        &policy,
+        &(chrono::Local::now() + chrono::Duration::weeks(100)),
+    );
+    repo.db.delete_snapshots(&prunable).unwrap();
+    repo.run_gc().unwrap();
+
+    let history = repo.get_history(&notes).unwrap();
+    let hashes: Vec<_> = history.iter().map(|s| s.content_hash.clone()).collect();
+    // The session's first and last save, and the commit-linked import
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[..2], [live[2].clone(), live[0].clone()]);
+    assert_eq!(history[2].commit_hash, Some(first));
+    assert_eq!(
+        repo.db.get_last_hash(&notes).unwrap(),
+        Some(live[2].clone())
+    );
+    assert_eq!(repo.get_content(&live[2]).unwrap(), b"v4");
+}
+
+#[test]
+fn test_imported_versions_come_before_the_live_ones() {
+    let dir = TempDir::new().unwrap();
+    let project = init(dir.path());
+    write_file(&project, "notes.md", "v1");
+    commit(&project, "Start", "2024-01-01T10:00:00+00:00");
+
+    let repo = open_project(dir.path(), "project");
+    let notes = path_of(&project, "notes.md");
+    write_file(&project, "notes.md", "v2");
+    let live = repo.save_snapshot_from_file(Path::new(&notes)).unwrap();
+    repo.import_git(&GitImportOptions::default()).unwrap();
+
+    // The import has the newest ids, but the live save is still the latest activity
+    let recent = repo.db.get_global_history(1).unwrap();
+    assert_eq!(recent[0].content_hash, live);
+
+    // Reverting the live save goes back to the imported version
+    let changeset = repo.get_history(&notes).unwrap()[0].changeset_id.unwrap();
+    assert_eq!(repo.revert_changeset(changeset).unwrap(), 1);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "v1");
+}
@@ -0,0 +1,150 @@
+use mnem_core::Repository;
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+        .args(["-c", "init.defaultBranch=main", "-c", "gc.auto=0"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("git is installed");
+    assert!(
+        output.status.success(),
+        "git {:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+fn init(dir: &Path) -> (PathBuf, Repository) {
+    let project = dir.join("project");
+    fs::create_dir_all(&project).unwrap();
+    git(&project, &["init", "-q"]);
+    fs::write(project.join(".gitignore"), ".mnemosyne/\n").unwrap();
+    let repo = open_project(dir, "project");
+    (project, repo)
+}
+
+fn commit(dir: &Path, message: &str) -> String {
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", message]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+fn linked(repo: &Repository, path: &Path) -> Vec<Option<String>> {
+    repo.get_history(&path.to_string_lossy())
+        .unwrap()
+        .into_iter()
+        .map(|s| s.commit_hash)
+        .collect()
+}
+
+#[test]
+fn test_commit_links_matching_snapshots_without_a_hook() {
+    let dir = TempDir::new().unwrap();
+    let (project, repo) = init(dir.path());
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let main = write(&repo, "src/main.rs", "fn main() {}");
+    let first = commit(&project, "Add the crate");
+
+    let sync = repo.sync_git_head().unwrap().unwrap();
+    assert_eq!(sync.head, first);
+    assert_eq!((sync.commits, sync.linked), (1, 2));
+    assert_eq!(linked(&repo, &lib), vec![Some(first.clone())]);
+    assert_eq!(linked(&repo, &main), vec![Some(first.clone())]);
+    assert_eq!(repo.sync_git_head().unwrap(), None);
+
+    let (hash, message, author, timestamp) = repo.get_commit_details(&first).unwrap().unwrap();
+    assert_eq!(hash, first);
+    assert_eq!(message, "Add the crate");
+    assert_eq!(author, "Ada");
+    assert_eq!(timestamp, git(&project, &["log", "-1", "--format=%cI"]));
+
+    // A save that was edited again before committing stays unlinked
+    write(&repo, "src/lib.rs", "v2");
+    write(&repo, "src/lib.rs", "v3");
+    // Only the paths a commit changed are looked at, so going back to the
+    // committed main.rs is not linked to a commit that leaves it alone
+    write(&repo, "src/main.rs", "fn main() { todo!() }");
+    write(&repo, "src/main.rs", "fn main() {}");
+    let second = commit(&project, "Bump");
+    let commits = repo.list_commits().unwrap();
+    assert_eq!(commits.len(), 2);
+    assert_eq!(linked(&repo, &main), vec![None, None, Some(first.clone())]);
+    assert_eq!(
+        linked(&repo, &lib),
+        vec![Some(second.clone()), None, Some(first)]
+    );
+    assert_eq!(repo.get_commit_files(&second).unwrap().len(), 1);
+}
+
+#[test]
+fn test_commits_are_read_from_packs_and_packed_refs() {
+    let dir = TempDir::new().unwrap();
+    let (project, repo) = init(dir.path());
+    let body: String = (0..400).map(|i| format!("line {}\n", i)).collect();
+    let mut hashes = Vec::new();
+    let mut path = PathBuf::new();
+    for i in 0..3 {
+        // Near-identical versions, so the pack stores them as deltas
+        path = write(&repo, "data.txt", &format!("{}edit {}\n", body, i));
+        hashes.push(commit(&project, &format!("Edit {}", i)));
+    }
+    git(&project, &["gc", "-q", "--aggressive", "--prune=now"]);
+    assert!(!project.join(".git/refs/heads/main").exists());
+    assert!(
+        fs::read_dir(project.join(".git/objects/pack"))
+            .unwrap()
+            .any(|e| e.unwrap().path().extension().is_some_and(|x| x == "pack"))
+    );
+
+    let sync = repo.sync_git_head().unwrap().unwrap();
+    assert_eq!((sync.commits, sync.linked), (3, 3));
+    let expected: Vec<Option<String>> = hashes.into_iter().rev().map(Some).collect();
+    assert_eq!(linked(&repo, &path), expected);
+}
+
+#[test]
+fn test_linked_worktree_and_detached_head() {
+    let dir = TempDir::new().unwrap();
+    let (project, main_repo) = init(dir.path());
+    write(&main_repo, "README.md", "readme");
+    commit(&project, "Initial");
+    assert_eq!(main_repo.get_current_branch().as_deref(), Some("main"));
+
+    let worktree = dir.path().join("feature");
+    git(
+        &project,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            worktree.to_str().unwrap(),
+        ],
+    );
+    assert!(worktree.join(".git").is_file());
+    let repo = open_project(dir.path(), "feature");
+    assert_eq!(repo.get_current_branch().as_deref(), Some("feature"));
+
+    let lib = write(&repo, "lib.rs", "feature work");
+    let on_feature = commit(&worktree, "Feature");
+    let sync = repo.sync_git_head().unwrap().unwrap();
+    // The initial commit is new to this project's history too
+    assert_eq!((sync.commits, sync.linked), (2, 1));
+    assert_eq!(linked(&repo, &lib), vec![Some(on_feature.clone())]);
+
+    git(&worktree, &["checkout", "-q", "--detach"]);
+    assert_eq!(repo.get_current_branch().as_deref(), Some(&on_feature[..7]));
+    write(&repo, "lib.rs", "detached work");
+    let detached = commit(&worktree, "Detached");
+    assert_eq!(repo.sync_git_head().unwrap().unwrap().linked, 1);
+    assert_eq!(linked(&repo, &lib), vec![Some(detached), Some(on_feature)]);
+}
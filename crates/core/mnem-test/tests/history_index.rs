@@ -0,0 +1,96 @@
+use mnem_core::Repository;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_exact_and_prefix_history_lookup() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::create_dir_all(project_dir.join("src")).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let main_rs = repo.project.path.clone() + "/src/main.rs";
+    let main_rs_bak = repo.project.path.clone() + "/src/main.rs.bak";
+
+    fs::write(&main_rs, "fn main() {}").unwrap();
+    repo.save_snapshot_from_file(Path::new(&main_rs)).unwrap();
+    fs::write(&main_rs, "fn main() { println!(\"hi\"); }").unwrap();
+    let latest = repo.save_snapshot_from_file(Path::new(&main_rs)).unwrap();
+    fs::write(&main_rs_bak, "fn main() {}").unwrap();
+    repo.save_snapshot_from_file(Path::new(&main_rs_bak))
+        .unwrap();
+
+    // Exact lookup must not pick up paths that merely contain the query
+    let exact = repo.get_file_history(&main_rs).unwrap();
+    assert_eq!(exact.len(), 2);
+    assert!(exact.iter().all(|s| s.file_path == main_rs));
+    assert_eq!(exact[0].content_hash, latest);
+    assert_eq!(repo.db.get_last_hash(&main_rs).unwrap(), Some(latest));
+
+    let prefix = repo
+        .db
+        .get_history_by_prefix(&(repo.project.path.clone() + "/src/"))
+        .unwrap();
+    assert_eq!(prefix.len(), 3);
+    assert!(prefix.windows(2).all(|w| w[0].id > w[1].id));
+}
+
+#[test]
+fn test_short_hash_lookup() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let a = repo.project.path.clone() + "/a.txt";
+    let b = repo.project.path.clone() + "/b.txt";
+    fs::write(&a, "same").unwrap();
+    let same = repo.save_snapshot_from_file(Path::new(&a)).unwrap();
+    fs::write(&b, "same").unwrap();
+    repo.save_snapshot_from_file(Path::new(&b)).unwrap();
+    fs::write(&b, "other").unwrap();
+    let other = repo.save_snapshot_from_file(Path::new(&b)).unwrap();
+
+    let copies = repo.db.get_history_by_hash(&same).unwrap();
+    assert_eq!(copies.len(), 2);
+    assert!(copies.iter().all(|s| s.content_hash == same));
+
+    assert_eq!(
+        repo.db.resolve_hash(&same[..8]).unwrap(),
+        Some(same.clone())
+    );
+    assert_eq!(
+        repo.db.resolve_hash(&same[..8].to_uppercase()).unwrap(),
+        Some(same.clone())
+    );
+    assert_eq!(repo.db.resolve_hash(&other).unwrap(), Some(other.clone()));
+    // Too short for the index, and still found by a scan
+    assert_eq!(
+        repo.db.resolve_hash(&other[..2]).unwrap(),
+        (same[..2] != other[..2]).then(|| other.clone())
+    );
+    assert_eq!(repo.db.resolve_hash("").unwrap(), None);
+    assert_eq!(repo.db.resolve_hash("zzzzzzzz").unwrap(), None);
+
+    // Pruned snapshots drop out of the index
+    let ids = repo
+        .db
+        .get_history_by_hash(&other)
+        .unwrap()
+        .iter()
+        .map(|s| s.id)
+        .collect();
+    repo.db.delete_snapshots(&ids).unwrap();
+    assert_eq!(repo.db.resolve_hash(&other[..8]).unwrap(), None);
+    assert!(repo.fsck(false).unwrap().is_clean());
+}
@@ -0,0 +1,106 @@
+use mnem_core::Repository;
+use mnem_core::models::PinKind;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_gc_keeps_recent_and_checkpointed_history() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let file = repo.project.path.clone() + "/main.rs";
+    for i in 0..5 {
+        fs::write(&file, format!("fn main() {{ {} }}", i)).unwrap();
+        repo.save_snapshot_from_file(Path::new(&file)).unwrap();
+    }
+    repo.create_checkpoint(Some("release")).unwrap();
+
+    // Everything is inside the keep-all window
+    let report = repo.run_gc().unwrap();
+    assert_eq!(report.snapshots_pruned, 0);
+    assert_eq!(repo.get_file_history(&file).unwrap().len(), 5);
+
+    // Two days on, the saves share an hourly bucket and only the newest,
+    // which is also the checkpointed one, survives
+    let candidates = repo.db.retention_candidates().unwrap();
+    assert_eq!(candidates.iter().filter(|c| c.exempt).count(), 1);
+    let prunable = mnem_core::storage::retention::select_prunable(
+        &candidates,
+        &repo.config.lock().unwrap().config.retention,
+        &(chrono::Local::now() + chrono::Duration::days(2)),
+    );
+    let latest = repo.get_file_history(&file).unwrap()[0].id;
+    assert!(!prunable.contains(&latest));
+    // Saves may straddle an hour boundary, leaving one extra survivor
+    assert!(prunable.len() >= 3);
+}
+
+#[test]
+fn test_pinned_snapshots_are_never_thinned() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let file = repo.project.path.clone() + "/parser.rs";
+    let mut hashes = Vec::new();
+    for i in 0..4 {
+        fs::write(&file, format!("fn parse() {{ {} }}", i)).unwrap();
+        hashes.push(repo.save_snapshot_from_file(Path::new(&file)).unwrap());
+    }
+
+    let pin = repo
+        .pin(&hashes[1][..10], "working parser before refactor")
+        .unwrap();
+    assert_eq!(pin.hash, hashes[1]);
+    assert_eq!(pin.kind, PinKind::Snapshot);
+    assert!(repo.pin("ffffffffffff", "nothing").is_err());
+    assert!(repo.pin(&hashes[1], "  ").is_err());
+
+    // The pinned save is exempt, so thinning spares it
+    let candidates = repo.db.retention_candidates().unwrap();
+    let pinned_id = repo.get_file_history(&file).unwrap()[2].id;
+    assert!(candidates.iter().any(|c| c.id == pinned_id && c.exempt));
+    let prunable = mnem_core::storage::retention::select_prunable(
+        &candidates,
+        &repo.config.lock().unwrap().config.retention,
+        &(chrono::Local::now() + chrono::Duration::days(2)),
+    );
+    assert!(!prunable.is_empty());
+    assert!(!prunable.contains(&pinned_id));
+    repo.db.delete_snapshots(&prunable).unwrap();
+    assert_eq!(repo.get_content(&hashes[1]).unwrap(), b"fn parse() { 1 }");
+
+    // Labels are searchable, and pinning again relabels
+    let checkpoint = repo.create_checkpoint(Some("before refactor")).unwrap();
+    repo.pin(&checkpoint[..8], "release candidate").unwrap();
+    assert_eq!(repo.list_pins(None).unwrap().len(), 2);
+    let found = repo.list_pins(Some("PARSER")).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].hash, hashes[1]);
+    repo.pin(&hashes[1], "known good parser").unwrap();
+    assert!(repo.list_pins(Some("before refactor")).unwrap().is_empty());
+
+    // Checkpoint pins go with their checkpoint; snapshot pins are removed by hash
+    repo.delete_checkpoint(&checkpoint).unwrap();
+    let pins = repo.list_pins(None).unwrap();
+    assert_eq!(pins.len(), 1);
+    assert_eq!(pins[0].kind, PinKind::Snapshot);
+    assert!(repo.unpin("0000000000").unwrap().is_none());
+    assert_eq!(
+        repo.unpin(&hashes[1][..8]).unwrap().unwrap().hash,
+        hashes[1]
+    );
+    assert!(repo.list_pins(None).unwrap().is_empty());
+}
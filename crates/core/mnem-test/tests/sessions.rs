@@ -0,0 +1,103 @@
+use mnem_core::Repository;
+use mnem_core::storage::timesheet::{self, GroupBy, TimesheetOptions};
+use mnem_test::{open_project, write};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn checkout(repo: &Repository, branch: &str) {
+    let git = Path::new(&repo.project.path).join(".git");
+    fs::create_dir_all(&git).unwrap();
+    fs::write(git.join("HEAD"), format!("ref: refs/heads/{}\n", branch)).unwrap();
+}
+
+fn session_of(repo: &Repository, path: &Path) -> Option<i64> {
+    repo.get_history(&path.to_string_lossy()).unwrap()[0].session_id
+}
+
+#[test]
+fn test_saves_open_a_session_and_count_files() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    assert!(repo.get_active_session().unwrap().is_none());
+
+    let lib = write(&repo, "src/lib.rs", "v1");
+    write(&repo, "src/lib.rs", "v2");
+    let main = write(&repo, "src/main.rs", "v1");
+
+    let active = repo.get_active_session().unwrap().unwrap();
+    assert_eq!(session_of(&repo, &lib), Some(active.id));
+    assert_eq!(session_of(&repo, &main), Some(active.id));
+    assert_eq!(active.file_count, 2);
+    assert_eq!(active.snapshot_count, 3);
+    assert!(active.end_time.is_none());
+    assert_eq!(repo.list_sessions(10).unwrap().len(), 1);
+}
+
+#[test]
+fn test_idle_session_is_closed_and_the_next_save_opens_another() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let first = session_of(&repo, &lib).unwrap();
+    assert_eq!(repo.close_idle_session().unwrap(), None);
+
+    repo.config.lock().unwrap().config.session_idle_minutes = 0;
+    assert_eq!(repo.close_idle_session().unwrap(), Some(first));
+    assert!(repo.get_active_session().unwrap().is_none());
+
+    repo.config.lock().unwrap().config.session_idle_minutes = 30;
+    write(&repo, "src/lib.rs", "v2");
+    let second = repo.get_active_session().unwrap().unwrap();
+    assert_ne!(second.id, first);
+    assert_eq!(second.file_count, 1);
+
+    let sessions = repo.list_sessions(10).unwrap();
+    assert_eq!(sessions.len(), 2);
+    let closed = sessions.iter().find(|s| s.id == first).unwrap();
+    assert!(closed.end_time.is_some());
+    assert_eq!(closed.snapshot_count, 1);
+}
+
+#[test]
+fn test_branch_switch_starts_a_new_session() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    checkout(&repo, "main");
+    let lib = write(&repo, "src/lib.rs", "v1");
+    let on_main = session_of(&repo, &lib).unwrap();
+
+    checkout(&repo, "feature");
+    write(&repo, "src/lib.rs", "v2");
+    let active = repo.get_active_session().unwrap().unwrap();
+    assert_ne!(active.id, on_main);
+    assert_eq!(active.git_branch.as_deref(), Some("feature"));
+
+    let sessions = repo.list_sessions(10).unwrap();
+    let main = sessions.iter().find(|s| s.id == on_main).unwrap();
+    assert_eq!(main.git_branch.as_deref(), Some("main"));
+    assert!(main.end_time.is_some());
+}
+
+#[test]
+fn test_open_session_spans_until_its_last_save() {
+    let dir = TempDir::new().unwrap();
+    let repo = open_project(dir.path(), "project");
+    write(&repo, "src/lib.rs", "v1");
+    write(&repo, "src/lib.rs", "v2");
+
+    let spans = repo.session_spans().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].project, "project");
+    assert!(spans[0].end >= spans[0].start);
+
+    let options = TimesheetOptions {
+        group_by: Some(GroupBy::Project),
+        ..Default::default()
+    };
+    let entries = timesheet::build(&spans, &options, &chrono::Local);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].project.as_deref(), Some("project"));
+    assert_eq!(entries[0].file_count, 1);
+    assert_eq!(entries[0].snapshot_count, 2);
+}
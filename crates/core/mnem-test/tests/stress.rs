@@ -37,7 +37,7 @@ fn stress_test_large_codebase() {
 
     // Stress test the grep (search) across all 10,000 unique content blobs
     let grep_start = std::time::Instant::now();
-    let search_results = repo.grep_contents("hello from 9999", None).unwrap();
+    let search_results = repo.grep_contents("hello from 9999", None, false).unwrap();
     let grep_duration = grep_start.elapsed();
     println!("Grep search took: {:?}", grep_duration);
 
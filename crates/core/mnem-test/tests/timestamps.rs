@@ -0,0 +1,69 @@
+use chrono::{DateTime, FixedOffset};
+use mnem_core::Repository;
+use mnem_core::utils::time::parse_ms;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn at_offset(timestamp: &str, hours: i32) -> String {
+    let offset = FixedOffset::east_opt(hours * 3600).unwrap();
+    DateTime::parse_from_rfc3339(timestamp)
+        .unwrap()
+        .with_timezone(&offset)
+        .to_rfc3339()
+}
+
+#[tokio::test]
+async fn test_time_range_queries_ignore_the_writers_offset() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let notes = repo.project.path.clone() + "/notes.txt";
+    let todo = repo.project.path.clone() + "/todo.txt";
+
+    fs::write(&notes, "morning").unwrap();
+    let morning = repo.save_snapshot_from_file(Path::new(&notes)).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    fs::write(&notes, "evening").unwrap();
+    repo.save_snapshot_from_file(Path::new(&notes)).unwrap();
+    fs::write(&todo, "ship it").unwrap();
+    repo.save_snapshot_from_file(Path::new(&todo)).unwrap();
+
+    let history = repo.get_file_history(&notes).unwrap();
+    let first = parse_ms(&history[1].timestamp).unwrap();
+    let last = parse_ms(&history[0].timestamp).unwrap();
+    assert!(first < last);
+
+    let window = repo.history_between(first, first).unwrap();
+    assert_eq!(window.len(), 1);
+    assert_eq!(window[0].content_hash, morning);
+    let all = repo.history_between(first, i64::MAX).unwrap();
+    assert_eq!(all.len(), 3);
+    assert!(all.windows(2).all(|w| w[0].id > w[1].id));
+    assert!(repo.history_between(last + 1, first).unwrap().is_empty());
+    let recent = repo.db.get_global_history(2).unwrap();
+    assert_eq!(
+        recent.iter().map(|s| s.id).collect::<Vec<_>>(),
+        all[..2].iter().map(|s| s.id).collect::<Vec<_>>()
+    );
+
+    let days = repo.activity_between(first, last + 1000).unwrap();
+    assert_eq!(days.iter().map(|(_, n)| n).sum::<usize>(), 3);
+
+    // The same instant written from the other side of the world
+    let tokyo = at_offset(&history[1].timestamp, 9);
+    let new_york = at_offset(&history[1].timestamp, -5);
+    assert_eq!(parse_ms(&tokyo), parse_ms(&new_york));
+
+    fs::write(&notes, "scribbles").unwrap();
+    assert_eq!(repo.revert_to_timestamp(&tokyo).unwrap(), 1);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "morning");
+    assert!(repo.revert_to_timestamp("last tuesday").is_err());
+}
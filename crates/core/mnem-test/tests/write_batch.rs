@@ -0,0 +1,57 @@
+use mnem_core::Repository;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_prepared_snapshots_commit_together() {
+    let dir = TempDir::new().unwrap();
+    let base_dir = dir.path().join("home_mnemosyne");
+    fs::create_dir_all(&base_dir).unwrap();
+    let project_dir = dir.path().join("project");
+    let project_mnem_dir = project_dir.join(".mnemosyne");
+    fs::create_dir_all(&project_mnem_dir).unwrap();
+    fs::write(project_mnem_dir.join("tracked"), "project_id: test-id").unwrap();
+
+    let repo = Repository::open(base_dir, project_dir).unwrap();
+    let a = repo.project.path.clone() + "/a.txt";
+    let b = repo.project.path.clone() + "/b.txt";
+    fs::write(&a, "alpha content").unwrap();
+    fs::write(&b, "beta content").unwrap();
+
+    let before = repo.db.get_snapshot_count().unwrap();
+    let prepared_a = repo
+        .prepare_snapshot(
+            Path::new(&a),
+            Repository::read_file_content(Path::new(&a)).unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+    let prepared_b = repo
+        .prepare_snapshot(
+            Path::new(&b),
+            Repository::read_file_content(Path::new(&b)).unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+
+    // Preparing only touches CAS, nothing is visible yet
+    assert_eq!(repo.db.get_snapshot_count().unwrap(), before);
+
+    let ids = repo.commit_snapshots(&[&prepared_a, &prepared_b]).unwrap();
+    assert_eq!(ids.len(), 2);
+    assert!(ids[1] > ids[0]);
+
+    let content = repo.get_content(&prepared_b.content_hash).unwrap();
+    assert_eq!(content, b"beta content");
+    assert_eq!(repo.get_file_history(&a).unwrap()[0].id, ids[0]);
+
+    // Unchanged content is not prepared again
+    let again = repo
+        .prepare_snapshot(
+            Path::new(&a),
+            Repository::read_file_content(Path::new(&a)).unwrap(),
+        )
+        .unwrap();
+    assert!(again.is_none());
+}
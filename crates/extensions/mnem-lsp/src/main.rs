@@ -2,6 +2,7 @@ use anyhow::Result;
 use async_trait::async_trait;
 use mnem_core::{
     client::DaemonClient,
+    models::Pin,
     protocol::{methods, SymbolHistoryEntry},
 };
 use serde_json::json;
@@ -181,6 +182,24 @@ impl Backend {
         Ok(Vec::new())
     }
 
+    /// Gets the pins of the project containing `file_path`
+    async fn get_pins(&self, file_path: &str) -> Vec<Pin> {
+        if !self.ensure_daemon().await {
+            return Vec::new();
+        }
+
+        let mut client_guard = self.mnem_client.lock().await;
+        if let Some(ref mut client) = *client_guard {
+            match client.call(methods::PIN_LIST, json!({ "project_path": file_path })) {
+                Ok(res) => return serde_json::from_value(res).unwrap_or_default(),
+                Err(_) => {
+                    *client_guard = None;
+                }
+            }
+        }
+        Vec::new()
+    }
+
     /// Gets the semantic diff between two versions of a symbol
     async fn get_symbol_diff(
         &self,
@@ -285,6 +304,7 @@ impl Backend {
         symbol_name: &str,
         current_file: &str,
         diff: Option<String>,
+        pins: &[Pin],
     ) -> String {
         if history.is_empty() {
             return format!("**Mnemosyne**: No history found for `{}`", symbol_name);
@@ -323,6 +343,21 @@ impl Backend {
             ts
         );
 
+        // Pins on versions of this file that contain the symbol
+        let pinned: Vec<String> = pins
+            .iter()
+            .filter(|pin| {
+                history.iter().any(|entry| {
+                    entry.snapshot.file_path == current_file
+                        && entry.snapshot.content_hash == pin.hash
+                })
+            })
+            .map(|pin| pin.label.replace('|', "\\|"))
+            .collect();
+        if !pinned.is_empty() {
+            content.push_str(&format!("| Pinned | {} |\n", pinned.join(", ")));
+        }
+
         if let Some(diff_text) = diff {
             let trimmed = diff_text.trim();
             if !trimmed.is_empty() {
@@ -663,7 +698,8 @@ impl LanguageServer for Backend {
             }
         }
 
-        let content = Self::format_hover_content(&history, &symbol, &file_path, diff);
+        let pins = self.get_pins(&file_path).await;
+        let content = Self::format_hover_content(&history, &symbol, &file_path, diff, &pins);
 
         Ok(Some(Hover {
             contents: HoverContents::Markup(MarkupContent {
@@ -312,11 +312,12 @@ fn tools_list() -> Value {
             },
             {
                 "name": "mnem_search_content",
-                "description": "Powerful grep across all project history. Searches all snapshots for a text query. Returns matching lines, file paths, and content hashes for further exploration. Supports regular expressions.",
+                "description": "Powerful grep across all project history. Searches all snapshots for a text query. Returns matching lines, file paths, and content hashes for further exploration. Set regex to search with a regular expression.",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
                         "query": { "type": "string", "description": "The text to search for in all file versions." },
+                        "regex": { "type": "boolean", "description": "Treat the query as a regular expression instead of a case-insensitive substring.", "default": false },
                         "limit": { "type": "integer", "description": "Optional maximum number of results to return.", "default": 50 }
                     },
                     "required": ["query"]
@@ -437,9 +438,10 @@ async fn handle_tool_call(params: Value) -> Result<Value> {
         "mnem_search_content" => {
             let query = args["query"].as_str().context("query required")?;
             let limit = args["limit"].as_u64();
+            let regex = args["regex"].as_bool().unwrap_or(false);
             let res = client.call(
                 methods::CONTENT_SEARCH_V1,
-                json!({ "query": query, "limit": limit }),
+                json!({ "query": query, "limit": limit, "regex": regex }),
             )?;
             Ok(mcp_text(&serde_json::to_string_pretty(&res)?))
         }